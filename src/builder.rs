@@ -0,0 +1,364 @@
+use crate::io::read_until_needle_internal;
+use crate::needle::Scanner;
+use crate::{Needle, UntilNeedleStatus};
+
+/// A read until a needle, combining options that the simple methods leave at their defaults.
+///
+/// The methods of [`UntilNeedleRead`](crate::io::UntilNeedleRead) are shorthands for a read
+/// with the default options.
+///
+/// # Options
+/// - [`limit`](Self::limit): at most this many bytes are consumed from the reader. A needle
+///   is only found if it lies within the limit, so a match ending exactly at the limit is
+///   found, while one crossing it is not.
+/// - [`consume_needle`](Self::consume_needle): whether the needle itself is consumed. The limit
+///   still applies to the whole needle, so an unconsumed needle must also lie within it.
+///
+/// # Example
+/// ```
+/// use until_needle::{UntilNeedle, UntilNeedleStatus};
+///
+/// let mut reader = &b"HTTP/1.1 200 OK\r\n\r\nbody"[..];
+/// let mut before = Vec::new();
+/// let mut matched = Vec::new();
+/// let status = UntilNeedle::new(b"\r\n\r\n")
+///     .limit(1024)
+///     .consume_needle(false)
+///     .read(&mut reader, &mut before, &mut matched)
+///     .unwrap();
+/// assert_eq!(status, UntilNeedleStatus::Found { bytes_read: 15 });
+/// assert_eq!(reader, b"\r\n\r\nbody");
+/// ```
+#[derive(Debug, Clone)]
+pub struct UntilNeedle<N> {
+    needle: N,
+    limit: usize,
+    consume_needle: bool,
+}
+
+impl<N: Needle> UntilNeedle<N> {
+    /// Creates a read until `needle` with the default options.
+    pub fn new(needle: N) -> Self {
+        Self {
+            needle,
+            limit: usize::MAX,
+            consume_needle: true,
+        }
+    }
+
+    /// Sets the maximum number of bytes consumed from the reader.
+    ///
+    /// If the needle has not been found within `limit` bytes, the read stops with
+    /// [`UntilNeedleStatus::LimitReached`] after appending them to `before`, leaving the reader
+    /// right after them. Defaults to no limit.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Sets whether the needle is consumed from the reader.
+    ///
+    /// Defaults to `true`. When `false`, the needle is still copied to `matched`, but left in the
+    /// reader so it is read next, and `bytes_read` does not include it. A reader can only keep
+    /// the bytes of its current buffer, so the beginning of a needle straddling two buffers is
+    /// consumed with the first one. [`read_held`](Self::read_held) returns it, to be read before
+    /// the rest of the reader; [`read`](Self::read) and `read_async` only copy it to `matched`.
+    pub fn consume_needle(mut self, consume: bool) -> Self {
+        self.consume_needle = consume;
+        self
+    }
+
+    /// Reads data from `reader` until the needle is found, EOF is reached, or the limit is
+    /// reached.
+    ///
+    /// The data before the needle is appended to `before` and the needle to `matched`.
+    pub fn read<R>(
+        &self,
+        reader: &mut R,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> std::io::Result<UntilNeedleStatus>
+    where
+        R: std::io::BufRead + ?Sized,
+    {
+        self.read_held(reader, before, matched)
+            .map(|(status, _)| status)
+    }
+
+    /// Reads like [`read`](Self::read), also returning the beginning of an unconsumed needle
+    /// that had to be consumed from `reader`.
+    ///
+    /// The returned bytes come before anything still in `reader`, as with
+    /// [`TakeUntil::into_inner`](crate::io::TakeUntil::into_inner), so chaining them in front of
+    /// it reads the whole needle next. They are empty if the needle is consumed or lay within
+    /// one buffer of the reader.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::{BufReader, Read};
+    /// use until_needle::UntilNeedle;
+    ///
+    /// // a buffer of 4 bytes splits the needle
+    /// let mut reader = BufReader::with_capacity(4, &b"abc<>def"[..]);
+    /// let (mut before, mut matched) = (Vec::new(), Vec::new());
+    /// let (status, held) = UntilNeedle::new(b"<>")
+    ///     .consume_needle(false)
+    ///     .read_held(&mut reader, &mut before, &mut matched)?;
+    /// assert_eq!((status.bytes_read(), &held[..]), (3, &b"<"[..]));
+    ///
+    /// let mut rest = Vec::new();
+    /// held.chain(reader).read_to_end(&mut rest)?;
+    /// assert_eq!(rest, b"<>def");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn read_held<R>(
+        &self,
+        reader: &mut R,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> std::io::Result<(UntilNeedleStatus, Vec<u8>)>
+    where
+        R: std::io::BufRead + ?Sized,
+    {
+        let mut scanner = Scanner::new();
+        scanner.limit = self.limit;
+        scanner.consume_needle = self.consume_needle;
+        let start = matched.len();
+        let status = read_until_needle_internal(
+            reader,
+            &self.needle,
+            &mut scanner,
+            before,
+            matched,
+            |_| Ok(false),
+        )?;
+        let held = matched[start..start + scanner.held()].to_vec();
+        Ok((status, held))
+    }
+
+    /// Asynchronously reads data from `reader` until the needle is found, EOF is reached, or the
+    /// limit is reached.
+    ///
+    /// This is the async counterpart of [`read`](Self::read).
+    #[cfg(feature = "futures")]
+    pub fn read_async<'a, R>(
+        &'a self,
+        reader: &'a mut R,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+    ) -> crate::futures::ReadUntilNeedleWith<'a, R, N>
+    where
+        R: futures_util::io::AsyncBufRead + Unpin + ?Sized,
+    {
+        let state = crate::futures::SearchState::new()
+            .with_limit(self.limit)
+            .with_consume_needle(self.consume_needle);
+        crate::futures::ReadUntilNeedleWith::new(reader, &self.needle, state, before, matched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::UntilNeedleRead;
+    use crate::testing::ChunkedReader;
+    use std::io::{BufRead, Read};
+
+    fn read_sync<N: Needle>(
+        builder: &UntilNeedle<N>,
+        data: &[u8],
+        capacity: usize,
+    ) -> (UntilNeedleStatus, Vec<u8>, Vec<u8>, Vec<u8>) {
+        let mut reader = ChunkedReader::new(data, [capacity]);
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        let status = builder
+            .read(&mut reader, &mut before, &mut matched)
+            .unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        (status, before, matched, rest)
+    }
+
+    #[test]
+    fn test_limit() {
+        use UntilNeedleStatus::*;
+
+        let data = b"abcdef<>gh";
+        for capacity in 1..12 {
+            // a match ending exactly at the limit is found
+            let builder = UntilNeedle::new(b"<>").limit(8);
+            let (status, before, matched, rest) = read_sync(&builder, data, capacity);
+            assert_eq!(status, Found { bytes_read: 8 });
+            assert_eq!(
+                (&before[..], &matched[..], &rest[..]),
+                (&b"abcdef"[..], &b"<>"[..], &b"gh"[..])
+            );
+
+            // a match crossing the limit is not
+            let builder = UntilNeedle::new(b"<>").limit(7);
+            let (status, before, matched, rest) = read_sync(&builder, data, capacity);
+            assert_eq!(status, LimitReached { bytes_read: 7 });
+            assert_eq!(
+                (&before[..], &matched[..], &rest[..]),
+                (&b"abcdef<"[..], &b""[..], &b">gh"[..])
+            );
+
+            let builder = UntilNeedle::new(b"<>").limit(20);
+            let (status, ..) = read_sync(&builder, b"abc", capacity);
+            assert_eq!(status, Eof { bytes_read: 3 });
+        }
+    }
+
+    #[test]
+    fn test_limit_and_exclusive() {
+        use UntilNeedleStatus::*;
+
+        let data = b"abcdef<>gh";
+        // the needle must lie within the limit even when it is not consumed
+        let builder = UntilNeedle::new(b"<>").limit(8).consume_needle(false);
+        let (status, before, matched, rest) = read_sync(&builder, data, 16);
+        assert_eq!(status, Found { bytes_read: 6 });
+        assert_eq!(
+            (&before[..], &matched[..], &rest[..]),
+            (&b"abcdef"[..], &b"<>"[..], &b"<>gh"[..])
+        );
+
+        let builder = UntilNeedle::new(b"<>").limit(7).consume_needle(false);
+        let (status, _, matched, rest) = read_sync(&builder, data, 16);
+        assert_eq!(status, LimitReached { bytes_read: 7 });
+        assert_eq!((&matched[..], &rest[..]), (&b""[..], &b">gh"[..]));
+
+        // the part of a needle in an earlier buffer has already been consumed
+        let builder = UntilNeedle::new(b"<>").consume_needle(false);
+        let (status, before, matched, rest) = read_sync(&builder, data, 7);
+        assert_eq!(status, Found { bytes_read: 6 });
+        assert_eq!(
+            (&before[..], &matched[..], &rest[..]),
+            (&b"abcdef"[..], &b"<>"[..], &b">gh"[..])
+        );
+    }
+
+    #[test]
+    fn test_read_held() {
+        use UntilNeedleStatus::*;
+
+        let data = b"abcdef<END>gh";
+        let builder = UntilNeedle::new(b"<END>").consume_needle(false);
+        for capacity in 1..16 {
+            // the needle is split across the buffers of the reader for small capacities
+            let mut reader = ChunkedReader::new(&data[..], [capacity]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            let (status, held) = builder
+                .read_held(&mut reader, &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(status, Found { bytes_read: 6 });
+            assert_eq!((&before[..], &matched[..]), (&b"abcdef"[..], &b"<END>"[..]));
+
+            // the next read still sees the whole needle
+            let mut rest = Vec::new();
+            held.chain(&mut reader).read_to_end(&mut rest).unwrap();
+            assert_eq!(rest, b"<END>gh");
+        }
+
+        // nothing is held back when the needle is consumed
+        let mut reader = ChunkedReader::new(&data[..], [7]);
+        let (status, held) = UntilNeedle::new(b"<END>")
+            .read_held(&mut reader, &mut Vec::new(), &mut Vec::new())
+            .unwrap();
+        assert_eq!((status, &held[..]), (Found { bytes_read: 11 }, &b""[..]));
+    }
+
+    #[test]
+    fn test_wrappers() {
+        let data = b"one\r\ntwo\r\nthree";
+        for capacity in 1..8 {
+            let mut reader = ChunkedReader::new(&data[..], [capacity]);
+            let mut before = Vec::new();
+            let mut matched = Vec::new();
+            let n = reader
+                .read_until_needle(b"\r\n", &mut before, &mut matched)
+                .unwrap();
+
+            let builder = UntilNeedle::new(b"\r\n");
+            let (status, b, m, _) = read_sync(&builder, data, capacity);
+            assert_eq!((status.bytes_read(), b, m), (n, before, matched));
+            assert_eq!(reader.fill_buf().unwrap()[0], b't');
+        }
+    }
+
+    #[cfg(feature = "futures")]
+    #[tokio::test]
+    async fn test_read_async() {
+        use crate::testing::ChunkedAsyncReader;
+        use futures::AsyncReadExt as _;
+
+        let data = b"abcdef<>gh";
+        let builders = [
+            UntilNeedle::new(b"<>").limit(8),
+            UntilNeedle::new(b"<>").limit(7),
+            UntilNeedle::new(b"<>").limit(8).consume_needle(false),
+            UntilNeedle::new(b"<>").consume_needle(false),
+        ];
+        for size in 1..12 {
+            for builder in &builders {
+                let mut reader = ChunkedAsyncReader::new(&data[..], [size]).pending(1);
+                let mut before = Vec::new();
+                let mut matched = Vec::new();
+                let status = builder
+                    .read_async(&mut reader, &mut before, &mut matched)
+                    .await
+                    .unwrap();
+                let mut rest = Vec::new();
+                reader.read_to_end(&mut rest).await.unwrap();
+                assert_eq!(
+                    (status, before, matched, rest),
+                    read_sync(builder, data, size)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_into_result() {
+        use crate::{LimitExceeded, UntilNeedleError};
+
+        let builder = UntilNeedle::new(b"<>").limit(4);
+        let (status, ..) = read_sync(&builder, b"ab<>", 1);
+        assert_eq!(status.into_result().unwrap(), 4);
+        let (status, ..) = read_sync(&builder, b"abcdef", 1);
+        let err = status.into_result().unwrap_err();
+        assert!(matches!(
+            err,
+            UntilNeedleError::LimitExceeded(LimitExceeded { limit: 4 })
+        ));
+
+        // the error survives a round trip through io::Error
+        let err = UntilNeedleError::from(std::io::Error::from(err));
+        assert!(matches!(err, UntilNeedleError::LimitExceeded(_)));
+        let err: std::io::Error = UntilNeedleStatus::Eof { bytes_read: 2 }
+            .into_result()
+            .unwrap_err()
+            .into();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert!(matches!(
+            UntilNeedleError::from(err),
+            UntilNeedleError::EofWithoutMatch { bytes_read: 2 }
+        ));
+
+        // a limited read fails with an io::Error the cause is recovered from
+        let mut reader = &b"abcdef"[..];
+        let err = reader
+            .read_until_needle_limited(b"<>", 4, &mut Vec::new(), &mut Vec::new())
+            .unwrap_err();
+        assert!(matches!(
+            UntilNeedleError::from(err),
+            UntilNeedleError::LimitExceeded(LimitExceeded { limit: 4 })
+        ));
+        let err = std::io::Error::from(std::io::ErrorKind::BrokenPipe);
+        assert!(matches!(
+            UntilNeedleError::from(err),
+            UntilNeedleError::Io(e) if e.kind() == std::io::ErrorKind::BrokenPipe
+        ));
+    }
+}
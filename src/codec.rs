@@ -0,0 +1,350 @@
+use crate::Needle;
+use bytes::{Bytes, BytesMut};
+use std::{fmt, io};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A frame produced by [`NeedleCodec`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NeedleFrame {
+    /// The frame data, which ends with the delimiter if the codec is configured to include it.
+    pub data: Bytes,
+    /// The delimiter that terminated the frame, or `None` for a trailing frame at EOF.
+    pub delimiter: Option<Bytes>,
+}
+
+/// An error occurred while decoding frames with [`NeedleCodec`].
+#[derive(Debug)]
+pub enum NeedleCodecError {
+    /// The frame exceeded the maximum length before the delimiter was found.
+    MaxFrameLengthExceeded,
+    /// An I/O error occurred.
+    Io(io::Error),
+}
+
+impl fmt::Display for NeedleCodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NeedleCodecError::MaxFrameLengthExceeded => write!(f, "max frame length exceeded"),
+            NeedleCodecError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for NeedleCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NeedleCodecError::MaxFrameLengthExceeded => None,
+            NeedleCodecError::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for NeedleCodecError {
+    fn from(e: io::Error) -> Self {
+        NeedleCodecError::Io(e)
+    }
+}
+
+/// A `tokio_util` [`Decoder`] that splits a byte stream into frames delimited by a [`Needle`].
+///
+/// Delimiters split across several `decode` calls are found, since the bytes that may begin a
+/// match (as bounded by [`Needle::max_len`]) are searched again once more data arrives.
+///
+/// It is also an [`Encoder`] writing each frame followed by the delimiter set with
+/// [`encode_delimiter`](Self::encode_delimiter), for use with `FramedWrite` or `Framed`.
+///
+/// # Example
+/// ```
+/// use bytes::BytesMut;
+/// use tokio_util::codec::Decoder;
+/// use until_needle::codec::NeedleCodec;
+///
+/// let mut codec = NeedleCodec::new(b"\r\n").max_frame_length(1024);
+/// let mut buf = BytesMut::from(&b"hello\r\nwor"[..]);
+/// let frame = codec.decode(&mut buf).unwrap().unwrap();
+/// assert_eq!(frame.data, &b"hello"[..]);
+/// assert_eq!(frame.delimiter.unwrap(), &b"\r\n"[..]);
+/// assert!(codec.decode(&mut buf).unwrap().is_none());
+/// ```
+#[doc(alias = "NeedleDelimitedCodec")]
+#[derive(Debug, Clone)]
+pub struct NeedleCodec<N> {
+    needle: N,
+    include_delimiter: bool,
+    encode_delimiter: Bytes,
+    max_frame_length: usize,
+    emit_trailing_frame: bool,
+    /// The offset in the buffer from which the next search starts.
+    next_index: usize,
+}
+
+impl<N: Needle> NeedleCodec<N> {
+    /// Creates a codec splitting frames at `needle`, without a maximum frame length.
+    pub fn new(needle: N) -> Self {
+        Self {
+            needle,
+            include_delimiter: false,
+            encode_delimiter: Bytes::new(),
+            max_frame_length: usize::MAX,
+            emit_trailing_frame: true,
+            next_index: 0,
+        }
+    }
+
+    /// Sets whether the delimiter is included at the end of [`NeedleFrame::data`].
+    ///
+    /// Defaults to `false`.
+    pub fn include_delimiter(mut self, include: bool) -> Self {
+        self.include_delimiter = include;
+        self
+    }
+
+    /// Sets the delimiter appended to each frame on encode.
+    ///
+    /// Needles such as regexes cannot tell which bytes to write, so this is set separately.
+    /// Defaults to none, writing the frames as they are.
+    pub fn encode_delimiter(mut self, delimiter: impl Into<Bytes>) -> Self {
+        self.encode_delimiter = delimiter.into();
+        self
+    }
+
+    /// Sets the maximum length of a frame, not counting the delimiter.
+    ///
+    /// Once more data than this has been buffered without finding the delimiter, decoding fails
+    /// with [`NeedleCodecError::MaxFrameLengthExceeded`] instead of buffering without bound.
+    pub fn max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = max;
+        self
+    }
+
+    /// Sets whether data left at EOF without a delimiter is emitted as a final frame.
+    ///
+    /// Defaults to `true`. When `false`, such data makes `decode_eof` fail with an error of kind
+    /// `UnexpectedEof`.
+    pub fn emit_trailing_frame(mut self, emit: bool) -> Self {
+        self.emit_trailing_frame = emit;
+        self
+    }
+
+    /// Returns the maximum frame length.
+    pub fn max_length(&self) -> usize {
+        self.max_frame_length
+    }
+}
+
+impl<N: Needle> Decoder for NeedleCodec<N> {
+    type Item = NeedleFrame;
+    type Error = NeedleCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<NeedleFrame>, NeedleCodecError> {
+        self.next_index = self.next_index.min(buf.len());
+        let found = self
+            .needle
+            .findin(&buf[self.next_index..])
+            .map(|range| self.next_index + range.start..self.next_index + range.end);
+
+        match found {
+            Some(range) => {
+                self.next_index = 0;
+                if range.start > self.max_frame_length {
+                    return Err(NeedleCodecError::MaxFrameLengthExceeded);
+                }
+                let frame = buf.split_to(range.end).freeze();
+                let delimiter = frame.slice(range.clone());
+                let data = if self.include_delimiter {
+                    frame
+                } else {
+                    frame.slice(..range.start)
+                };
+                Ok(Some(NeedleFrame {
+                    data,
+                    delimiter: Some(delimiter),
+                }))
+            }
+            None => {
+                // bytes that may begin a delimiter are searched again with the next data
+                let min_frame_length = match self.needle.max_len() {
+                    Some(len) => {
+                        self.next_index = buf.len().saturating_sub(len.saturating_sub(1));
+                        self.next_index
+                    }
+                    None => buf.len(),
+                };
+                if min_frame_length > self.max_frame_length {
+                    return Err(NeedleCodecError::MaxFrameLengthExceeded);
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<NeedleFrame>, NeedleCodecError> {
+        if let Some(frame) = self.decode(buf)? {
+            return Ok(Some(frame));
+        }
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        if !self.emit_trailing_frame {
+            return Err(
+                io::Error::new(io::ErrorKind::UnexpectedEof, "bytes remaining on stream").into(),
+            );
+        }
+        if buf.len() > self.max_frame_length {
+            return Err(NeedleCodecError::MaxFrameLengthExceeded);
+        }
+        self.next_index = 0;
+        let data = buf.split_to(buf.len()).freeze();
+        Ok(Some(NeedleFrame {
+            data,
+            delimiter: None,
+        }))
+    }
+}
+
+impl<T: AsRef<[u8]>, N> Encoder<T> for NeedleCodec<N> {
+    type Error = NeedleCodecError;
+
+    fn encode(&mut self, item: T, buf: &mut BytesMut) -> Result<(), NeedleCodecError> {
+        let item = item.as_ref();
+        buf.reserve(item.len() + self.encode_delimiter.len());
+        buf.extend_from_slice(item);
+        buf.extend_from_slice(&self.encode_delimiter);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::SinkExt as _;
+    use futures::StreamExt as _;
+    use tokio::io::AsyncWriteExt as _;
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    fn decode_bytewise<N: Needle>(
+        codec: &mut NeedleCodec<N>,
+        data: &[u8],
+    ) -> Result<Vec<NeedleFrame>, NeedleCodecError> {
+        let mut buf = BytesMut::new();
+        let mut frames = Vec::new();
+        for &b in data {
+            buf.extend_from_slice(&[b]);
+            while let Some(frame) = codec.decode(&mut buf)? {
+                frames.push(frame);
+            }
+        }
+        while let Some(frame) = codec.decode_eof(&mut buf)? {
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+
+    #[test]
+    fn test_decode_split_delimiter() {
+        let mut codec = NeedleCodec::new(b"<>|");
+        let frames = decode_bytewise(&mut codec, b"one<>|two<><>|<>|three").unwrap();
+        let data: Vec<_> = frames.iter().map(|f| &f.data[..]).collect();
+        assert_eq!(data, vec![&b"one"[..], b"two<>", b"", b"three"]);
+        assert_eq!(frames[0].delimiter.as_deref(), Some(&b"<>|"[..]));
+        assert_eq!(frames[3].delimiter, None);
+
+        let mut codec = NeedleCodec::new(b"<>|").include_delimiter(true);
+        let frames = decode_bytewise(&mut codec, b"one<>|two").unwrap();
+        let data: Vec<_> = frames.iter().map(|f| &f.data[..]).collect();
+        assert_eq!(data, vec![&b"one<>|"[..], b"two"]);
+    }
+
+    #[test]
+    fn test_decode_eof() {
+        let mut codec = NeedleCodec::new(b"\n").emit_trailing_frame(false);
+        let err = decode_bytewise(&mut codec, b"a\nb").unwrap_err();
+        assert!(matches!(err, NeedleCodecError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof));
+
+        let mut codec = NeedleCodec::new(b"\n").emit_trailing_frame(false);
+        assert_eq!(decode_bytewise(&mut codec, b"a\nb\n").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_decode_max_frame_length() {
+        let mut codec = NeedleCodec::new(b"\r\n").max_frame_length(4);
+        assert_eq!(decode_bytewise(&mut codec, b"abcd\r\n").unwrap().len(), 1);
+
+        // "abcd\r" may still be completed into a frame of 4 bytes
+        let mut codec = NeedleCodec::new(b"\r\n").max_frame_length(4);
+        let mut buf = BytesMut::from(&b"abcd\r"[..]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        buf.extend_from_slice(b"x");
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(NeedleCodecError::MaxFrameLengthExceeded)
+        ));
+
+        let mut codec = NeedleCodec::new(b"\r\n").max_frame_length(4);
+        assert!(matches!(
+            decode_bytewise(&mut codec, b"abcde\r\n"),
+            Err(NeedleCodecError::MaxFrameLengthExceeded)
+        ));
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut codec = NeedleCodec::new(b"\r\n").encode_delimiter(&b"\r\n"[..]);
+        let mut buf = BytesMut::new();
+        codec.encode("one", &mut buf).unwrap();
+        codec.encode(b"", &mut buf).unwrap();
+        assert_eq!(buf, &b"one\r\n\r\n"[..]);
+        let frames = decode_bytewise(&mut codec, &buf).unwrap();
+        let data: Vec<_> = frames.iter().map(|f| &f.data[..]).collect();
+        assert_eq!(data, vec![&b"one"[..], b""]);
+
+        let mut codec = NeedleCodec::new(b"\n");
+        let mut buf = BytesMut::new();
+        codec.encode(Bytes::from_static(b"raw"), &mut buf).unwrap();
+        assert_eq!(buf, &b"raw"[..]);
+    }
+
+    #[tokio::test]
+    async fn test_framed_write() {
+        let mut out = Vec::new();
+        let codec = NeedleCodec::new(b"\n").encode_delimiter(&b"\n"[..]);
+        let mut framed = FramedWrite::new(&mut out, codec);
+        framed.send("a").await.unwrap();
+        framed.send("bc").await.unwrap();
+        drop(framed);
+        assert_eq!(out, b"a\nbc\n");
+    }
+
+    #[tokio::test]
+    async fn test_framed_read() {
+        // a duplex buffer of one byte delivers the data byte by byte
+        let (client, mut server) = tokio::io::duplex(1);
+        let writer = tokio::spawn(async move {
+            server
+                .write_all(b"login: \r\n\r\nPassword: \r\n\r\n$ ")
+                .await
+                .unwrap();
+        });
+
+        let mut framed = FramedRead::new(client, NeedleCodec::new(b"\r\n\r\n"));
+        let mut frames = Vec::new();
+        while let Some(frame) = framed.next().await {
+            frames.push(frame.unwrap().data);
+        }
+        writer.await.unwrap();
+        assert_eq!(frames, vec![&b"login: "[..], b"Password: ", b"$ "]);
+
+        let (client, mut server) = tokio::io::duplex(1);
+        let writer = tokio::spawn(async move {
+            let _ = server.write_all(b"ok\nthis line is too long\n").await;
+        });
+        let mut framed = FramedRead::new(client, NeedleCodec::new(b"\n").max_frame_length(8));
+        assert_eq!(framed.next().await.unwrap().unwrap().data, &b"ok"[..]);
+        assert!(matches!(
+            framed.next().await,
+            Some(Err(NeedleCodecError::MaxFrameLengthExceeded))
+        ));
+        drop(framed);
+        writer.await.unwrap();
+    }
+}
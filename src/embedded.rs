@@ -0,0 +1,261 @@
+//! Reading until a needle from an [`embedded_io::BufRead`], without requiring `std`.
+//!
+//! [`read_until_needle_slice`](crate::embedded::UntilNeedleRead::read_until_needle_slice) works
+//! without an allocator and collects the data into a caller-provided slice. With the `alloc`
+//! feature, [`read_until_needle`](crate::embedded::UntilNeedleRead::read_until_needle) collects
+//! it into vectors like its `std::io` counterpart.
+
+#[cfg(feature = "alloc")]
+use crate::needle::Scanner;
+#[cfg(feature = "alloc")]
+use crate::trace;
+use crate::Needle;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::ops::Range;
+use embedded_io::{BufRead, Error as _, ErrorKind};
+
+/// The outcome of [`UntilNeedleRead::read_until_needle_slice`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SliceStatus {
+    /// The needle was found; holds its position in the buffer. The data before it is
+    /// `buf[..matched.start]`.
+    Found { matched: Range<usize> },
+    /// EOF was reached without a match; holds the number of bytes written to the buffer.
+    Eof { len: usize },
+    /// The buffer was filled without a match. The reader is left right after the buffered data.
+    Full,
+}
+
+// The trait to extend embedded_io::BufRead for until_needle functionality
+pub trait UntilNeedleRead: BufRead {
+    /// Reads data into `buf` until the specified `needle` is found, EOF is reached or `buf` is
+    /// full.
+    ///
+    /// Only the bytes up to the end of the needle are consumed from the reader, so the next
+    /// read starts right after it. Reads failing with [`ErrorKind::Interrupted`] are retried.
+    ///
+    /// # Returns
+    /// - [`SliceStatus::Found`] with the range of the needle in `buf`.
+    /// - [`SliceStatus::Eof`] with the number of bytes written to `buf`.
+    /// - [`SliceStatus::Full`] if `buf` was filled before the needle was found.
+    fn read_until_needle_slice(
+        &mut self,
+        needle: impl Needle,
+        buf: &mut [u8],
+    ) -> Result<SliceStatus, Self::Error>;
+
+    /// Reads data until the specified `needle` is found or EOF is reached.
+    ///
+    /// This is [`crate::io::UntilNeedleRead::read_until_needle`] for embedded-io readers.
+    ///
+    /// # Returns
+    /// - On success, it returns the total number of bytes read, including the needle.
+    /// - If EOF is reached before the needle is found, `matched` will remain untouched.
+    #[cfg(feature = "alloc")]
+    fn read_until_needle(
+        &mut self,
+        needle: impl Needle,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> Result<usize, Self::Error>;
+}
+
+impl<R: BufRead + ?Sized> UntilNeedleRead for R {
+    fn read_until_needle_slice(
+        &mut self,
+        needle: impl Needle,
+        buf: &mut [u8],
+    ) -> Result<SliceStatus, Self::Error> {
+        let mut filled = 0;
+        let mut scan_from = 0;
+        loop {
+            if filled == buf.len() {
+                return Ok(SliceStatus::Full);
+            }
+
+            let available = match self.fill_buf() {
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            if available.is_empty() {
+                // EOF
+                return Ok(SliceStatus::Eof { len: filled });
+            }
+
+            let n = available.len().min(buf.len() - filled);
+            buf[filled..filled + n].copy_from_slice(&available[..n]);
+            if let Some(range) = needle.findin(&buf[scan_from..filled + n]) {
+                let matched = scan_from + range.start..scan_from + range.end;
+                self.consume(matched.end - filled);
+                return Ok(SliceStatus::Found { matched });
+            }
+            self.consume(n);
+            filled += n;
+            if let Some(len) = needle.max_len() {
+                scan_from = scan_from.max(filled.saturating_sub(len.saturating_sub(1)));
+            }
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    fn read_until_needle(
+        &mut self,
+        needle: impl Needle,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> Result<usize, Self::Error> {
+        let mut scanner = Scanner::new();
+        trace::read_span(&needle).in_scope(|| {
+            let status = loop {
+                let available = match self.fill_buf() {
+                    Ok(n) => n,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+
+                if available.is_empty() {
+                    // EOF
+                    break scanner.finish();
+                }
+
+                let step = scanner.push(&needle, available);
+                step.apply(available, before, matched);
+                let (done, used) = (step.is_done(), step.consumed());
+                self.consume(used);
+                if done {
+                    break scanner.finish();
+                }
+            };
+            trace::outcome(&status);
+            Ok(status.bytes_read())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_io::{ErrorType, Read};
+
+    /// Hands out `data` at most `size` bytes at a time, failing with `errors` first.
+    struct Chunked<'a> {
+        data: &'a [u8],
+        size: usize,
+        errors: Vec<ErrorKind>,
+    }
+
+    impl<'a> Chunked<'a> {
+        fn new(data: &'a [u8], size: usize) -> Self {
+            Self {
+                data,
+                size,
+                errors: Vec::new(),
+            }
+        }
+    }
+
+    impl ErrorType for Chunked<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, ErrorKind> {
+            let available = self.fill_buf()?;
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            self.consume(n);
+            Ok(n)
+        }
+    }
+
+    impl BufRead for Chunked<'_> {
+        fn fill_buf(&mut self) -> Result<&[u8], ErrorKind> {
+            if !self.errors.is_empty() {
+                return Err(self.errors.remove(0));
+            }
+            Ok(&self.data[..self.size.min(self.data.len())])
+        }
+
+        fn consume(&mut self, amt: usize) {
+            self.data = &self.data[amt..];
+        }
+    }
+
+    #[test]
+    fn test_read_until_needle_slice() {
+        for size in 1..8 {
+            let mut reader = Chunked::new(b"abc<END>def<END>gh", size);
+            let mut buf = [0; 16];
+            assert_eq!(
+                reader.read_until_needle_slice(b"<END>", &mut buf).unwrap(),
+                SliceStatus::Found { matched: 3..8 }
+            );
+            assert_eq!(&buf[..8], b"abc<END>");
+            assert_eq!(
+                reader.read_until_needle_slice(b"<END>", &mut buf).unwrap(),
+                SliceStatus::Found { matched: 3..8 }
+            );
+            assert_eq!(&buf[..8], b"def<END>");
+            assert_eq!(
+                reader.read_until_needle_slice(b"<END>", &mut buf).unwrap(),
+                SliceStatus::Eof { len: 2 }
+            );
+            assert_eq!(&buf[..2], b"gh");
+        }
+
+        let mut reader = &b"abcdef<END>"[..];
+        let mut buf = [0; 4];
+        assert_eq!(
+            reader.read_until_needle_slice(b"<END>", &mut buf).unwrap(),
+            SliceStatus::Full
+        );
+        assert_eq!(&buf, b"abcd");
+        assert_eq!(reader, b"ef<END>");
+    }
+
+    #[test]
+    fn test_read_until_needle_slice_errors() {
+        let mut reader = Chunked::new(b"ab<END>", 2);
+        reader.errors = vec![ErrorKind::Interrupted, ErrorKind::Interrupted];
+        let mut buf = [0; 16];
+        assert_eq!(
+            reader.read_until_needle_slice(b"<END>", &mut buf).unwrap(),
+            SliceStatus::Found { matched: 2..7 }
+        );
+
+        let mut reader = Chunked::new(b"ab<END>", 2);
+        reader.errors = vec![ErrorKind::ConnectionReset];
+        assert_eq!(
+            reader.read_until_needle_slice(b"<END>", &mut buf),
+            Err(ErrorKind::ConnectionReset)
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_read_until_needle() {
+        for size in 1..8 {
+            let mut reader = Chunked::new(b"abc<END>def", size);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            assert_eq!(
+                reader
+                    .read_until_needle(b"<END>", &mut before, &mut matched)
+                    .unwrap(),
+                8
+            );
+            assert_eq!((&before[..], &matched[..]), (&b"abc"[..], &b"<END>"[..]));
+
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            assert_eq!(
+                reader
+                    .read_until_needle(b"<END>", &mut before, &mut matched)
+                    .unwrap(),
+                3
+            );
+            assert_eq!((&before[..], &matched[..]), (&b"def"[..], &b""[..]));
+        }
+    }
+}
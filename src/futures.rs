@@ -1,13 +1,53 @@
-use crate::Needle;
+use crate::needle::{PartialMatch, Scanner, SearchStep, SseEventEnd};
+use crate::output::{HeldSearch, OutputBuf};
+pub use crate::search::SearchState;
+use crate::search::{limit_exceeded, DEFAULT_BUDGET};
+pub use crate::CopyReport;
+use crate::{Needle, SkipReport, UntilNeedleOutput, UntilNeedleStatus};
+#[cfg(feature = "bytes")]
+use bytes::{Bytes, BytesMut};
+#[cfg(feature = "tokio")]
+pub use child::ChildPipes;
+use futures_core::future::FusedFuture;
 use futures_core::ready;
-use futures_util::io::AsyncBufRead;
-use std::future::Future;
+use futures_core::stream::{FusedStream, Stream};
+use futures_util::io::{AsyncBufRead, AsyncRead, AsyncWrite};
+use pin_project_lite::pin_project;
+pub use session::{Interaction, Session};
+use std::future::{Future, Pending};
 use std::io::{self};
 use std::mem;
+use std::ops::Range;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
+#[cfg(feature = "tokio")]
+mod child;
+mod session;
+
 /// The trait to extend `AsyncBufRead` for `read_until_needle` functionality.
+///
+/// # Readers that are not `Unpin`
+///
+/// The methods need `Self: Unpin`, which readers built from `async` code are not. A pinned
+/// reference `Pin<&mut R>` is `Unpin` and implements `AsyncBufRead` for any such `R`, so it
+/// works as the reader, without boxing: pin the reader once, with [`futures_util::pin_mut!`] or
+/// `Box::pin`, and call the methods on `reader.as_mut()`. [`poll_read_until_needle`] also takes
+/// a `Pin<&mut R>`.
+///
+/// ```
+/// use futures::io::AsyncBufRead;
+/// use std::pin::Pin;
+/// use until_needle::futures::AsyncUntilNeedleRead;
+///
+/// async fn read_line<R: AsyncBufRead + ?Sized>(
+///     mut reader: Pin<&mut R>,
+/// ) -> std::io::Result<Vec<u8>> {
+///     let (mut line, mut newline) = (Vec::new(), Vec::new());
+///     reader.read_until_needle(b"\n", &mut line, &mut newline).await?;
+///     Ok(line)
+/// }
+/// ```
 pub trait AsyncUntilNeedleRead: futures_util::io::AsyncBufRead {
     /// Asynchronously reads data from the underlying reader until the specified `needle` is found or EOF is reached.
     ///
@@ -15,25 +55,258 @@ pub trait AsyncUntilNeedleRead: futures_util::io::AsyncBufRead {
     /// - `needle`: An object implementing the `Needle` trait, which defines the search pattern.
     /// - `before`: A mutable buffer to store the data read before the `needle` is found.
     /// - `matched`: A mutable buffer to store the `needle` itself, if found. If EOF is reached without finding
-    ///              the needle, this buffer will not be modified.
+    ///   the needle, this buffer will not be modified.
     ///
     /// # Returns
     /// - On success, it returns the total number of bytes read, including the needle.
     /// - If EOF is reached before the needle is found, `matched` will remain untouched.
+    ///
+    /// Each new chunk is searched together with the last [`Needle::max_len`] - 1 bytes read
+    /// before it, so memory and search time stay proportional to the data read. For needles
+    /// without a maximum length, such as regular expressions not wrapped in
+    /// [`WithMaxLen`](crate::needle::WithMaxLen), everything read since the call started is
+    /// searched again with each chunk.
+    ///
+    /// Apart from a copy of the bytes that may begin a match, the future keeps no buffer of its
+    /// own: the data is accumulated directly in `before`. To avoid allocating for every message
+    /// in a loop, clear and reuse the same `before` and `matched` buffers; their capacity is kept
+    /// across calls.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe: every byte consumed from the reader has already been appended
+    /// to `before` whenever the future returns `Poll::Pending`. If the future is dropped, calling
+    /// this method again with the same buffers continues the read. The new call first searches
+    /// the end of `before` again, the last [`Needle::max_len`] - 1 bytes or the suffix told by
+    /// [`Needle::findin_partial`], so a needle straddling the point where the first future was
+    /// dropped is still found, and its beginning is moved from `before` to `matched`. For the
+    /// same reason, clear `before` between messages instead of appending a new message to data
+    /// that may begin the needle.
     fn read_until_needle<'a, N>(
         &'a mut self,
         needle: N,
         before: &'a mut Vec<u8>,
         matched: &'a mut Vec<u8>,
     ) -> ReadUntilNeedle<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a;
+
+    /// Asynchronously reads data until the specified `needle` is found or EOF is reached, giving
+    /// up after `limit` bytes.
+    ///
+    /// This is [`read_until_needle`](Self::read_until_needle) with
+    /// [`ReadUntilNeedle::limit`]: if `limit` bytes were read without finding the needle, the
+    /// future fails with an error of kind `InvalidData` wrapping
+    /// [`LimitExceeded`](crate::LimitExceeded), and the bytes read are left in `before`.
+    fn read_until_needle_limited<'a, N>(
+        &'a mut self,
+        needle: N,
+        limit: usize,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+    ) -> ReadUntilNeedle<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a;
+
+    /// Asynchronously reads data until the specified `needle` is found, EOF is reached, or
+    /// `cancel` completes.
+    ///
+    /// This works like [`read_until_needle`](Self::read_until_needle), but stops at the next
+    /// opportunity once `cancel` completes, such as a shutdown signal.
+    ///
+    /// # Returns
+    /// - [`UntilNeedleStatus::Found`] or [`UntilNeedleStatus::Eof`] like `read_until_needle`.
+    /// - [`UntilNeedleStatus::Cancelled`] if `cancel` completed first. Everything read so far has
+    ///   been appended to `before` and the reader is left where the read stopped, so calling
+    ///   `read_until_needle` again with the same buffers continues from there, and still finds
+    ///   a needle whose beginning was read before the cancel.
+    fn read_until_needle_until<'a, N, C>(
+        &'a mut self,
+        needle: N,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+        cancel: C,
+    ) -> ReadUntilNeedleUntil<'a, Self, N, C>
+    where
+        Self: Unpin,
+        N: Needle + 'a,
+        C: Future<Output = ()>;
+
+    /// Asynchronously reads data until the specified `needle` is found or EOF is reached,
+    /// returning owned buffers.
+    ///
+    /// This is [`read_until_needle`](Self::read_until_needle) for callers that just want the
+    /// data back, without threading buffers through the future. [`UntilNeedleOutput::matched`]
+    /// is `None` if EOF was reached before the needle was found.
+    fn read_until_needle_owned<N>(&mut self, needle: N) -> ReadUntilNeedleOwned<'_, Self, N>
+    where
+        Self: Unpin,
+        N: Needle;
+
+    /// Asynchronously reads data until the specified `needle` is found or EOF is reached,
+    /// writing it to any [`OutputBuf`].
+    ///
+    /// This is the async counterpart of
+    /// [`UntilNeedleRead::read_until_needle_into_out`](crate::io::UntilNeedleRead::read_until_needle_into_out).
+    /// The bytes that may begin the needle, at most [`Needle::max_len`] - 1 or the suffix told by
+    /// [`Needle::findin_partial`], are held by the future until the next chunk shows they do not
+    /// begin it, so they are lost if it is dropped.
+    fn read_until_needle_into_out<N, O, M>(
+        &mut self,
+        needle: N,
+        before: O,
+        matched: M,
+    ) -> ReadUntilNeedleIntoOut<'_, Self, N, O, M>
+    where
+        Self: Unpin,
+        N: Needle,
+        O: OutputBuf,
+        M: OutputBuf;
+
+    /// Asynchronously reads data until the specified `needle` is found or EOF is reached,
+    /// accumulating it in a [`BytesMut`].
+    ///
+    /// This is the async counterpart of
+    /// [`UntilNeedleRead::read_until_needle_bytes`](crate::io::UntilNeedleRead::read_until_needle_bytes).
+    /// The data is appended to `buf` as it is read, so if the future is dropped, nothing read so
+    /// far is lost.
+    #[cfg(feature = "bytes")]
+    fn read_until_needle_bytes<'a, N>(
+        &'a mut self,
+        needle: N,
+        buf: &'a mut BytesMut,
+    ) -> ReadUntilNeedleBytes<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a;
+
+    /// Splits the underlying reader into a stream of segments delimited by `needle`.
+    ///
+    /// Each item holds the data before an occurrence of the needle together with the matched
+    /// delimiter. The data after the last delimiter is yielded as a final segment without a
+    /// delimiter; whether it is yielded when empty is set with
+    /// [`SplitByNeedle::emit_empty_trailing`]. The stream ends after yielding an error.
+    fn split_by_needle<N>(self, needle: N) -> SplitByNeedle<Self, N>
+    where
+        Self: Unpin + Sized,
+        N: Needle;
+
+    /// Splits the underlying reader into a stream of frames delimited by `needle`.
+    ///
+    /// This is [`split_by_needle`](Self::split_by_needle) yielding only the data of each
+    /// segment, without the delimiter. The data after the last delimiter is yielded as the final
+    /// frame unless it is empty.
+    fn frames<N>(self, needle: N) -> Frames<Self, N>
+    where
+        Self: Unpin + Sized,
+        N: Needle;
+
+    /// Splits the underlying reader into a stream of Server-Sent Events.
+    ///
+    /// Each item is the raw block of one event, its lines up to the blank line ending it as
+    /// matched by [`SseEventEnd`], without the line endings around
+    /// it. Blocks left empty are skipped. This suits the bodies of streaming HTTP responses
+    /// read through an `AsyncBufRead`.
+    ///
+    /// # Example
+    /// ```
+    /// use futures::TryStreamExt;
+    /// use until_needle::futures::AsyncUntilNeedleRead;
+    ///
+    /// # futures::executor::block_on(async {
+    /// let body = &b": keep-alive\n\ndata: {\"n\": 1}\r\n\r\nevent: done\ndata:\n\n"[..];
+    /// let events: Vec<_> = body.sse_events().try_collect().await.unwrap();
+    /// assert_eq!(events, [&b": keep-alive"[..], b"data: {\"n\": 1}", b"event: done\ndata:"]);
+    /// # });
+    /// ```
+    fn sse_events(self) -> SseEvents<Self>
+    where
+        Self: Unpin + Sized;
+
+    /// Asynchronously skips data until the specified `needle` is found or EOF is reached.
+    ///
+    /// The skipped data is dropped as it is read; only the bytes that may begin a needle
+    /// completed by the next chunk are kept: the last [`Needle::max_len`] - 1 bytes, or the
+    /// suffix told by [`Needle::findin_partial`] for needles without a maximum length. Needles
+    /// with neither keep everything since the call started. The needle is consumed,
+    /// so a following read starts right after it. Like
+    /// [`read_until_needle`](Self::read_until_needle), the future yields after a budget of
+    /// chunks, so it can be raced against a timeout or a cancel signal even when the reader is
+    /// always ready.
+    fn skip_until_needle<N>(&mut self, needle: N) -> SkipUntilNeedle<'_, Self, N>
+    where
+        Self: Unpin,
+        N: Needle;
+
+    /// Creates an adapter reading from the underlying reader up to the next occurrence of
+    /// `needle`.
+    ///
+    /// The adapter implements [`AsyncRead`] and [`AsyncBufRead`] and reports EOF once the needle
+    /// is reached, so it can be handed to consumers such as [`futures_util::io::copy`]. Nothing
+    /// past the needle is consumed from the underlying reader, which is returned by
+    /// [`TakeUntil::into_inner`] to continue reading after the needle.
+    fn take_until<N>(self, needle: N) -> TakeUntil<Self, N>
     where
         Self: Unpin + Sized,
+        N: Needle;
+
+    /// Creates a read until `needle` into `buf`, whose options are chained before awaiting it.
+    ///
+    /// The returned [`ReadUntilNeedleOptions`] implements [`IntoFuture`](std::future::IntoFuture),
+    /// so it is awaited directly once its options are set. It resolves to the
+    /// [`UntilNeedleStatus`] of the read; reaching the limit or the time limit is a status, not
+    /// an error.
+    ///
+    /// # Example
+    /// ```
+    /// use until_needle::futures::AsyncUntilNeedleRead;
+    /// use until_needle::UntilNeedleStatus;
+    ///
+    /// # futures::executor::block_on(async {
+    /// let mut reader = &b"HTTP/1.1 200 OK\r\n\r\nbody"[..];
+    /// let mut head = Vec::new();
+    /// let status = reader
+    ///     .read_until_needle_options(b"\r\n\r\n", &mut head)
+    ///     .limit(1024)
+    ///     .include_match(true)
+    ///     .await?;
+    /// assert_eq!(status, UntilNeedleStatus::Found { bytes_read: 19 });
+    /// assert_eq!(head, b"HTTP/1.1 200 OK\r\n\r\n");
+    /// # Ok::<(), std::io::Error>(())
+    /// # }).unwrap();
+    /// ```
+    fn read_until_needle_options<'a, N>(
+        &'a mut self,
+        needle: N,
+        buf: &'a mut Vec<u8>,
+    ) -> ReadUntilNeedleOptions<'a, Self, N>
+    where
+        Self: Unpin,
         N: Needle + 'a;
+
+    /// Asynchronously reads data from the underlying reader as long as each byte satisfies `pred`.
+    ///
+    /// Reading stops right before the first byte for which `pred` returns `false`; that byte is
+    /// left unconsumed in the reader, so a following read starts with it.
+    ///
+    /// # Arguments
+    /// - `pred`: A predicate deciding whether a byte belongs to the run being read.
+    /// - `out`: A mutable buffer the matching bytes are appended to.
+    ///
+    /// # Returns
+    /// - On success, it returns the number of bytes read. Reaching EOF while the predicate still
+    ///   holds is a normal completion.
+    fn read_while<'a, P>(&'a mut self, pred: P, out: &'a mut Vec<u8>) -> ReadWhile<'a, Self, P>
+    where
+        Self: Unpin,
+        P: Fn(u8) -> bool + 'a;
 }
 
 impl<R> AsyncUntilNeedleRead for R
 where
-    R: AsyncBufRead + Unpin,
+    R: AsyncBufRead + Unpin + ?Sized,
 {
     fn read_until_needle<'a, N>(
         &'a mut self,
@@ -42,31 +315,274 @@ where
         matched: &'a mut Vec<u8>,
     ) -> ReadUntilNeedle<'a, Self, N>
     where
-        Self: Unpin + Sized,
+        Self: Unpin,
         N: Needle + 'a,
     {
         ReadUntilNeedle {
             reader: self,
             needle,
-            buf: Vec::new(),
+            state: SearchState::new().resuming(),
+            before,
+            matched,
+            done: false,
+        }
+    }
+
+    fn read_until_needle_limited<'a, N>(
+        &'a mut self,
+        needle: N,
+        limit: usize,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+    ) -> ReadUntilNeedle<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a,
+    {
+        self.read_until_needle(needle, before, matched).limit(limit)
+    }
+
+    fn read_until_needle_until<'a, N, C>(
+        &'a mut self,
+        needle: N,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+        cancel: C,
+    ) -> ReadUntilNeedleUntil<'a, Self, N, C>
+    where
+        Self: Unpin,
+        N: Needle + 'a,
+        C: Future<Output = ()>,
+    {
+        ReadUntilNeedleUntil {
+            inner: self.read_until_needle(needle, before, matched),
+            cancel,
+        }
+    }
+
+    fn read_until_needle_owned<N>(&mut self, needle: N) -> ReadUntilNeedleOwned<'_, Self, N>
+    where
+        Self: Unpin,
+        N: Needle,
+    {
+        ReadUntilNeedleOwned {
+            reader: self,
+            needle,
+            state: SearchState::new(),
+            before: Vec::new(),
+            matched: Vec::new(),
+        }
+    }
+
+    fn read_until_needle_into_out<N, O, M>(
+        &mut self,
+        needle: N,
+        before: O,
+        matched: M,
+    ) -> ReadUntilNeedleIntoOut<'_, Self, N, O, M>
+    where
+        Self: Unpin,
+        N: Needle,
+        O: OutputBuf,
+        M: OutputBuf,
+    {
+        ReadUntilNeedleIntoOut {
+            reader: self,
+            needle,
+            search: HeldSearch::default(),
             before,
             matched,
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    fn read_until_needle_bytes<'a, N>(
+        &'a mut self,
+        needle: N,
+        buf: &'a mut BytesMut,
+    ) -> ReadUntilNeedleBytes<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a,
+    {
+        ReadUntilNeedleBytes {
+            reader: self,
+            needle,
+            scan_from: buf.len(),
+            buf,
+        }
+    }
+
+    fn split_by_needle<N>(self, needle: N) -> SplitByNeedle<Self, N>
+    where
+        Self: Unpin + Sized,
+        N: Needle,
+    {
+        SplitByNeedle {
+            reader: self,
+            needle,
+            state: SearchState::new(),
+            data: Vec::new(),
+            delimiter: Vec::new(),
+            max_segment_len: usize::MAX,
+            emit_empty_trailing: false,
+            done: false,
+        }
+    }
+
+    fn frames<N>(self, needle: N) -> Frames<Self, N>
+    where
+        Self: Unpin + Sized,
+        N: Needle,
+    {
+        Frames {
+            inner: self.split_by_needle(needle),
+        }
+    }
+
+    fn sse_events(self) -> SseEvents<Self>
+    where
+        Self: Unpin + Sized,
+    {
+        SseEvents {
+            inner: self.frames(SseEventEnd),
+        }
+    }
+
+    fn skip_until_needle<N>(&mut self, needle: N) -> SkipUntilNeedle<'_, Self, N>
+    where
+        Self: Unpin,
+        N: Needle,
+    {
+        SkipUntilNeedle {
+            reader: self,
+            needle,
+            scanner: Scanner::new(),
+            skipped: 0,
+        }
+    }
+
+    fn take_until<N>(self, needle: N) -> TakeUntil<Self, N>
+    where
+        Self: Unpin + Sized,
+        N: Needle,
+    {
+        TakeUntil {
+            inner: self,
+            needle,
+            consume_needle: true,
+            held: Vec::new(),
+            pos: 0,
+            readable: 0,
+            matched: Vec::new(),
+            found: false,
+            eof: false,
+        }
+    }
+
+    fn read_until_needle_options<'a, N>(
+        &'a mut self,
+        needle: N,
+        buf: &'a mut Vec<u8>,
+    ) -> ReadUntilNeedleOptions<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a,
+    {
+        ReadUntilNeedleOptions {
+            reader: self,
+            needle,
+            buf,
+            limit: usize::MAX,
+            include_match: false,
+            timer: std::future::pending(),
+        }
+    }
+
+    fn read_while<'a, P>(&'a mut self, pred: P, out: &'a mut Vec<u8>) -> ReadWhile<'a, Self, P>
+    where
+        Self: Unpin,
+        P: Fn(u8) -> bool + 'a,
+    {
+        ReadWhile {
+            reader: self,
+            pred,
+            out,
             total_bytes_read: 0,
         }
     }
 }
 
 /// A future that reads data until the specified needle is found.
+///
+/// Once it has completed, polling it again panics; it implements [`FusedFuture`], so it can be
+/// used with `select!` directly.
 pub struct ReadUntilNeedle<'a, R, N>
 where
     R: Unpin + ?Sized,
 {
     reader: &'a mut R,
     needle: N,
-    buf: Vec<u8>,
+    state: SearchState,
     before: &'a mut Vec<u8>,
     matched: &'a mut Vec<u8>,
-    total_bytes_read: usize,
+    done: bool,
+}
+
+impl<R: ?Sized + Unpin, N> ReadUntilNeedle<'_, R, N> {
+    /// Sets how many chunks the future processes in a single poll before yielding.
+    ///
+    /// When the reader always has data ready, the future would otherwise run the whole search in
+    /// one poll and starve other tasks on the same executor thread. After processing `chunks`
+    /// chunks, it wakes its task and returns `Poll::Pending` to let other futures run. This does
+    /// not change the result. Defaults to 128.
+    pub fn budget(mut self, chunks: usize) -> Self {
+        self.state = self.state.with_budget(chunks);
+        self
+    }
+
+    /// Sets the maximum number of bytes read before giving up on the needle.
+    ///
+    /// If the needle has not been found within `max_bytes` bytes, the future fails with an
+    /// error of kind `InvalidData` wrapping [`LimitExceeded`](crate::LimitExceeded), after
+    /// appending those bytes to `before`. This bounds the memory used when scanning untrusted
+    /// streams. Defaults to no limit.
+    pub fn limit(mut self, max_bytes: usize) -> Self {
+        self.state = self.state.with_limit(max_bytes);
+        self
+    }
+
+    /// Sets whether the needle is consumed from the reader.
+    ///
+    /// Defaults to `true`. When `false`, the needle is still copied to `matched`, but left in the
+    /// reader for the next read, as when it is the magic starting the next message. See
+    /// [`UntilNeedle::consume_needle`](crate::UntilNeedle::consume_needle) for a needle
+    /// straddling two buffers of the reader.
+    ///
+    /// # Example
+    /// ```
+    /// use futures::io::AsyncReadExt;
+    /// use until_needle::futures::AsyncUntilNeedleRead;
+    ///
+    /// # futures::executor::block_on(async {
+    /// let mut reader = &b"first\x7fELFsecond"[..];
+    /// let (mut before, mut matched) = (Vec::new(), Vec::new());
+    /// let n = reader
+    ///     .read_until_needle(b"\x7fELF", &mut before, &mut matched)
+    ///     .limit(64)
+    ///     .consume_needle(false)
+    ///     .await?;
+    /// assert_eq!((n, &matched[..]), (5, &b"\x7fELF"[..]));
+    /// let mut rest = Vec::new();
+    /// reader.read_to_end(&mut rest).await?;
+    /// assert_eq!(rest, b"\x7fELFsecond");
+    /// # Ok::<(), std::io::Error>(())
+    /// # }).unwrap();
+    /// ```
+    pub fn consume_needle(mut self, consume: bool) -> Self {
+        self.state = self.state.with_consume_needle(consume);
+        self
+    }
 }
 
 impl<R: ?Sized + Unpin, N> Unpin for ReadUntilNeedle<'_, R, N> {}
@@ -82,103 +598,2580 @@ where
         let ReadUntilNeedle {
             reader,
             needle,
-            buf,
+            state,
             before,
             matched,
-            total_bytes_read,
+            done,
         } = &mut *self;
-        let reader = Pin::new(reader);
-        read_until_needle_internal(reader, cx, needle, buf, before, matched, total_bytes_read)
+        assert!(!*done, "`ReadUntilNeedle` polled after completion");
+        let status = ready!(read_until_needle_internal(
+            Pin::new(reader),
+            cx,
+            needle,
+            state,
+            before,
+            matched
+        ));
+        *done = true;
+        Poll::Ready(status.and_then(limit_exceeded))
     }
 }
 
-/// Internal function to read until the needle is found.
-fn read_until_needle_internal<R, N>(
-    mut reader: Pin<&mut R>,
-    cx: &mut Context<'_>,
-    needle: &N,
-    buf: &mut Vec<u8>,
-    before: &mut Vec<u8>,
-    matched: &mut Vec<u8>,
-    total_bytes_read: &mut usize,
-) -> Poll<io::Result<usize>>
+impl<R, N> FusedFuture for ReadUntilNeedle<'_, R, N>
 where
-    R: AsyncBufRead + ?Sized,
+    R: AsyncBufRead + Unpin + ?Sized,
     N: Needle,
 {
-    loop {
-        let (done, used) = {
-            let available = ready!(reader.as_mut().poll_fill_buf(cx))?;
-            buf.extend_from_slice(available);
-            // must consume to detect EOF
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
 
-            if available.is_empty() {
-                // EOF reached
-                before.extend_from_slice(buf);
-                (true, available.len())
-            } else if let Some(range) = needle.findin(buf) {
-                // Needle found
-                before.extend_from_slice(&buf[..range.start]);
-                matched.extend_from_slice(&buf[range.clone()]);
-                (true, available.len() - (buf.len() - range.end))
-            } else {
-                (false, available.len())
-            }
-        };
+pin_project! {
+    /// A future that reads data until the specified needle is found or a cancel signal
+    /// completes.
+    ///
+    /// This struct is created by [`AsyncUntilNeedleRead::read_until_needle_until`].
+    pub struct ReadUntilNeedleUntil<'a, R, N, C>
+    where
+        R: Unpin,
+        R: ?Sized,
+    {
+        inner: ReadUntilNeedle<'a, R, N>,
+        #[pin]
+        cancel: C,
+    }
+}
 
-        reader.as_mut().consume(used);
-        *total_bytes_read += used;
+impl<R, N, C> Future for ReadUntilNeedleUntil<'_, R, N, C>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+    C: Future<Output = ()>,
+{
+    type Output = io::Result<UntilNeedleStatus>;
 
-        if done {
-            return Poll::Ready(Ok(mem::replace(total_bytes_read, 0)));
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let ReadUntilNeedle {
+            reader,
+            needle,
+            state,
+            before,
+            matched,
+            ..
+        } = this.inner;
+
+        let reader = Pin::new(&mut **reader);
+        if let Poll::Ready(status) =
+            read_until_needle_internal(reader, cx, needle, state, before, matched)
+        {
+            return Poll::Ready(status);
         }
+
+        ready!(this.cancel.poll(cx));
+        Poll::Ready(Ok(state.finish_with(|bytes_read| {
+            UntilNeedleStatus::Cancelled { bytes_read }
+        })))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use futures::{
-        stream::{iter, TryStreamExt as _},
-        AsyncReadExt as _,
-    };
+/// The options of a read until a needle, turned into a [`ReadUntilNeedleOptionsFuture`] by
+/// awaiting them.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::read_until_needle_options`]. `T` is the
+/// timer set by [`timeout`](Self::timeout).
+pub struct ReadUntilNeedleOptions<'a, R: ?Sized, N, T = Pending<()>> {
+    reader: &'a mut R,
+    needle: N,
+    buf: &'a mut Vec<u8>,
+    limit: usize,
+    include_match: bool,
+    timer: T,
+}
 
-    #[tokio::test]
-    async fn test_async_read() {
-        let mut stream = iter(vec![
-            Ok(b"hello".to_vec()),
-            Ok(b" wo".to_vec()),
-            Ok(b"rld!".to_vec()),
-        ])
-        .into_async_read();
-        let mut buf = Vec::new();
-        stream.read_to_end(&mut buf).await.unwrap();
-        assert_eq!(buf, b"hello world!");
+impl<'a, R: ?Sized, N, T> ReadUntilNeedleOptions<'a, R, N, T> {
+    /// Sets the maximum number of bytes consumed from the reader.
+    ///
+    /// If the needle has not been found within `limit` bytes, the read resolves to
+    /// [`UntilNeedleStatus::LimitReached`] after appending them to the buffer. See
+    /// [`UntilNeedle::limit`](crate::UntilNeedle::limit). Defaults to no limit.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
     }
 
-    #[tokio::test]
-    async fn test_read_until_needle() {
-        let mut stream = iter(vec![
-            Ok(b"hello".to_vec()),
-            Ok(b" wo".to_vec()),
-            Ok(b"rld!!".to_vec()),
-        ])
-        .into_async_read();
-
-        let mut before = Vec::new();
-        let mut matched = Vec::new();
-        let mut buf = Vec::new();
+    /// Sets whether the needle is appended to the buffer after the data before it.
+    ///
+    /// Defaults to `false`, which consumes the needle without keeping it, as a line reader drops
+    /// the line ending. When `true`, the buffer ends with the needle, as with `read_until`.
+    pub fn include_match(mut self, include: bool) -> Self {
+        self.include_match = include;
+        self
+    }
 
-        assert_eq!(
-            stream
-                .read_until_needle(b"world", &mut before, &mut matched)
-                .await
+    /// Gives up on the needle once `timer` completes.
+    ///
+    /// The read then resolves to [`UntilNeedleStatus::TimedOut`], with the bytes read so far
+    /// appended to the buffer. Any future works as the timer, so the read is not tied to a
+    /// runtime: a `tokio::time::sleep`, an `async_io::Timer` or a `futures_timer::Delay`. The
+    /// timer is only polled while the read is. Defaults to no time limit.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "tokio")]
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> std::io::Result<()> {
+    /// use futures::TryStreamExt as _;
+    /// use std::time::Duration;
+    /// use until_needle::futures::AsyncUntilNeedleRead;
+    /// use until_needle::UntilNeedleStatus;
+    ///
+    /// let (tx, rx) = futures::channel::mpsc::unbounded::<std::io::Result<Vec<u8>>>();
+    /// let mut reader = rx.into_async_read();
+    /// tx.unbounded_send(Ok(b"booting".to_vec())).unwrap();
+    /// let mut buf = Vec::new();
+    /// let status = reader
+    ///     .read_until_needle_options("$ ", &mut buf)
+    ///     .timeout(tokio::time::sleep(Duration::from_millis(10)))
+    ///     .await?;
+    /// assert_eq!(status, UntilNeedleStatus::TimedOut { bytes_read: 7 });
+    /// assert_eq!(buf, b"booting");
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "tokio"))]
+    /// # fn main() {}
+    /// ```
+    pub fn timeout<U>(self, timer: U) -> ReadUntilNeedleOptions<'a, R, N, U>
+    where
+        U: Future<Output = ()>,
+    {
+        ReadUntilNeedleOptions {
+            reader: self.reader,
+            needle: self.needle,
+            buf: self.buf,
+            limit: self.limit,
+            include_match: self.include_match,
+            timer,
+        }
+    }
+}
+
+impl<'a, R, N, T> std::future::IntoFuture for ReadUntilNeedleOptions<'a, R, N, T>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+    T: Future<Output = ()>,
+{
+    type Output = io::Result<UntilNeedleStatus>;
+    type IntoFuture = ReadUntilNeedleOptionsFuture<'a, R, N, T>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        ReadUntilNeedleOptionsFuture {
+            reader: self.reader,
+            needle: self.needle,
+            state: SearchState::new().with_limit(self.limit),
+            buf: self.buf,
+            matched: Vec::new(),
+            include_match: self.include_match,
+            timer: self.timer,
+            done: false,
+        }
+    }
+}
+
+pin_project! {
+    /// A future that reads data until the specified needle is found, with the options of a
+    /// [`ReadUntilNeedleOptions`].
+    ///
+    /// Once it has completed, polling it again panics; it implements [`FusedFuture`], so it can
+    /// be used with `select!` directly.
+    pub struct ReadUntilNeedleOptionsFuture<'a, R, N, T>
+    where
+        R: ?Sized,
+    {
+        reader: &'a mut R,
+        needle: N,
+        state: SearchState,
+        buf: &'a mut Vec<u8>,
+        matched: Vec<u8>,
+        include_match: bool,
+        #[pin]
+        timer: T,
+        done: bool,
+    }
+}
+
+impl<R, N, T> Future for ReadUntilNeedleOptionsFuture<'_, R, N, T>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+    T: Future<Output = ()>,
+{
+    type Output = io::Result<UntilNeedleStatus>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        assert!(
+            !*this.done,
+            "`ReadUntilNeedleOptionsFuture` polled after completion"
+        );
+        let reader = Pin::new(&mut **this.reader);
+        if let Poll::Ready(status) =
+            read_until_needle_internal(reader, cx, this.needle, this.state, this.buf, this.matched)
+        {
+            *this.done = true;
+            if *this.include_match {
+                this.buf.append(this.matched);
+            }
+            return Poll::Ready(status);
+        }
+
+        ready!(this.timer.poll(cx));
+        *this.done = true;
+        Poll::Ready(Ok(this.state.finish_with(|bytes_read| {
+            UntilNeedleStatus::TimedOut { bytes_read }
+        })))
+    }
+}
+
+impl<R, N, T> FusedFuture for ReadUntilNeedleOptionsFuture<'_, R, N, T>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+    T: Future<Output = ()>,
+{
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+/// A future that reads data until the specified needle is found, with the options of an
+/// [`UntilNeedle`](crate::UntilNeedle).
+///
+/// This struct is created by [`UntilNeedle::read_async`](crate::UntilNeedle::read_async).
+pub struct ReadUntilNeedleWith<'a, R, N>
+where
+    R: Unpin + ?Sized,
+{
+    reader: &'a mut R,
+    needle: &'a N,
+    state: SearchState,
+    before: &'a mut Vec<u8>,
+    matched: &'a mut Vec<u8>,
+}
+
+impl<'a, R: ?Sized + Unpin, N> ReadUntilNeedleWith<'a, R, N> {
+    pub(crate) fn new(
+        reader: &'a mut R,
+        needle: &'a N,
+        state: SearchState,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+    ) -> Self {
+        Self {
+            reader,
+            needle,
+            state,
+            before,
+            matched,
+        }
+    }
+}
+
+impl<R: ?Sized + Unpin, N> Unpin for ReadUntilNeedleWith<'_, R, N> {}
+
+impl<R, N> Future for ReadUntilNeedleWith<'_, R, N>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+{
+    type Output = io::Result<UntilNeedleStatus>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ReadUntilNeedleWith {
+            reader,
+            needle,
+            state,
+            before,
+            matched,
+        } = &mut *self;
+        let reader = Pin::new(&mut **reader);
+        read_until_needle_internal(reader, cx, *needle, state, before, matched)
+    }
+}
+
+/// Attempts to read data from `reader` until `needle` is found or EOF is reached.
+///
+/// This is the poll-based counterpart of [`AsyncUntilNeedleRead::read_until_needle`], for use
+/// inside a hand-written [`Future`] or `Stream` that cannot borrow the reader for the lifetime of
+/// a [`ReadUntilNeedle`] future. The progress of the search is kept in `state`, which must be
+/// passed together with the same `before` buffer on every call until `Poll::Ready` is returned.
+/// The state is then reset and can be used for the next search.
+///
+/// Returns `Poll::Ready(Ok(n))` with the total number of bytes read, including the needle, once
+/// the needle is found or EOF is reached. `Poll::Pending` is returned when the reader is not
+/// ready, or after the [budget](SearchState::with_budget) of chunks is used up.
+///
+/// # Example
+/// ```
+/// use futures::io::AsyncBufRead;
+/// use std::future::Future;
+/// use std::io;
+/// use std::pin::Pin;
+/// use std::task::{Context, Poll};
+/// use until_needle::futures::{poll_read_until_needle, SearchState};
+///
+/// /// Reads the line following a prompt.
+/// struct Prompt<R> {
+///     reader: R,
+///     state: SearchState,
+///     line: Vec<u8>,
+///     newline: Vec<u8>,
+/// }
+///
+/// impl<R: AsyncBufRead + Unpin> Future for Prompt<R> {
+///     type Output = io::Result<Vec<u8>>;
+///
+///     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+///         let this = &mut *self;
+///         let n = futures::ready!(poll_read_until_needle(
+///             Pin::new(&mut this.reader),
+///             cx,
+///             &b"\n"[..],
+///             &mut this.state,
+///             &mut this.line,
+///             &mut this.newline,
+///         ))?;
+///         assert_eq!(n, this.line.len() + this.newline.len());
+///         Poll::Ready(Ok(std::mem::take(&mut this.line)))
+///     }
+/// }
+///
+/// # futures::executor::block_on(async {
+/// let prompt = Prompt {
+///     reader: &b"$ ls\nfile\n"[..],
+///     state: SearchState::new(),
+///     line: Vec::new(),
+///     newline: Vec::new(),
+/// };
+/// assert_eq!(prompt.await.unwrap(), b"$ ls");
+/// # });
+/// ```
+pub fn poll_read_until_needle<R, N>(
+    reader: Pin<&mut R>,
+    cx: &mut Context<'_>,
+    needle: &N,
+    state: &mut SearchState,
+    before: &mut Vec<u8>,
+    matched: &mut Vec<u8>,
+) -> Poll<io::Result<usize>>
+where
+    R: AsyncBufRead + ?Sized,
+    N: Needle + ?Sized,
+{
+    read_until_needle_internal(reader, cx, needle, state, before, matched)
+        .map_ok(|status| status.bytes_read())
+}
+
+/// Internal function to read until the needle is found.
+fn read_until_needle_internal<R, N>(
+    mut reader: Pin<&mut R>,
+    cx: &mut Context<'_>,
+    needle: &N,
+    state: &mut SearchState,
+    before: &mut Vec<u8>,
+    matched: &mut Vec<u8>,
+) -> Poll<io::Result<UntilNeedleStatus>>
+where
+    R: AsyncBufRead + ?Sized,
+    N: Needle + ?Sized,
+{
+    let mut budget = state.budget();
+    loop {
+        if budget == 0 {
+            // yield to other tasks; the search continues on the next poll
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        budget -= 1;
+
+        let (done, used) = {
+            let available = ready!(reader.as_mut().poll_fill_buf(cx))?;
+            state.step(needle, available, before, matched)
+        };
+
+        reader.as_mut().consume(used);
+
+        if done {
+            return Poll::Ready(Ok(state.finish()));
+        }
+    }
+}
+
+/// A future that reads data until the specified needle is found, returning owned buffers.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::read_until_needle_owned`].
+pub struct ReadUntilNeedleOwned<'a, R, N>
+where
+    R: Unpin + ?Sized,
+{
+    reader: &'a mut R,
+    needle: N,
+    state: SearchState,
+    before: Vec<u8>,
+    matched: Vec<u8>,
+}
+
+impl<R: ?Sized + Unpin, N> Unpin for ReadUntilNeedleOwned<'_, R, N> {}
+
+impl<R, N> Future for ReadUntilNeedleOwned<'_, R, N>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+{
+    type Output = io::Result<UntilNeedleOutput>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ReadUntilNeedleOwned {
+            reader,
+            needle,
+            state,
+            before,
+            matched,
+        } = &mut *self;
+        let reader = Pin::new(&mut **reader);
+        let status = ready!(read_until_needle_internal(
+            reader, cx, needle, state, before, matched
+        ))?;
+        Poll::Ready(Ok(UntilNeedleOutput {
+            before: mem::take(before),
+            matched: status.is_found().then(|| mem::take(matched)),
+            bytes_read: status.bytes_read(),
+        }))
+    }
+}
+
+/// A future that reads data into an [`OutputBuf`] until the specified needle is found.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::read_until_needle_into_out`].
+pub struct ReadUntilNeedleIntoOut<'a, R, N, O, M>
+where
+    R: Unpin + ?Sized,
+{
+    reader: &'a mut R,
+    needle: N,
+    search: HeldSearch,
+    before: O,
+    matched: M,
+}
+
+impl<R: ?Sized + Unpin, N, O, M> Unpin for ReadUntilNeedleIntoOut<'_, R, N, O, M> {}
+
+impl<R, N, O, M> Future for ReadUntilNeedleIntoOut<'_, R, N, O, M>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+    O: OutputBuf,
+    M: OutputBuf,
+{
+    type Output = io::Result<UntilNeedleStatus>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            let (used, status) = {
+                let available = ready!(Pin::new(&mut *this.reader).poll_fill_buf(cx))?;
+                if available.is_empty() {
+                    // EOF
+                    return Poll::Ready(Ok(this.search.finish(&mut this.before)));
+                }
+                this.search
+                    .push(&this.needle, available, &mut this.before, &mut this.matched)
+            };
+
+            Pin::new(&mut *this.reader).consume(used);
+            if let Some(status) = status {
+                return Poll::Ready(Ok(status));
+            }
+        }
+    }
+}
+
+/// A future that reads data into a [`BytesMut`] until the specified needle is found.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::read_until_needle_bytes`].
+#[cfg(feature = "bytes")]
+pub struct ReadUntilNeedleBytes<'a, R, N>
+where
+    R: Unpin + ?Sized,
+{
+    reader: &'a mut R,
+    needle: N,
+    buf: &'a mut BytesMut,
+    scan_from: usize,
+}
+
+#[cfg(feature = "bytes")]
+impl<R: ?Sized + Unpin, N> Unpin for ReadUntilNeedleBytes<'_, R, N> {}
+
+#[cfg(feature = "bytes")]
+impl<R, N> Future for ReadUntilNeedleBytes<'_, R, N>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+{
+    type Output = io::Result<Option<(Bytes, Bytes)>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            let (found, used) = {
+                let available = ready!(Pin::new(&mut *this.reader).poll_fill_buf(cx))?;
+                if available.is_empty() {
+                    // EOF
+                    return Poll::Ready(Ok(None));
+                }
+                crate::io::search_bytes_mut(&this.needle, available, this.buf, &mut this.scan_from)
+            };
+
+            Pin::new(&mut *this.reader).consume(used);
+            if let Some(range) = found {
+                return Poll::Ready(Ok(Some(crate::io::split_bytes_mut(this.buf, range))));
+            }
+        }
+    }
+}
+
+/// A future that skips data until the specified needle is found.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::skip_until_needle`].
+pub struct SkipUntilNeedle<'a, R, N>
+where
+    R: Unpin + ?Sized,
+{
+    reader: &'a mut R,
+    needle: N,
+    scanner: Scanner,
+    skipped: usize,
+}
+
+impl<R: ?Sized + Unpin, N> Unpin for SkipUntilNeedle<'_, R, N> {}
+
+impl<R, N> Future for SkipUntilNeedle<'_, R, N>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+{
+    type Output = io::Result<SkipReport>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut budget = DEFAULT_BUDGET;
+        loop {
+            if budget == 0 {
+                // yield to other tasks; the search continues on the next poll
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            budget -= 1;
+
+            let (matched, used) = {
+                let available = ready!(Pin::new(&mut *this.reader).poll_fill_buf(cx))?;
+                if available.is_empty() {
+                    // EOF
+                    this.scanner.finish();
+                    return Poll::Ready(Ok(SkipReport {
+                        skipped: mem::take(&mut this.skipped),
+                        matched: None,
+                    }));
+                }
+
+                // the scanner keeps only the bytes that may begin a match straddling chunks
+                match this.scanner.push(&this.needle, available) {
+                    SearchStep::Found {
+                        before,
+                        retract,
+                        matched,
+                        consumed,
+                    } => {
+                        this.skipped = this.skipped + before - retract;
+                        let matched = matched.to_vec();
+                        this.scanner.finish();
+                        (Some(matched), consumed)
+                    }
+                    step => {
+                        this.skipped += step.consumed();
+                        (None, step.consumed())
+                    }
+                }
+            };
+
+            Pin::new(&mut *this.reader).consume(used);
+            if let Some(matched) = matched {
+                return Poll::Ready(Ok(SkipReport {
+                    skipped: mem::take(&mut this.skipped),
+                    matched: Some(matched),
+                }));
+            }
+        }
+    }
+}
+
+/// A segment yielded by [`SplitByNeedle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// The data before the delimiter.
+    pub data: Vec<u8>,
+    /// The delimiter that terminated the segment, or `None` for the final segment at EOF.
+    pub delimiter: Option<Vec<u8>>,
+}
+
+/// A stream of the segments of a reader delimited by a needle.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::split_by_needle`].
+#[derive(Debug)]
+pub struct SplitByNeedle<R, N> {
+    reader: R,
+    needle: N,
+    state: SearchState,
+    data: Vec<u8>,
+    delimiter: Vec<u8>,
+    max_segment_len: usize,
+    emit_empty_trailing: bool,
+    done: bool,
+}
+
+impl<R, N> SplitByNeedle<R, N> {
+    /// Sets the maximum number of bytes buffered for a single segment, including its delimiter.
+    ///
+    /// A segment growing beyond this makes the stream yield an error of kind `InvalidData` and
+    /// end, instead of buffering without bound.
+    pub fn max_segment_len(mut self, max: usize) -> Self {
+        self.max_segment_len = max;
+        self
+    }
+
+    /// Sets whether an empty final segment is yielded when the reader ends right after a
+    /// delimiter (or is empty). Defaults to `false`.
+    pub fn emit_empty_trailing(mut self, emit: bool) -> Self {
+        self.emit_empty_trailing = emit;
+        self
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Unwraps this `SplitByNeedle`, returning the underlying reader.
+    ///
+    /// Data of the current segment that was already read is lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R, N> Stream for SplitByNeedle<R, N>
+where
+    R: AsyncBufRead + Unpin,
+    N: Needle + Unpin,
+{
+    type Item = io::Result<Segment>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        let this = &mut *self;
+        let reader = Pin::new(&mut this.reader);
+        let status = read_until_needle_internal(
+            reader,
+            cx,
+            &this.needle,
+            &mut this.state,
+            &mut this.data,
+            &mut this.delimiter,
+        );
+
+        if this.data.len() + this.delimiter.len() > this.max_segment_len {
+            this.done = true;
+            return Poll::Ready(Some(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "segment exceeds the maximum length",
+            ))));
+        }
+
+        let segment = match ready!(status) {
+            Ok(UntilNeedleStatus::Found { .. }) => Segment {
+                data: mem::take(&mut this.data),
+                delimiter: Some(mem::take(&mut this.delimiter)),
+            },
+            Ok(_) => {
+                this.done = true;
+                if this.data.is_empty() && !this.emit_empty_trailing {
+                    return Poll::Ready(None);
+                }
+                Segment {
+                    data: mem::take(&mut this.data),
+                    delimiter: None,
+                }
+            }
+            Err(e) => {
+                this.done = true;
+                return Poll::Ready(Some(Err(e)));
+            }
+        };
+        Poll::Ready(Some(Ok(segment)))
+    }
+}
+
+impl<R, N> FusedStream for SplitByNeedle<R, N>
+where
+    R: AsyncBufRead + Unpin,
+    N: Needle + Unpin,
+{
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+/// A stream of the frames of a reader delimited by a needle.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::frames`].
+#[derive(Debug)]
+pub struct Frames<R, N> {
+    inner: SplitByNeedle<R, N>,
+}
+
+impl<R, N> Frames<R, N> {
+    /// Sets the maximum number of bytes buffered for a single frame, including its delimiter.
+    ///
+    /// See [`SplitByNeedle::max_segment_len`].
+    pub fn max_frame_len(mut self, max: usize) -> Self {
+        self.inner = self.inner.max_segment_len(max);
+        self
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut()
+    }
+
+    /// Unwraps this `Frames`, returning the underlying reader.
+    ///
+    /// Data of the current frame that was already read is lost.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R, N> Stream for Frames<R, N>
+where
+    R: AsyncBufRead + Unpin,
+    N: Needle + Unpin,
+{
+    type Item = io::Result<Vec<u8>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let segment = ready!(Pin::new(&mut self.inner).poll_next(cx));
+        Poll::Ready(segment.map(|segment| segment.map(|segment| segment.data)))
+    }
+}
+
+impl<R, N> FusedStream for Frames<R, N>
+where
+    R: AsyncBufRead + Unpin,
+    N: Needle + Unpin,
+{
+    fn is_terminated(&self) -> bool {
+        self.inner.is_terminated()
+    }
+}
+
+/// A stream of the Server-Sent Events of a reader.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::sse_events`].
+#[derive(Debug)]
+pub struct SseEvents<R> {
+    inner: Frames<R, SseEventEnd>,
+}
+
+impl<R> SseEvents<R> {
+    /// Sets the maximum number of bytes buffered for a single event.
+    ///
+    /// See [`SplitByNeedle::max_segment_len`].
+    pub fn max_event_len(mut self, max: usize) -> Self {
+        self.inner = self.inner.max_frame_len(max);
+        self
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut()
+    }
+
+    /// Unwraps this `SseEvents`, returning the underlying reader.
+    ///
+    /// Data of the current event that was already read is lost.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for SseEvents<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let mut event = match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+                Some(Ok(event)) => event,
+                other => return Poll::Ready(other),
+            };
+            // a `\n` left by an event ended with `\r`, or blank lines before the first event
+            let start = event.iter().position(|&b| b != b'\r' && b != b'\n');
+            let Some(start) = start else { continue };
+            let end = event.iter().rposition(|&b| b != b'\r' && b != b'\n');
+            event.truncate(end.map_or(0, |end| end + 1));
+            event.drain(..start);
+            return Poll::Ready(Some(Ok(event)));
+        }
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> FusedStream for SseEvents<R> {
+    fn is_terminated(&self) -> bool {
+        self.inner.is_terminated()
+    }
+}
+
+/// A reader that reads from the underlying reader up to the next occurrence of a needle.
+///
+/// As long as the data buffered by the underlying reader is enough to rule out a needle, it is
+/// passed through without copying. Only the last bytes of a buffer that may begin a needle
+/// completed by the next buffer are taken from the underlying reader and held by the adapter
+/// until the next buffer decides it. Needles without a [maximum length](Needle::max_len) may
+/// span any number of buffers, so all data is held until the needle is found.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::take_until`].
+#[derive(Debug)]
+pub struct TakeUntil<R, N> {
+    inner: R,
+    needle: N,
+    consume_needle: bool,
+    /// Data taken from the inner reader; `held[pos..readable]` can be read through the adapter.
+    held: Vec<u8>,
+    pos: usize,
+    readable: usize,
+    matched: Vec<u8>,
+    found: bool,
+    eof: bool,
+}
+
+impl<R, N> TakeUntil<R, N> {
+    /// Sets whether the needle is consumed from the underlying reader once it is reached.
+    ///
+    /// Defaults to `true`, leaving the underlying reader positioned right after the needle. When
+    /// `false`, it is left positioned at the needle, so the needle is read next. If the needle
+    /// was split across buffers of the underlying reader, its beginning has already been taken
+    /// and is returned by [`into_inner`](Self::into_inner) instead.
+    pub fn consume_needle(mut self, consume: bool) -> Self {
+        self.consume_needle = consume;
+        self
+    }
+
+    /// Returns `true` if the needle has been reached, and `false` if it has not been reached yet
+    /// or the underlying reader reached EOF first.
+    pub fn is_found(&self) -> bool {
+        self.found
+    }
+
+    /// Returns the needle once it has been reached.
+    pub fn matched(&self) -> Option<&[u8]> {
+        self.found.then_some(&self.matched[..])
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps this `TakeUntil`, returning the underlying reader and the data taken from it that
+    /// has not been read through the adapter.
+    ///
+    /// The held data comes before anything still in the underlying reader.
+    pub fn into_inner(mut self) -> (R, Vec<u8>) {
+        self.held.drain(..self.pos);
+        (self.inner, self.held)
+    }
+}
+
+impl<R, N> AsyncRead for TakeUntil<R, N>
+where
+    R: AsyncBufRead + Unpin,
+    N: Needle + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let available = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<R, N> AsyncBufRead for TakeUntil<R, N>
+where
+    R: AsyncBufRead + Unpin,
+    N: Needle + Unpin,
+{
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        loop {
+            if this.pos < this.readable {
+                return Poll::Ready(Ok(&this.held[this.pos..this.readable]));
+            }
+            if this.found || this.eof {
+                return Poll::Ready(Ok(&[]));
+            }
+
+            if this.pos == this.held.len() {
+                this.held.clear();
+                this.pos = 0;
+                this.readable = 0;
+                // search the buffer of the inner reader; only the suffix that may begin a
+                // needle completed later is taken from it, as the `Scanner` keeps it
+                let (pass, used) = {
+                    let available = ready!(Pin::new(&mut this.inner).poll_fill_buf(cx))?;
+                    if available.is_empty() {
+                        this.eof = true;
+                        continue;
+                    }
+                    match this.needle.findin_partial(available) {
+                        PartialMatch::Full(range) if range.start > 0 => (range.start, 0),
+                        PartialMatch::Full(range) => {
+                            this.found = true;
+                            this.matched.extend_from_slice(&available[range.clone()]);
+                            (0, if this.consume_needle { range.end } else { 0 })
+                        }
+                        PartialMatch::Prefix(start) if start > 0 => (start, 0),
+                        PartialMatch::Prefix(_) => {
+                            this.held.extend_from_slice(available);
+                            (0, available.len())
+                        }
+                        PartialMatch::None => (available.len(), 0),
+                    }
+                };
+                Pin::new(&mut this.inner).consume(used);
+                if pass > 0 {
+                    let available = ready!(Pin::new(&mut this.inner).poll_fill_buf(cx))?;
+                    return Poll::Ready(Ok(&available[..pass]));
+                }
+                continue;
+            }
+
+            // the held data may begin a needle; search it together with the next buffer
+            let used = {
+                let available = ready!(Pin::new(&mut this.inner).poll_fill_buf(cx))?;
+                let held_len = this.held.len() - this.pos;
+                if available.is_empty() {
+                    this.eof = true;
+                    this.readable = this.held.len();
+                    0
+                } else {
+                    // a needle starting in the held bytes ends within the next `max_len - 1`
+                    let head = match this.needle.max_len() {
+                        Some(len) => &available[..available.len().min(len.saturating_sub(1))],
+                        None => available,
+                    };
+                    let mut window = Vec::with_capacity(held_len + head.len());
+                    window.extend_from_slice(&this.held[this.pos..]);
+                    window.extend_from_slice(head);
+                    match this.needle.findin_partial(&window) {
+                        PartialMatch::Full(range) if range.start < held_len => {
+                            this.found = true;
+                            this.matched.extend_from_slice(&window[range.clone()]);
+                            this.readable = this.pos + range.start;
+                            if this.consume_needle {
+                                this.held.truncate(this.readable);
+                                range.end.saturating_sub(held_len)
+                            } else {
+                                0
+                            }
+                        }
+                        // the held bytes that cannot begin a needle are passed on
+                        PartialMatch::Prefix(start) if start > 0 => {
+                            this.readable = this.pos + start.min(held_len);
+                            0
+                        }
+                        PartialMatch::Prefix(_) => {
+                            this.held.extend_from_slice(available);
+                            available.len()
+                        }
+                        PartialMatch::Full(_) | PartialMatch::None => {
+                            this.readable = this.held.len();
+                            0
+                        }
+                    }
+                }
+            };
+            Pin::new(&mut this.inner).consume(used);
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        if this.pos < this.readable {
+            this.pos = (this.pos + amt).min(this.readable);
+        } else {
+            Pin::new(&mut this.inner).consume(amt);
+        }
+    }
+}
+
+/// Copies data from `reader` to `writer` until `needle` is found or EOF is reached.
+///
+/// The data before the needle is written as it is read, without being collected first. Data is
+/// consumed from the reader only once the writer has accepted it, so a slow writer slows down
+/// reading. The needle itself is consumed and, by default, not written; see
+/// [`CopyUntilNeedle::forward_needle`]. The writer is flushed before the future completes.
+///
+/// # Cancel safety
+///
+/// Dropping the future before it completes loses no data written so far and leaves the rest in
+/// the reader, except for the bytes that may begin the needle and were taken from the reader to
+/// be checked against its next buffer. Those are at most [`Needle::max_len`] - 1 bytes, or the
+/// suffix told by [`Needle::findin_partial`] for needles without a maximum length.
+pub fn copy_until_needle<'a, R, W, N>(
+    reader: &'a mut R,
+    writer: &'a mut W,
+    needle: N,
+) -> CopyUntilNeedle<'a, R, W, N>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+    N: Needle,
+{
+    CopyUntilNeedle {
+        take: AsyncUntilNeedleRead::take_until(reader, needle),
+        writer,
+        forward_needle: false,
+        copied: 0,
+        needle_written: 0,
+        need_flush: false,
+    }
+}
+
+/// A future that copies data from a reader to a writer until the specified needle is found.
+///
+/// This struct is created by [`copy_until_needle`].
+pub struct CopyUntilNeedle<'a, R, W, N>
+where
+    R: ?Sized,
+    W: ?Sized,
+{
+    take: TakeUntil<&'a mut R, N>,
+    writer: &'a mut W,
+    forward_needle: bool,
+    copied: usize,
+    /// How much of the needle has been written, when forwarding it.
+    needle_written: usize,
+    need_flush: bool,
+}
+
+impl<R: ?Sized, W: ?Sized, N> CopyUntilNeedle<'_, R, W, N> {
+    /// Sets whether the needle is written to the writer after the data before it.
+    ///
+    /// Defaults to `false`.
+    pub fn forward_needle(mut self, forward: bool) -> Self {
+        self.forward_needle = forward;
+        self
+    }
+}
+
+impl<R: ?Sized, W: ?Sized, N> Unpin for CopyUntilNeedle<'_, R, W, N> {}
+
+impl<R, W, N> Future for CopyUntilNeedle<'_, R, W, N>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+    N: Needle + Unpin,
+{
+    type Output = io::Result<CopyReport>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            let available = match Pin::new(&mut this.take).poll_fill_buf(cx) {
+                Poll::Ready(result) => result?,
+                Poll::Pending => {
+                    // pass on what has been written while waiting for more data
+                    if this.need_flush {
+                        ready!(Pin::new(&mut *this.writer).poll_flush(cx))?;
+                        this.need_flush = false;
+                    }
+                    return Poll::Pending;
+                }
+            };
+            if available.is_empty() {
+                break;
+            }
+            let n = ready!(Pin::new(&mut *this.writer).poll_write(cx, available))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            Pin::new(&mut this.take).consume(n);
+            this.copied += n;
+            this.need_flush = true;
+        }
+
+        if let (true, Some(matched)) = (this.forward_needle, this.take.matched()) {
+            while this.needle_written < matched.len() {
+                let rest = &matched[this.needle_written..];
+                let n = ready!(Pin::new(&mut *this.writer).poll_write(cx, rest))?;
+                if n == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                }
+                this.needle_written += n;
+                this.copied += n;
+            }
+        }
+        ready!(Pin::new(&mut *this.writer).poll_flush(cx))?;
+        this.need_flush = false;
+
+        Poll::Ready(Ok(CopyReport {
+            copied: this.copied,
+            matched: this.take.matched().map(<[u8]>::to_vec),
+        }))
+    }
+}
+
+/// A buffered reader for plain [`AsyncRead`] sources, tailored to needle searches.
+///
+/// It implements [`AsyncBufRead`] itself, so every method of [`AsyncUntilNeedleRead`] is
+/// available. Its own [`read_until_needle`](Self::read_until_needle) searches in place: the
+/// buffer grows while a match is pending, instead of being limited by a fixed capacity, and only
+/// the data up to the end of the match is consumed.
+#[derive(Debug)]
+pub struct UntilNeedleAsyncReader<R> {
+    inner: R,
+    /// Buffered data; `buf[pos..]` has not been consumed yet.
+    buf: Vec<u8>,
+    pos: usize,
+    /// How many bytes to read from the inner reader at once.
+    capacity: usize,
+}
+
+impl<R: AsyncRead> UntilNeedleAsyncReader<R> {
+    /// Creates a new reader reading 8 KiB at a time from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(8 * 1024, inner)
+    }
+
+    /// Creates a new reader reading `capacity` bytes at a time from `inner`.
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(capacity),
+            pos: 0,
+            capacity: capacity.max(1),
+        }
+    }
+}
+
+impl<R> UntilNeedleAsyncReader<R> {
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns the buffered data that has not been consumed yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+
+    /// Unwraps this `UntilNeedleAsyncReader`, returning the underlying reader and the buffered
+    /// data that has not been consumed yet.
+    pub fn into_inner(mut self) -> (R, Vec<u8>) {
+        self.buf.drain(..self.pos);
+        (self.inner, self.buf)
+    }
+}
+
+impl<R: AsyncRead + Unpin> UntilNeedleAsyncReader<R> {
+    /// Asynchronously reads data until the specified `needle` is found or EOF is reached.
+    ///
+    /// This has the same semantics as [`AsyncUntilNeedleRead::read_until_needle`], but the data
+    /// is searched inside this reader's buffer and copied to `before` and `matched` only when the
+    /// read completes.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe. Nothing is consumed until the read completes, so if the future
+    /// is dropped, calling this method again searches the same data again.
+    pub fn read_until_needle<'a, N>(
+        &'a mut self,
+        needle: N,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+    ) -> ReadUntilNeedleInPlace<'a, R, N>
+    where
+        N: Needle + 'a,
+    {
+        ReadUntilNeedleInPlace {
+            reader: self,
+            needle,
+            before,
+            matched,
+            scan_from: 0,
+        }
+    }
+
+    /// Asynchronously reads data until the specified `needle` is found or EOF is reached,
+    /// without consuming it.
+    ///
+    /// Resolves to the range of the needle in [`buffer`](Self::buffer), or `None` if EOF was
+    /// reached first. All the data read stays buffered, so the following reads see it again, for
+    /// example to sniff a protocol before handing the reader to its handler.
+    pub fn peek_until<N: Needle>(&mut self, needle: N) -> PeekUntilNeedle<'_, R, N> {
+        PeekUntilNeedle {
+            reader: self,
+            needle,
+            scan_from: 0,
+        }
+    }
+
+    /// Reads more data from the inner reader, appending it to the buffered data.
+    ///
+    /// Returns the number of bytes read, where 0 means EOF.
+    fn poll_read_more(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+        let filled = self.buf.len();
+        self.buf.resize(filled + self.capacity, 0);
+        let result = Pin::new(&mut self.inner).poll_read(cx, &mut self.buf[filled..]);
+        let n = match &result {
+            Poll::Ready(Ok(n)) => *n,
+            _ => 0,
+        };
+        self.buf.truncate(filled + n);
+        result
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for UntilNeedleAsyncReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let available = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncBufRead for UntilNeedleAsyncReader<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.pos == this.buf.len() {
+            ready!(this.poll_read_more(cx))?;
+        }
+        Poll::Ready(Ok(&this.buf[this.pos..]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        this.pos = (this.pos + amt).min(this.buf.len());
+    }
+}
+
+/// A future that reads data until the specified needle is found, searching in the reader's
+/// buffer.
+///
+/// This struct is created by [`UntilNeedleAsyncReader::read_until_needle`].
+pub struct ReadUntilNeedleInPlace<'a, R, N> {
+    reader: &'a mut UntilNeedleAsyncReader<R>,
+    needle: N,
+    before: &'a mut Vec<u8>,
+    matched: &'a mut Vec<u8>,
+    /// The offset in the unconsumed data from which the next search starts.
+    scan_from: usize,
+}
+
+impl<R, N> Unpin for ReadUntilNeedleInPlace<'_, R, N> {}
+
+impl<'a, R, N> Future for ReadUntilNeedleInPlace<'a, R, N>
+where
+    R: AsyncRead + Unpin,
+    N: Needle,
+{
+    type Output = io::Result<usize>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ReadUntilNeedleInPlace {
+            reader,
+            needle,
+            before,
+            matched,
+            scan_from,
+        } = &mut *self;
+        loop {
+            let buffered = reader.buffer();
+            if let Some(range) = needle.findin(&buffered[*scan_from..]) {
+                let range = *scan_from + range.start..*scan_from + range.end;
+                before.extend_from_slice(&buffered[..range.start]);
+                matched.extend_from_slice(&buffered[range.clone()]);
+                Pin::new(&mut **reader).consume(range.end);
+                *scan_from = 0;
+                return Poll::Ready(Ok(range.end));
+            }
+            if let Some(len) = needle.max_len() {
+                // only a match starting in the last `len - 1` bytes can be completed later
+                let next = buffered.len().saturating_sub(len.saturating_sub(1));
+                *scan_from = (*scan_from).max(next);
+            }
+
+            if ready!(reader.poll_read_more(cx))? == 0 {
+                // EOF reached
+                let used = reader.buffer().len();
+                before.extend_from_slice(reader.buffer());
+                Pin::new(&mut **reader).consume(used);
+                *scan_from = 0;
+                return Poll::Ready(Ok(used));
+            }
+        }
+    }
+}
+
+/// A future that reads data until the specified needle is found, without consuming it.
+///
+/// This struct is created by [`UntilNeedleAsyncReader::peek_until`].
+pub struct PeekUntilNeedle<'a, R, N> {
+    reader: &'a mut UntilNeedleAsyncReader<R>,
+    needle: N,
+    /// The offset in the unconsumed data from which the next search starts.
+    scan_from: usize,
+}
+
+impl<R, N> Unpin for PeekUntilNeedle<'_, R, N> {}
+
+impl<R, N> Future for PeekUntilNeedle<'_, R, N>
+where
+    R: AsyncRead + Unpin,
+    N: Needle,
+{
+    type Output = io::Result<Option<Range<usize>>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            let buffered = this.reader.buffer();
+            match this.needle.findin_partial(&buffered[this.scan_from..]) {
+                PartialMatch::Full(range) => {
+                    let range = this.scan_from + range.start..this.scan_from + range.end;
+                    return Poll::Ready(Ok(Some(range)));
+                }
+                // only a match starting in the suffix can be completed later
+                PartialMatch::Prefix(start) => this.scan_from += start,
+                PartialMatch::None => this.scan_from = buffered.len(),
+            }
+            if ready!(this.reader.poll_read_more(cx))? == 0 {
+                // EOF reached
+                return Poll::Ready(Ok(None));
+            }
+        }
+    }
+}
+
+/// A future that reads data as long as it satisfies a predicate.
+pub struct ReadWhile<'a, R, P>
+where
+    R: Unpin + ?Sized,
+{
+    reader: &'a mut R,
+    pred: P,
+    out: &'a mut Vec<u8>,
+    total_bytes_read: usize,
+}
+
+impl<R: ?Sized + Unpin, P> Unpin for ReadWhile<'_, R, P> {}
+
+impl<'a, R, P> Future for ReadWhile<'a, R, P>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    P: Fn(u8) -> bool,
+{
+    type Output = io::Result<usize>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ReadWhile {
+            reader,
+            pred,
+            out,
+            total_bytes_read,
+        } = &mut *self;
+        let mut reader = Pin::new(reader);
+        loop {
+            let (done, used) = {
+                let available = ready!(reader.as_mut().poll_fill_buf(cx))?;
+                match available.iter().position(|&b| !pred(b)) {
+                    Some(pos) => {
+                        out.extend_from_slice(&available[..pos]);
+                        (true, pos)
+                    }
+                    None => {
+                        // an empty buffer means EOF
+                        out.extend_from_slice(available);
+                        (available.is_empty(), available.len())
+                    }
+                }
+            };
+
+            reader.as_mut().consume(used);
+            *total_bytes_read += used;
+
+            if done {
+                return Poll::Ready(Ok(mem::replace(total_bytes_read, 0)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ChunkedAsyncReader;
+    use futures::{
+        stream::{iter, TryStreamExt as _},
+        AsyncReadExt as _,
+    };
+    use std::pin::pin;
+
+    #[tokio::test]
+    async fn test_async_read() {
+        let mut stream = ChunkedAsyncReader::from_chunks(["hello", " wo", "rld!"]).pending(1);
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"hello world!");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle() {
+        let mut stream = ChunkedAsyncReader::from_chunks(["hello", " wo", "rld!!"]).pending(1);
+
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        let mut buf = Vec::new();
+
+        assert_eq!(
+            stream
+                .read_until_needle(b"world", &mut before, &mut matched)
+                .await
+                .unwrap(),
+            11
+        );
+        assert_eq!(before, b"hello ");
+        assert_eq!(matched, b"world");
+        assert_eq!(stream.read_to_end(&mut buf).await.unwrap(), 2);
+        assert_eq!(buf, b"!!");
+    }
+
+    #[tokio::test]
+    async fn test_read_while() {
+        let mut stream = ChunkedAsyncReader::from_chunks(["12", "34", "5:ok;6"]).pending(1);
+
+        let mut digits = Vec::new();
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+
+        assert_eq!(
+            stream
+                .read_while(|b| b.is_ascii_digit(), &mut digits)
+                .await
+                .unwrap(),
+            5
+        );
+        assert_eq!(digits, b"12345");
+        assert_eq!(
+            stream
+                .read_until_needle(b";", &mut before, &mut matched)
+                .await
+                .unwrap(),
+            4
+        );
+        assert_eq!(before, b":ok");
+        assert_eq!(matched, b";");
+
+        // EOF while the predicate still holds
+        digits.clear();
+        assert_eq!(
+            stream
+                .read_while(|b| b.is_ascii_digit(), &mut digits)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(digits, b"6");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_cancel() {
+        let (tx, rx) = futures::channel::mpsc::unbounded::<io::Result<Vec<u8>>>();
+        let mut stream = rx.into_async_read();
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+
+        tx.unbounded_send(Ok(b"hel".to_vec())).unwrap();
+        tx.unbounded_send(Ok(b"lo ".to_vec())).unwrap();
+        {
+            let mut future = stream.read_until_needle(b"world", &mut before, &mut matched);
+            assert!(futures::poll!(&mut future).is_pending());
+            tx.unbounded_send(Ok(b"w".to_vec())).unwrap();
+            assert!(futures::poll!(&mut future).is_pending());
+        }
+        // the dropped future has handed over everything it consumed
+        assert_eq!(before, b"hello w");
+        assert_eq!(matched, b"");
+
+        tx.unbounded_send(Ok(b"orld: world!".to_vec())).unwrap();
+        assert_eq!(
+            stream
+                .read_until_needle(b"world", &mut before, &mut matched)
+                .await
+                .unwrap(),
+            4
+        );
+        // the needle begun by the dropped future is found
+        assert_eq!(before, b"hello ");
+        assert_eq!(matched, b"world");
+        let mut rest = Vec::new();
+        drop(tx);
+        stream.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b": world!");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_dyn() {
+        let chunks = || ChunkedAsyncReader::from_chunks(["$ ls", "\r\nfile\r\n$ "]).pending(1);
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+
+        let mut boxed: Box<dyn AsyncBufRead + Unpin + Send> = Box::new(chunks());
+        assert_eq!(
+            boxed
+                .read_until_needle(b"\r\n", &mut before, &mut matched)
+                .await
+                .unwrap(),
+            6
+        );
+        assert_eq!(before, b"$ ls");
+        before.clear();
+        matched.clear();
+        // the unsized reader behind the box
+        let reader: &mut (dyn AsyncBufRead + Unpin + Send) = &mut *boxed;
+        assert_eq!(
+            reader
+                .read_until_needle(b"\r\n", &mut before, &mut matched)
+                .await
+                .unwrap(),
+            6
+        );
+        assert_eq!(before, b"file");
+
+        let mut pinned: Pin<Box<dyn AsyncBufRead + Send>> = Box::pin(chunks());
+        before.clear();
+        matched.clear();
+        assert_eq!(
+            pinned
+                .read_until_needle(b"\r\n", &mut before, &mut matched)
+                .await
+                .unwrap(),
+            6
+        );
+        assert_eq!(before, b"$ ls");
+        assert_eq!(matched, b"\r\n");
+    }
+
+    /// A needle counting how many bytes it has been asked to search.
+    struct CountingNeedle<'a>(&'a [u8], std::rc::Rc<std::cell::Cell<usize>>);
+
+    impl Needle for CountingNeedle<'_> {
+        fn findin(&self, haystack: &[u8]) -> Option<std::ops::Range<usize>> {
+            self.1.set(self.1.get() + haystack.len());
+            self.0.findin(haystack)
+        }
+
+        fn max_len(&self) -> Option<usize> {
+            self.0.max_len()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_many_chunks() {
+        let mut chunks: Vec<io::Result<Vec<u8>>> = (0..10_000)
+            .map(|i| Ok(format!("{:09} ", i).into_bytes()))
+            .collect();
+        chunks.push(Ok(b"NEEDLE tail".to_vec()));
+        let mut stream = iter(chunks).into_async_read();
+
+        let scanned = std::rc::Rc::new(std::cell::Cell::new(0));
+        let needle = CountingNeedle(b"NEEDLE", scanned.clone());
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        assert_eq!(
+            stream
+                .read_until_needle(needle, &mut before, &mut matched)
+                .await
+                .unwrap(),
+            100_006
+        );
+        assert_eq!(before.len(), 100_000);
+        assert!(before.starts_with(b"000000000 000000001 "));
+        assert!(before.ends_with(b"000009999 "));
+        assert_eq!(matched, b"NEEDLE");
+        // each byte is searched at most once more as part of the overlap
+        assert!(scanned.get() <= 100_011 + 10_001 * 5);
+    }
+
+    /// A literal needle that does not report its maximum length.
+    struct Unbounded(&'static [u8]);
+
+    impl Needle for Unbounded {
+        fn findin(&self, haystack: &[u8]) -> Option<std::ops::Range<usize>> {
+            self.0.findin(haystack)
+        }
+    }
+
+    /// A literal needle without a maximum length that tells where a match may begin.
+    struct Partial(&'static [u8]);
+
+    impl Needle for Partial {
+        fn findin(&self, haystack: &[u8]) -> Option<std::ops::Range<usize>> {
+            self.0.findin(haystack)
+        }
+
+        fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+            self.0.findin_partial(haystack)
+        }
+    }
+
+    /// A needle recording the largest haystack it has been asked to search.
+    struct RecordingNeedle<N>(N, std::rc::Rc<std::cell::Cell<usize>>);
+
+    impl<N: Needle> Needle for RecordingNeedle<N> {
+        fn findin(&self, haystack: &[u8]) -> Option<std::ops::Range<usize>> {
+            self.1.set(self.1.get().max(haystack.len()));
+            self.0.findin(haystack)
+        }
+
+        fn max_len(&self) -> Option<usize> {
+            self.0.max_len()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_bounded_haystack() {
+        let chunks = || {
+            let mut chunks: Vec<io::Result<Vec<u8>>> =
+                (0..1000).map(|_| Ok(b"0123456789".to_vec())).collect();
+            chunks.push(Ok(b"<END>".to_vec()));
+            iter(chunks).into_async_read()
+        };
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+
+        let largest = std::rc::Rc::new(std::cell::Cell::new(0));
+        let needle = RecordingNeedle(b"<END>", largest.clone());
+        chunks()
+            .read_until_needle(needle, &mut before, &mut matched)
+            .await
+            .unwrap();
+        assert_eq!(matched, b"<END>");
+        // one chunk plus the overlap of `max_len - 1` bytes
+        assert_eq!(largest.get(), 10 + 4);
+
+        // without a maximum length everything read so far is searched again
+        let largest = std::rc::Rc::new(std::cell::Cell::new(0));
+        let needle = RecordingNeedle(Unbounded(b"<END>"), largest.clone());
+        before.clear();
+        matched.clear();
+        chunks()
+            .read_until_needle(needle, &mut before, &mut matched)
+            .await
+            .unwrap();
+        assert_eq!(matched, b"<END>");
+        assert_eq!(largest.get(), 10_005);
+
+        let largest = std::rc::Rc::new(std::cell::Cell::new(0));
+        let needle = RecordingNeedle(
+            crate::needle::WithMaxLen::new(Unbounded(b"<END>"), 5),
+            largest.clone(),
+        );
+        before.clear();
+        matched.clear();
+        chunks()
+            .read_until_needle(needle, &mut before, &mut matched)
+            .await
+            .unwrap();
+        assert_eq!(matched, b"<END>");
+        assert_eq!(largest.get(), 14);
+    }
+
+    /// A waker counting how often it was woken.
+    struct CountingWaker(std::sync::atomic::AtomicUsize);
+
+    impl futures::task::ArcWake for CountingWaker {
+        fn wake_by_ref(arc_self: &std::sync::Arc<Self>) {
+            arc_self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_read_until_needle_yields() {
+        let run = |budget: Option<usize>| {
+            let mut chunks: Vec<io::Result<Vec<u8>>> =
+                (0..1000).map(|_| Ok(b"abc".to_vec())).collect();
+            chunks.push(Ok(b"!".to_vec()));
+            let mut stream = iter(chunks).into_async_read();
+            let mut before = Vec::new();
+            let mut matched = Vec::new();
+
+            let counter = std::sync::Arc::new(CountingWaker(Default::default()));
+            let waker = futures::task::waker(counter.clone());
+            let mut cx = Context::from_waker(&waker);
+            let mut future = stream.read_until_needle(b"!", &mut before, &mut matched);
+            if let Some(budget) = budget {
+                future = future.budget(budget);
+            }
+            let mut polls = 0;
+            let n = loop {
+                polls += 1;
+                if let Poll::Ready(n) = Pin::new(&mut future).poll(&mut cx) {
+                    break n.unwrap();
+                }
+            };
+            assert_eq!(n, 3001);
+            assert_eq!(before.len(), 3000);
+            assert_eq!(matched, b"!");
+            // every yield wakes the task so that it is polled again
+            assert_eq!(
+                counter.0.load(std::sync::atomic::Ordering::SeqCst),
+                polls - 1
+            );
+            polls
+        };
+
+        assert!(run(None) > 1);
+        assert!(run(Some(10)) > 100);
+    }
+
+    #[tokio::test]
+    async fn test_poll_read_until_needle() {
+        let chunks = ["a<", ">b<", ">", "c"];
+        let mut reader = ChunkedAsyncReader::from_chunks(chunks).pending(1);
+        let mut state = SearchState::new();
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+
+        // the state is reset after each search and is used for the next one
+        let mut results = Vec::new();
+        for _ in 0..3 {
+            let n = futures::future::poll_fn(|cx| {
+                poll_read_until_needle(
+                    Pin::new(&mut reader),
+                    cx,
+                    &b"<>"[..],
+                    &mut state,
+                    &mut before,
+                    &mut matched,
+                )
+            })
+            .await
+            .unwrap();
+            results.push((n, before.clone(), matched.clone()));
+        }
+        assert_eq!(
+            results,
+            vec![
+                (3, b"a".to_vec(), b"<>".to_vec()),
+                (3, b"ab".to_vec(), b"<><>".to_vec()),
+                (1, b"abc".to_vec(), b"<><>".to_vec()),
+            ]
+        );
+        assert_eq!(state.bytes_read(), 0);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[tokio::test]
+    async fn test_read_until_needle_bytes() {
+        let chunks = ["key", "=va", "lue;", "x"];
+        let mut reader = ChunkedAsyncReader::from_chunks(chunks).pending(1);
+        let mut buf = BytesMut::new();
+        let (before, matched) = reader
+            .read_until_needle_bytes(b"=", &mut buf)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((&before[..], &matched[..]), (&b"key"[..], &b"="[..]));
+        assert_eq!(matched.as_ptr(), before[before.len()..].as_ptr());
+
+        let (before, _) = reader
+            .read_until_needle_bytes(b";", &mut buf)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(before, &b"value"[..]);
+
+        assert!(reader
+            .read_until_needle_bytes(b";", &mut buf)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(buf, &b"x"[..]);
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_reuses_buffers() {
+        let messages: Vec<io::Result<Vec<u8>>> = (0..4)
+            .map(|i| Ok(format!("message {:04}\r\n", i).into_bytes()))
+            .collect();
+        let mut reader = iter(messages).into_async_read();
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        reader
+            .read_until_needle(b"\r\n", &mut before, &mut matched)
+            .await
+            .unwrap();
+        let (before_ptr, matched_ptr) = (before.as_ptr(), matched.as_ptr());
+
+        for i in 1..4 {
+            before.clear();
+            matched.clear();
+            reader
+                .read_until_needle(b"\r\n", &mut before, &mut matched)
+                .await
+                .unwrap();
+            assert_eq!(before, format!("message {:04}", i).as_bytes());
+            // same-sized messages fit in the allocations of the first call
+            assert_eq!(
+                (before.as_ptr(), matched.as_ptr()),
+                (before_ptr, matched_ptr)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_until() {
+        let (tx, rx) = futures::channel::mpsc::unbounded::<io::Result<Vec<u8>>>();
+        let mut reader = rx.into_async_read();
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        tx.unbounded_send(Ok(b"abc".to_vec())).unwrap();
+
+        let (cancel_tx, cancel_rx) = futures::channel::oneshot::channel::<()>();
+        // the cancel future is stored inline, so the read is pinned like it
+        let cancel = async {
+            let _ = cancel_rx.await;
+        };
+        let mut read =
+            pin!(reader.read_until_needle_until(b"<>", &mut before, &mut matched, cancel));
+        assert!(futures::poll!(&mut read).is_pending());
+        cancel_tx.send(()).unwrap();
+        assert_eq!(
+            read.await.unwrap(),
+            UntilNeedleStatus::Cancelled { bytes_read: 3 }
+        );
+        assert_eq!(before, b"abc");
+
+        // calling again continues the read without losing or duplicating data
+        tx.unbounded_send(Ok(b"de<".to_vec())).unwrap();
+        tx.unbounded_send(Ok(b">f".to_vec())).unwrap();
+        let status = reader
+            .read_until_needle_until(b"<>", &mut before, &mut matched, futures::future::pending())
+            .await
+            .unwrap();
+        assert_eq!(status, UntilNeedleStatus::Found { bytes_read: 4 });
+        assert_eq!((&before[..], &matched[..]), (&b"abcde"[..], &b"<>"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_until_straddled() {
+        let (tx, rx) = futures::channel::mpsc::unbounded::<io::Result<Vec<u8>>>();
+        let mut reader = rx.into_async_read();
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        tx.unbounded_send(Ok(b"ab<".to_vec())).unwrap();
+
+        let (cancel_tx, cancel_rx) = futures::channel::oneshot::channel::<()>();
+        let cancel = async {
+            let _ = cancel_rx.await;
+        };
+        let mut read =
+            pin!(reader.read_until_needle_until(b"<>", &mut before, &mut matched, cancel));
+        assert!(futures::poll!(&mut read).is_pending());
+        cancel_tx.send(()).unwrap();
+        assert_eq!(
+            read.await.unwrap(),
+            UntilNeedleStatus::Cancelled { bytes_read: 3 }
+        );
+        assert_eq!(before, b"ab<");
+
+        // the needle begun before the cancel is found once the rest arrives
+        tx.unbounded_send(Ok(b">f".to_vec())).unwrap();
+        drop(tx);
+        let status = reader
+            .read_until_needle_until(b"<>", &mut before, &mut matched, futures::future::pending())
+            .await
+            .unwrap();
+        assert_eq!(status, UntilNeedleStatus::Found { bytes_read: 1 });
+        assert_eq!((&before[..], &matched[..]), (&b"ab"[..], &b"<>"[..]));
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"f");
+    }
+
+    #[test]
+    fn test_read_until_needle_fused() {
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        let mut reader = &b"abc<>def"[..];
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        let mut read = reader.read_until_needle(b"<>", &mut before, &mut matched);
+        assert!(!read.is_terminated());
+        assert!(matches!(
+            Pin::new(&mut read).poll(&mut cx),
+            Poll::Ready(Ok(5))
+        ));
+        assert!(read.is_terminated());
+
+        // polling again panics instead of appending the data again
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = Pin::new(&mut read).poll(&mut cx);
+        }));
+        assert!(result.is_err());
+        assert_eq!(before, b"abc");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_select() {
+        let mut reader = &b"abc<>def"[..];
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        let mut read = reader.read_until_needle(b"<>", &mut before, &mut matched);
+        let mut never = futures::future::pending::<()>();
+        let mut completed = 0;
+        loop {
+            futures::select! {
+                n = read => completed += n.unwrap(),
+                _ = never => unreachable!(),
+                complete => break,
+            }
+        }
+        assert_eq!(completed, 5);
+    }
+
+    #[tokio::test]
+    async fn test_skip_until_needle() {
+        let mut chunks: Vec<io::Result<Vec<u8>>> = (0..10_000)
+            .map(|i| Ok(format!("{:09} ", i).into_bytes()))
+            .collect();
+        chunks.push(Ok(b"garbage<MA".to_vec()));
+        chunks.push(Ok(b"RK>payload".to_vec()));
+        let mut reader = iter(chunks).into_async_read();
+
+        let report = reader.skip_until_needle(b"<MARK>").await.unwrap();
+        assert_eq!(
+            report,
+            SkipReport {
+                skipped: 100_007,
+                matched: Some(b"<MARK>".to_vec()),
+            }
+        );
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"payload");
+
+        let mut reader = &b"no marker"[..];
+        let report = reader.skip_until_needle(b"<MARK>").await.unwrap();
+        assert_eq!((report.skipped, report.matched), (9, None));
+
+        let chunks = ["ab<", "x<", ">c"];
+        let mut reader = ChunkedAsyncReader::from_chunks(chunks).pending(1);
+        let report = reader.skip_until_needle(Unbounded(b"<>")).await.unwrap();
+        assert_eq!(report.skipped, 4);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"c");
+    }
+
+    #[tokio::test]
+    async fn test_split_by_needle() {
+        let chunks = || {
+            ChunkedAsyncReader::from_chunks(["one\r", "\ntwo", "\r\n", "\r\nthree\r", "\n"])
+                .pending(1)
+        };
+
+        let segments: Vec<_> = chunks()
+            .split_by_needle(b"\r\n")
+            .try_collect()
+            .await
+            .unwrap();
+        let data: Vec<_> = segments.iter().map(|s| &s.data[..]).collect();
+        assert_eq!(data, vec![&b"one"[..], b"two", b"", b"three"]);
+        assert!(segments
+            .iter()
+            .all(|s| s.delimiter.as_deref() == Some(b"\r\n")));
+
+        let segments: Vec<_> = chunks()
+            .split_by_needle(b"\r\n")
+            .emit_empty_trailing(true)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(segments.len(), 5);
+        assert_eq!(
+            segments[4],
+            Segment {
+                data: Vec::new(),
+                delimiter: None
+            }
+        );
+
+        let mut stream = ChunkedAsyncReader::from_chunks(["a|b", "c|d"])
+            .pending(1)
+            .split_by_needle(b"|");
+        let mut data = Vec::new();
+        while let Some(segment) = stream.try_next().await.unwrap() {
+            data.push(segment.data);
+        }
+        assert_eq!(data, vec![b"a".to_vec(), b"bc".to_vec(), b"d".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_frames() {
+        for size in 1..6 {
+            let reader = ChunkedAsyncReader::new(&b"a<>bc<><>d"[..], [size]).pending(1);
+            let frames: Vec<_> = reader.frames(b"<>").try_collect().await.unwrap();
+            assert_eq!(frames, [&b"a"[..], b"bc", b"", b"d"]);
+        }
+
+        let mut frames = ChunkedAsyncReader::new(&b"ok;too long;"[..], [3])
+            .frames(b";")
+            .max_frame_len(4);
+        assert_eq!(frames.try_next().await.unwrap().unwrap(), b"ok");
+        assert!(frames.try_next().await.is_err());
+        assert!(frames.is_terminated());
+    }
+
+    #[tokio::test]
+    async fn test_sse_events() {
+        let data = b"\r\ndata: 1\r\rid: 2\r\ndata: 2\r\n\r\n:\n\n\ndata: 3\n";
+        for size in 1..data.len() {
+            let reader = ChunkedAsyncReader::new(&data[..], [size]).pending(1);
+            let events: Vec<_> = reader.sse_events().try_collect().await.unwrap();
+            assert_eq!(
+                events,
+                [&b"data: 1"[..], b"id: 2\r\ndata: 2", b":", b"data: 3"]
+            );
+        }
+
+        let mut events = ChunkedAsyncReader::new(&b"data: 1\n\ndata: 333\n\n"[..], [4])
+            .sse_events()
+            .max_event_len(10);
+        assert_eq!(events.try_next().await.unwrap().unwrap(), b"data: 1");
+        assert!(events.try_next().await.is_err());
+        assert!(events.is_terminated());
+    }
+
+    #[tokio::test]
+    async fn test_split_by_needle_max_segment_len() {
+        let mut stream = ChunkedAsyncReader::from_chunks(["ok;", "too", " long;", "never;"])
+            .pending(1)
+            .split_by_needle(b";")
+            .max_segment_len(4);
+        assert_eq!(stream.try_next().await.unwrap().unwrap().data, b"ok");
+        let err = stream.try_next().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // the stream ends after an error
+        assert!(stream.is_terminated());
+        assert!(stream.try_next().await.unwrap().is_none());
+
+        let mut inner = stream.into_inner();
+        let mut rest = Vec::new();
+        inner.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"never;");
+    }
+
+    #[tokio::test]
+    async fn test_take_until() {
+        let data = b"header<>|body<>|tail";
+        for seed in 0..30u32 {
+            for consume_needle in [true, false] {
+                // chunks of 1 to 3 bytes
+                let mut chunks = Vec::new();
+                let mut rest = &data[..];
+                let mut seed = seed;
+                while !rest.is_empty() {
+                    seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                    let len = ((seed >> 16) as usize % 3 + 1).min(rest.len());
+                    chunks.push(&rest[..len]);
+                    rest = &rest[len..];
+                }
+                let reader = ChunkedAsyncReader::from_chunks(chunks).pending(1);
+
+                let mut take = reader.take_until(b"<>|").consume_needle(consume_needle);
+                let mut out = Vec::new();
+                futures::io::copy(&mut take, &mut out).await.unwrap();
+                assert_eq!(out, b"header");
+                assert!(take.is_found());
+
+                let (mut reader, mut rest) = take.into_inner();
+                reader.read_to_end(&mut rest).await.unwrap();
+                if consume_needle {
+                    assert_eq!(rest, b"body<>|tail");
+                } else {
+                    assert_eq!(rest, b"<>|body<>|tail");
+                }
+            }
+        }
+
+        let mut take = (&b"no needle"[..]).take_until(b"<>|");
+        let mut out = Vec::new();
+        take.read_to_end(&mut out).await.unwrap();
+        assert_eq!(out, b"no needle");
+        assert!(!take.is_found());
+
+        // an unbounded needle holds the data until it is found
+        let chunks = ["ab<", ">c"];
+        let mut take = ChunkedAsyncReader::from_chunks(chunks)
+            .pending(1)
+            .take_until(Unbounded(b"<>"));
+        let mut out = Vec::new();
+        take.read_to_end(&mut out).await.unwrap();
+        assert_eq!(out, b"ab");
+        let (mut reader, mut rest) = take.into_inner();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"c");
+
+        // a needle telling where a match may begin lets the rest through before it is found
+        let chunks = ["abcdef<E", "ND>tail"];
+        let mut take = ChunkedAsyncReader::from_chunks(chunks).take_until(Partial(b"<END>"));
+        assert_eq!(
+            futures::AsyncBufReadExt::fill_buf(&mut take).await.unwrap(),
+            b"abcdef"
+        );
+        futures::AsyncBufReadExt::consume_unpin(&mut take, 6);
+        let mut out = Vec::new();
+        take.read_to_end(&mut out).await.unwrap();
+        assert!(out.is_empty());
+        assert!(take.is_found());
+        let (mut reader, mut rest) = take.into_inner();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"tail");
+    }
+
+    /// A writer accepting at most 3 bytes at a time, and only every other poll.
+    #[derive(Default)]
+    struct SlowWriter {
+        data: Vec<u8>,
+        ready: bool,
+        flushed: usize,
+    }
+
+    impl AsyncWrite for SlowWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let n = buf.len().min(3);
+            self.data.extend_from_slice(&buf[..n]);
+            Poll::Ready(Ok(n))
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.flushed = self.data.len();
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_copy_until_needle() {
+        let chunks = || {
+            let chunks = ["first pa", "rt--bou", "ndary--second part"];
+            ChunkedAsyncReader::from_chunks(chunks).pending(1)
+        };
+
+        let mut reader = chunks();
+        let mut writer = SlowWriter::default();
+        let report = copy_until_needle(&mut reader, &mut writer, b"--boundary--")
+            .await
+            .unwrap();
+        assert_eq!(
+            report,
+            CopyReport {
+                copied: 10,
+                matched: Some(b"--boundary--".to_vec()),
+            }
+        );
+        assert_eq!(writer.data, b"first part");
+        assert_eq!(writer.flushed, 10);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"second part");
+
+        let mut reader = chunks();
+        let mut writer = SlowWriter::default();
+        let report = copy_until_needle(&mut reader, &mut writer, b"--boundary--")
+            .forward_needle(true)
+            .await
+            .unwrap();
+        assert_eq!(report.copied, 22);
+        assert_eq!(writer.data, b"first part--boundary--");
+
+        let mut reader = &b"no boundary"[..];
+        let mut writer = SlowWriter::default();
+        let report = copy_until_needle(&mut reader, &mut writer, b"--boundary--")
+            .forward_needle(true)
+            .await
+            .unwrap();
+        assert_eq!((report.copied, report.matched), (11, None));
+        assert_eq!(writer.data, b"no boundary");
+    }
+
+    #[tokio::test]
+    async fn test_until_needle_async_reader() {
+        let chunks = || {
+            ChunkedAsyncReader::from_chunks([
+                "user",
+                "name: ",
+                "alice\npass",
+                "word: ",
+                "secret\n$ ",
+                "exit",
+            ])
+            .pending(1)
+        };
+        let needles: [&[u8]; 4] = [b"name: ", b"word: ", b"$ ", b"never"];
+
+        let mut reader = UntilNeedleAsyncReader::with_capacity(4, chunks());
+        let mut expected = futures::io::BufReader::with_capacity(4, chunks());
+        for needle in needles {
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            let (mut expected_before, mut expected_matched) = (Vec::new(), Vec::new());
+            assert_eq!(
+                reader
+                    .read_until_needle(needle, &mut before, &mut matched)
+                    .await
+                    .unwrap(),
+                AsyncUntilNeedleRead::read_until_needle(
+                    &mut expected,
+                    needle,
+                    &mut expected_before,
+                    &mut expected_matched
+                )
+                .await
+                .unwrap()
+            );
+            assert_eq!(before, expected_before);
+            assert_eq!(matched, expected_matched);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_until_needle_async_reader_peek() {
+        let chunks = ChunkedAsyncReader::from_chunks(["GE", "T / HT", "TP/1.1\r", "\n"]).pending(1);
+        let mut reader = UntilNeedleAsyncReader::with_capacity(2, chunks);
+        assert_eq!(reader.peek_until(b" ").await.unwrap(), Some(3..4));
+        assert_eq!(reader.peek_until(b"\r\n").await.unwrap(), Some(14..16));
+        assert_eq!(reader.peek_until(b"POST").await.unwrap(), None);
+        // everything peeked is read again
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).await.unwrap();
+        assert_eq!(all, b"GET / HTTP/1.1\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_until_needle_async_reader_cancel() {
+        let (tx, rx) = futures::channel::mpsc::unbounded::<io::Result<Vec<u8>>>();
+        let mut reader = UntilNeedleAsyncReader::with_capacity(2, rx.into_async_read());
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+
+        tx.unbounded_send(Ok(b"hello wo".to_vec())).unwrap();
+        {
+            let mut future = reader.read_until_needle(b"world", &mut before, &mut matched);
+            assert!(futures::poll!(&mut future).is_pending());
+        }
+        // nothing was consumed by the dropped future
+        assert_eq!(before, b"");
+        assert_eq!(reader.buffer(), b"hello wo");
+
+        tx.unbounded_send(Ok(b"rld; a;b".to_vec())).unwrap();
+        assert_eq!(
+            reader
+                .read_until_needle(b"world", &mut before, &mut matched)
+                .await
                 .unwrap(),
             11
         );
         assert_eq!(before, b"hello ");
         assert_eq!(matched, b"world");
-        assert_eq!(stream.read_to_end(&mut buf).await.unwrap(), 2);
-        assert_eq!(buf, b"!!");
+
+        // through the extension trait, as an AsyncBufRead
+        before.clear();
+        assert_eq!(
+            AsyncUntilNeedleRead::read_until_needle(&mut reader, b";", &mut before, &mut matched)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(before, b"");
+
+        before.clear();
+        assert_eq!(
+            reader
+                .read_until_needle(b";", &mut before, &mut matched)
+                .await
+                .unwrap(),
+            3
+        );
+        assert_eq!(before, b" a");
+
+        tx.unbounded_send(Ok(b"c;d".to_vec())).unwrap();
+        drop(tx);
+        before.clear();
+        assert_eq!(
+            reader
+                .read_until_needle(b";", &mut before, &mut matched)
+                .await
+                .unwrap(),
+            3
+        );
+        assert_eq!(before, b"bc");
+
+        let (mut inner, buffered) = reader.into_inner();
+        let mut rest = Vec::new();
+        inner.read_to_end(&mut rest).await.unwrap();
+        assert_eq!([buffered, rest].concat(), b"d");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_owned() {
+        fn assert_send<T: Send>(_: &T) {}
+
+        let mut stream = ChunkedAsyncReader::from_chunks(["key", "=value"]).pending(1);
+        let future = stream.read_until_needle_owned(b"=");
+        assert_send(&future);
+        assert_eq!(
+            future.await.unwrap(),
+            UntilNeedleOutput {
+                before: b"key".to_vec(),
+                matched: Some(b"=".to_vec()),
+                bytes_read: 4,
+            }
+        );
+
+        let output = stream.read_until_needle_owned(b"=").await.unwrap();
+        assert_eq!(output.before, b"value");
+        assert_eq!(output.matched, None);
+        assert_eq!(output.bytes_read, 5);
+    }
+
+    #[tokio::test]
+    async fn test_consume_needle() {
+        let mut stream = ChunkedAsyncReader::from_chunks(["head<>tail<>"]).pending(1);
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let mut read = stream
+            .read_until_needle(b"<>", &mut before, &mut matched)
+            .limit(64)
+            .consume_needle(false);
+        let n = futures::select! {
+            n = read => n.unwrap(),
+        };
+        assert!(read.is_terminated());
+        assert_eq!(n, 4);
+        assert_eq!((&before[..], &matched[..]), (&b"head"[..], &b"<>"[..]));
+
+        // the needle left in the reader is found again at once
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let n = stream
+            .read_until_needle(b"<>", &mut before, &mut matched)
+            .await
+            .unwrap();
+        assert_eq!((n, &before[..]), (2, &b""[..]));
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"tail<>");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_options() {
+        use std::future::IntoFuture as _;
+
+        let mut stream =
+            ChunkedAsyncReader::from_chunks(["abc<E", "ND>", "defgh<END>ij"]).pending(1);
+        let mut buf = Vec::new();
+        let status = stream.read_until_needle_options(b"<END>", &mut buf).await;
+        assert_eq!(status.unwrap(), UntilNeedleStatus::Found { bytes_read: 8 });
+        assert_eq!(buf, b"abc");
+
+        // the needle is kept when asked, and the future works with `select!` unfused
+        let mut buf = Vec::new();
+        let mut read = stream
+            .read_until_needle_options(b"<END>", &mut buf)
+            .include_match(true)
+            .into_future();
+        let status = futures::select! {
+            status = read => status.unwrap(),
+        };
+        assert!(read.is_terminated());
+        assert_eq!(status, UntilNeedleStatus::Found { bytes_read: 10 });
+        assert_eq!(buf, b"defgh<END>");
+
+        let mut stream = ChunkedAsyncReader::from_chunks(["abcdef<END>"]).pending(1);
+        let mut buf = Vec::new();
+        let status = stream
+            .read_until_needle_options(b"<END>", &mut buf)
+            .limit(8)
+            .include_match(true)
+            .await;
+        assert_eq!(
+            status.unwrap(),
+            UntilNeedleStatus::LimitReached { bytes_read: 8 }
+        );
+        assert_eq!(buf, b"abcdef<E");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_options_timeout() {
+        use futures::stream::{self, StreamExt as _};
+
+        // a reader that never sends more after its first chunks
+        let chunks = iter([Ok(&b"boot"[..]), Ok(b"ing")]).chain(stream::pending());
+        let mut reader = chunks.into_async_read();
+        let mut buf = Vec::new();
+        let (timer_tx, timer_rx) = futures::channel::oneshot::channel::<()>();
+        let timer = async {
+            let _ = timer_rx.await;
+        };
+        let options = reader
+            .read_until_needle_options("$ ", &mut buf)
+            .limit(64)
+            .timeout(timer);
+        let mut read = pin!(std::future::IntoFuture::into_future(options));
+        assert!(futures::poll!(&mut read).is_pending());
+        timer_tx.send(()).unwrap();
+        assert_eq!(
+            read.await.unwrap(),
+            UntilNeedleStatus::TimedOut { bytes_read: 7 }
+        );
+        assert_eq!(buf, b"booting");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_limit() {
+        let mut stream = ChunkedAsyncReader::from_chunks(["abc<E", "ND>", "defgh<END>"]).pending(1);
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let n = stream
+            .read_until_needle(b"<END>", &mut before, &mut matched)
+            .limit(8)
+            .await
+            .unwrap();
+        assert_eq!(n, 8);
+        assert_eq!((&before[..], &matched[..]), (&b"abc"[..], &b"<END>"[..]));
+
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let err = stream
+            .read_until_needle(b"<END>", &mut before, &mut matched)
+            .limit(6)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let limit = err
+            .get_ref()
+            .unwrap()
+            .downcast_ref::<crate::LimitExceeded>();
+        assert_eq!(limit, Some(&crate::LimitExceeded { limit: 6 }));
+        assert_eq!(before, b"defgh<");
+
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let err = stream
+            .read_until_needle_limited(b"<END>", 2, &mut before, &mut matched)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "needle not found within 2 bytes");
+        assert_eq!(before, b"EN");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_into_out() {
+        use crate::output::{Discard, Limited};
+
+        let mut stream =
+            ChunkedAsyncReader::from_chunks(["ab<E", "ND>cd<", "END>efghij<END>"]).pending(1);
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let status = stream
+            .read_until_needle_into_out(b"<END>", &mut before, &mut matched)
+            .await
+            .unwrap();
+        assert_eq!(status, UntilNeedleStatus::Found { bytes_read: 7 });
+        assert_eq!((&before[..], &matched[..]), (&b"ab"[..], &b"<END>"[..]));
+
+        let status = stream
+            .read_until_needle_into_out(b"<END>", Discard, Discard)
+            .await
+            .unwrap();
+        assert_eq!(status, UntilNeedleStatus::Found { bytes_read: 7 });
+
+        let mut before = Limited::new(Vec::new(), 3);
+        let status = stream
+            .read_until_needle_into_out(b"<END>", &mut before, Discard)
+            .await
+            .unwrap();
+        assert_eq!(status, UntilNeedleStatus::OutputFull { bytes_read: 11 });
+        assert_eq!(before.into_inner(), b"efg");
     }
 }
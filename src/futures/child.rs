@@ -0,0 +1,308 @@
+#[cfg(feature = "process")]
+use super::Session;
+use ::tokio::io::{self as tokio_io, ReadBuf};
+#[cfg(feature = "process")]
+use ::tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout};
+use futures_core::ready;
+use futures_util::io::{AsyncRead, AsyncWrite};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The pipes of a child process as one transport for a [`Session`](super::Session).
+///
+/// Reads come from the child's stdout and, if added with [`with_stderr`](Self::with_stderr),
+/// its stderr, in the order the data arrives; EOF is reported once all of them are closed.
+/// While both have data they are read in turns, so a chatty stdout does not hold back stderr.
+/// Writes go to the child's stdin. The pipes are tokio readers and writers, such as the
+/// `ChildStdout`, `ChildStderr` and `ChildStdin` of a `tokio::process::Child` spawned with
+/// piped stdio. With the `process` feature, `Session::from_child` joins them for you.
+#[derive(Debug)]
+pub struct ChildPipes<I, O, E = tokio_io::Empty> {
+    stdin: I,
+    stdout: O,
+    stderr: E,
+    stdout_eof: bool,
+    stderr_eof: bool,
+    /// Whether stderr is polled before stdout by the next read.
+    next_stderr: bool,
+}
+
+impl<I, O> ChildPipes<I, O> {
+    /// Joins the `stdin` and `stdout` of a child process.
+    pub fn new(stdin: I, stdout: O) -> Self {
+        Self {
+            stdin,
+            stdout,
+            stderr: tokio_io::empty(),
+            stdout_eof: false,
+            stderr_eof: true,
+            next_stderr: false,
+        }
+    }
+
+    /// Merges the `stderr` of the child process into the output read.
+    pub fn with_stderr<E>(self, stderr: E) -> ChildPipes<I, O, E> {
+        ChildPipes {
+            stdin: self.stdin,
+            stdout: self.stdout,
+            stderr,
+            stdout_eof: false,
+            stderr_eof: false,
+            next_stderr: false,
+        }
+    }
+}
+
+impl<I, O, E> ChildPipes<I, O, E> {
+    /// Unwraps this `ChildPipes`, returning the stdin, stdout and stderr pipes.
+    pub fn into_inner(self) -> (I, O, E) {
+        (self.stdin, self.stdout, self.stderr)
+    }
+}
+
+/// Reads from a tokio reader into `buf`, returning the number of bytes read.
+fn poll_read_pipe<R: tokio_io::AsyncRead + Unpin>(
+    pipe: &mut R,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+) -> Poll<io::Result<usize>> {
+    let mut buf = ReadBuf::new(buf);
+    ready!(Pin::new(pipe).poll_read(cx, &mut buf))?;
+    Poll::Ready(Ok(buf.filled().len()))
+}
+
+impl<I, O, E> AsyncRead for ChildPipes<I, O, E>
+where
+    I: Unpin,
+    O: tokio_io::AsyncRead + Unpin,
+    E: tokio_io::AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let this = &mut *self;
+        for stderr in [this.next_stderr, !this.next_stderr] {
+            let (eof, poll) = if stderr {
+                if this.stderr_eof {
+                    continue;
+                }
+                let poll = poll_read_pipe(&mut this.stderr, cx, buf)?;
+                (&mut this.stderr_eof, poll)
+            } else {
+                if this.stdout_eof {
+                    continue;
+                }
+                let poll = poll_read_pipe(&mut this.stdout, cx, buf)?;
+                (&mut this.stdout_eof, poll)
+            };
+            match poll {
+                Poll::Ready(0) => *eof = true,
+                Poll::Ready(n) => {
+                    // the other pipe goes first next time, so neither starves
+                    this.next_stderr = !stderr;
+                    return Poll::Ready(Ok(n));
+                }
+                Poll::Pending => {}
+            }
+        }
+        if this.stdout_eof && this.stderr_eof {
+            Poll::Ready(Ok(0))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<I, O, E> AsyncWrite for ChildPipes<I, O, E>
+where
+    I: tokio_io::AsyncWrite + Unpin,
+    O: Unpin,
+    E: Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stdin).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stdin).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stdin).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "process")]
+impl Session<ChildPipes<ChildStdin, ChildStdout>> {
+    /// Creates a session over the stdin and stdout of `child`, taking them out of it.
+    ///
+    /// The child keeps its other handles, so it can still be waited for or killed. Use
+    /// [`from_child_with_stderr`](Session::from_child_with_stderr) to read its stderr as well.
+    ///
+    /// # Errors
+    /// An error of kind `InvalidInput` is returned, and nothing is taken, if the stdin or stdout
+    /// of `child` was not piped or was already taken.
+    ///
+    /// # Example
+    /// ```
+    /// use std::process::Stdio;
+    /// use tokio::process::Command;
+    /// use until_needle::futures::Session;
+    ///
+    /// # #[cfg(unix)]
+    /// # #[tokio::main]
+    /// # async fn main() -> std::io::Result<()> {
+    /// let mut child = Command::new("sh")
+    ///     .args(["-c", "printf 'Name: '; read name; echo \"hello $name\""])
+    ///     .stdin(Stdio::piped())
+    ///     .stdout(Stdio::piped())
+    ///     .spawn()?;
+    /// let mut session = Session::from_child(&mut child)?;
+    /// session.expect(&["Name: "]).await?;
+    /// session.send_line("admin").await?;
+    /// session.expect(&["hello admin"]).await?;
+    /// assert!(child.wait().await?.success());
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(unix))]
+    /// # fn main() {}
+    /// ```
+    pub fn from_child(child: &mut Child) -> io::Result<Self> {
+        if child.stdin.is_none() || child.stdout.is_none() {
+            return Err(not_piped());
+        }
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        Ok(Session::new(ChildPipes::new(stdin, stdout)))
+    }
+}
+
+#[cfg(feature = "process")]
+impl Session<ChildPipes<ChildStdin, ChildStdout, ChildStderr>> {
+    /// Creates a session over the stdin, stdout and stderr of `child`, taking them out of it.
+    ///
+    /// The output of stdout and stderr is merged as by [`ChildPipes::with_stderr`].
+    ///
+    /// # Errors
+    /// An error of kind `InvalidInput` is returned, and nothing is taken, if the stdin, stdout or
+    /// stderr of `child` was not piped or was already taken.
+    pub fn from_child_with_stderr(child: &mut Child) -> io::Result<Self> {
+        if child.stdin.is_none() || child.stdout.is_none() || child.stderr.is_none() {
+            return Err(not_piped());
+        }
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let stderr = child.stderr.take().unwrap();
+        Ok(Session::new(
+            ChildPipes::new(stdin, stdout).with_stderr(stderr),
+        ))
+    }
+}
+
+/// The error returned when a pipe of a child process is missing.
+#[cfg(feature = "process")]
+fn not_piped() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "the stdio of the child process is not piped",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::futures::Session;
+    use ::tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    #[tokio::test]
+    async fn test_child_pipes() {
+        let (stdin, mut child_stdin) = ::tokio::io::duplex(64);
+        let (stdout, mut child_stdout) = ::tokio::io::duplex(64);
+        let (stderr, mut child_stderr) = ::tokio::io::duplex(64);
+
+        let mut session = Session::new(ChildPipes::new(stdin, stdout).with_stderr(stderr));
+        child_stdout.write_all(b"login: ").await.unwrap();
+        assert_eq!(session.expect(&["login: "]).await.unwrap().index, 0);
+        session.send_line("admin").await.unwrap();
+        let mut input = [0; 6];
+        child_stdin.read_exact(&mut input).await.unwrap();
+        assert_eq!(&input, b"admin\n");
+
+        // the output of stderr is merged in, taking turns with stdout when both have data
+        child_stderr.write_all(b"warning\n").await.unwrap();
+        child_stdout.write_all(b"$ ").await.unwrap();
+        let m = session.expect(&["$ "]).await.unwrap();
+        assert_eq!(m.before, b"warning\n");
+
+        // EOF is only reported once both outputs are closed
+        drop(child_stdout);
+        child_stderr.write_all(b"bye").await.unwrap();
+        drop(child_stderr);
+        let err = session.expect(&["$ "]).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(session.buffer(), b"bye");
+    }
+
+    #[tokio::test]
+    async fn test_child_pipes_fair() {
+        use futures_util::io::AsyncReadExt as _;
+
+        let (stdout, mut child_stdout) = ::tokio::io::duplex(64);
+        let (stderr, mut child_stderr) = ::tokio::io::duplex(64);
+        let mut pipes = ChildPipes::new(::tokio::io::sink(), stdout).with_stderr(stderr);
+
+        // both pipes stay ready; each read takes from the other one than the last
+        let mut reads = Vec::new();
+        for _ in 0..8 {
+            child_stdout.write_all(b"out").await.unwrap();
+            child_stderr.write_all(b"err").await.unwrap();
+            let mut buf = [0; 3];
+            pipes.read_exact(&mut buf).await.unwrap();
+            reads.push(buf);
+            pipes.read_exact(&mut buf).await.unwrap();
+            reads.push(buf);
+        }
+        assert!(reads.chunks(2).all(|pair| pair == [*b"out", *b"err"]));
+    }
+
+    #[cfg(all(feature = "process", unix))]
+    #[tokio::test]
+    async fn test_from_child() {
+        use ::tokio::process::Command;
+        use std::process::Stdio;
+
+        let mut child = Command::new("sh")
+            .args([
+                "-c",
+                "printf 'login: '; read name; echo \"hi $name\" >&2; echo done",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut session = Session::from_child_with_stderr(&mut child).unwrap();
+        assert!(child.stdin.is_none() && child.stderr.is_none());
+        session.expect(&["login: "]).await.unwrap();
+        session.send_line("root").await.unwrap();
+        session.expect(&["hi root\n"]).await.unwrap();
+        session.expect(&["done\n"]).await.unwrap();
+        assert!(child.wait().await.unwrap().success());
+
+        let mut child = Command::new("true").stdin(Stdio::piped()).spawn().unwrap();
+        let err = Session::from_child(&mut child).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(child.stdin.is_some());
+        child.wait().await.unwrap();
+    }
+}
@@ -0,0 +1,461 @@
+use crate::output::HeldSearch;
+use crate::trace;
+use crate::{ExpectMatch, Needle};
+use futures_util::future::{self, Either};
+use futures_util::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+use std::io;
+#[cfg(feature = "tokio")]
+use std::time::Duration;
+
+/// How [`Session::interact`] returned control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interaction {
+    /// The needle was found in the local input. The input before it was sent; the needle itself
+    /// and the input after it were not.
+    Input,
+    /// The needle was found in the output. The output up to the end of the needle was passed
+    /// through; the output after it is kept for the next [`Session::expect`].
+    Output,
+    /// The local input reached EOF. All of it was sent.
+    InputEof,
+    /// The transport reached EOF. All of its output was passed through.
+    OutputEof,
+}
+
+/// An expect-style session over an async transport.
+///
+/// The session writes commands to the transport and waits for one of several needles in its
+/// output. Output received after a match is kept in the session and searched first by the next
+/// [`expect`](Self::expect), so nothing is lost between calls.
+///
+/// # Example
+/// ```
+/// use futures::io::Cursor;
+/// use until_needle::futures::Session;
+///
+/// # futures::executor::block_on(async {
+/// let mut session = Session::new(Cursor::new(b"login: ".to_vec()));
+/// let m = session.expect(&["login: ", "$ "]).await.unwrap();
+/// assert_eq!(m.index, 0);
+/// session.send_line("admin").await.unwrap();
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct Session<T> {
+    inner: T,
+    /// Output received but not yet returned by `expect`.
+    buf: Vec<u8>,
+    /// The offset in `buf` from which a needle may still start.
+    scan_from: usize,
+}
+
+impl<T> Session<T> {
+    /// Creates a session over `inner`.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            scan_from: 0,
+        }
+    }
+
+    /// Gets a reference to the underlying transport.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying transport.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Returns the output received but not yet returned by [`expect`](Self::expect).
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Unwraps this `Session`, returning the underlying transport and the output received but
+    /// not yet returned by [`expect`](Self::expect).
+    pub fn into_inner(self) -> (T, Vec<u8>) {
+        (self.inner, self.buf)
+    }
+
+    /// Searches the buffered output for the needle that starts first.
+    fn find<N: Needle>(&mut self, needles: &[N]) -> Option<ExpectMatch> {
+        let haystack = &self.buf[self.scan_from..];
+        let (index, range) = needles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, needle)| needle.findin(haystack).map(|range| (index, range)))
+            .min_by_key(|(_, range)| range.start)?;
+        let range = self.scan_from + range.start..self.scan_from + range.end;
+
+        let rest = self.buf.split_off(range.end);
+        let matched = self.buf.split_off(range.start);
+        let before = std::mem::replace(&mut self.buf, rest);
+        self.scan_from = 0;
+        trace::pattern(index);
+        Some(ExpectMatch {
+            index,
+            before,
+            matched,
+        })
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> Session<T> {
+    /// Writes `data` to the transport and flushes it.
+    pub async fn send(&mut self, data: impl AsRef<[u8]>) -> io::Result<()> {
+        self.inner.write_all(data.as_ref()).await?;
+        self.inner.flush().await
+    }
+
+    /// Writes `line` followed by `\n` to the transport and flushes it.
+    pub async fn send_line(&mut self, line: impl AsRef<[u8]>) -> io::Result<()> {
+        self.inner.write_all(line.as_ref()).await?;
+        self.inner.write_all(b"\n").await?;
+        self.inner.flush().await
+    }
+
+    /// Reads output until one of `needles` is found.
+    ///
+    /// If several needles are found, the one starting first is reported, and among those
+    /// starting at the same position, the first in `needles`. The output after the match is
+    /// kept for the next call.
+    ///
+    /// # Errors
+    /// If the transport reaches EOF before any needle is found, an error of kind
+    /// `UnexpectedEof` is returned. The output received is kept and available through
+    /// [`buffer`](Self::buffer).
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe. The output received is kept in the session, so if the future
+    /// is dropped, calling this method again searches it again.
+    pub async fn expect<N: Needle>(&mut self, needles: &[N]) -> io::Result<ExpectMatch> {
+        let mut chunk = [0; 4096];
+        loop {
+            if let Some(found) = self.find(needles) {
+                return Ok(found);
+            }
+            // only a needle starting in the last `max_len - 1` bytes can be completed later
+            let keep = needles
+                .iter()
+                .map(|needle| needle.max_len().map(|len| len.saturating_sub(1)))
+                .try_fold(0, |keep, len| len.map(|len| keep.max(len)));
+            if let Some(keep) = keep {
+                self.scan_from = self.scan_from.max(self.buf.len().saturating_sub(keep));
+            }
+
+            let n = self.inner.read(&mut chunk).await?;
+            if n == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "EOF reached before any needle was found",
+                ));
+            }
+            self.buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    /// Hands the session over to a user until `needle` appears in either direction.
+    ///
+    /// The local `input`, such as a terminal, is sent to the transport and the output of the
+    /// transport is written to the local `output`, as with the `interact` command of expect.
+    /// Control returns as soon as `needle` is typed, which makes it an escape sequence, or shows
+    /// up in the output, such as a prompt that a script knows how to answer. Output buffered by
+    /// earlier [`expect`](Self::expect) calls is passed through first.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is not cancel safe: input bytes that may begin the needle are held back and
+    /// are lost if the future is dropped.
+    pub async fn interact<I, O, N>(
+        &mut self,
+        mut input: I,
+        mut output: O,
+        needle: N,
+    ) -> io::Result<Interaction>
+    where
+        I: AsyncRead + Unpin,
+        O: AsyncWrite + Unpin,
+        N: Needle,
+    {
+        let needles = std::slice::from_ref(&needle);
+        let mut held = HeldSearch::default();
+        let (mut in_chunk, mut out_chunk) = ([0; 1024], [0; 4096]);
+        // the bytes of `buf` already written to `output`
+        let mut passed = 0;
+        loop {
+            if let Some(found) = self.find(needles) {
+                let shown = [&found.before[..], &found.matched[..]].concat();
+                output.write_all(&shown[passed.min(shown.len())..]).await?;
+                output.flush().await?;
+                return Ok(Interaction::Output);
+            }
+            output.write_all(&self.buf[passed..]).await?;
+            output.flush().await?;
+            // drop the output that can no longer begin a match
+            if let Some(len) = needle.max_len() {
+                let drop = self.buf.len().saturating_sub(len.saturating_sub(1));
+                self.buf.drain(..drop);
+                self.scan_from = 0;
+            }
+            passed = self.buf.len();
+
+            match future::select(input.read(&mut in_chunk), self.inner.read(&mut out_chunk)).await {
+                Either::Left((n, _)) => {
+                    let n = n?;
+                    let mut before = Vec::new();
+                    if n == 0 {
+                        held.finish(&mut before);
+                        self.send(&before).await?;
+                        return Ok(Interaction::InputEof);
+                    }
+                    let (_, status) = held.push(&needle, &in_chunk[..n], &mut before, Vec::new());
+                    self.send(&before).await?;
+                    if status.is_some() {
+                        return Ok(Interaction::Input);
+                    }
+                }
+                Either::Right((n, _)) => {
+                    let n = n?;
+                    if n == 0 {
+                        return Ok(Interaction::OutputEof);
+                    }
+                    self.buf.extend_from_slice(&out_chunk[..n]);
+                }
+            }
+        }
+    }
+
+    /// Reads output until one of `needles` is found, failing if it takes longer than `timeout`.
+    ///
+    /// This is [`expect`](Self::expect) with a time limit. If it passes, an error of kind
+    /// `TimedOut` is returned and the output received so far is kept for the next call.
+    #[cfg(feature = "tokio")]
+    pub async fn expect_timeout<N: Needle>(
+        &mut self,
+        needles: &[N],
+        timeout: Duration,
+    ) -> io::Result<ExpectMatch> {
+        match ::tokio::time::timeout(timeout, self.expect(needles)).await {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "timed out before any needle was found",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+    use futures::{SinkExt as _, StreamExt as _};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// One end of an in-memory pipe, sending what is written to the peer.
+    struct Pipe {
+        rx: mpsc::UnboundedReceiver<Vec<u8>>,
+        tx: mpsc::UnboundedSender<Vec<u8>>,
+        pending: Vec<u8>,
+    }
+
+    impl AsyncRead for Pipe {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            while self.pending.is_empty() {
+                match futures::ready!(self.rx.poll_next_unpin(cx)) {
+                    Some(data) => self.pending = data,
+                    None => return Poll::Ready(Ok(0)),
+                }
+            }
+            let n = buf.len().min(self.pending.len());
+            buf[..n].copy_from_slice(&self.pending[..n]);
+            self.pending.drain(..n);
+            Poll::Ready(Ok(n))
+        }
+    }
+
+    impl AsyncWrite for Pipe {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let _ = self.tx.unbounded_send(buf.to_vec());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Creates a pipe and the peer's channels.
+    fn pipe() -> (
+        Pipe,
+        mpsc::UnboundedSender<Vec<u8>>,
+        mpsc::UnboundedReceiver<Vec<u8>>,
+    ) {
+        let (peer_tx, rx) = mpsc::unbounded();
+        let (tx, peer_rx) = mpsc::unbounded();
+        let pipe = Pipe {
+            rx,
+            tx,
+            pending: Vec::new(),
+        };
+        (pipe, peer_tx, peer_rx)
+    }
+
+    #[tokio::test]
+    async fn test_session_expect() {
+        let (pipe, mut peer_tx, mut peer_rx) = pipe();
+        let peer = tokio::spawn(async move {
+            // the prompt arrives byte by byte
+            for &b in b"login: " {
+                peer_tx.send(vec![b]).await.unwrap();
+            }
+            let mut input = Vec::new();
+            while !input.ends_with(b"\n") {
+                input.extend(peer_rx.next().await.unwrap());
+            }
+            assert_eq!(input, b"admin\n");
+            // the output after the prompt arrives together with it
+            peer_tx.send(b"welcome\n$ motd".to_vec()).await.unwrap();
+        });
+
+        let mut session = Session::new(pipe);
+        let m = session.expect(&["login: ", "$ "]).await.unwrap();
+        assert_eq!((m.index, &m.before[..]), (0, &b""[..]));
+        session.send_line("admin").await.unwrap();
+
+        let m = session.expect(&["# ", "$ "]).await.unwrap();
+        assert_eq!(
+            m,
+            ExpectMatch {
+                index: 1,
+                before: b"welcome\n".to_vec(),
+                matched: b"$ ".to_vec(),
+            }
+        );
+        peer.await.unwrap();
+
+        // the rest is kept for the next call, which then reaches EOF
+        let err = session.expect(&["$ "]).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(session.buffer(), b"motd");
+    }
+
+    #[tokio::test]
+    async fn test_session_expect_first_match() {
+        let (pipe, mut peer_tx, _peer_rx) = pipe();
+        peer_tx.send(b"error: denied\n$ ".to_vec()).await.unwrap();
+        let mut session = Session::new(pipe);
+        let m = session.expect(&["$ ", "error"]).await.unwrap();
+        assert_eq!((m.index, &m.matched[..]), (1, &b"error"[..]));
+        let m = session.expect(&["$ ", "error"]).await.unwrap();
+        assert_eq!((m.index, &m.before[..]), (0, &b": denied\n"[..]));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_session_expect_timeout() {
+        let (pipe, mut peer_tx, _peer_rx) = pipe();
+        peer_tx.send(b"loading".to_vec()).await.unwrap();
+        let mut session = Session::new(pipe);
+        let err = session
+            .expect_timeout(&["$ "], Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        peer_tx.send(b"...\n$ ".to_vec()).await.unwrap();
+        let m = session
+            .expect_timeout(&["$ "], Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert_eq!(m.before, b"loading...\n");
+    }
+
+    #[tokio::test]
+    async fn test_session_interact() {
+        let (transport, mut peer_tx, mut peer_rx) = pipe();
+        peer_tx.send(b"$ ".to_vec()).await.unwrap();
+        let mut session = Session::new(transport);
+        session.expect(&["$ "]).await.unwrap();
+
+        // the escape sequence typed by the user returns control
+        let peer = tokio::spawn(async move {
+            let mut input = Vec::new();
+            while !input.ends_with(b"\n") {
+                input.extend(peer_rx.next().await.unwrap());
+            }
+            assert_eq!(input, b"make\n");
+            for &b in b"building\nPassword: rest" {
+                peer_tx.send(vec![b]).await.unwrap();
+            }
+        });
+        let input = &b"make\n\x1d]ignored"[..];
+        let mut output = Vec::new();
+        let end = session
+            .interact(input, &mut output, b"\x1d]")
+            .await
+            .unwrap();
+        assert_eq!(end, Interaction::Input);
+        assert_eq!(output, b"");
+        peer.await.unwrap();
+
+        // a needle in the output returns control, keeping what follows it
+        let (idle, _idle_tx, _idle_rx) = pipe();
+        let mut output = Vec::new();
+        let end = session.interact(idle, &mut output, "Password: ").await;
+        assert_eq!(end.unwrap(), Interaction::Output);
+        assert_eq!(output, b"building\nPassword: ");
+        let m = session.expect(&["rest"]).await.unwrap();
+        assert_eq!(m.before, b"");
+    }
+
+    /// A needle counting how many bytes it has been asked to search.
+    struct CountingNeedle(&'static [u8], std::rc::Rc<std::cell::Cell<usize>>);
+
+    impl Needle for CountingNeedle {
+        fn findin(&self, haystack: &[u8]) -> Option<std::ops::Range<usize>> {
+            self.1.set(self.1.get() + haystack.len());
+            self.0.findin(haystack)
+        }
+
+        fn max_len(&self) -> Option<usize> {
+            self.0.max_len()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_expect_scans_incrementally() {
+        let (pipe, mut peer_tx, _peer_rx) = pipe();
+        for _ in 0..10_000 {
+            peer_tx.send(b"0123456789".to_vec()).await.unwrap();
+        }
+        peer_tx.send(b"$ ".to_vec()).await.unwrap();
+
+        let scanned = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut session = Session::new(pipe);
+        let m = session
+            .expect(&[CountingNeedle(b"$ ", scanned.clone())])
+            .await
+            .unwrap();
+        assert_eq!(m.before.len(), 100_000);
+        // each byte is searched at most once more as part of the overlap
+        assert!(scanned.get() <= 100_002 + 10_001);
+    }
+}
@@ -0,0 +1,124 @@
+//! Reading the header block of an HTTP/1.x message.
+//!
+//! The header block ends with an empty line, `\r\n\r\n`. A peer that never sends it would make
+//! a plain read until the needle buffer without bound, so
+//! [`read_http_headers`](crate::http::read_http_headers) and, with the `futures` feature,
+//! `read_http_headers_async` give up after a maximum size.
+
+use crate::io::UntilNeedleRead;
+use std::io;
+
+/// The empty line ending the header block.
+pub const HEADERS_END: &[u8] = b"\r\n\r\n";
+
+/// Reads the header block of an HTTP/1.x message, the request or status line included.
+///
+/// # Returns
+/// - `Some` with the header block, ending with `\r\n\r\n`, ready for an HTTP parser. The reader
+///   is left at the start of the body.
+/// - `None` if EOF was reached before any byte, as when a kept-alive connection is closed.
+///
+/// # Errors
+/// - If `max_len` bytes were read without the end of the header block, an error of kind
+///   `InvalidData` wrapping [`LimitExceeded`](crate::LimitExceeded) is returned.
+/// - If EOF is reached inside the header block, an error of kind `UnexpectedEof` is returned.
+///
+/// # Example
+/// ```
+/// use until_needle::http::read_http_headers;
+///
+/// let mut reader = &b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\nbody"[..];
+/// let headers = read_http_headers(&mut reader, 8 * 1024).unwrap();
+/// assert_eq!(headers.as_deref(), Some(&b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n"[..]));
+/// assert_eq!(reader, b"body");
+///
+/// let mut reader = &b"GET / HTTP/1.1\r\nX-Padding: aaaaaaaaaaaaaaaa"[..];
+/// let err = read_http_headers(&mut reader, 32).unwrap_err();
+/// assert!(err.get_ref().unwrap().is::<until_needle::LimitExceeded>());
+/// ```
+pub fn read_http_headers<R: io::BufRead>(
+    reader: &mut R,
+    max_len: usize,
+) -> io::Result<Option<Vec<u8>>> {
+    let (mut headers, mut end) = (Vec::new(), Vec::new());
+    reader.read_until_needle_limited(HEADERS_END, max_len, &mut headers, &mut end)?;
+    headers_block(headers, end)
+}
+
+/// Asynchronously reads the header block of an HTTP/1.x message.
+///
+/// This is the async variant of [`read_http_headers`].
+#[cfg(feature = "futures")]
+pub async fn read_http_headers_async<R>(
+    reader: &mut R,
+    max_len: usize,
+) -> io::Result<Option<Vec<u8>>>
+where
+    R: futures_util::io::AsyncBufRead + Unpin,
+{
+    use crate::futures::AsyncUntilNeedleRead;
+
+    let (mut headers, mut end) = (Vec::new(), Vec::new());
+    reader
+        .read_until_needle_limited(HEADERS_END, max_len, &mut headers, &mut end)
+        .await?;
+    headers_block(headers, end)
+}
+
+/// Joins the header block and its end, or tells why there is none.
+fn headers_block(mut headers: Vec<u8>, end: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+    if !end.is_empty() {
+        headers.extend_from_slice(&end);
+        return Ok(Some(headers));
+    }
+    if headers.is_empty() {
+        return Ok(None);
+    }
+    Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "EOF reached inside the HTTP headers",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ChunkedReader;
+    use crate::LimitExceeded;
+
+    #[test]
+    fn test_read_http_headers() {
+        let data = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let headers = read_http_headers(&mut reader, 64).unwrap().unwrap();
+            assert_eq!(headers, &data[..data.len() - 2]);
+        }
+
+        assert_eq!(read_http_headers(&mut &b""[..], 64).unwrap(), None);
+        let err = read_http_headers(&mut &b"HTTP/1.1 200 OK\r\n"[..], 64).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // the limit counts the end of the header block
+        assert!(read_http_headers(&mut &data[..], 38).unwrap().is_some());
+        let err = read_http_headers(&mut &data[..], 37).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let limit = err.get_ref().unwrap().downcast_ref::<LimitExceeded>();
+        assert_eq!(limit, Some(&LimitExceeded { limit: 37 }));
+    }
+
+    #[cfg(feature = "futures")]
+    #[tokio::test]
+    async fn test_read_http_headers_async() {
+        use crate::testing::ChunkedAsyncReader;
+
+        let mut reader =
+            ChunkedAsyncReader::from_chunks(["GET / HTTP/1.1\r", "\n\r\n", "body"]).pending(1);
+        let headers = read_http_headers_async(&mut reader, 64).await.unwrap();
+        assert_eq!(headers.as_deref(), Some(&b"GET / HTTP/1.1\r\n\r\n"[..]));
+
+        let mut reader = ChunkedAsyncReader::from_chunks(["GET / HTTP/1.1\r\n", "X: y\r\n"]);
+        let err = read_http_headers_async(&mut reader, 16).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
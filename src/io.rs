@@ -1,4 +1,78 @@
-use crate::Needle;
+#[cfg(feature = "regex")]
+use crate::needle::NeedleMatch;
+use crate::needle::{combinators, MultipartBoundary, PartialMatch, Scanner};
+use crate::output::{Discard, HeldSearch, OutputBuf, Writer};
+use crate::trace;
+use crate::{
+    CopyReport, InvalidUtf8, LimitExceeded, Needle, SkipReport, UntilNeedle, UntilNeedleOutput,
+    UntilNeedleStatus,
+};
+#[cfg(feature = "bytes")]
+use bytes::{Bytes, BytesMut};
+#[cfg(feature = "pty")]
+pub use portable_pty::{CommandBuilder, ExitStatus, PtySize};
+#[cfg(feature = "pty")]
+pub use pty::PtyChild;
+#[cfg(feature = "regex")]
+use regex::bytes::Regex;
+pub use session::Session;
+use std::cell::Cell;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "pty")]
+mod pty;
+mod session;
+
+/// The outcome of [`UntilNeedleRead::read_until_needle_or_rewind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewindStatus {
+    /// The needle was found; holds the total number of bytes read, including the needle.
+    Found(usize),
+    /// EOF was reached without finding the needle and the reader was moved back to where it started.
+    Rewound,
+}
+
+/// The outcome of [`UntilNeedleRead::read_lines_until`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinesStatus {
+    /// A line matching the needle was found.
+    Found,
+    /// EOF was reached before any line matched the needle.
+    Eof,
+}
+
+/// The outcome of [`UntilNeedleRead::read_between_needles`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BetweenStatus {
+    /// Both needles were found.
+    Found,
+    /// EOF was reached before the start needle was found.
+    NoStart,
+    /// EOF was reached after the start needle but before the end needle.
+    NoEnd,
+}
+
+/// Options for [`UntilNeedleRead::read_until_needle_follow`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FollowOpts {
+    /// How long to sleep after hitting EOF before trying to read again.
+    pub poll_interval: Duration,
+    /// The overall time limit for the call, or `None` to wait forever.
+    pub deadline: Option<Duration>,
+    /// The longest time to wait without receiving any data, or `None` for no limit.
+    pub max_idle: Option<Duration>,
+}
+
+impl Default for FollowOpts {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(100),
+            deadline: None,
+            max_idle: None,
+        }
+    }
+}
 
 // The trait to extend BufRead for until_needle functionality
 pub trait UntilNeedleRead {
@@ -8,7 +82,7 @@ pub trait UntilNeedleRead {
     /// - `needle`: An object implementing the `Needle` trait, which defines the search pattern.
     /// - `before`: A mutable buffer to store the data read before the `needle` is found.
     /// - `matched`: A mutable buffer to store the `needle` itself, if found. If EOF is reached without finding
-    ///              the needle, this buffer will not be modified.
+    ///   the needle, this buffer will not be modified.
     ///
     /// # Returns
     /// - On success, it returns the total number of bytes read, including the needle.
@@ -19,53 +93,1750 @@ pub trait UntilNeedleRead {
         before: &mut Vec<u8>,
         matched: &mut Vec<u8>,
     ) -> std::io::Result<usize>;
+
+    /// Reads data until the specified `needle` is found or EOF is reached, giving up after
+    /// `limit` bytes.
+    ///
+    /// This bounds the memory used when scanning untrusted peers that may never send the needle.
+    /// A needle is only found if it lies within the limit.
+    ///
+    /// # Returns
+    /// - On success, it returns the total number of bytes read, including the needle, like
+    ///   [`read_until_needle`](Self::read_until_needle).
+    ///
+    /// # Errors
+    /// If `limit` bytes were read without finding the needle, an error of kind `InvalidData`
+    /// wrapping [`LimitExceeded`] is returned. The bytes read are left in `before` and the reader
+    /// is left right after them.
+    fn read_until_needle_limited(
+        &mut self,
+        needle: impl Needle,
+        limit: usize,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> std::io::Result<usize>;
+
+    /// Reads data until the specified `needle` is found, failing if EOF is reached first.
+    ///
+    /// This is [`read_until_needle`](Self::read_until_needle) for reads where a missing needle
+    /// is a failure, such as waiting for a prompt, so the caller does not have to check
+    /// `matched` after each read.
+    ///
+    /// # Errors
+    /// If EOF is reached before the needle is found, an error of kind `UnexpectedEof` wrapping
+    /// [`UntilNeedleError::EofWithoutMatch`](crate::UntilNeedleError::EofWithoutMatch) is
+    /// returned. The bytes read are left in `before`.
+    ///
+    /// # Example
+    /// ```
+    /// use until_needle::io::UntilNeedleRead;
+    ///
+    /// let mut reader = &b"Connection closed by foreign host.\r\n"[..];
+    /// let (mut before, mut matched) = (Vec::new(), Vec::new());
+    /// let err = reader
+    ///     .expect_needle("login: ", &mut before, &mut matched)
+    ///     .unwrap_err();
+    /// assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    /// assert_eq!(before, b"Connection closed by foreign host.\r\n");
+    /// ```
+    fn expect_needle(
+        &mut self,
+        needle: impl Needle,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> std::io::Result<usize>;
+
+    /// Reads data until the specified `needle` is found or EOF is reached, returning owned buffers.
+    ///
+    /// This is [`read_until_needle`](Self::read_until_needle) for callers that just want the
+    /// data back. [`UntilNeedleOutput::matched`] is `None` if EOF was reached before the needle
+    /// was found.
+    fn read_until_needle_owned(
+        &mut self,
+        needle: impl Needle,
+    ) -> std::io::Result<UntilNeedleOutput>;
+
+    /// Reads data until the specified `needle` is found or EOF is reached, returning it as text.
+    ///
+    /// The data is only decoded once the read is over, so characters split across reads of the
+    /// underlying reader are decoded whole. The second string is `None` if EOF was reached
+    /// before the needle was found.
+    ///
+    /// # Errors
+    /// If the data before the needle or the needle itself is not valid UTF-8, an error of kind
+    /// `InvalidData` wrapping [`InvalidUtf8`] is returned. It holds the offset of the first
+    /// invalid byte and the data read, which is consumed from the reader either way.
+    ///
+    /// # Example
+    /// ```
+    /// use until_needle::io::UntilNeedleRead;
+    ///
+    /// let mut reader = "Passwort für root: ".as_bytes();
+    /// let (before, matched) = reader.read_until_needle_str(": ")?;
+    /// assert_eq!(before, "Passwort für root");
+    /// assert_eq!(matched.as_deref(), Some(": "));
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    fn read_until_needle_str(
+        &mut self,
+        needle: impl Needle,
+    ) -> std::io::Result<(String, Option<String>)>;
+
+    /// Reads data until the specified `needle` is found or EOF is reached, returning it as text
+    /// with invalid UTF-8 replaced.
+    ///
+    /// This is [`read_until_needle_str`](Self::read_until_needle_str) for output that may hold
+    /// stray bytes, such as binary noise on a serial console: each invalid sequence is replaced
+    /// with U+FFFD instead of failing the read.
+    fn read_until_needle_str_lossy(
+        &mut self,
+        needle: impl Needle,
+    ) -> std::io::Result<(String, Option<String>)>;
+
+    /// Reads data until the `n`th occurrence of the specified `needle` is found or EOF is reached.
+    ///
+    /// The earlier occurrences are part of the data and go to `before` along with the data around
+    /// them, so a read can get past a prompt that is first echoed back. Occurrences do not
+    /// overlap: the search for the next one starts after the end of the previous one.
+    ///
+    /// # Returns
+    /// - On success, it returns the total number of bytes read, including the needles. If EOF is
+    ///   reached before the `n`th occurrence, `matched` will remain untouched.
+    ///
+    /// # Panics
+    /// Panics if `n` is 0.
+    ///
+    /// # Example
+    /// ```
+    /// use until_needle::io::UntilNeedleRead;
+    ///
+    /// let mut reader = &b"$ echo hi\r\nhi\r\n$ "[..];
+    /// let (mut before, mut matched) = (Vec::new(), Vec::new());
+    /// reader.read_until_nth_needle("$ ", 2, &mut before, &mut matched).unwrap();
+    /// assert_eq!(before, b"$ echo hi\r\nhi\r\n");
+    /// ```
+    fn read_until_nth_needle(
+        &mut self,
+        needle: impl Needle,
+        n: usize,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> std::io::Result<usize>;
+
+    /// Reads data until the specified `needle` is found, then up to `after_len` bytes after it.
+    ///
+    /// This keeps what follows a marker along with what precedes it, as when the lines after an
+    /// error in a log matter too. `before` and `matched` are filled as by
+    /// [`read_until_needle`](Self::read_until_needle), and the bytes after the needle are
+    /// appended to `after`. Only those bytes are consumed, so the reader is left right after
+    /// them; fewer than `after_len` are read if EOF comes first.
+    ///
+    /// # Returns
+    /// - On success, it returns the total number of bytes read, including the needle and the
+    ///   bytes after it. If EOF is reached before the needle is found, `matched` and `after` will
+    ///   remain untouched.
+    ///
+    /// # Example
+    /// ```
+    /// use until_needle::io::UntilNeedleRead;
+    ///
+    /// let mut reader = &b"ok\nERROR: disk full\n  at write()\nok\n"[..];
+    /// let (mut before, mut matched, mut after) = (Vec::new(), Vec::new(), Vec::new());
+    /// reader
+    ///     .read_until_needle_and_after("ERROR", 11, &mut before, &mut matched, &mut after)
+    ///     .unwrap();
+    /// assert_eq!(after, b": disk full");
+    /// assert_eq!(reader, b"\n  at write()\nok\n");
+    /// ```
+    fn read_until_needle_and_after(
+        &mut self,
+        needle: impl Needle,
+        after_len: usize,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+        after: &mut Vec<u8>,
+    ) -> std::io::Result<usize>;
+
+    /// Reads data until any of `needles` is found or EOF is reached, returning which one it was.
+    ///
+    /// The earliest match wins; among needles matching at the same position, the first one in
+    /// `needles` wins. `before` and `matched` are filled as by
+    /// [`read_until_needle`](Self::read_until_needle).
+    ///
+    /// # Returns
+    /// - `Some` with the index in `needles` of the needle found.
+    /// - `None` if EOF was reached before any needle was found.
+    ///
+    /// # Example
+    /// ```
+    /// use until_needle::io::UntilNeedleRead;
+    ///
+    /// let mut reader = &b"Welcome\r\nPassword: "[..];
+    /// let (mut before, mut matched) = (Vec::new(), Vec::new());
+    /// let found = reader
+    ///     .read_until_any(&["login: ", "Password: "], &mut before, &mut matched)
+    ///     .unwrap();
+    /// assert_eq!(found, Some(1));
+    /// assert_eq!(matched, b"Password: ");
+    /// ```
+    fn read_until_any<N: Needle>(
+        &mut self,
+        needles: &[N],
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> std::io::Result<Option<usize>>;
+
+    /// Reads data until the specified `needle` is found or EOF is reached, writing it to any
+    /// [`OutputBuf`].
+    ///
+    /// This is [`read_until_needle`](Self::read_until_needle) for sinks other than `Vec<u8>`,
+    /// such as [`Discard`] or a
+    /// [`Limited`](crate::output::Limited) wrapper. Pass `&mut` to keep using a sink afterwards.
+    /// Since a sink cannot take data back, the bytes that may begin the needle are only written
+    /// once the next chunk shows they do not. That is at most [`Needle::max_len`] - 1 bytes, or
+    /// the suffix told by [`Needle::findin_partial`] for needles without a maximum length. A
+    /// needle with neither, such as a regex not wrapped in
+    /// [`WithMaxLen`](crate::needle::WithMaxLen), holds everything read until it is found.
+    ///
+    /// # Returns
+    /// - [`UntilNeedleStatus::Found`] or [`UntilNeedleStatus::Eof`] like `read_until_needle`.
+    /// - [`UntilNeedleStatus::OutputFull`] if a sink was full. The bytes that did not fit are
+    ///   dropped; the reader is left after the data offered to the sink.
+    fn read_until_needle_into_out(
+        &mut self,
+        needle: impl Needle,
+        before: impl OutputBuf,
+        matched: impl OutputBuf,
+    ) -> std::io::Result<UntilNeedleStatus>;
+
+    /// Skips data until the specified `needle` is found or EOF is reached.
+    ///
+    /// The skipped data is dropped as it is read, as by the async `skip_until_needle`; only the
+    /// bytes that may begin a needle completed by the next chunk are kept, bounded as for
+    /// [`read_until_needle_into_out`](Self::read_until_needle_into_out). The needle is consumed,
+    /// so a following read starts right after it.
+    fn skip_until_needle(&mut self, needle: impl Needle) -> std::io::Result<SkipReport>;
+
+    /// Copies data to `writer` until the specified `needle` is found or EOF is reached.
+    ///
+    /// As with the async `copy_until_needle`, the data before the needle is written as it is
+    /// read. Scanning a large dump for a terminator only keeps the bytes that may begin it, as
+    /// [`read_until_needle_into_out`](Self::read_until_needle_into_out) does.
+    /// The needle is consumed and returned in the report, not written. Write it on from there if
+    /// it belongs to the copy. The writer is flushed before returning.
+    ///
+    /// # Errors
+    /// Errors of `writer` are returned, leaving the reader after the data offered to it.
+    ///
+    /// # Example
+    /// ```
+    /// use until_needle::io::UntilNeedleRead;
+    ///
+    /// let mut reader = &b"first part--boundary--second part"[..];
+    /// let mut file = Vec::new();
+    /// let report = reader.copy_until_needle(b"--boundary--", &mut file).unwrap();
+    /// assert_eq!(report.copied, 10);
+    /// assert_eq!(report.matched.as_deref(), Some(&b"--boundary--"[..]));
+    /// assert_eq!(file, b"first part");
+    /// assert_eq!(reader, b"second part");
+    /// ```
+    fn copy_until_needle<W: std::io::Write + ?Sized>(
+        &mut self,
+        needle: impl Needle,
+        writer: &mut W,
+    ) -> std::io::Result<CopyReport>;
+
+    /// Skips data until the `start` needle, then reads data until the `end` needle.
+    ///
+    /// The data between the two needles is appended to `between`, without the needles
+    /// themselves. The data before `start` is dropped as by
+    /// [`skip_until_needle`](Self::skip_until_needle). Both needles are consumed, so a following
+    /// read starts right after `end`.
+    ///
+    /// # Returns
+    /// - [`BetweenStatus::Found`] if both needles were found.
+    /// - [`BetweenStatus::NoStart`] if EOF was reached before `start`; `between` is untouched.
+    /// - [`BetweenStatus::NoEnd`] if EOF was reached before `end`; the data read after `start`
+    ///   is left in `between`.
+    ///
+    /// # Example
+    /// ```
+    /// use until_needle::io::{BetweenStatus, UntilNeedleRead};
+    ///
+    /// let mut reader = &b"]]>]]><rpc-reply><ok/></rpc-reply>]]>]]>"[..];
+    /// let mut body = Vec::new();
+    /// let status = reader
+    ///     .read_between_needles("<rpc-reply>", "</rpc-reply>", &mut body)
+    ///     .unwrap();
+    /// assert_eq!(status, BetweenStatus::Found);
+    /// assert_eq!(body, b"<ok/>");
+    /// ```
+    fn read_between_needles(
+        &mut self,
+        start: impl Needle,
+        end: impl Needle,
+        between: &mut Vec<u8>,
+    ) -> std::io::Result<BetweenStatus>;
+
+    /// Reads data until the specified `needle` is found or EOF is reached, passing it to `f`
+    /// without copying when possible.
+    ///
+    /// If the needle is found in the data currently buffered by the reader, `f` is called with
+    /// the data before it and the needle, both borrowed from the reader's own buffer. Otherwise
+    /// the data is collected into temporary buffers as by
+    /// [`read_until_needle`](Self::read_until_needle) and `f` is called with those; the needle
+    /// is `None` if EOF was reached first.
+    ///
+    /// The data passed to `f` is consumed once `f` returns, so the next read starts right after
+    /// the needle.
+    ///
+    /// # Returns
+    /// - On success, it returns the value returned by `f`.
+    fn read_until_needle_ref<O>(
+        &mut self,
+        needle: impl Needle,
+        f: impl FnOnce(&[u8], Option<&[u8]>) -> O,
+    ) -> std::io::Result<O>;
+
+    /// Reads data until the specified `needle` is found or EOF is reached, accumulating it in a
+    /// [`BytesMut`].
+    ///
+    /// The data is appended to `buf` as it is read, and the search starts at the end of what
+    /// `buf` already holds. Once the needle is found, the data before it and the needle itself
+    /// are split off `buf` and returned as [`Bytes`] sharing its memory, without copying.
+    ///
+    /// # Returns
+    /// - `Some((before, matched))` if the needle was found. `buf` is left empty.
+    /// - `None` if EOF was reached first. The data read is left in `buf`.
+    #[cfg(feature = "bytes")]
+    fn read_until_needle_bytes(
+        &mut self,
+        needle: impl Needle,
+        buf: &mut BytesMut,
+    ) -> std::io::Result<Option<(Bytes, Bytes)>>;
+
+    /// Reads data until the specified `regex` matches or EOF is reached, returning the match
+    /// with its capture groups.
+    ///
+    /// This is [`read_until_needle`](Self::read_until_needle) for callers that need the groups
+    /// of the match, such as a session ID in a prompt.
+    ///
+    /// # Returns
+    /// - `Some` with the match and its groups if the regex matched.
+    /// - `None` if EOF was reached first; the data read is left in `before`.
+    #[cfg(feature = "regex")]
+    fn read_until_needle_match(
+        &mut self,
+        regex: &Regex,
+        before: &mut Vec<u8>,
+    ) -> std::io::Result<Option<NeedleMatch>>;
+
+    /// Reads data until the specified `needle` is found, or rewinds the reader if it is not.
+    ///
+    /// On a successful match this behaves exactly like [`read_until_needle`](Self::read_until_needle).
+    /// If EOF is reached before the needle is found, the reader is seeked back to the position it
+    /// had when the call started (taking data held in the reader's buffer into account), so the
+    /// following reads see the same bytes as if the probe never happened.
+    ///
+    /// # Arguments
+    /// - `needle`: An object implementing the `Needle` trait, which defines the search pattern.
+    /// - `before`: A mutable buffer to store the data read before the `needle` is found. It is
+    ///   restored to its original length when the reader is rewound.
+    /// - `matched`: A mutable buffer to store the `needle` itself, if found.
+    ///
+    /// # Returns
+    /// - [`RewindStatus::Found`] with the total number of bytes read, including the needle.
+    /// - [`RewindStatus::Rewound`] if EOF was reached before the needle was found.
+    fn read_until_needle_or_rewind(
+        &mut self,
+        needle: impl Needle,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> std::io::Result<RewindStatus>
+    where
+        Self: std::io::Seek;
+
+    /// Reads data until the specified `needle` is found, waiting for more data on EOF.
+    ///
+    /// This is the `tail -f` flavor of [`read_until_needle`](Self::read_until_needle): when the
+    /// reader reports EOF, it sleeps for [`FollowOpts::poll_interval`] and tries again instead of
+    /// giving up. Data read before an EOF is kept, so a needle completed by data appended later
+    /// is still found.
+    ///
+    /// # Arguments
+    /// - `needle`: An object implementing the `Needle` trait, which defines the search pattern.
+    /// - `before`: A mutable buffer to store the data read before the `needle` is found.
+    /// - `matched`: A mutable buffer to store the `needle` itself, if found.
+    /// - `opts`: The polling interval and time limits.
+    ///
+    /// # Returns
+    /// - On success, it returns the total number of bytes read, including the needle.
+    ///
+    /// # Errors
+    /// If the deadline or the idle limit passes without a match, an error of kind `TimedOut` is
+    /// returned and all data read so far is left in `before`.
+    fn read_until_needle_follow(
+        &mut self,
+        needle: impl Needle,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+        opts: FollowOpts,
+    ) -> std::io::Result<usize>;
+
+    /// Reads whole lines until one of them matches the specified `needle` or EOF is reached.
+    ///
+    /// Unlike [`read_until_needle`](Self::read_until_needle), the needle is evaluated against each
+    /// line on its own rather than across the raw stream, so reading never stops mid-line. Line
+    /// terminators (`\n` or `\r\n`) are stripped before matching and are not stored.
+    ///
+    /// # Arguments
+    /// - `needle`: An object implementing the `Needle` trait, which is applied to each line's bytes.
+    /// - `preceding`: A mutable buffer to store the lines read before the matching line.
+    /// - `matching`: A mutable buffer to store the matching line, if found. If EOF is reached
+    ///   without a matching line, this buffer will not be modified.
+    ///
+    /// # Returns
+    /// - [`LinesStatus::Found`] if a matching line was read.
+    /// - [`LinesStatus::Eof`] if EOF was reached first; the lines read so far are kept in `preceding`.
+    ///
+    /// # Errors
+    /// Lines that are not valid UTF-8 produce an error of kind `InvalidData`, as with
+    /// [`BufRead::read_line`](std::io::BufRead::read_line).
+    fn read_lines_until(
+        &mut self,
+        needle: impl Needle,
+        preceding: &mut Vec<String>,
+        matching: &mut String,
+    ) -> std::io::Result<LinesStatus>;
+
+    /// Returns an iterator over the non-overlapping matches of `needle` in the underlying reader.
+    ///
+    /// Each item is the absolute byte offset of a match (counted from where the iterator started)
+    /// together with the matched bytes. The data between matches is consumed without being
+    /// collected; only the trailing bytes that may begin a match straddling two reads are kept,
+    /// as bounded by [`Needle::max_len`].
+    ///
+    /// After a match has been yielded, the reader is positioned right after it, so stopping early
+    /// (e.g. with [`Iterator::take`]) and calling [`NeedleMatches::into_inner`] leaves the reader
+    /// ready to continue from there.
+    fn matches_by_needle<N: Needle>(self, needle: N) -> NeedleMatches<Self, N>
+    where
+        Self: Sized;
+
+    /// Returns an iterator over the segments of the underlying reader delimited by `needle`.
+    ///
+    /// This is [`BufRead::split`](std::io::BufRead::split) for multi-byte and regex delimiters.
+    /// Each item is the data before an occurrence of the needle, without the needle. The data
+    /// after the last needle is yielded as the final segment unless it is empty. The iterator
+    /// ends after yielding an error.
+    fn segments<N: Needle>(self, needle: N) -> Segments<Self, N>
+    where
+        Self: Sized;
+
+    /// Creates an adapter reading from the underlying reader up to the next occurrence of
+    /// `needle`.
+    ///
+    /// The adapter implements [`Read`](std::io::Read) and [`BufRead`](std::io::BufRead) and
+    /// reports EOF once the needle is reached, so a decoder or parser taking a reader can be
+    /// bounded by a delimiter. Nothing past the needle is consumed from the underlying reader,
+    /// which is returned by [`TakeUntil::into_inner`] to continue reading after the needle.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Read;
+    /// use until_needle::io::UntilNeedleRead;
+    ///
+    /// let mut take = (&b"first record\n--\nsecond record"[..]).take_until(b"\n--\n");
+    /// let mut record = String::new();
+    /// take.read_to_string(&mut record).unwrap();
+    /// assert_eq!(record, "first record");
+    /// assert_eq!(take.matched(), Some(&b"\n--\n"[..]));
+    /// let (rest, held) = take.into_inner();
+    /// assert_eq!((rest, &held[..]), (&b"second record"[..], &b""[..]));
+    /// ```
+    fn take_until<N: Needle>(self, needle: N) -> TakeUntil<Self, N>
+    where
+        Self: Sized;
+
+    /// Returns an iterator over the parts of a MIME multipart body delimited by `boundary`.
+    ///
+    /// The preamble before the first delimiter is skipped. Each item is the body of a part,
+    /// starting with its headers, without the CRLF that belongs to the delimiter after it. The
+    /// iterator ends at the close delimiter, leaving the reader right after its `--` so the
+    /// epilogue can still be read. See [`MultipartBoundary`] for the delimiters matched.
+    ///
+    /// # Errors
+    /// If EOF is reached before the close delimiter, the iterator yields an error of kind
+    /// `UnexpectedEof` and ends.
+    ///
+    /// # Example
+    /// ```
+    /// use until_needle::io::UntilNeedleRead;
+    ///
+    /// let body = &b"--b0\r\nContent-Type: text/plain\r\n\r\nhi\r\n--b0--\r\n"[..];
+    /// let parts = body.multipart_parts("b0").collect::<std::io::Result<Vec<_>>>().unwrap();
+    /// assert_eq!(parts, [&b"Content-Type: text/plain\r\n\r\nhi"[..]]);
+    /// ```
+    fn multipart_parts(self, boundary: impl AsRef<[u8]>) -> MultipartParts<Self>
+    where
+        Self: Sized;
+
+    /// Reads data from the underlying reader as long as each byte satisfies `pred`.
+    ///
+    /// Reading stops right before the first byte for which `pred` returns `false`; that byte is
+    /// left unconsumed in the reader, so a following read starts with it.
+    ///
+    /// # Arguments
+    /// - `pred`: A predicate deciding whether a byte belongs to the run being read.
+    /// - `out`: A mutable buffer the matching bytes are appended to.
+    ///
+    /// # Returns
+    /// - On success, it returns the number of bytes read. Reaching EOF while the predicate still
+    ///   holds is a normal completion.
+    fn read_while(
+        &mut self,
+        pred: impl Fn(u8) -> bool,
+        out: &mut Vec<u8>,
+    ) -> std::io::Result<usize>;
+}
+
+impl<T: std::io::BufRead> UntilNeedleRead for T {
+    fn read_until_needle(
+        &mut self,
+        needle: impl Needle,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> std::io::Result<usize> {
+        UntilNeedle::new(needle)
+            .read(self, before, matched)
+            .map(|status| status.bytes_read())
+    }
+
+    fn read_until_needle_limited(
+        &mut self,
+        needle: impl Needle,
+        limit: usize,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> std::io::Result<usize> {
+        match UntilNeedle::new(needle)
+            .limit(limit)
+            .read(self, before, matched)?
+        {
+            UntilNeedleStatus::LimitReached { bytes_read } => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                LimitExceeded { limit: bytes_read },
+            )),
+            status => Ok(status.bytes_read()),
+        }
+    }
+
+    fn expect_needle(
+        &mut self,
+        needle: impl Needle,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> std::io::Result<usize> {
+        let status = UntilNeedle::new(needle).read(self, before, matched)?;
+        Ok(status.into_result()?)
+    }
+
+    fn read_until_needle_owned(
+        &mut self,
+        needle: impl Needle,
+    ) -> std::io::Result<UntilNeedleOutput> {
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        let status = UntilNeedle::new(needle).read(self, &mut before, &mut matched)?;
+        Ok(UntilNeedleOutput {
+            before,
+            matched: status.is_found().then_some(matched),
+            bytes_read: status.bytes_read(),
+        })
+    }
+
+    fn read_until_needle_str(
+        &mut self,
+        needle: impl Needle,
+    ) -> std::io::Result<(String, Option<String>)> {
+        let UntilNeedleOutput {
+            before,
+            matched,
+            bytes_read,
+        } = self.read_until_needle_owned(needle)?;
+        let invalid = |offset, before, matched| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                InvalidUtf8 {
+                    offset,
+                    output: UntilNeedleOutput {
+                        before,
+                        matched,
+                        bytes_read,
+                    },
+                },
+            )
+        };
+        let before = match String::from_utf8(before) {
+            Ok(before) => before,
+            Err(e) => {
+                let offset = e.utf8_error().valid_up_to();
+                return Err(invalid(offset, e.into_bytes(), matched));
+            }
+        };
+        let matched = match matched.map(String::from_utf8).transpose() {
+            Ok(matched) => matched,
+            Err(e) => {
+                let offset = before.len() + e.utf8_error().valid_up_to();
+                return Err(invalid(offset, before.into_bytes(), Some(e.into_bytes())));
+            }
+        };
+        Ok((before, matched))
+    }
+
+    fn read_until_needle_str_lossy(
+        &mut self,
+        needle: impl Needle,
+    ) -> std::io::Result<(String, Option<String>)> {
+        let output = self.read_until_needle_owned(needle)?;
+        let lossy = |data: Vec<u8>| {
+            String::from_utf8(data)
+                .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+        };
+        Ok((lossy(output.before), output.matched.map(lossy)))
+    }
+
+    fn read_until_nth_needle(
+        &mut self,
+        needle: impl Needle,
+        n: usize,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> std::io::Result<usize> {
+        assert!(n > 0, "the occurrence to stop at is counted from 1");
+        let mut bytes_read = 0;
+        for i in 1..=n {
+            let mut found = Vec::new();
+            let status = read_until_needle_internal(
+                self,
+                &needle,
+                &mut Scanner::new(),
+                before,
+                &mut found,
+                |_| Ok(false),
+            )?;
+            bytes_read += status.bytes_read();
+            if !status.is_found() {
+                break;
+            }
+            if i == n {
+                matched.append(&mut found);
+            } else {
+                before.append(&mut found);
+            }
+        }
+        Ok(bytes_read)
+    }
+
+    fn read_until_needle_and_after(
+        &mut self,
+        needle: impl Needle,
+        after_len: usize,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+        after: &mut Vec<u8>,
+    ) -> std::io::Result<usize> {
+        use std::io::Read;
+
+        let status = UntilNeedle::new(needle).read(self, before, matched)?;
+        if !status.is_found() {
+            return Ok(status.bytes_read());
+        }
+        let after_read = self.by_ref().take(after_len as u64).read_to_end(after)?;
+        Ok(status.bytes_read() + after_read)
+    }
+
+    fn read_until_any<N: Needle>(
+        &mut self,
+        needles: &[N],
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> std::io::Result<Option<usize>> {
+        let needle = FirstOf {
+            needles,
+            found: Cell::new(None),
+        };
+        let status = read_until_needle_internal(
+            self,
+            &needle,
+            &mut Scanner::new(),
+            before,
+            matched,
+            |_| Ok(false),
+        )?;
+        let found = needle.found.get().filter(|_| status.is_found());
+        if let Some(index) = found {
+            trace::pattern(index);
+        }
+        Ok(found)
+    }
+
+    fn read_until_needle_into_out(
+        &mut self,
+        needle: impl Needle,
+        mut before: impl OutputBuf,
+        mut matched: impl OutputBuf,
+    ) -> std::io::Result<UntilNeedleStatus> {
+        let mut search = HeldSearch::default();
+        trace::read_span(&needle).in_scope(|| {
+            let status = loop {
+                let available = match self.fill_buf() {
+                    Ok(n) => n,
+                    Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+
+                if available.is_empty() {
+                    // EOF
+                    break search.finish(&mut before);
+                }
+
+                let (used, status) = search.push(&needle, available, &mut before, &mut matched);
+                self.consume(used);
+                if let Some(status) = status {
+                    break status;
+                }
+            };
+            trace::outcome(&status);
+            Ok(status)
+        })
+    }
+
+    fn skip_until_needle(&mut self, needle: impl Needle) -> std::io::Result<SkipReport> {
+        let mut matched = Vec::new();
+        let status = self.read_until_needle_into_out(needle, Discard, &mut matched)?;
+        if !status.is_found() {
+            return Ok(SkipReport {
+                skipped: status.bytes_read(),
+                matched: None,
+            });
+        }
+        Ok(SkipReport {
+            skipped: status.bytes_read() - matched.len(),
+            matched: Some(matched),
+        })
+    }
+
+    fn copy_until_needle<W: std::io::Write + ?Sized>(
+        &mut self,
+        needle: impl Needle,
+        writer: &mut W,
+    ) -> std::io::Result<CopyReport> {
+        let mut out = Writer::new(&mut *writer);
+        let mut matched = Vec::new();
+        let status = self.read_until_needle_into_out(needle, &mut out, &mut matched)?;
+        if let Some(e) = out.take_error() {
+            return Err(e);
+        }
+        writer.flush()?;
+        let copied = status.bytes_read() - matched.len();
+        Ok(CopyReport {
+            copied,
+            matched: status.is_found().then_some(matched),
+        })
+    }
+
+    fn read_between_needles(
+        &mut self,
+        start: impl Needle,
+        end: impl Needle,
+        between: &mut Vec<u8>,
+    ) -> std::io::Result<BetweenStatus> {
+        if self.skip_until_needle(start)?.matched.is_none() {
+            return Ok(BetweenStatus::NoStart);
+        }
+        let mut matched = Vec::new();
+        match UntilNeedle::new(end).read(self, between, &mut matched)? {
+            UntilNeedleStatus::Found { .. } => Ok(BetweenStatus::Found),
+            _ => Ok(BetweenStatus::NoEnd),
+        }
+    }
+
+    fn read_until_needle_ref<O>(
+        &mut self,
+        needle: impl Needle,
+        f: impl FnOnce(&[u8], Option<&[u8]>) -> O,
+    ) -> std::io::Result<O> {
+        loop {
+            let available = match self.fill_buf() {
+                Ok(n) => n,
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if let Some(range) = needle.findin(available) {
+                let output = f(&available[..range.start], Some(&available[range.clone()]));
+                self.consume(range.end);
+                return Ok(output);
+            }
+            break;
+        }
+
+        // the needle does not fit in the buffer, so fall back to copying
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        let status = UntilNeedle::new(needle).read(self, &mut before, &mut matched)?;
+        Ok(f(&before, status.is_found().then_some(&matched[..])))
+    }
+
+    #[cfg(feature = "bytes")]
+    fn read_until_needle_bytes(
+        &mut self,
+        needle: impl Needle,
+        buf: &mut BytesMut,
+    ) -> std::io::Result<Option<(Bytes, Bytes)>> {
+        let mut scan_from = buf.len();
+
+        loop {
+            let (found, used) = {
+                let available = match self.fill_buf() {
+                    Ok(n) => n,
+                    Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+                if available.is_empty() {
+                    // EOF
+                    return Ok(None);
+                }
+                search_bytes_mut(&needle, available, buf, &mut scan_from)
+            };
+
+            self.consume(used);
+            if let Some(range) = found {
+                return Ok(Some(split_bytes_mut(buf, range)));
+            }
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    fn read_until_needle_match(
+        &mut self,
+        regex: &Regex,
+        before: &mut Vec<u8>,
+    ) -> std::io::Result<Option<NeedleMatch>> {
+        let mut matched = Vec::new();
+        let status = read_until_needle_internal(
+            self,
+            regex,
+            &mut Scanner::new(),
+            before,
+            &mut matched,
+            |_| Ok(false),
+        )?;
+        if !status.is_found() {
+            return Ok(None);
+        }
+        Ok(NeedleMatch::captures(regex, before, &matched))
+    }
+
+    fn read_until_needle_or_rewind(
+        &mut self,
+        needle: impl Needle,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> std::io::Result<RewindStatus>
+    where
+        Self: std::io::Seek,
+    {
+        // `stream_position` of a buffered reader already subtracts the data it holds in its buffer
+        let start = self.stream_position()?;
+        let before_len = before.len();
+
+        match UntilNeedle::new(needle).read(self, before, matched)? {
+            UntilNeedleStatus::Found { bytes_read } => Ok(RewindStatus::Found(bytes_read)),
+            _ => {
+                before.truncate(before_len);
+                self.seek(std::io::SeekFrom::Start(start))?;
+                Ok(RewindStatus::Rewound)
+            }
+        }
+    }
+
+    fn read_until_needle_follow(
+        &mut self,
+        needle: impl Needle,
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+        opts: FollowOpts,
+    ) -> std::io::Result<usize> {
+        let start = Instant::now();
+        let mut last_total = 0;
+        let mut last_activity = start;
+
+        let on_eof = |total: usize| {
+            let now = Instant::now();
+            if total != last_total {
+                last_total = total;
+                last_activity = now;
+            }
+            let remaining = [
+                opts.deadline
+                    .map(|d| (start + d).saturating_duration_since(now)),
+                opts.max_idle
+                    .map(|d| (last_activity + d).saturating_duration_since(now)),
+            ]
+            .into_iter()
+            .flatten()
+            .min();
+            if remaining == Some(Duration::ZERO) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "needle not found before the deadline",
+                ));
+            }
+            let interval = remaining.map_or(opts.poll_interval, |r| r.min(opts.poll_interval));
+            std::thread::sleep(interval);
+            Ok(true)
+        };
+
+        read_until_needle_internal(self, &needle, &mut Scanner::new(), before, matched, on_eof)
+            .map(|status| status.bytes_read())
+    }
+
+    fn read_lines_until(
+        &mut self,
+        needle: impl Needle,
+        preceding: &mut Vec<String>,
+        matching: &mut String,
+    ) -> std::io::Result<LinesStatus> {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            if self.read_line(&mut line)? == 0 {
+                return Ok(LinesStatus::Eof);
+            }
+            if line.ends_with('\n') {
+                line.pop();
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+            }
+
+            if needle.findin(line.as_bytes()).is_some() {
+                matching.push_str(&line);
+                return Ok(LinesStatus::Found);
+            }
+            preceding.push(std::mem::take(&mut line));
+        }
+    }
+
+    fn matches_by_needle<N: Needle>(self, needle: N) -> NeedleMatches<Self, N> {
+        NeedleMatches {
+            reader: self,
+            needle,
+            window: Vec::new(),
+            position: 0,
+        }
+    }
+
+    fn segments<N: Needle>(self, needle: N) -> Segments<Self, N> {
+        Segments {
+            reader: self,
+            needle,
+            done: false,
+        }
+    }
+
+    fn take_until<N: Needle>(self, needle: N) -> TakeUntil<Self, N> {
+        TakeUntil {
+            inner: self,
+            needle,
+            consume_needle: true,
+            held: Vec::new(),
+            pos: 0,
+            readable: 0,
+            matched: Vec::new(),
+            found: false,
+            eof: false,
+        }
+    }
+
+    fn multipart_parts(self, boundary: impl AsRef<[u8]>) -> MultipartParts<Self> {
+        MultipartParts {
+            reader: self,
+            first: Some(MultipartBoundary::first(&boundary)),
+            boundary: MultipartBoundary::new(boundary),
+            done: false,
+        }
+    }
+
+    fn read_while(
+        &mut self,
+        pred: impl Fn(u8) -> bool,
+        out: &mut Vec<u8>,
+    ) -> std::io::Result<usize> {
+        let mut total_read = 0;
+
+        loop {
+            let (done, used) = {
+                let available = match self.fill_buf() {
+                    Ok(n) => n,
+                    Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+
+                match available.iter().position(|&b| !pred(b)) {
+                    Some(pos) => {
+                        out.extend_from_slice(&available[..pos]);
+                        (true, pos)
+                    }
+                    None => {
+                        // an empty buffer means EOF
+                        out.extend_from_slice(available);
+                        (available.is_empty(), available.len())
+                    }
+                }
+            };
+
+            self.consume(used);
+            total_read += used;
+            if done {
+                return Ok(total_read);
+            }
+        }
+    }
+}
+
+/// An iterator over the matches of a needle in a reader.
+///
+/// This struct is created by [`UntilNeedleRead::matches_by_needle`].
+#[derive(Debug)]
+pub struct NeedleMatches<R, N> {
+    reader: R,
+    needle: N,
+    /// Consumed bytes that did not contain a match but may begin one.
+    window: Vec<u8>,
+    /// Number of bytes consumed from the reader so far.
+    position: u64,
+}
+
+impl<R, N> NeedleMatches<R, N> {
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Unwraps this `NeedleMatches`, returning the underlying reader.
+    ///
+    /// The reader is positioned right after the last yielded match, or at EOF if the iterator
+    /// was exhausted.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: std::io::BufRead, N: Needle> Iterator for NeedleMatches<R, N> {
+    type Item = std::io::Result<(u64, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // bytes to keep across reads for a match straddling them
+        let keep = self
+            .needle
+            .max_len()
+            .map_or(usize::MAX, |len| len.saturating_sub(1));
+
+        loop {
+            let available = match self.reader.fill_buf() {
+                Ok(n) => n,
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if available.is_empty() {
+                // EOF
+                self.window.clear();
+                return None;
+            }
+
+            // search the new data together with the kept window without consuming it yet
+            let window_len = self.window.len();
+            let found = if window_len == 0 {
+                self.needle.findin(available)
+            } else {
+                self.window.extend_from_slice(available);
+                self.needle.findin(&self.window)
+            };
+
+            match found {
+                Some(range) => {
+                    let offset = self.position - window_len as u64 + range.start as u64;
+                    let matched = if window_len == 0 {
+                        available[range.clone()].to_vec()
+                    } else {
+                        self.window[range.clone()].to_vec()
+                    };
+                    if range.end > window_len {
+                        // consume exactly up to the end of the match
+                        let used = range.end - window_len;
+                        self.reader.consume(used);
+                        self.position += used as u64;
+                        self.window.clear();
+                    } else {
+                        self.window.truncate(window_len);
+                        self.window.drain(..range.end);
+                    }
+                    return Some(Ok((offset, matched)));
+                }
+                None => {
+                    let used = available.len();
+                    if window_len == 0 {
+                        self.window
+                            .extend_from_slice(&available[used.saturating_sub(keep)..]);
+                    } else if self.window.len() > keep {
+                        let excess = self.window.len() - keep;
+                        self.window.drain(..excess);
+                    }
+                    self.reader.consume(used);
+                    self.position += used as u64;
+                }
+            }
+        }
+    }
+}
+
+/// An iterator over the segments of a reader delimited by a needle.
+///
+/// This struct is created by [`UntilNeedleRead::segments`].
+#[derive(Debug)]
+pub struct Segments<R, N> {
+    reader: R,
+    needle: N,
+    done: bool,
+}
+
+impl<R, N> Segments<R, N> {
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Unwraps this `Segments`, returning the underlying reader.
+    ///
+    /// The reader is positioned right after the needle ending the last yielded segment.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: std::io::BufRead, N: Needle> Iterator for Segments<R, N> {
+    type Item = std::io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut segment = Vec::new();
+        let mut matched = Vec::new();
+        let status = read_until_needle_internal(
+            &mut self.reader,
+            &self.needle,
+            &mut Scanner::new(),
+            &mut segment,
+            &mut matched,
+            |_| Ok(false),
+        );
+        match status {
+            Ok(UntilNeedleStatus::Found { .. }) => Some(Ok(segment)),
+            Ok(_) => {
+                self.done = true;
+                (!segment.is_empty()).then_some(Ok(segment))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R: std::io::BufRead, N: Needle> std::iter::FusedIterator for Segments<R, N> {}
+
+/// An iterator over the parts of a MIME multipart body.
+///
+/// This struct is created by [`UntilNeedleRead::multipart_parts`].
+#[derive(Debug)]
+pub struct MultipartParts<R> {
+    reader: R,
+    /// The needle for the first delimiter, until the preamble is skipped.
+    first: Option<MultipartBoundary>,
+    boundary: MultipartBoundary,
+    done: bool,
+}
+
+impl<R> MultipartParts<R> {
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Unwraps this `MultipartParts`, returning the underlying reader.
+    ///
+    /// The reader is positioned right after the delimiter ending the last yielded part.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Reads up to the next delimiter, returning the data before it and whether it closes the body.
+fn read_part<R: std::io::BufRead>(
+    reader: &mut R,
+    needle: &MultipartBoundary,
+) -> std::io::Result<(Vec<u8>, bool)> {
+    let (mut part, mut matched) = (Vec::new(), Vec::new());
+    let status = read_until_needle_internal(
+        reader,
+        needle,
+        &mut Scanner::new(),
+        &mut part,
+        &mut matched,
+        |_| Ok(false),
+    )?;
+    if !status.is_found() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "EOF reached before the close delimiter",
+        ));
+    }
+    Ok((part, MultipartBoundary::is_close(&matched)))
+}
+
+impl<R: std::io::BufRead> MultipartParts<R> {
+    /// Reads the next part and whether it is the last one, or `None` for a body without parts.
+    fn next_part(&mut self) -> std::io::Result<Option<(Vec<u8>, bool)>> {
+        if let Some(first) = self.first.take() {
+            // skip the preamble
+            let (_, close) = read_part(&mut self.reader, &first)?;
+            if close {
+                return Ok(None);
+            }
+        }
+        read_part(&mut self.reader, &self.boundary).map(Some)
+    }
+}
+
+impl<R: std::io::BufRead> Iterator for MultipartParts<R> {
+    type Item = std::io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_part() {
+            Ok(Some((part, close))) => {
+                self.done = close;
+                Some(Ok(part))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R: std::io::BufRead> std::iter::FusedIterator for MultipartParts<R> {}
+
+/// A reader that reads from the underlying reader up to the next occurrence of a needle.
+///
+/// As long as the data buffered by the underlying reader is enough to rule out a needle, it is
+/// passed through without copying. Only the last bytes of a buffer that may begin a needle
+/// completed by the next buffer are taken from the underlying reader and held by the adapter
+/// until the next buffer decides it. Needles without a [maximum length](Needle::max_len) may
+/// span any number of buffers, so all data is held until the needle is found.
+///
+/// This struct is created by [`UntilNeedleRead::take_until`].
+#[derive(Debug)]
+pub struct TakeUntil<R, N> {
+    inner: R,
+    needle: N,
+    consume_needle: bool,
+    /// Data taken from the inner reader; `held[pos..readable]` can be read through the adapter.
+    held: Vec<u8>,
+    pos: usize,
+    readable: usize,
+    matched: Vec<u8>,
+    found: bool,
+    eof: bool,
+}
+
+impl<R, N> TakeUntil<R, N> {
+    /// Sets whether the needle is consumed from the underlying reader once it is reached.
+    ///
+    /// Defaults to `true`, leaving the underlying reader positioned right after the needle. When
+    /// `false`, it is left positioned at the needle, so the needle is read next. If the needle
+    /// was split across buffers of the underlying reader, its beginning has already been taken
+    /// and is returned by [`into_inner`](Self::into_inner) instead.
+    pub fn consume_needle(mut self, consume: bool) -> Self {
+        self.consume_needle = consume;
+        self
+    }
+
+    /// Returns `true` if the needle has been reached, and `false` if it has not been reached yet
+    /// or the underlying reader reached EOF first.
+    pub fn is_found(&self) -> bool {
+        self.found
+    }
+
+    /// Returns the needle once it has been reached.
+    pub fn matched(&self) -> Option<&[u8]> {
+        self.found.then_some(&self.matched[..])
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps this `TakeUntil`, returning the underlying reader and the data taken from it that
+    /// has not been read through the adapter.
+    ///
+    /// The held data comes before anything still in the underlying reader.
+    pub fn into_inner(mut self) -> (R, Vec<u8>) {
+        self.held.drain(..self.pos);
+        (self.inner, self.held)
+    }
+}
+
+impl<R: std::io::BufRead, N: Needle> std::io::Read for TakeUntil<R, N> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        use std::io::BufRead;
+
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: std::io::BufRead, N: Needle> std::io::BufRead for TakeUntil<R, N> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        loop {
+            if self.pos < self.readable {
+                return Ok(&self.held[self.pos..self.readable]);
+            }
+            if self.found || self.eof {
+                return Ok(&[]);
+            }
+            if self.pos == self.held.len() {
+                self.held.clear();
+                self.pos = 0;
+                self.readable = 0;
+                // search the buffer of the inner reader; only the suffix that may begin a
+                // needle completed later is taken from it
+                let (pass, used) = {
+                    let available = self.inner.fill_buf()?;
+                    if available.is_empty() {
+                        self.eof = true;
+                        continue;
+                    }
+                    match self.needle.findin_partial(available) {
+                        PartialMatch::Full(range) if range.start > 0 => (range.start, 0),
+                        PartialMatch::Full(range) => {
+                            self.found = true;
+                            self.matched.extend_from_slice(&available[range.clone()]);
+                            (0, if self.consume_needle { range.end } else { 0 })
+                        }
+                        PartialMatch::Prefix(start) if start > 0 => (start, 0),
+                        PartialMatch::Prefix(_) => {
+                            self.held.extend_from_slice(available);
+                            (0, available.len())
+                        }
+                        PartialMatch::None => (available.len(), 0),
+                    }
+                };
+                self.inner.consume(used);
+                if pass > 0 {
+                    let available = self.inner.fill_buf()?;
+                    return Ok(&available[..pass]);
+                }
+                continue;
+            }
+
+            // the held data may begin a needle; search it together with the next buffer
+            let used = {
+                let available = self.inner.fill_buf()?;
+                let held_len = self.held.len() - self.pos;
+                if available.is_empty() {
+                    self.eof = true;
+                    self.readable = self.held.len();
+                    0
+                } else {
+                    // a needle starting in the held bytes ends within the next `max_len - 1`
+                    let head = match self.needle.max_len() {
+                        Some(len) => &available[..available.len().min(len.saturating_sub(1))],
+                        None => available,
+                    };
+                    let mut window = Vec::with_capacity(held_len + head.len());
+                    window.extend_from_slice(&self.held[self.pos..]);
+                    window.extend_from_slice(head);
+                    match self.needle.findin_partial(&window) {
+                        PartialMatch::Full(range) if range.start < held_len => {
+                            self.found = true;
+                            self.matched.extend_from_slice(&window[range.clone()]);
+                            self.readable = self.pos + range.start;
+                            if self.consume_needle {
+                                self.held.truncate(self.readable);
+                                range.end.saturating_sub(held_len)
+                            } else {
+                                0
+                            }
+                        }
+                        // the held bytes that cannot begin a needle are passed on
+                        PartialMatch::Prefix(start) if start > 0 => {
+                            self.readable = self.pos + start.min(held_len);
+                            0
+                        }
+                        PartialMatch::Prefix(_) => {
+                            self.held.extend_from_slice(available);
+                            available.len()
+                        }
+                        PartialMatch::Full(_) | PartialMatch::None => {
+                            self.readable = self.held.len();
+                            0
+                        }
+                    }
+                }
+            };
+            self.inner.consume(used);
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if self.pos < self.readable {
+            self.pos = (self.pos + amt).min(self.readable);
+        } else {
+            self.inner.consume(amt);
+        }
+    }
+}
+
+/// A buffered reader for plain [`Read`](std::io::Read) sources, tailored to needle searches.
+///
+/// It implements [`BufRead`](std::io::BufRead) itself, so every method of [`UntilNeedleRead`]
+/// is available. Its own [`next_until`](Self::next_until) searches in place and returns the data
+/// borrowed from its buffer, without copying it to caller-provided vectors. The buffer grows
+/// while a match is pending, instead of being limited by a fixed capacity.
+///
+/// # Example
+/// ```
+/// use until_needle::io::UntilNeedleReader;
+///
+/// let mut reader = UntilNeedleReader::new(&b"HTTP/1.1 200 OK\r\nServer: x\r\n\r\nbody"[..]);
+/// let head = reader.next_until(b"\r\n\r\n").unwrap();
+/// assert_eq!(head.before, b"HTTP/1.1 200 OK\r\nServer: x");
+/// assert_eq!(head.matched, Some(&b"\r\n\r\n"[..]));
+/// assert_eq!(reader.buffer(), b"body");
+/// ```
+#[derive(Debug)]
+pub struct UntilNeedleReader<R> {
+    inner: R,
+    /// Buffered data; `buf[pos..]` has not been consumed yet.
+    buf: Vec<u8>,
+    pos: usize,
+    /// How many bytes to read from the inner reader at once.
+    capacity: usize,
+}
+
+/// The data returned by [`UntilNeedleReader::next_until`], borrowed from the reader's buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match<'a> {
+    /// The data before the needle, or all the data up to EOF.
+    pub before: &'a [u8],
+    /// The needle, or `None` if EOF was reached before it was found.
+    pub matched: Option<&'a [u8]>,
+}
+
+impl<R: std::io::Read> UntilNeedleReader<R> {
+    /// Creates a new reader reading 8 KiB at a time from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(8 * 1024, inner)
+    }
+
+    /// Creates a new reader reading `capacity` bytes at a time from `inner`.
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(capacity),
+            pos: 0,
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Reads data until the specified `needle` is found or EOF is reached, returning it
+    /// borrowed from this reader's buffer.
+    ///
+    /// The returned data is consumed, so the next read starts right after the needle. It stays
+    /// borrowed until the next call on this reader.
+    pub fn next_until<N: Needle>(&mut self, needle: N) -> std::io::Result<Match<'_>> {
+        let found = self.peek_until(needle)?;
+        let start = self.pos;
+        match found {
+            Some(range) => {
+                self.pos += range.end;
+                Ok(Match {
+                    before: &self.buf[start..start + range.start],
+                    matched: Some(&self.buf[start + range.start..start + range.end]),
+                })
+            }
+            None => {
+                self.pos = self.buf.len();
+                Ok(Match {
+                    before: &self.buf[start..],
+                    matched: None,
+                })
+            }
+        }
+    }
+
+    /// Reads data until the specified `needle` is found or EOF is reached, without consuming
+    /// it.
+    ///
+    /// Returns the range of the needle in [`buffer`](Self::buffer), or `None` if EOF was reached
+    /// first. All the data read stays buffered, so the following reads see it again, for example
+    /// to sniff a protocol before handing the reader to its handler.
+    pub fn peek_until<N: Needle>(&mut self, needle: N) -> std::io::Result<Option<Range<usize>>> {
+        let mut scan_from = 0;
+        loop {
+            let buffered = &self.buf[self.pos..];
+            match needle.findin_partial(&buffered[scan_from..]) {
+                PartialMatch::Full(range) => {
+                    return Ok(Some(scan_from + range.start..scan_from + range.end));
+                }
+                // only a match starting in the suffix can be completed later
+                PartialMatch::Prefix(start) => scan_from += start,
+                PartialMatch::None => scan_from = buffered.len(),
+            }
+            if self.read_more()? == 0 {
+                // EOF
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Reads more data from the inner reader, appending it to the buffered data.
+    ///
+    /// Returns the number of bytes read, where 0 means EOF.
+    fn read_more(&mut self) -> std::io::Result<usize> {
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+        let filled = self.buf.len();
+        self.buf.resize(filled + self.capacity, 0);
+        let result = loop {
+            match self.inner.read(&mut self.buf[filled..]) {
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        self.buf.truncate(filled + *result.as_ref().unwrap_or(&0));
+        result
+    }
+}
+
+impl<R> UntilNeedleReader<R> {
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns the buffered data that has not been consumed yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+
+    /// Unwraps this `UntilNeedleReader`, returning the underlying reader and the buffered data
+    /// that has not been consumed yet.
+    pub fn into_inner(mut self) -> (R, Vec<u8>) {
+        self.buf.drain(..self.pos);
+        (self.inner, self.buf)
+    }
+}
+
+impl<R: std::io::Read> std::io::Read for UntilNeedleReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = std::io::BufRead::fill_buf(self)?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        std::io::BufRead::consume(self, n);
+        Ok(n)
+    }
+}
+
+impl<R: std::io::Read> std::io::BufRead for UntilNeedleReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.pos == self.buf.len() {
+            self.read_more()?;
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buf.len());
+    }
+}
+
+/// A needle matching the earliest match of any of `needles`, remembering which one matched.
+struct FirstOf<'a, N> {
+    needles: &'a [N],
+    found: Cell<Option<usize>>,
+}
+
+impl<N: Needle> Needle for FirstOf<'_, N> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        let (i, range) = combinators::find_earliest(self.needles, haystack)?;
+        self.found.set(Some(i));
+        Some(range)
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        match self.findin(haystack) {
+            Some(range) => PartialMatch::Full(range),
+            None => combinators::partial_earliest(self.needles, haystack),
+        }
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        combinators::max_len_of(self.needles)
+    }
 }
 
-impl<T: std::io::BufRead> UntilNeedleRead for T {
-    fn read_until_needle(
-        &mut self,
-        needle: impl Needle,
-        before: &mut Vec<u8>,
-        matched: &mut Vec<u8>,
-    ) -> std::io::Result<usize> {
-        let mut total_buffered = 0;
-
-        loop {
-            let (done, used, buffered) = {
-                let available = match self.fill_buf() {
-                    Ok(n) => n,
-                    Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
-                    Err(e) => return Err(e),
-                };
+/// Internal function to read until the needle is found.
+///
+/// The search is driven by `scanner`, which keeps the trailing bytes that may begin a match so a
+/// needle straddling two `fill_buf` calls is found. Everything searched is appended to `before`
+/// right away, and the part of the needle found in earlier buffers is taken back from it. When
+/// the reader reports EOF, `on_eof` is called with the number of bytes consumed so far and
+/// decides whether to read again.
+pub(crate) fn read_until_needle_internal<R, N>(
+    reader: &mut R,
+    needle: &N,
+    scanner: &mut Scanner,
+    before: &mut Vec<u8>,
+    matched: &mut Vec<u8>,
+    mut on_eof: impl FnMut(usize) -> std::io::Result<bool>,
+) -> std::io::Result<UntilNeedleStatus>
+where
+    R: std::io::BufRead + ?Sized,
+    N: Needle + ?Sized,
+{
+    trace::read_span(needle).in_scope(|| {
+        let status = loop {
+            if scanner.at_limit() {
+                break scanner.finish();
+            }
 
-                let buffered = available.len() - total_buffered;
+            let available = match reader.fill_buf() {
+                Ok(n) => n,
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
 
-                if let Some(range) = needle.findin(available) {
-                    before.extend_from_slice(&available[..range.start]);
-                    matched.extend_from_slice(&available[range.clone()]);
-                    (true, range.end, available.len() - range.end)
-                } else if buffered > 0 {
-                    (false, 0, buffered)
-                } else {
-                    // EOF
-                    before.extend_from_slice(available);
-                    (true, available.len(), 0)
+            if available.is_empty() {
+                // EOF
+                if on_eof(scanner.bytes_read())? {
+                    continue;
                 }
-            };
+                break scanner.finish();
+            }
 
-            self.consume(used);
+            let step = scanner.push(needle, available);
+            step.apply(available, before, matched);
+            let (done, used) = (step.is_done(), step.consumed());
+            reader.consume(used);
             if done {
-                return Ok(used);
+                break scanner.finish();
+            }
+        };
+        trace::outcome(&status);
+        Ok(status)
+    })
+}
+
+/// Appends `available` to `buf` and searches it for `needle`, starting at `scan_from`.
+///
+/// If the needle is found, `buf` is truncated at its end and its range in `buf` is returned.
+/// Otherwise `scan_from` is advanced past the data that cannot begin a match. Also returns the
+/// number of bytes of `available` used.
+#[cfg(feature = "bytes")]
+pub(crate) fn search_bytes_mut<N: Needle + ?Sized>(
+    needle: &N,
+    available: &[u8],
+    buf: &mut BytesMut,
+    scan_from: &mut usize,
+) -> (Option<std::ops::Range<usize>>, usize) {
+    buf.extend_from_slice(available);
+    match needle.findin(&buf[*scan_from..]) {
+        Some(range) => {
+            let range = *scan_from + range.start..*scan_from + range.end;
+            let used = available.len() - (buf.len() - range.end);
+            buf.truncate(range.end);
+            (Some(range), used)
+        }
+        None => {
+            if let Some(len) = needle.max_len() {
+                let start = buf.len().saturating_sub(len.saturating_sub(1));
+                *scan_from = (*scan_from).max(start);
             }
-            total_buffered += buffered;
+            (None, available.len())
         }
     }
 }
 
+/// Splits `buf`, which ends with the needle at `range`, into the data before it and the needle.
+#[cfg(feature = "bytes")]
+pub(crate) fn split_bytes_mut(buf: &mut BytesMut, range: std::ops::Range<usize>) -> (Bytes, Bytes) {
+    let before = buf.split_to(range.start).freeze();
+    let matched = buf.split().freeze();
+    (before, matched)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::{BufRead, Cursor};
+    use crate::needle::FnNeedle;
+    use crate::testing::{ChunkedReader, ErrorAfter};
+    use std::io::{BufRead, BufReader, Cursor, Read, Seek, Write};
 
     #[test]
     fn test_bufread() {
@@ -124,4 +1895,912 @@ mod tests {
         assert_eq!(before, b"hello ");
         assert_eq!(matched, b"world");
     }
+
+    #[test]
+    fn test_read_until_needle_across_refills() {
+        // a default `BufReader` holds 8 KiB, so these needles straddle a refill or exceed it
+        let needle: Vec<u8> = (0..10_000).map(|i| b'a' + (i % 26) as u8).collect();
+        for (offset, needle) in [(8190, &needle[..4]), (5000, &needle[..])] {
+            let mut data = vec![b'-'; offset];
+            data.extend_from_slice(needle);
+            data.extend_from_slice(b"rest");
+            let mut reader = BufReader::new(&data[..]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            assert_eq!(
+                reader
+                    .read_until_needle(needle, &mut before, &mut matched)
+                    .unwrap(),
+                offset + needle.len()
+            );
+            assert_eq!(before.len(), offset);
+            assert_eq!(matched, needle);
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).unwrap();
+            assert_eq!(rest, b"rest");
+        }
+    }
+
+    #[test]
+    fn test_read_until_needle_errors() {
+        // an interrupted read is retried
+        let inner = ChunkedReader::new(b"hello\r\nworld", [3]);
+        let mut reader = ErrorAfter::new(inner, 6, std::io::ErrorKind::Interrupted);
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        assert_eq!(
+            reader
+                .read_until_needle(b"\r\n", &mut before, &mut matched)
+                .unwrap(),
+            7
+        );
+        assert!(reader.has_failed());
+        assert_eq!((&before[..], &matched[..]), (&b"hello"[..], &b"\r\n"[..]));
+
+        // other errors are returned, keeping the data read before them
+        let inner = ChunkedReader::new(b"hello\r\nworld", [3]);
+        let mut reader = ErrorAfter::new(inner, 6, std::io::ErrorKind::ConnectionReset);
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let err = reader
+            .read_until_needle(b"\r\n", &mut before, &mut matched)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::ConnectionReset);
+        assert_eq!((&before[..], &matched[..]), (&b"hello\r"[..], &b""[..]));
+    }
+
+    #[test]
+    fn test_read_while() {
+        let mut cur = Cursor::new(b"123abc 456");
+        let mut out = Vec::new();
+        assert_eq!(cur.read_while(|b| b.is_ascii_digit(), &mut out).unwrap(), 3);
+        assert_eq!(out, b"123");
+        // the failing byte is left in the reader
+        out.clear();
+        assert_eq!(cur.read_while(|b| b.is_ascii_digit(), &mut out).unwrap(), 0);
+        assert_eq!(out, b"");
+        let mut rest = Vec::new();
+        cur.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"abc 456");
+
+        // EOF while the predicate still holds
+        let mut cur = Cursor::new(b"4567");
+        out.clear();
+        assert_eq!(cur.read_while(|b| b.is_ascii_digit(), &mut out).unwrap(), 4);
+        assert_eq!(out, b"4567");
+        out.clear();
+        assert_eq!(cur.read_while(|b| b.is_ascii_digit(), &mut out).unwrap(), 0);
+        assert_eq!(out, b"");
+    }
+
+    #[test]
+    fn test_read_while_across_fill_buf() {
+        let mut reader = ChunkedReader::new(b"12345x", [2]);
+        let mut out = Vec::new();
+        assert_eq!(
+            reader.read_while(|b| b.is_ascii_digit(), &mut out).unwrap(),
+            5
+        );
+        assert_eq!(out, b"12345");
+        assert_eq!(reader.fill_buf().unwrap(), b"x");
+    }
+
+    #[test]
+    fn test_read_while_with_read_until_needle() {
+        let mut cur = Cursor::new(b"  42:hello;  7:world;");
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        let mut fields = Vec::new();
+        loop {
+            let mut ws = Vec::new();
+            let mut digits = Vec::new();
+            cur.read_while(|b| b == b' ', &mut ws).unwrap();
+            if cur.read_while(|b| b.is_ascii_digit(), &mut digits).unwrap() == 0 {
+                break;
+            }
+            before.clear();
+            matched.clear();
+            cur.read_until_needle(b";", &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(matched, b";");
+            fields.push((digits, before.clone()));
+        }
+        assert_eq!(
+            fields,
+            vec![
+                (b"42".to_vec(), b":hello".to_vec()),
+                (b"7".to_vec(), b":world".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_until_needle_or_rewind() {
+        let path = std::env::temp_dir().join(format!("until_needle_rewind_{}", std::process::id()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(b"header\nPK\x03\x04 payload")
+            .unwrap();
+
+        let mut reader = BufReader::new(std::fs::File::open(&path).unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "header\n");
+
+        let mut before = b"kept".to_vec();
+        let mut matched = Vec::new();
+        assert_eq!(
+            reader
+                .read_until_needle_or_rewind(b"%PDF", &mut before, &mut matched)
+                .unwrap(),
+            RewindStatus::Rewound
+        );
+        assert_eq!(before, b"kept");
+        assert_eq!(matched, b"");
+
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"PK\x03\x04 payload");
+
+        reader.seek(std::io::SeekFrom::Start(7)).unwrap();
+        before.clear();
+        assert_eq!(
+            reader
+                .read_until_needle_or_rewind(b"\x03\x04", &mut before, &mut matched)
+                .unwrap(),
+            RewindStatus::Found(4)
+        );
+        assert_eq!(before, b"PK");
+        assert_eq!(matched, b"\x03\x04");
+        rest.clear();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b" payload");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_lines_until() {
+        let mut cur = Cursor::new("ERROR first\r\nok\nrest");
+        let mut preceding = Vec::new();
+        let mut matching = String::new();
+        assert_eq!(
+            cur.read_lines_until("ERROR", &mut preceding, &mut matching)
+                .unwrap(),
+            LinesStatus::Found
+        );
+        assert!(preceding.is_empty());
+        assert_eq!(matching, "ERROR first");
+
+        // the needle is matched per line, so it cannot span a line break
+        let mut cur = Cursor::new("boot\r\nlog\nin: ok\nlogin: ");
+        matching.clear();
+        assert_eq!(
+            cur.read_lines_until("login:", &mut preceding, &mut matching)
+                .unwrap(),
+            LinesStatus::Found
+        );
+        assert_eq!(preceding, vec!["boot", "log", "in: ok"]);
+        assert_eq!(matching, "login: ");
+
+        let mut cur = Cursor::new("a\r\nb\n\nc\n");
+        preceding.clear();
+        matching.clear();
+        assert_eq!(
+            cur.read_lines_until("z", &mut preceding, &mut matching)
+                .unwrap(),
+            LinesStatus::Eof
+        );
+        assert_eq!(preceding, vec!["a", "b", "", "c"]);
+        assert_eq!(matching, "");
+    }
+
+    #[test]
+    fn test_segments() {
+        for size in 1..6 {
+            let reader = ChunkedReader::new(&b"a<>bc<><>d"[..], [size]);
+            let segments = reader
+                .segments(b"<>")
+                .collect::<std::io::Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(segments, [&b"a"[..], b"bc", b"", b"d"]);
+        }
+
+        let mut segments = (&b"a;b;"[..]).segments(b";");
+        assert_eq!(segments.next().unwrap().unwrap(), b"a");
+        assert_eq!(segments.next().unwrap().unwrap(), b"b");
+        assert!(segments.next().is_none());
+
+        let reader = ErrorAfter::new(&b"a;bc"[..], 3, std::io::ErrorKind::ConnectionReset);
+        let mut segments = reader.segments(b";");
+        assert_eq!(segments.next().unwrap().unwrap(), b"a");
+        assert!(segments.next().unwrap().is_err());
+        assert!(segments.next().is_none());
+    }
+
+    #[test]
+    fn test_expect_needle() {
+        use crate::UntilNeedleError;
+
+        let data = b"Password: ";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            let n = reader
+                .expect_needle(": ", &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(n, 10);
+            assert_eq!((&before[..], &matched[..]), (&b"Password"[..], &b": "[..]));
+
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            let err = reader
+                .expect_needle(": ", &mut before, &mut matched)
+                .unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        }
+
+        let mut reader = &b"$ exit\r\nlogout\r\n"[..];
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let err = reader
+            .expect_needle("$ ", &mut before, &mut matched)
+            .and_then(|_| reader.expect_needle("$ ", &mut before, &mut matched))
+            .unwrap_err();
+        assert!(matches!(
+            UntilNeedleError::from(err),
+            UntilNeedleError::EofWithoutMatch { bytes_read: 14 }
+        ));
+        assert_eq!(before, b"exit\r\nlogout\r\n");
+        assert_eq!(matched, b"$ ");
+    }
+
+    #[test]
+    fn test_read_until_needle_str() {
+        let data = "ça va ?\r\n> ".as_bytes();
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(data, [size]);
+            let (before, matched) = reader.read_until_needle_str("\r\n").unwrap();
+            assert_eq!((&before[..], matched.as_deref()), ("ça va ?", Some("\r\n")));
+            let (before, matched) = reader.read_until_needle_str("\r\n").unwrap();
+            assert_eq!((&before[..], matched), ("> ", None));
+        }
+
+        let mut reader = &b"ab\xffc\r\nd"[..];
+        let err = reader.read_until_needle_str("\r\n").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let invalid = err.into_inner().unwrap().downcast::<InvalidUtf8>().unwrap();
+        assert_eq!(invalid.offset, 2);
+        assert_eq!(invalid.output.before, b"ab\xffc");
+        assert_eq!(reader, b"d");
+
+        // an invalid needle is reported past the data before it
+        let mut reader = &b"ab\xfe;"[..];
+        let err = reader.read_until_needle_str(b"\xfe;").unwrap_err();
+        let invalid = err.into_inner().unwrap().downcast::<InvalidUtf8>().unwrap();
+        assert_eq!(invalid.offset, 2);
+        assert_eq!(invalid.output.matched.as_deref(), Some(&b"\xfe;"[..]));
+
+        let mut reader = &b"ab\xffc\r\nd"[..];
+        let (before, matched) = reader.read_until_needle_str_lossy("\r\n").unwrap();
+        assert_eq!(before, "ab\u{fffd}c");
+        assert_eq!(matched.as_deref(), Some("\r\n"));
+    }
+
+    #[test]
+    fn test_take_until() {
+        let data = b"header<>|body<>|tail";
+        for size in 1..data.len() {
+            for consume_needle in [true, false] {
+                let reader = ChunkedReader::new(&data[..], [size]);
+                let mut take = reader.take_until(b"<>|").consume_needle(consume_needle);
+                let mut out = Vec::new();
+                take.read_to_end(&mut out).unwrap();
+                assert_eq!(out, b"header");
+                assert_eq!(take.matched(), Some(&b"<>|"[..]));
+
+                let (mut reader, mut rest) = take.into_inner();
+                reader.read_to_end(&mut rest).unwrap();
+                if consume_needle {
+                    assert_eq!(rest, b"body<>|tail");
+                } else {
+                    assert_eq!(rest, b"<>|body<>|tail");
+                }
+            }
+        }
+
+        let mut take = (&b"no needle"[..]).take_until(b"<>|");
+        let mut out = String::new();
+        take.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "no needle");
+        assert!(!take.is_found());
+        assert_eq!(take.matched(), None);
+
+        // a needle without a maximum length holds the data until it is found
+        let unbounded = FnNeedle(|haystack: &[u8]| b"<>".findin(haystack));
+        let mut take = ChunkedReader::new(&b"ab<>c"[..], [3]).take_until(unbounded);
+        let mut lines = take.by_ref().lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "ab");
+        assert!(lines.next().is_none());
+        let (mut reader, mut rest) = take.into_inner();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"c");
+
+        // unless it tells where a match may begin
+        struct Partial;
+        impl Needle for Partial {
+            fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+                b"<END>".findin(haystack)
+            }
+
+            fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+                b"<END>".findin_partial(haystack)
+            }
+        }
+        let mut take = ChunkedReader::new(&b"abcdef<END>tail"[..], [8]).take_until(Partial);
+        assert_eq!(take.fill_buf().unwrap(), b"abcdef");
+        take.consume(6);
+        assert_eq!(take.fill_buf().unwrap(), b"");
+        assert_eq!(take.matched(), Some(&b"<END>"[..]));
+        let (mut reader, mut rest) = take.into_inner();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"tail");
+    }
+
+    #[test]
+    fn test_multipart_parts() {
+        let body = b"preamble\r\n--XyZ\r\nContent-Type: text/plain\r\n\r\none\r\n--XyZ \r\n\
+            \r\ntwo--XyZ\r\n\r\n--XyZ--\r\nepilogue";
+        for size in 1..body.len() {
+            let mut parts = ChunkedReader::new(&body[..], [size]).multipart_parts("XyZ");
+            assert_eq!(
+                parts.next().unwrap().unwrap(),
+                b"Content-Type: text/plain\r\n\r\none"
+            );
+            assert_eq!(parts.next().unwrap().unwrap(), b"\r\ntwo--XyZ\r\n");
+            assert!(parts.next().is_none());
+            let mut epilogue = Vec::new();
+            parts.into_inner().read_to_end(&mut epilogue).unwrap();
+            assert_eq!(epilogue, b"\r\nepilogue");
+        }
+
+        assert!((&b"--XyZ--"[..]).multipart_parts("XyZ").next().is_none());
+        let mut parts = (&b"--XyZ\r\none\r\n--XyZ\r\ntw"[..]).multipart_parts("XyZ");
+        assert_eq!(parts.next().unwrap().unwrap(), b"one");
+        let err = parts.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert!(parts.next().is_none());
+    }
+
+    #[test]
+    fn test_until_needle_reader() {
+        for size in 1..8 {
+            let inner = ChunkedReader::new(&b"abc<END>de<END>f"[..], [size]);
+            let mut reader = UntilNeedleReader::with_capacity(2, inner);
+            let found = reader.next_until(b"<END>").unwrap();
+            assert_eq!(found.before, b"abc");
+            assert_eq!(found.matched, Some(&b"<END>"[..]));
+            assert_eq!(reader.next_until(b"<END>").unwrap().before, b"de");
+            assert_eq!(
+                reader.next_until(b"<END>").unwrap(),
+                Match {
+                    before: b"f",
+                    matched: None
+                }
+            );
+            assert_eq!(reader.next_until(b"<END>").unwrap().before, b"");
+        }
+
+        let mut reader = UntilNeedleReader::with_capacity(2, &b"GET / HTTP/1.1\r\n"[..]);
+        assert_eq!(reader.peek_until(b" ").unwrap(), Some(3..4));
+        assert_eq!(reader.peek_until(b"\r\n").unwrap(), Some(14..16));
+        assert_eq!(reader.peek_until(b"POST").unwrap(), None);
+        assert_eq!(reader.buffer(), b"GET / HTTP/1.1\r\n");
+        assert_eq!(reader.next_until(b" ").unwrap().before, b"GET");
+
+        // the reader also works with the trait methods
+        let mut reader = UntilNeedleReader::with_capacity(3, &b"ab\ncd\nef"[..]);
+        assert_eq!(reader.next_until(b"\n").unwrap().before, b"ab");
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        reader
+            .read_until_needle(b"\n", &mut before, &mut matched)
+            .unwrap();
+        assert_eq!(before, b"cd");
+        let (inner, buffered) = reader.into_inner();
+        assert_eq!((inner, &buffered[..]), (&b"ef"[..], &b""[..]));
+    }
+
+    #[test]
+    fn test_skip_until_needle() {
+        let mut data = Vec::new();
+        for i in 0..10_000 {
+            data.extend_from_slice(format!("{:09} ", i).as_bytes());
+        }
+        data.extend_from_slice(b"garbage<MARK>payload");
+        let mut reader = ChunkedReader::new(data, [7]);
+        let report = reader.skip_until_needle(b"<MARK>").unwrap();
+        assert_eq!(
+            report,
+            SkipReport {
+                skipped: 100_007,
+                matched: Some(b"<MARK>".to_vec()),
+            }
+        );
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"payload");
+
+        let mut reader = &b"no marker"[..];
+        let report = reader.skip_until_needle(b"<MARK>").unwrap();
+        assert_eq!((report.skipped, report.matched), (9, None));
+    }
+
+    #[test]
+    fn test_copy_until_needle() {
+        let data = b"first part--boundary--second part";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let mut writer = std::io::BufWriter::new(Vec::new());
+            let report = reader
+                .copy_until_needle(b"--boundary--", &mut writer)
+                .unwrap();
+            assert_eq!(
+                report,
+                CopyReport {
+                    copied: 10,
+                    matched: Some(b"--boundary--".to_vec()),
+                }
+            );
+            // flushed before returning
+            assert_eq!(writer.buffer(), b"");
+            assert_eq!(writer.get_ref(), b"first part");
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).unwrap();
+            assert_eq!(rest, b"second part");
+        }
+
+        let mut reader = &b"no boundary"[..];
+        let mut writer = Vec::new();
+        let report = reader.copy_until_needle(b"--", &mut writer).unwrap();
+        assert_eq!((report.copied, report.matched), (11, None));
+        assert_eq!(writer, b"no boundary");
+
+        let mut reader = &b"too long for the writer--"[..];
+        let mut buf = [0; 4];
+        let err = reader
+            .copy_until_needle(b"--", &mut &mut buf[..])
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn test_read_between_needles() {
+        let data = b"log <<<first>>> noise <<<second";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let mut between = Vec::new();
+            let status = reader
+                .read_between_needles("<<<", ">>>", &mut between)
+                .unwrap();
+            assert_eq!(
+                (status, &between[..]),
+                (BetweenStatus::Found, &b"first"[..])
+            );
+
+            let mut between = Vec::new();
+            let status = reader
+                .read_between_needles("<<<", ">>>", &mut between)
+                .unwrap();
+            assert_eq!(
+                (status, &between[..]),
+                (BetweenStatus::NoEnd, &b"second"[..])
+            );
+
+            let mut between = Vec::new();
+            let status = reader
+                .read_between_needles("<<<", ">>>", &mut between)
+                .unwrap();
+            assert_eq!((status, &between[..]), (BetweenStatus::NoStart, &b""[..]));
+        }
+    }
+
+    #[test]
+    fn test_read_until_needle_limited() {
+        let data = b"abc<>defghij<>";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            let n = reader
+                .read_until_needle_limited("<>", 5, &mut before, &mut matched)
+                .unwrap();
+            assert_eq!((n, &before[..], &matched[..]), (5, &b"abc"[..], &b"<>"[..]));
+
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            let err = reader
+                .read_until_needle_limited("<>", 8, &mut before, &mut matched)
+                .unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            let limit = err.get_ref().unwrap().downcast_ref::<LimitExceeded>();
+            assert_eq!(limit, Some(&LimitExceeded { limit: 8 }));
+            assert_eq!((&before[..], &matched[..]), (&b"defghij<"[..], &b""[..]));
+
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).unwrap();
+            assert_eq!(rest, b">");
+        }
+    }
+
+    #[test]
+    fn test_read_until_needle_and_after() {
+        let data = b"a<END>bcdef";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let (mut before, mut matched, mut after) = (Vec::new(), Vec::new(), Vec::new());
+            let n = reader
+                .read_until_needle_and_after(b"<END>", 3, &mut before, &mut matched, &mut after)
+                .unwrap();
+            assert_eq!(n, 9);
+            assert_eq!(
+                (&before[..], &matched[..], &after[..]),
+                (&b"a"[..], &b"<END>"[..], &b"bcd"[..])
+            );
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).unwrap();
+            assert_eq!(rest, b"ef");
+        }
+
+        let mut reader = &b"a<END>b"[..];
+        let (mut before, mut matched, mut after) = (Vec::new(), Vec::new(), Vec::new());
+        let n = reader
+            .read_until_needle_and_after(b"<END>", 3, &mut before, &mut matched, &mut after)
+            .unwrap();
+        assert_eq!((n, &after[..]), (7, &b"b"[..]));
+
+        let mut reader = &b"no end"[..];
+        let (mut before, mut matched, mut after) = (Vec::new(), Vec::new(), Vec::new());
+        let n = reader
+            .read_until_needle_and_after(b"<END>", 3, &mut before, &mut matched, &mut after)
+            .unwrap();
+        assert_eq!((n, &matched[..], &after[..]), (6, &b""[..], &b""[..]));
+    }
+
+    #[test]
+    fn test_read_until_nth_needle() {
+        let data = b"a<>b<>c<>d";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            let n = reader
+                .read_until_nth_needle("<>", 2, &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(n, 6);
+            assert_eq!((&before[..], &matched[..]), (&b"a<>b"[..], &b"<>"[..]));
+
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            let n = reader
+                .read_until_nth_needle("<>", 2, &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(n, 4);
+            assert_eq!((&before[..], &matched[..]), (&b"c<>d"[..], &b""[..]));
+        }
+
+        // occurrences do not overlap
+        let mut reader = &b"aaaa"[..];
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        reader
+            .read_until_nth_needle("aa", 2, &mut before, &mut matched)
+            .unwrap();
+        assert_eq!((&before[..], &matched[..]), (&b"aa"[..], &b"aa"[..]));
+    }
+
+    #[test]
+    fn test_read_until_any() {
+        let data = b"$ ls\r\nfile\r\n# \r\n> ";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let needles = ["# ", "\r\n", "\r\nfile"];
+            let mut found = Vec::new();
+            loop {
+                let (mut before, mut matched) = (Vec::new(), Vec::new());
+                match reader
+                    .read_until_any(&needles, &mut before, &mut matched)
+                    .unwrap()
+                {
+                    Some(i) => found.push((i, before, matched)),
+                    None => {
+                        assert_eq!(before, b"> ");
+                        break;
+                    }
+                }
+            }
+            // the needles overlap, yet the index is the one whose match ended the read
+            assert_eq!(
+                found,
+                [
+                    (1, b"$ ls".to_vec(), b"\r\n".to_vec()),
+                    (1, b"file".to_vec(), b"\r\n".to_vec()),
+                    (0, b"".to_vec(), b"# ".to_vec()),
+                    (1, b"".to_vec(), b"\r\n".to_vec()),
+                ]
+            );
+        }
+
+        let mut reader = &b"abc"[..];
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let none: [&str; 0] = [];
+        assert_eq!(
+            reader
+                .read_until_any(&none, &mut before, &mut matched)
+                .unwrap(),
+            None
+        );
+        assert_eq!(before, b"abc");
+    }
+
+    #[test]
+    fn test_matches_by_needle() {
+        // pseudo-random lowercase data with the needle planted at known offsets
+        let mut data = Vec::with_capacity(3 << 20);
+        let mut seed = 0x2545_f491_u32;
+        let mut expected = Vec::new();
+        while data.len() < 3 << 20 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            if seed >> 16 < 64 {
+                expected.push(data.len() as u64);
+                data.extend_from_slice(b"NEEDLE");
+            } else {
+                data.push(b'a' + (seed >> 16) as u8 % 26);
+            }
+        }
+        assert!(expected.len() > 100);
+
+        let reader = ChunkedReader::new(&data[..], [7]);
+        let found = reader
+            .matches_by_needle(b"NEEDLE")
+            .collect::<std::io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(found.len(), expected.len());
+        for ((offset, matched), expected) in found.into_iter().zip(expected) {
+            assert_eq!(offset, expected);
+            assert_eq!(matched, b"NEEDLE");
+        }
+    }
+
+    #[test]
+    fn test_matches_by_needle_into_inner() {
+        let reader = ChunkedReader::new(b"aXbXXcX tail", [3]);
+        let mut matches = reader.matches_by_needle(b"X");
+        let first: Vec<_> = matches.by_ref().take(2).map(Result::unwrap).collect();
+        assert_eq!(first, vec![(1, b"X".to_vec()), (3, b"X".to_vec())]);
+
+        // the reader continues right after the last yielded match
+        let mut rest = Vec::new();
+        matches.into_inner().read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"XcX tail");
+
+        // non-overlapping, straddling a refill
+        let reader = ChunkedReader::new(b"aaaa", [2]);
+        let found: Vec<_> = reader
+            .matches_by_needle(b"aa")
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(found, vec![(0, b"aa".to_vec()), (2, b"aa".to_vec())]);
+        let reader = ChunkedReader::new(b"xaaa", [2]);
+        let found: Vec<_> = reader
+            .matches_by_needle(b"aa")
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(found, vec![(1, b"aa".to_vec())]);
+    }
+
+    /// A reader over a buffer that another thread appends to.
+    struct Appending(std::sync::Arc<std::sync::Mutex<Vec<u8>>>, usize);
+
+    impl Read for Appending {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let data = self.0.lock().unwrap();
+            let n = buf.len().min(data.len() - self.1);
+            buf[..n].copy_from_slice(&data[self.1..self.1 + n]);
+            self.1 += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_read_until_needle_follow() {
+        let data = std::sync::Arc::new(std::sync::Mutex::new(b"boot\nwai".to_vec()));
+        let mut reader = BufReader::new(Appending(data.clone(), 0));
+        let writer = std::thread::spawn(move || {
+            for chunk in [&b"t"[..], b"ing", b": ok\nafter"] {
+                std::thread::sleep(Duration::from_millis(20));
+                data.lock().unwrap().extend_from_slice(chunk);
+            }
+        });
+
+        let opts = FollowOpts {
+            poll_interval: Duration::from_millis(5),
+            deadline: Some(Duration::from_secs(10)),
+            ..Default::default()
+        };
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        assert_eq!(
+            reader
+                .read_until_needle_follow(b"waiting:", &mut before, &mut matched, opts)
+                .unwrap(),
+            13
+        );
+        assert_eq!(before, b"boot\n");
+        assert_eq!(matched, b"waiting:");
+        writer.join().unwrap();
+
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b" ok\nafter");
+    }
+
+    #[test]
+    fn test_read_until_needle_follow_timeout() {
+        let opts = FollowOpts {
+            poll_interval: Duration::from_millis(5),
+            max_idle: Some(Duration::from_millis(30)),
+            ..Default::default()
+        };
+        let mut cur = Cursor::new(b"no prompt here");
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        let err = cur
+            .read_until_needle_follow(b"$ ", &mut before, &mut matched, opts)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert_eq!(before, b"no prompt here");
+        assert_eq!(matched, b"");
+    }
+
+    #[test]
+    fn test_read_until_needle_owned() {
+        let mut cur = Cursor::new(b"key=value");
+        let output = cur.read_until_needle_owned(b"=").unwrap();
+        assert_eq!(
+            output,
+            UntilNeedleOutput {
+                before: b"key".to_vec(),
+                matched: Some(b"=".to_vec()),
+                bytes_read: 4,
+            }
+        );
+        let output = cur.read_until_needle_owned(b"=").unwrap();
+        assert_eq!(output.before, b"value");
+        assert_eq!(output.matched, None);
+        assert_eq!(output.bytes_read, 5);
+    }
+
+    #[test]
+    fn test_read_until_needle_into_out() {
+        use crate::output::{Discard, Limited};
+
+        for size in 1..6 {
+            let mut reader = ChunkedReader::new(b"abc<END>defgh<END>ijklmnop<END>", [size]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            assert_eq!(
+                reader
+                    .read_until_needle_into_out(b"<END>", &mut before, &mut matched)
+                    .unwrap(),
+                UntilNeedleStatus::Found { bytes_read: 8 }
+            );
+            assert_eq!((&before[..], &matched[..]), (&b"abc"[..], &b"<END>"[..]));
+
+            assert_eq!(
+                reader
+                    .read_until_needle_into_out(b"<END>", Discard, Discard)
+                    .unwrap(),
+                UntilNeedleStatus::Found { bytes_read: 10 }
+            );
+
+            // the needle is not written to a full sink
+            let mut before = Limited::new(Vec::new(), 4);
+            let status = reader
+                .read_until_needle_into_out(b"<END>", &mut before, Discard)
+                .unwrap();
+            assert!(matches!(status, UntilNeedleStatus::OutputFull { .. }));
+            assert_eq!(before.get_ref(), b"ijkl");
+
+            let mut before = Vec::new();
+            reader.read_to_end(&mut before).unwrap();
+            assert_eq!(&b"ijklmnop<END>"[status.bytes_read()..], &before[..]);
+        }
+
+        let mut reader = ChunkedReader::new(b"abc<E", [2]);
+        let mut before = Limited::new(Vec::new(), 4);
+        assert_eq!(
+            reader
+                .read_until_needle_into_out(b"<END>", &mut before, Discard)
+                .unwrap(),
+            UntilNeedleStatus::OutputFull { bytes_read: 5 }
+        );
+        assert_eq!(before.into_inner(), b"abc<");
+
+        let mut reader = &b"abc<E"[..];
+        let mut before = std::collections::VecDeque::new();
+        assert_eq!(
+            reader
+                .read_until_needle_into_out(b"<END>", &mut before, Discard)
+                .unwrap(),
+            UntilNeedleStatus::Eof { bytes_read: 5 }
+        );
+        assert_eq!(before, b"abc<E");
+    }
+
+    #[test]
+    fn test_read_until_needle_ref() {
+        let data = b"user: root\npassword: secret\n";
+
+        // a cursor over a slice buffers all of it, so nothing is copied
+        let mut cur = Cursor::new(&data[..]);
+        let borrowed = cur
+            .read_until_needle_ref(b"\n", |before, matched| {
+                assert_eq!(before, b"user: root");
+                assert_eq!(matched, Some(&b"\n"[..]));
+                before.as_ptr() == data.as_ptr()
+            })
+            .unwrap();
+        assert!(borrowed);
+        // the data is consumed up to the end of the needle once the borrow ends
+        assert_eq!(cur.position(), 11);
+
+        // the needle does not fit in a buffer of 4 bytes
+        let mut reader = ChunkedReader::new(&data[11..], [4]);
+        let line = reader
+            .read_until_needle_ref(b"\n", |before, matched| {
+                assert_eq!(matched, Some(&b"\n"[..]));
+                before.to_vec()
+            })
+            .unwrap();
+        assert_eq!(line, b"password: secret");
+        let rest = reader
+            .read_until_needle_ref(b"\n", |before, matched| (before.len(), matched.is_none()));
+        assert_eq!(rest.unwrap(), (0, true));
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_read_until_needle_bytes() {
+        let data = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\nbody";
+        let mut reader = ChunkedReader::new(&data[..], [4]);
+        let mut buf = BytesMut::new();
+        let (before, matched) = reader
+            .read_until_needle_bytes(b"\r\n\r\n", &mut buf)
+            .unwrap()
+            .unwrap();
+        assert_eq!(before, &data[..33]);
+        assert_eq!(matched, &b"\r\n\r\n"[..]);
+        // both are views of the same allocation
+        assert_eq!(matched.as_ptr(), before[before.len()..].as_ptr());
+        assert!(buf.is_empty());
+
+        assert_eq!(
+            reader.read_until_needle_bytes(b"\r\n", &mut buf).unwrap(),
+            None
+        );
+        assert_eq!(buf, &b"body"[..]);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_read_until_needle_match() {
+        let regex = Regex::new(r"\bsid=(?<sid>\d+)\$ ").unwrap();
+        for size in 1..8 {
+            let mut reader = ChunkedReader::new(&b"xsid=1$ ok sid=20$ sid="[..], [size]);
+            let mut before = Vec::new();
+            let found = reader
+                .read_until_needle_match(&regex, &mut before)
+                .unwrap()
+                .unwrap();
+            assert_eq!(before, b"xsid=1$ ok ");
+            assert_eq!(found.as_bytes(), b"sid=20$ ");
+            assert_eq!(found.name("sid"), Some(&b"20"[..]));
+
+            let mut before = Vec::new();
+            assert_eq!(
+                reader.read_until_needle_match(&regex, &mut before).unwrap(),
+                None
+            );
+            assert_eq!(before, b"sid=");
+        }
+    }
 }
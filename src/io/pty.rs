@@ -0,0 +1,132 @@
+use super::Session;
+use portable_pty::{native_pty_system, Child, CommandBuilder, ExitStatus, MasterPty, PtySize};
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// A process running in a pseudo-terminal, whose output is read by a [`Session`].
+///
+/// This struct is created by [`Session::spawn_pty`]. It keeps the master side of the terminal
+/// open, so the terminal can be resized while the session reads from it.
+pub struct PtyChild {
+    child: Box<dyn Child + Send + Sync>,
+    master: Box<dyn MasterPty + Send>,
+}
+
+impl PtyChild {
+    /// Resizes the terminal, which sends `SIGWINCH` to the process on Unix.
+    pub fn resize(&self, size: PtySize) -> io::Result<()> {
+        self.master.resize(size).map_err(pty_error)
+    }
+
+    /// Returns the process ID, if it is known.
+    pub fn process_id(&self) -> Option<u32> {
+        self.child.process_id()
+    }
+
+    /// Returns the exit status if the process has exited, without waiting.
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.child.try_wait()
+    }
+
+    /// Waits for the process to exit.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.child.wait()
+    }
+
+    /// Kills the process.
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.child.kill()
+    }
+}
+
+impl fmt::Debug for PtyChild {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PtyChild")
+            .field("child", &self.child)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Session<Box<dyn Read + Send>, Box<dyn Write + Send>> {
+    /// Spawns `command` in a new pseudo-terminal of `size` and creates a session over it.
+    ///
+    /// [`CommandBuilder`] and [`PtySize`] are re-exported from `portable-pty` in this module.
+    ///
+    /// Many interactive programs only print prompts, or disable buffering, when attached to a
+    /// terminal. The process runs with a Unix pty or a Windows ConPTY as its stdin, stdout and
+    /// stderr, and the session reads and writes the master side. A terminal echoes the input
+    /// back, so the output read after [`send_line`](Self::send_line) starts with the line sent,
+    /// and it turns `\n` in the output into `\r\n`. Once the process and every other user of the
+    /// terminal have exited, reads reach EOF.
+    ///
+    /// # Example
+    /// ```
+    /// use until_needle::io::{CommandBuilder, PtySize, Session};
+    ///
+    /// # #[cfg(unix)]
+    /// # fn main() -> std::io::Result<()> {
+    /// let mut command = CommandBuilder::new("sh");
+    /// command.args(["-c", "printf 'Name: '; read name; echo \"hello $name\""]);
+    /// let (mut session, mut child) = Session::spawn_pty(command, PtySize::default())?;
+    /// session.expect(&["Name: "])?;
+    /// session.send_line("admin")?;
+    /// let m = session.expect(&["hello admin"])?;
+    /// assert_eq!(m.before, b"admin\r\n");
+    /// assert!(child.wait()?.success());
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(unix))]
+    /// # fn main() {}
+    /// ```
+    pub fn spawn_pty(command: CommandBuilder, size: PtySize) -> io::Result<(Self, PtyChild)> {
+        let pair = native_pty_system().openpty(size).map_err(pty_error)?;
+        let child = pair.slave.spawn_command(command).map_err(pty_error)?;
+        // only the process may hold the slave side, or reads never reach EOF
+        drop(pair.slave);
+        let reader = pair.master.try_clone_reader().map_err(pty_error)?;
+        let writer = pair.master.take_writer().map_err(pty_error)?;
+        let child = PtyChild {
+            child,
+            master: pair.master,
+        };
+        Ok((Session::new(reader, writer), child))
+    }
+}
+
+/// Converts an error of the PTY system into an `io::Error`.
+fn pty_error(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::other(e)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawn_pty() {
+        let mut command = CommandBuilder::new("sh");
+        command.args([
+            "-c",
+            "stty -echo; printf 'login: '; read name; echo \"hi $name\"",
+        ]);
+        let (mut session, mut child) = Session::spawn_pty(command, PtySize::default()).unwrap();
+        assert!(child.process_id().is_some());
+        child
+            .resize(PtySize {
+                rows: 40,
+                cols: 120,
+                ..PtySize::default()
+            })
+            .unwrap();
+
+        session.expect(&["login: "]).unwrap();
+        session.send_line("root").unwrap();
+        let m = session.expect(&["\r\n"]).unwrap();
+        assert_eq!(m.before, b"hi root");
+        assert!(child.wait().unwrap().success());
+
+        // the terminal is closed once the process has exited
+        let err = session.expect(&["$ "]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::needle::combinators;
+use crate::trace;
+use crate::{ExpectMatch, Needle};
+use std::io::{self, Read, Write};
+
+/// An expect-style session over a reader and a writer, such as the stdout and stdin of a child
+/// process.
+///
+/// The session writes commands to the writer and waits for one of several needles in the
+/// reader's output. Output received after a match is kept in the session and searched first by
+/// the next [`expect`](Self::expect), so nothing is lost between calls. For a transport that is
+/// both, such as a `TcpStream`, pass `&stream` as the reader and the writer.
+///
+/// This is the blocking counterpart of the async `futures::Session`.
+///
+/// Programs that only print prompts on a terminal can be driven through a pseudo-terminal with
+/// `Session::spawn_pty`, behind the `pty` feature.
+///
+/// # Example
+/// ```
+/// use until_needle::io::Session;
+///
+/// let mut input = Vec::new();
+/// let mut session = Session::new(&b"login: "[..], &mut input);
+/// let m = session.expect(&["login: ", "$ "]).unwrap();
+/// assert_eq!(m.index, 0);
+/// session.send_line("admin").unwrap();
+/// drop(session);
+/// assert_eq!(input, b"admin\n");
+/// ```
+#[derive(Debug)]
+pub struct Session<R, W> {
+    reader: R,
+    writer: W,
+    /// Output received but not yet returned by `expect`.
+    buf: Vec<u8>,
+    /// The offset in `buf` from which a needle may still start.
+    scan_from: usize,
+}
+
+impl<R, W> Session<R, W> {
+    /// Creates a session reading output from `reader` and writing commands to `writer`.
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            buf: Vec::new(),
+            scan_from: 0,
+        }
+    }
+
+    /// Gets references to the underlying reader and writer.
+    pub fn get_ref(&self) -> (&R, &W) {
+        (&self.reader, &self.writer)
+    }
+
+    /// Gets mutable references to the underlying reader and writer.
+    pub fn get_mut(&mut self) -> (&mut R, &mut W) {
+        (&mut self.reader, &mut self.writer)
+    }
+
+    /// Returns the output received but not yet returned by [`expect`](Self::expect).
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Unwraps this `Session`, returning the underlying reader and writer and the output
+    /// received but not yet returned by [`expect`](Self::expect).
+    pub fn into_inner(self) -> (R, W, Vec<u8>) {
+        (self.reader, self.writer, self.buf)
+    }
+
+    /// Searches the buffered output for the needle that starts first.
+    fn find<N: Needle>(&mut self, needles: &[N]) -> Option<ExpectMatch> {
+        let (index, range) = combinators::find_earliest(needles, &self.buf[self.scan_from..])?;
+        let range = self.scan_from + range.start..self.scan_from + range.end;
+
+        let rest = self.buf.split_off(range.end);
+        let matched = self.buf.split_off(range.start);
+        let before = std::mem::replace(&mut self.buf, rest);
+        self.scan_from = 0;
+        trace::pattern(index);
+        Some(ExpectMatch {
+            index,
+            before,
+            matched,
+        })
+    }
+}
+
+impl<R: Read, W: Write> Session<R, W> {
+    /// Writes `data` to the writer and flushes it.
+    pub fn send(&mut self, data: impl AsRef<[u8]>) -> io::Result<()> {
+        self.writer.write_all(data.as_ref())?;
+        self.writer.flush()
+    }
+
+    /// Writes `line` followed by `\n` to the writer and flushes it.
+    pub fn send_line(&mut self, line: impl AsRef<[u8]>) -> io::Result<()> {
+        self.writer.write_all(line.as_ref())?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+
+    /// Reads output until one of `needles` is found.
+    ///
+    /// If several needles are found, the one starting first is reported, and among those
+    /// starting at the same position, the first in `needles`. The output after the match is
+    /// kept for the next call.
+    ///
+    /// # Errors
+    /// If the reader reaches EOF before any needle is found, an error of kind `UnexpectedEof`
+    /// is returned. The output received is kept and available through
+    /// [`buffer`](Self::buffer).
+    pub fn expect<N: Needle>(&mut self, needles: &[N]) -> io::Result<ExpectMatch> {
+        let mut chunk = [0; 4096];
+        loop {
+            if let Some(found) = self.find(needles) {
+                return Ok(found);
+            }
+            // only a needle starting in the last `max_len - 1` bytes can be completed later
+            if let Some(len) = combinators::max_len_of(needles) {
+                let keep = len.saturating_sub(1);
+                self.scan_from = self.scan_from.max(self.buf.len().saturating_sub(keep));
+            }
+
+            let n = match self.reader.read(&mut chunk) {
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if n == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "EOF reached before any needle was found",
+                ));
+            }
+            self.buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ChunkedReader;
+
+    #[test]
+    fn test_session_expect() {
+        let output = b"login: welcome\n$ motd";
+        for size in 1..output.len() {
+            let reader = ChunkedReader::new(&output[..], [size]);
+            let mut session = Session::new(reader, Vec::new());
+            let m = session.expect(&["login: ", "$ "]).unwrap();
+            assert_eq!((m.index, &m.before[..]), (0, &b""[..]));
+            session.send_line("admin").unwrap();
+
+            let m = session.expect(&["# ", "$ "]).unwrap();
+            assert_eq!(
+                m,
+                ExpectMatch {
+                    index: 1,
+                    before: b"welcome\n".to_vec(),
+                    matched: b"$ ".to_vec(),
+                }
+            );
+
+            // the rest is kept for the next call, which then reaches EOF
+            let err = session.expect(&["$ "]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+            assert_eq!(session.buffer(), b"motd");
+            let (_, input, _) = session.into_inner();
+            assert_eq!(input, b"admin\n");
+        }
+    }
+
+    #[test]
+    fn test_session_expect_first_match() {
+        let mut session = Session::new(&b"error: denied\n$ "[..], io::sink());
+        let m = session.expect(&["$ ", "error"]).unwrap();
+        assert_eq!((m.index, &m.matched[..]), (1, &b"error"[..]));
+        let m = session.expect(&["$ ", "error"]).unwrap();
+        assert_eq!((m.index, &m.before[..]), (0, &b": denied\n"[..]));
+    }
+
+    #[test]
+    fn test_session_boxed() {
+        // PTY crates hand out the master side as boxed readers and writers
+        let reader: Box<dyn Read + Send> = Box::new(&b"ls\r\nfile\r\n$ "[..]);
+        let writer: Box<dyn Write + Send> = Box::new(io::sink());
+        let mut session = Session::new(reader, writer);
+        session.send_line("ls").unwrap();
+        let m = session.expect(&["$ "]).unwrap();
+        assert_eq!(m.before, b"ls\r\nfile\r\n");
+    }
+}
@@ -1,8 +1,344 @@
-#![doc = include_str!("../README.md")]
-/// Implementation for std::io
-pub mod io;
-pub mod needle;
+#![cfg_attr(any(feature = "std", not(doctest)), doc = include_str!("../README.md"))]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod builder;
+/// `tokio_util` codec splitting frames at a needle
+#[cfg(feature = "codec")]
+pub mod codec;
+/// Implementation for embedded-io
+#[cfg(feature = "embedded")]
+pub mod embedded;
 /// Implementation for futures
 #[cfg(feature = "futures")]
 pub mod futures;
+/// Reading HTTP/1.x header blocks
+#[cfg(feature = "std")]
+pub mod http;
+/// Implementation for std::io
+#[cfg(feature = "std")]
+pub mod io;
+pub mod needle;
+/// Reading NETCONF messages
+#[cfg(feature = "netconf")]
+pub mod netconf;
+pub mod output;
+#[cfg(feature = "std")]
+pub mod recorder;
+#[cfg(any(feature = "futures", feature = "tokio"))]
+mod search;
+/// Splitting streams of byte chunks at a needle
+#[cfg(feature = "stream")]
+pub mod stream;
+/// Filtering telnet negotiation out of a stream
+#[cfg(feature = "telnet")]
+pub mod telnet;
+/// Readers for testing code that reads until a needle
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+/// Implementation for tokio
+#[cfg(feature = "tokio")]
+pub mod tokio;
+#[cfg(feature = "alloc")]
+#[cfg_attr(not(any(feature = "std", feature = "embedded")), allow(dead_code))]
+mod trace;
+#[cfg(feature = "std")]
+pub use crate::builder::UntilNeedle;
 pub use crate::needle::Needle;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// The outcome of a read that may end without finding the needle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UntilNeedleStatus {
+    /// The needle was found.
+    Found {
+        /// The total number of bytes read, including the needle.
+        bytes_read: usize,
+    },
+    /// EOF was reached before the needle was found.
+    Eof {
+        /// The total number of bytes read.
+        bytes_read: usize,
+    },
+    /// The time limit passed before the needle was found.
+    TimedOut {
+        /// The total number of bytes read.
+        bytes_read: usize,
+    },
+    /// The read was cancelled before the needle was found.
+    Cancelled {
+        /// The total number of bytes read.
+        bytes_read: usize,
+    },
+    /// The byte limit was reached before the needle was found.
+    LimitReached {
+        /// The total number of bytes read.
+        bytes_read: usize,
+    },
+    /// An [`OutputBuf`](crate::output::OutputBuf) was full before the read ended.
+    OutputFull {
+        /// The total number of bytes read, including those that did not fit.
+        bytes_read: usize,
+    },
+}
+
+impl UntilNeedleStatus {
+    /// Returns the total number of bytes read.
+    pub fn bytes_read(&self) -> usize {
+        match *self {
+            UntilNeedleStatus::Found { bytes_read }
+            | UntilNeedleStatus::Eof { bytes_read }
+            | UntilNeedleStatus::TimedOut { bytes_read }
+            | UntilNeedleStatus::Cancelled { bytes_read }
+            | UntilNeedleStatus::LimitReached { bytes_read }
+            | UntilNeedleStatus::OutputFull { bytes_read } => bytes_read,
+        }
+    }
+
+    /// Returns `true` if the needle was found.
+    pub fn is_found(&self) -> bool {
+        matches!(self, UntilNeedleStatus::Found { .. })
+    }
+
+    /// Returns the number of bytes read if the needle was found, or why it was not as an error.
+    ///
+    /// # Example
+    /// ```
+    /// use until_needle::{UntilNeedle, UntilNeedleError};
+    ///
+    /// fn read_prompt(mut reader: &[u8]) -> Result<usize, UntilNeedleError> {
+    ///     let (mut before, mut matched) = (Vec::new(), Vec::new());
+    ///     let status = UntilNeedle::new("$ ")
+    ///         .limit(64)
+    ///         .read(&mut reader, &mut before, &mut matched)?;
+    ///     status.into_result()
+    /// }
+    ///
+    /// assert_eq!(read_prompt(b"~$ ").unwrap(), 3);
+    /// assert!(matches!(
+    ///     read_prompt(b"~"),
+    ///     Err(UntilNeedleError::EofWithoutMatch { bytes_read: 1 })
+    /// ));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn into_result(self) -> Result<usize, UntilNeedleError> {
+        match self {
+            UntilNeedleStatus::Found { bytes_read } => Ok(bytes_read),
+            UntilNeedleStatus::Eof { bytes_read } => {
+                Err(UntilNeedleError::EofWithoutMatch { bytes_read })
+            }
+            UntilNeedleStatus::TimedOut { bytes_read } => {
+                Err(UntilNeedleError::Timeout { bytes_read })
+            }
+            UntilNeedleStatus::Cancelled { bytes_read } => {
+                Err(UntilNeedleError::Cancelled { bytes_read })
+            }
+            UntilNeedleStatus::LimitReached { bytes_read } => {
+                let limit = LimitExceeded { limit: bytes_read };
+                Err(UntilNeedleError::LimitExceeded(limit))
+            }
+            UntilNeedleStatus::OutputFull { bytes_read } => {
+                Err(UntilNeedleError::OutputFull { bytes_read })
+            }
+        }
+    }
+}
+
+/// The reasons a read until a needle can end without the needle.
+///
+/// The readers report I/O errors as `io::Error` and the other endings as an
+/// [`UntilNeedleStatus`]; [`UntilNeedleStatus::into_result`] and the conversion from
+/// `io::Error` bring both into this type, so retry logic can match on the cause. It converts
+/// back into an `io::Error` wrapping itself, or the original error for [`Io`](Self::Io).
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum UntilNeedleError {
+    /// The underlying reader failed.
+    Io(std::io::Error),
+    /// EOF was reached before the needle was found.
+    EofWithoutMatch {
+        /// The total number of bytes read.
+        bytes_read: usize,
+    },
+    /// The byte limit was reached before the needle was found.
+    LimitExceeded(LimitExceeded),
+    /// The time limit passed before the needle was found.
+    Timeout {
+        /// The total number of bytes read.
+        bytes_read: usize,
+    },
+    /// The read was cancelled before the needle was found.
+    Cancelled {
+        /// The total number of bytes read.
+        bytes_read: usize,
+    },
+    /// An [`OutputBuf`](crate::output::OutputBuf) was full before the read ended.
+    OutputFull {
+        /// The total number of bytes read, including those that did not fit.
+        bytes_read: usize,
+    },
+}
+
+#[cfg(feature = "std")]
+impl core::fmt::Display for UntilNeedleError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            UntilNeedleError::Io(e) => e.fmt(f),
+            UntilNeedleError::EofWithoutMatch { bytes_read } => {
+                write!(f, "EOF after {} bytes without the needle", bytes_read)
+            }
+            UntilNeedleError::LimitExceeded(e) => e.fmt(f),
+            UntilNeedleError::Timeout { bytes_read } => {
+                write!(f, "timed out after {} bytes without the needle", bytes_read)
+            }
+            UntilNeedleError::Cancelled { bytes_read } => {
+                write!(f, "cancelled after {} bytes without the needle", bytes_read)
+            }
+            UntilNeedleError::OutputFull { bytes_read } => {
+                write!(f, "output full after {} bytes", bytes_read)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UntilNeedleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UntilNeedleError::Io(e) => Some(e),
+            UntilNeedleError::LimitExceeded(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Recovers the cause of errors returned by this crate, such as [`LimitExceeded`] from a
+/// limited read.
+#[cfg(feature = "std")]
+impl From<std::io::Error> for UntilNeedleError {
+    fn from(e: std::io::Error) -> Self {
+        match e.get_ref() {
+            Some(inner) if inner.is::<LimitExceeded>() => {}
+            Some(inner) if inner.is::<UntilNeedleError>() => {}
+            _ => return UntilNeedleError::Io(e),
+        }
+        let kind = e.kind();
+        let Some(inner) = e.into_inner() else {
+            return UntilNeedleError::Io(kind.into());
+        };
+        let inner = match inner.downcast::<LimitExceeded>() {
+            Ok(limit) => return UntilNeedleError::LimitExceeded(*limit),
+            Err(inner) => inner,
+        };
+        match inner.downcast::<UntilNeedleError>() {
+            Ok(e) => *e,
+            Err(inner) => UntilNeedleError::Io(std::io::Error::new(kind, inner)),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<UntilNeedleError> for std::io::Error {
+    fn from(e: UntilNeedleError) -> Self {
+        use std::io::ErrorKind;
+
+        let kind = match e {
+            UntilNeedleError::Io(e) => return e,
+            UntilNeedleError::EofWithoutMatch { .. } => ErrorKind::UnexpectedEof,
+            UntilNeedleError::LimitExceeded(limit) => {
+                return std::io::Error::new(ErrorKind::InvalidData, limit)
+            }
+            UntilNeedleError::Timeout { .. } => ErrorKind::TimedOut,
+            UntilNeedleError::Cancelled { .. } | UntilNeedleError::OutputFull { .. } => {
+                ErrorKind::Other
+            }
+        };
+        std::io::Error::new(kind, e)
+    }
+}
+
+/// The error wrapped in an `io::Error` of kind `InvalidData` when a read gives up after its
+/// byte limit without finding the needle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitExceeded {
+    /// The limit, which is also the number of bytes read.
+    pub limit: usize,
+}
+
+impl core::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "needle not found within {} bytes", self.limit)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LimitExceeded {}
+
+/// The owned result of a read until a needle.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UntilNeedleOutput {
+    /// The data read before the needle, or all data read if EOF was reached first.
+    pub before: Vec<u8>,
+    /// The matched needle, or `None` if EOF was reached before the needle was found.
+    pub matched: Option<Vec<u8>>,
+    /// The total number of bytes read, including the needle.
+    pub bytes_read: usize,
+}
+
+/// The error wrapped in an `io::Error` of kind `InvalidData` when the data read until a needle
+/// is not valid UTF-8.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidUtf8 {
+    /// The offset of the first invalid byte, counted from the start of the data read, so an
+    /// offset past `before` points into the needle.
+    pub offset: usize,
+    /// The data read, which was consumed from the reader.
+    pub output: UntilNeedleOutput,
+}
+
+#[cfg(feature = "alloc")]
+impl core::fmt::Display for InvalidUtf8 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "invalid UTF-8 at byte {} of the data read", self.offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidUtf8 {}
+
+/// A match reported by an expect-style session.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectMatch {
+    /// The index of the needle that matched, in the slice of needles passed to `expect`.
+    pub index: usize,
+    /// The output received before the match.
+    pub before: Vec<u8>,
+    /// The matched bytes.
+    pub matched: Vec<u8>,
+}
+
+/// The outcome of skipping data until a needle.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkipReport {
+    /// The number of bytes skipped before the needle, or all bytes read if EOF was reached first.
+    pub skipped: usize,
+    /// The matched needle, or `None` if EOF was reached before the needle was found.
+    pub matched: Option<Vec<u8>>,
+}
+
+/// The outcome of copying data to a writer until a needle.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyReport {
+    /// The number of bytes written to the writer, including the needle if it was forwarded.
+    pub copied: usize,
+    /// The matched needle, or `None` if EOF was reached before the needle was found.
+    pub matched: Option<Vec<u8>>,
+}
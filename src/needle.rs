@@ -1,9 +1,108 @@
-use std::ops::Range;
+use core::ops::Range;
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+#[cfg(feature = "alloc")]
+pub use ansi::AnsiTolerant;
+#[cfg(feature = "aho-corasick")]
+pub use any_of::AnyOf;
+#[cfg(feature = "regex")]
+pub use captures::NeedleMatch;
+#[cfg(feature = "regex")]
+pub use case_fold::UnicodeCaseInsensitive;
+pub use cdc::CdcBoundary;
+#[cfg(feature = "regex-automata")]
+pub use dfa::DfaRegex;
+#[cfg(feature = "alloc")]
+pub use fuzzy::Fuzzy;
+#[cfg(feature = "alloc")]
+pub use glob::Glob;
+#[cfg(feature = "alloc")]
+pub use multipart::MultipartBoundary;
+#[cfg(feature = "alloc")]
+pub use pattern::{ParsePatternError, Pattern};
+#[cfg(feature = "regex")]
+pub use regex_set::RegexSetNeedle;
+#[cfg(any(feature = "std", all(feature = "embedded", feature = "alloc")))]
+pub(crate) use searcher::Scanner;
+#[cfg(feature = "alloc")]
+pub use searcher::{SearchStep, Searcher};
+pub use sse::SseEventEnd;
+pub use xml::XmlElementEnd;
+
+#[cfg(feature = "alloc")]
+mod ansi;
+#[cfg(feature = "aho-corasick")]
+mod any_of;
+#[cfg(feature = "regex")]
+mod captures;
+#[cfg(feature = "regex")]
+mod case_fold;
+mod cdc;
+pub mod combinators;
+#[cfg(feature = "regex-automata")]
+mod dfa;
+#[cfg(feature = "alloc")]
+mod fuzzy;
+#[cfg(feature = "alloc")]
+mod glob;
+#[cfg(feature = "alloc")]
+mod multipart;
+#[cfg(feature = "alloc")]
+mod pattern;
+#[cfg(feature = "regex")]
+mod regex_set;
+#[cfg(feature = "alloc")]
+mod searcher;
+mod sse;
+mod xml;
 
 pub trait Needle {
     /// Finds the first occurrence of the pattern in the given haystack (as &[u8]).
     /// Returns a `Range<usize>` if found, otherwise returns `None`.
     fn findin(&self, haystack: &[u8]) -> Option<Range<usize>>;
+
+    /// Returns the maximum length of a match, if the needle has one.
+    ///
+    /// Readers that scan a stream piece by piece use this to decide how many trailing bytes must be
+    /// kept to find a match straddling two reads. `None` means the length is unbounded and all
+    /// unmatched data has to be kept.
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+
+    /// Finds the first occurrence of the pattern in the given haystack, or else the suffix of the
+    /// haystack that may still begin one once more data follows.
+    ///
+    /// Readers keep only that suffix when they read on. The default keeps the last
+    /// [`max_len`](Self::max_len) - 1 bytes, or the whole haystack for unbounded needles; needles
+    /// that know which bytes can begin a match may keep fewer.
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        if let Some(range) = self.findin(haystack) {
+            return PartialMatch::Full(range);
+        }
+        let start = match self.max_len() {
+            Some(len) => haystack.len().saturating_sub(len.saturating_sub(1)),
+            None => 0,
+        };
+        if start < haystack.len() {
+            PartialMatch::Prefix(start)
+        } else {
+            PartialMatch::None
+        }
+    }
+}
+
+/// The outcome of [`Needle::findin_partial`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartialMatch {
+    /// The range of the first match in the haystack.
+    Full(Range<usize>),
+    /// There is no match yet, but one may begin at this offset or after it, in the suffix of
+    /// the haystack starting here.
+    Prefix(usize),
+    /// There is no match, and none can begin in the haystack.
+    None,
 }
 
 impl Needle for [u8] {
@@ -13,39 +112,433 @@ impl Needle for [u8] {
             .position(|window| window == self)
             .map(|pos| pos..pos + self.len())
     }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        if let Some(range) = self.findin(haystack) {
+            return PartialMatch::Full(range);
+        }
+        // the longest suffix of the haystack that the needle starts with
+        let shortest = haystack.len().saturating_sub(self.len().saturating_sub(1));
+        (shortest..haystack.len())
+            .find(|&start| self.starts_with(&haystack[start..]))
+            .map_or(PartialMatch::None, PartialMatch::Prefix)
+    }
 }
 
-impl Needle for &[u8] {
+#[cfg(feature = "alloc")]
+impl Needle for Vec<u8> {
     fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
-        haystack
-            .windows(self.len())
-            .position(|window| window == *self)
-            .map(|pos| pos..pos + self.len())
+        self.as_slice().findin(haystack)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        self.as_slice().findin_partial(haystack)
     }
 }
 
-impl<const N: usize> Needle for &[u8; N] {
+impl<const N: usize> Needle for [u8; N] {
     fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
         self[..].findin(haystack)
     }
-}
 
-impl Needle for Vec<u8> {
-    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
-        self.as_slice().findin(haystack)
+    fn max_len(&self) -> Option<usize> {
+        Some(N)
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        self[..].findin_partial(haystack)
     }
 }
 
-impl Needle for &str {
+impl Needle for str {
     fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
         self.as_bytes().findin(haystack)
     }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        self.as_bytes().findin_partial(haystack)
+    }
 }
 
+#[cfg(feature = "alloc")]
 impl Needle for String {
     fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
         self.as_str().findin(haystack)
     }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        self.as_bytes().findin_partial(haystack)
+    }
+}
+
+/// A needle matching the bytes of any byte string type literally.
+///
+/// Byte strings from other crates, such as `Bytes` of `bytes` or `Cow<[u8]>`, are needles once
+/// wrapped, without converting them to `&[u8]` or `Vec<u8>` at each call site. Any type
+/// implementing `AsRef<[u8]>` works. `&BStr` and `BString` of `bstr` are needles without the
+/// wrapper behind the `bstr` feature.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use std::borrow::Cow;
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::Literal;
+///
+/// let delimiter: Cow<[u8]> = Cow::Borrowed(b"\r\n");
+/// let mut reader = &b"ok\r\nrest"[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader
+///     .read_until_needle(Literal(delimiter), &mut before, &mut matched)
+///     .unwrap();
+/// assert_eq!(before, b"ok");
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Literal<T>(pub T);
+
+impl<T: AsRef<[u8]>> Needle for Literal<T> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        self.0.as_ref().findin(haystack)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.0.as_ref().len())
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        self.0.as_ref().findin_partial(haystack)
+    }
+}
+
+/// Returns the offset of the first `byte` in `haystack`.
+fn find_byte(byte: u8, haystack: &[u8]) -> Option<usize> {
+    #[cfg(feature = "memchr")]
+    return memchr::memchr(byte, haystack);
+    #[cfg(not(feature = "memchr"))]
+    haystack.iter().position(|&b| b == byte)
+}
+
+/// A single byte, found with `memchr` behind the `memchr` feature.
+impl Needle for u8 {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        find_byte(*self, haystack).map(|pos| pos..pos + 1)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        self.findin(haystack)
+            .map_or(PartialMatch::None, PartialMatch::Full)
+    }
+}
+
+/// A character, matched as its UTF-8 encoding.
+impl Needle for char {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        if self.is_ascii() {
+            return (*self as u8).findin(haystack);
+        }
+        let mut buf = [0; 4];
+        self.encode_utf8(&mut buf).findin(haystack)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.len_utf8())
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        if self.is_ascii() {
+            return (*self as u8).findin_partial(haystack);
+        }
+        let mut buf = [0; 4];
+        self.encode_utf8(&mut buf).findin_partial(haystack)
+    }
+}
+
+/// A borrowed needle, such as one kept in a configuration and lent to each read, or a trait
+/// object `&dyn Needle` chosen at runtime.
+impl<N: Needle + ?Sized> Needle for &N {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        (**self).findin(haystack)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        (**self).max_len()
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        (**self).findin_partial(haystack)
+    }
+}
+
+/// A boxed trait object, such as a needle stored in a configuration.
+#[cfg(feature = "alloc")]
+impl Needle for Box<dyn Needle + '_> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        (**self).findin(haystack)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        (**self).max_len()
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        (**self).findin_partial(haystack)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Needle for Box<dyn Needle + Send + Sync + '_> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        (**self).findin(haystack)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        (**self).max_len()
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        (**self).findin_partial(haystack)
+    }
+}
+
+/// A shared trait object, such as a needle shared between tasks.
+#[cfg(feature = "alloc")]
+impl Needle for Arc<dyn Needle + Send + Sync + '_> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        (**self).findin(haystack)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        (**self).max_len()
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        (**self).findin_partial(haystack)
+    }
+}
+
+/// A needle wrapping a closure that finds the first match in the haystack, for one-off matching
+/// logic.
+///
+/// Closures are wrapped rather than being needles themselves, which would overlap with the
+/// implementation for references to needles. They have no maximum match length, so readers keep
+/// and search again everything read since the start of a call; wrap them in [`WithMaxLen`] to
+/// bound that.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::FnNeedle;
+///
+/// // a length-prefixed frame: one length byte, then that many bytes
+/// let frame = |haystack: &[u8]| {
+///     let len = usize::from(*haystack.first()?);
+///     (haystack.len() > len).then_some(0..len + 1)
+/// };
+/// let mut reader = &b"\x03abcrest"[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader
+///     .read_until_needle(FnNeedle(frame), &mut before, &mut matched)
+///     .unwrap();
+/// assert_eq!(matched, b"\x03abc");
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct FnNeedle<F>(pub F);
+
+impl<F> Needle for FnNeedle<F>
+where
+    F: Fn(&[u8]) -> Option<Range<usize>>,
+{
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        (self.0)(haystack)
+    }
+}
+
+/// A needle wrapper declaring the maximum length of a match of the wrapped needle.
+///
+/// Needles such as regular expressions cannot tell their maximum match length, so readers keep
+/// and search again everything read since the start of a call. Wrapping them lets readers bound
+/// that to the last `max_len - 1` bytes; a longer match straddling two reads may then be missed.
+#[derive(Debug, Clone)]
+pub struct WithMaxLen<N> {
+    needle: N,
+    max_len: usize,
+}
+
+impl<N: Needle> WithMaxLen<N> {
+    /// Wraps `needle`, whose matches are never longer than `max_len` bytes.
+    pub fn new(needle: N, max_len: usize) -> Self {
+        Self { needle, max_len }
+    }
+
+    /// Unwraps this `WithMaxLen`, returning the inner needle.
+    pub fn into_inner(self) -> N {
+        self.needle
+    }
+}
+
+impl<N: Needle> Needle for WithMaxLen<N> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        self.needle.findin(haystack)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.max_len)
+    }
+}
+
+/// A needle matching the end of a line, `\n` or `\r\n`.
+///
+/// This splits lines of streams mixing both line endings without a regex for `\r?\n`. A `\r`
+/// belongs to the match only if it directly precedes the `\n`; [`Line::is_crlf`] tells which
+/// ending matched.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::Line;
+///
+/// let mut reader = &b"first\r\nsecond\n"[..];
+/// let (mut line, mut ending) = (Vec::new(), Vec::new());
+/// reader.read_until_needle(Line, &mut line, &mut ending).unwrap();
+/// assert_eq!(line, b"first");
+/// assert!(Line::is_crlf(&ending));
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Line;
+
+impl Line {
+    /// Returns `true` if `matched`, a match of this needle, is `\r\n`.
+    pub fn is_crlf(matched: &[u8]) -> bool {
+        matched == b"\r\n"
+    }
+}
+
+impl Needle for Line {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        let end = haystack.iter().position(|&b| b == b'\n')?;
+        if end > 0 && haystack[end - 1] == b'\r' {
+            Some(end - 1..end + 1)
+        } else {
+            Some(end..end + 1)
+        }
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        match self.findin(haystack) {
+            Some(range) => PartialMatch::Full(range),
+            None if haystack.ends_with(b"\r") => PartialMatch::Prefix(haystack.len() - 1),
+            None => PartialMatch::None,
+        }
+    }
+}
+
+/// A needle matching a literal pattern regardless of ASCII case.
+///
+/// Prompts and banners vary in case, such as `Login:` and `login:`. The match is reported in the
+/// bytes read, with their case as it was. Bytes outside the ASCII range match only themselves.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::CaseInsensitive;
+///
+/// let mut reader = &b"Welcome\r\nLOGIN: "[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader
+///     .read_until_needle(CaseInsensitive::new("login: "), &mut before, &mut matched)
+///     .unwrap();
+/// assert_eq!(matched, b"LOGIN: ");
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone)]
+pub struct CaseInsensitive<P> {
+    pattern: P,
+}
+
+impl<P: AsRef<[u8]>> CaseInsensitive<P> {
+    /// Creates a needle matching `pattern`, which must not be empty, in any ASCII case.
+    pub fn new(pattern: P) -> Self {
+        assert!(
+            !pattern.as_ref().is_empty(),
+            "the pattern must not be empty"
+        );
+        Self { pattern }
+    }
+
+    /// Unwraps this `CaseInsensitive`, returning the pattern.
+    pub fn into_inner(self) -> P {
+        self.pattern
+    }
+}
+
+impl<P: AsRef<[u8]>> Needle for CaseInsensitive<P> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        let pattern = self.pattern.as_ref();
+        haystack
+            .windows(pattern.len())
+            .position(|window| window.eq_ignore_ascii_case(pattern))
+            .map(|pos| pos..pos + pattern.len())
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.pattern.as_ref().len())
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        if let Some(range) = self.findin(haystack) {
+            return PartialMatch::Full(range);
+        }
+        let pattern = self.pattern.as_ref();
+        let shortest = haystack.len().saturating_sub(pattern.len() - 1);
+        (shortest..haystack.len())
+            .find(|&start| {
+                let suffix = &haystack[start..];
+                pattern[..suffix.len()].eq_ignore_ascii_case(suffix)
+            })
+            .map_or(PartialMatch::None, PartialMatch::Prefix)
+    }
 }
 
 #[cfg(feature = "regex")]
@@ -55,6 +548,116 @@ impl Needle for regex::bytes::Regex {
     }
 }
 
+/// A `str` regex searches the text read where it is valid UTF-8.
+///
+/// Bytes that are not valid UTF-8 split the text: no match spans them, and each run of valid
+/// UTF-8 between them is searched on its own, so `^` and `\b` also match at its edges. A
+/// character cut at the end of a read is not searched until the rest of it is read, and a match
+/// ending just before it is only reported once it is known whether that character extends it.
+///
+/// # Example
+/// ```
+/// use until_needle::io::UntilNeedleRead;
+///
+/// let regex = regex::Regex::new(r"\p{Sc}\d+").unwrap();
+/// let mut reader = "\u{feff}Gesamt: \u{20ac}42\n".as_bytes();
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader.read_until_needle(regex, &mut before, &mut matched).unwrap();
+/// assert_eq!(matched, "\u{20ac}42".as_bytes());
+/// ```
+#[cfg(feature = "regex")]
+impl Needle for regex::Regex {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        find_utf8(self, haystack).map(|(range, _)| range)
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        match find_utf8(self, haystack) {
+            Some((range, false)) => PartialMatch::Full(range),
+            // the cut character may extend the match
+            Some((range, true)) => PartialMatch::Prefix(range.start),
+            None if haystack.is_empty() => PartialMatch::None,
+            None => PartialMatch::Prefix(0),
+        }
+    }
+}
+
+/// Searches the runs of valid UTF-8 of `haystack` in turn, also telling whether the match ends
+/// just before a character cut at the end of the haystack.
+#[cfg(feature = "regex")]
+fn find_utf8(regex: &regex::Regex, haystack: &[u8]) -> Option<(Range<usize>, bool)> {
+    let mut start = 0;
+    loop {
+        let (text, invalid) = match core::str::from_utf8(&haystack[start..]) {
+            Ok(text) => (text, None),
+            Err(err) => {
+                let valid = &haystack[start..start + err.valid_up_to()];
+                let text = core::str::from_utf8(valid).unwrap_or_default();
+                (text, Some(err.error_len()))
+            }
+        };
+        if let Some(m) = regex.find(text) {
+            let cut = invalid == Some(None) && m.end() == text.len();
+            return Some((start + m.start()..start + m.end(), cut));
+        }
+        match invalid {
+            Some(Some(len)) => start += text.len() + len,
+            _ => return None,
+        }
+    }
+}
+
+/// A precompiled substring searcher, built once and reused across reads.
+///
+/// The search uses SIMD where available, which is much faster than the naive search of the
+/// slice needles on large buffers. Use [`Finder::into_owned`](memchr::memmem::Finder::into_owned)
+/// to keep one without borrowing the needle.
+#[cfg(feature = "memchr")]
+impl Needle for memchr::memmem::Finder<'_> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        self.find(haystack)
+            .map(|pos| pos..pos + self.needle().len())
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.needle().len())
+    }
+}
+
+/// A byte string of `bstr`, found with its substring search.
+#[cfg(feature = "bstr")]
+impl Needle for bstr::BStr {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        bstr::ByteSlice::find(haystack, self).map(|pos| pos..pos + self.len())
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        match self.findin(haystack) {
+            Some(range) => PartialMatch::Full(range),
+            None => <[u8] as Needle>::findin_partial(self, haystack),
+        }
+    }
+}
+
+#[cfg(all(feature = "bstr", feature = "alloc"))]
+impl Needle for bstr::BString {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        bstr::BStr::new(self.as_slice()).findin(haystack)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        bstr::BStr::new(self.as_slice()).findin_partial(haystack)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -71,6 +674,209 @@ mod tests {
         assert_eq!("world".findin(haystack), Some(6..11));
         assert_eq!("foo".findin(haystack), None);
 
+        assert_eq!(b"hello".max_len(), Some(5));
+        assert_eq!(Needle::max_len(&[0u8; 3]), Some(3));
+        assert_eq!(Needle::max_len("hello"), Some(5));
+        assert_eq!([b'o'; 2].findin(b"foo"), Some(1..3));
+        #[cfg(feature = "alloc")]
+        assert_eq!("hello".to_string().max_len(), Some(5));
+    }
+
+    #[test]
+    fn test_findin_partial() {
+        assert_eq!(
+            b"<END>".findin_partial(b"ab<END>"),
+            PartialMatch::Full(2..7)
+        );
+        assert_eq!(b"<END>".findin_partial(b"ab<EN"), PartialMatch::Prefix(2));
+        assert_eq!(b"<END>".findin_partial(b"ab<EN<"), PartialMatch::Prefix(5));
+        assert_eq!(b"<END>".findin_partial(b"ab<ENx"), PartialMatch::None);
+        assert_eq!(b"aab".findin_partial(b"xaa"), PartialMatch::Prefix(1));
+        assert_eq!("ab".findin_partial(b""), PartialMatch::None);
+
+        // the default keeps the last `max_len - 1` bytes
+        let needle = WithMaxLen::new(&b"<END>"[..], 3);
+        assert_eq!(needle.findin_partial(b"abcd"), PartialMatch::Prefix(2));
+    }
+
+    #[test]
+    fn test_literal() {
+        struct ByteString(Vec<u8>);
+
+        impl AsRef<[u8]> for ByteString {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        let needle = Literal(ByteString(b"<END>".to_vec()));
+        assert_eq!(needle.findin(b"ab<END>"), Some(2..7));
+        assert_eq!(needle.max_len(), Some(5));
+        assert_eq!(needle.findin_partial(b"ab<EN"), PartialMatch::Prefix(2));
+    }
+
+    #[cfg(feature = "bstr")]
+    #[test]
+    fn test_bstr() {
+        use bstr::BStr;
+
+        let needle = BStr::new("<END>");
+        assert_eq!(needle.findin(b"ab<END>"), Some(2..7));
+        assert_eq!(needle.max_len(), Some(5));
+        assert_eq!(needle.findin_partial(b"ab<EN"), PartialMatch::Prefix(2));
+        assert_eq!(needle.findin_partial(b"ab<ENx"), PartialMatch::None);
+
+        #[cfg(feature = "alloc")]
+        {
+            let needle = bstr::BString::from("\r\n");
+            assert_eq!(needle.findin(b"ok\r\nrest"), Some(2..4));
+            assert_eq!(needle.findin_partial(b"ok\r"), PartialMatch::Prefix(2));
+        }
+    }
+
+    #[test]
+    fn test_byte_and_char() {
+        assert_eq!(b'\0'.findin(b"ab\0cd\0"), Some(2..3));
+        assert_eq!(b'\0'.findin(b"abcd"), None);
+        assert_eq!(b'\n'.findin_partial(b"ab"), PartialMatch::None);
+        assert_eq!('\n'.findin(b"a\nb"), Some(1..2));
+        assert_eq!('\u{2192}'.findin("a \u{2192} b".as_bytes()), Some(2..5));
+        assert_eq!('\u{2192}'.max_len(), Some(3));
+        assert_eq!(
+            '\u{2192}'.findin_partial(b"a \xe2\x86"),
+            PartialMatch::Prefix(2)
+        );
+    }
+
+    #[test]
+    fn test_trait_objects() {
+        let needles: [&dyn Needle; 2] = [&"<END>", &Line];
+        assert_eq!(needles[0].findin(b"ab<END>"), Some(2..7));
+        assert_eq!(needles[1].max_len(), Some(2));
+        assert_eq!(needles[1].findin_partial(b"ab\r"), PartialMatch::Prefix(2));
+
+        #[cfg(feature = "alloc")]
+        {
+            let boxed: Box<dyn Needle> = Box::new(CaseInsensitive::new("end"));
+            assert_eq!(boxed.findin(b"ab<END>"), Some(3..6));
+            let shared: Arc<dyn Needle + Send + Sync> = Arc::new(b'>');
+            assert_eq!(shared.findin(b"ab<END>"), Some(6..7));
+            assert_eq!(shared.max_len(), Some(1));
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_with_trait_object() {
+        use crate::io::UntilNeedleRead;
+
+        struct Config {
+            prompt: Box<dyn Needle + Send + Sync>,
+        }
+
+        let config = Config {
+            prompt: Box::new(Glob::new("*$ ")),
+        };
+        let mut reader = &b"motd\r\nuser$ ls"[..];
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        reader
+            .read_until_needle(&*config.prompt, &mut before, &mut matched)
+            .unwrap();
+        assert_eq!(before, b"motd\r\nuser");
+        assert_eq!(matched, b"$ ");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_with_reference() {
+        use crate::io::UntilNeedleRead;
+
+        struct Config {
+            delimiter: Literal<Vec<u8>>,
+            prompt: Glob,
+        }
+
+        let config = Config {
+            delimiter: Literal(b"\r\n".to_vec()),
+            prompt: Glob::new("*$ "),
+        };
+        let mut reader = &b"motd\r\nuser$ ls"[..];
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        reader
+            .read_until_needle(&config.delimiter, &mut before, &mut matched)
+            .unwrap();
+        assert_eq!(before, b"motd");
+        before.clear();
+        reader
+            .read_until_needle(&config.prompt, &mut before, &mut matched)
+            .unwrap();
+        assert_eq!(before, b"user");
+    }
+
+    #[test]
+    fn test_closure() {
+        let nul_terminated = FnNeedle(|haystack: &[u8]| {
+            let end = haystack.iter().position(|&b| b == 0)?;
+            Some(end..end + 1)
+        });
+        assert_eq!(nul_terminated.findin(b"ab\0cd\0"), Some(2..3));
+        assert_eq!(nul_terminated.max_len(), None);
+        assert_eq!(
+            nul_terminated.findin_partial(b"ab"),
+            PartialMatch::Prefix(0)
+        );
+        let bounded = WithMaxLen::new(nul_terminated, 1);
+        assert_eq!(bounded.findin_partial(b"ab"), PartialMatch::None);
+    }
+
+    #[test]
+    fn test_line() {
+        assert_eq!(Line.findin(b"ab\r\ncd\n"), Some(2..4));
+        assert_eq!(Line.findin(b"ab\ncd\r\n"), Some(2..3));
+        assert_eq!(Line.findin(b"\n"), Some(0..1));
+        assert_eq!(Line.findin(b"a\rb"), None);
+        assert_eq!(Line.findin_partial(b"ab\r"), PartialMatch::Prefix(2));
+        assert_eq!(Line.findin_partial(b"ab"), PartialMatch::None);
+        assert!(Line::is_crlf(b"\r\n"));
+        assert!(!Line::is_crlf(b"\n"));
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let needle = CaseInsensitive::new("Login:");
+        assert_eq!(needle.findin(b"x LOGIN: "), Some(2..8));
+        assert_eq!(needle.findin(b"login:"), Some(0..6));
+        assert_eq!(needle.findin(b"log in:"), None);
+        assert_eq!(needle.findin_partial(b"abLOG"), PartialMatch::Prefix(2));
+        assert_eq!(needle.findin_partial(b"abLOGx"), PartialMatch::None);
+        assert_eq!(needle.max_len(), Some(6));
+        assert_eq!(
+            CaseInsensitive::new("\u{e9}").findin("\u{c9}".as_bytes()),
+            None
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_line_in_chunks() {
+        use crate::io::UntilNeedleRead;
+        use crate::testing::ChunkedReader;
+
+        let data = b"one\r\ntwo\nthree\r\r\n";
+        for size in 1..data.len() {
+            let reader = ChunkedReader::new(&data[..], [size]);
+            let lines: Vec<_> = reader.segments(Line).map(Result::unwrap).collect();
+            assert_eq!(lines, [&b"one"[..], b"two", b"three\r"]);
+        }
+    }
+
+    #[cfg(feature = "memchr")]
+    #[test]
+    fn test_finder_findin() {
+        let finder = memchr::memmem::Finder::new("world");
+        assert_eq!(finder.findin(b"hello world"), Some(6..11));
+        assert_eq!(finder.findin(b"hello"), None);
+        assert_eq!(finder.max_len(), Some(5));
     }
 
     #[cfg(feature = "regex")]
@@ -79,5 +885,52 @@ mod tests {
         let haystack = b" hello world";
         let regex = regex::bytes::Regex::new(r"\b\w+\b").unwrap();
         assert_eq!(regex.findin(haystack), Some(1..6));
+        assert_eq!(regex.max_len(), None);
+        let regex = WithMaxLen::new(regex, 16);
+        assert_eq!(regex.findin(haystack), Some(1..6));
+        assert_eq!(regex.max_len(), Some(16));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_str_regex_findin() {
+        let regex = regex::Regex::new(r"\w+").unwrap();
+        assert_eq!(regex.findin(b"\xffab\xfecd"), Some(1..3));
+        assert_eq!(regex.findin(b" \xff "), None);
+        let regex = regex::Regex::new(r"^cd").unwrap();
+        assert_eq!(regex.findin(b"ab\xffcd"), Some(3..5));
+
+        let regex = regex::Regex::new(r"caf\w*").unwrap();
+        assert_eq!(regex.findin_partial(b"un caf\xc3"), PartialMatch::Prefix(3));
+        assert_eq!(
+            regex.findin_partial("un café".as_bytes()),
+            PartialMatch::Full(3..8)
+        );
+        assert_eq!(
+            regex.findin_partial(b"un caf\xff"),
+            PartialMatch::Full(3..6)
+        );
+        assert_eq!(regex.findin(b"un caf\xc3"), Some(3..6));
+        assert_eq!(regex.findin_partial(b"\xc3"), PartialMatch::Prefix(0));
+        assert_eq!(regex.findin_partial(b""), PartialMatch::None);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_read_with_str_regex() {
+        use crate::io::UntilNeedleRead;
+        use crate::testing::ChunkedReader;
+
+        let data = "Prix : 12 \u{20ac} \u{2014} réglé\n".as_bytes();
+        let regex = regex::Regex::new(r"\d+ \p{Sc}").unwrap();
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(data, [size]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(regex.clone(), &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(before, b"Prix : ");
+            assert_eq!(matched, "12 \u{20ac}".as_bytes());
+        }
     }
 }
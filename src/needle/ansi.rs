@@ -0,0 +1,203 @@
+use super::{Needle, PartialMatch};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+const ESC: u8 = 0x1b;
+
+/// A needle matching another needle against text with ANSI escape sequences removed.
+///
+/// Terminal output is interleaved with color codes and cursor movements, which break literal
+/// matching of prompts. This wrapper searches the text without them, and reports the match in
+/// the raw bytes: readers still return the escape sequences in `before` and `matched`. A match
+/// spans from the raw byte of its first character to the raw byte of its last one, so
+/// sequences before the match go to `before` and sequences inside it to `matched`.
+///
+/// CSI sequences (`ESC [`), OSC sequences (`ESC ]` up to BEL or `ESC \`) and two-byte
+/// escapes are removed. A sequence cut off at the end of the data read so far is kept until
+/// it completes.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::AnsiTolerant;
+///
+/// let mut reader = &b"ok\r\n\x1b[1;32muser@host\x1b[0m:~\x1b[0m$ "[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader
+///     .read_until_needle(AnsiTolerant::new("host:~$ "), &mut before, &mut matched)
+///     .unwrap();
+/// assert_eq!(before, b"ok\r\n\x1b[1;32muser@");
+/// assert_eq!(matched, b"host\x1b[0m:~\x1b[0m$ ");
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone)]
+pub struct AnsiTolerant<N> {
+    needle: N,
+}
+
+impl<N: Needle> AnsiTolerant<N> {
+    /// Wraps `needle`, matching it against the text with escape sequences removed.
+    pub fn new(needle: N) -> Self {
+        Self { needle }
+    }
+
+    /// Unwraps this `AnsiTolerant`, returning the inner needle.
+    pub fn into_inner(self) -> N {
+        self.needle
+    }
+}
+
+/// Text with the escape sequences removed.
+struct Stripped {
+    text: Vec<u8>,
+    /// The offset in the raw bytes of each byte of `text`.
+    offsets: Vec<usize>,
+    /// The offset of an escape sequence cut off at the end of the raw bytes, or their length.
+    incomplete: usize,
+}
+
+impl Stripped {
+    fn new(raw: &[u8]) -> Self {
+        let mut text = Vec::with_capacity(raw.len());
+        let mut offsets = Vec::with_capacity(raw.len());
+        let mut i = 0;
+        while i < raw.len() {
+            if raw[i] != ESC {
+                text.push(raw[i]);
+                offsets.push(i);
+                i += 1;
+                continue;
+            }
+            match escape_len(&raw[i..]) {
+                Some(len) => i += len,
+                None => {
+                    return Self {
+                        text,
+                        offsets,
+                        incomplete: i,
+                    }
+                }
+            }
+        }
+        Self {
+            text,
+            offsets,
+            incomplete: raw.len(),
+        }
+    }
+
+    /// Maps a match in the text to the raw bytes.
+    fn raw_range(&self, range: Range<usize>) -> Range<usize> {
+        let start = self.raw_offset(range.start);
+        if range.is_empty() {
+            return start..start;
+        }
+        start..self.offsets[range.end - 1] + 1
+    }
+
+    /// Maps an offset in the text to the raw bytes.
+    fn raw_offset(&self, offset: usize) -> usize {
+        self.offsets.get(offset).copied().unwrap_or(self.incomplete)
+    }
+}
+
+/// Returns the length of the escape sequence at the start of `bytes`, or `None` if it is
+/// cut off.
+fn escape_len(bytes: &[u8]) -> Option<usize> {
+    match bytes.get(1)? {
+        // CSI: parameter and intermediate bytes up to a final byte
+        b'[' => {
+            let end = bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b))?;
+            Some(2 + end + 1)
+        }
+        // OSC: up to BEL or ST
+        b']' => {
+            let mut i = 2;
+            loop {
+                match bytes.get(i)? {
+                    0x07 => return Some(i + 1),
+                    &ESC if *bytes.get(i + 1)? == b'\\' => return Some(i + 2),
+                    _ => i += 1,
+                }
+            }
+        }
+        // character set selection takes one more byte
+        b'(' | b')' => bytes.get(2).map(|_| 3),
+        _ => Some(2),
+    }
+}
+
+impl<N: Needle> Needle for AnsiTolerant<N> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        let stripped = Stripped::new(haystack);
+        let found = self.needle.findin(&stripped.text)?;
+        Some(stripped.raw_range(found))
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        let stripped = Stripped::new(haystack);
+        let keep_from = match self.needle.findin_partial(&stripped.text) {
+            PartialMatch::Full(found) => return PartialMatch::Full(stripped.raw_range(found)),
+            PartialMatch::Prefix(start) => stripped.raw_offset(start),
+            PartialMatch::None => stripped.incomplete,
+        };
+        if keep_from < haystack.len() {
+            PartialMatch::Prefix(keep_from)
+        } else {
+            PartialMatch::None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_findin() {
+        let needle = AnsiTolerant::new("$ ");
+        assert_eq!(needle.findin(b"a\x1b[1m$\x1b[0m b"), Some(5..11));
+        assert_eq!(needle.findin(b"\x1b]0;title\x07$ "), Some(10..12));
+        assert_eq!(needle.findin(b"\x1b]0;title\x1b\\$ "), Some(11..13));
+        assert_eq!(needle.findin(b"\x1b(B$ "), Some(3..5));
+        assert_eq!(needle.findin(b"\x1b[$ "), None);
+        assert_eq!(needle.findin(b"$\x1b[3"), None);
+    }
+
+    #[test]
+    fn test_findin_partial() {
+        let needle = AnsiTolerant::new("$ ");
+        assert_eq!(
+            needle.findin_partial(b"ab\x1b[1m$"),
+            PartialMatch::Prefix(6)
+        );
+        assert_eq!(needle.findin_partial(b"ab\x1b[1"), PartialMatch::Prefix(2));
+        assert_eq!(needle.findin_partial(b"ab\x1b[1m"), PartialMatch::None);
+        assert_eq!(
+            needle.findin_partial(b"ab\x1b[1m$\x1b[0"),
+            PartialMatch::Prefix(6)
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_in_chunks() {
+        use crate::io::UntilNeedleRead;
+        use crate::testing::ChunkedReader;
+
+        let data = b"\x1b[32mok\x1b[0m\r\n\x1b]0;sh\x07\x1b[1m$\x1b[0m rest";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(AnsiTolerant::new("$ "), &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(before, b"\x1b[32mok\x1b[0m\r\n\x1b]0;sh\x07\x1b[1m");
+            assert_eq!(matched, b"$\x1b[0m ");
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use super::Needle;
+use aho_corasick::{AhoCorasick, BuildError, MatchKind};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// A needle matching any of several patterns, searched for at once with Aho-Corasick.
+///
+/// The leftmost match is found; among patterns matching at the same position, the first one
+/// given wins. [`pattern`](Self::pattern) tells which pattern the matched bytes are, so a single
+/// read can branch on which of several prompts arrived.
+///
+/// When a pattern is a prefix of one given before it, a read may report the shorter pattern
+/// while the longer one has only partly arrived, since a reader cannot wait for data that may
+/// never come.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::AnyOf;
+///
+/// let prompts = AnyOf::new(["login: ", "Password: ", "# "]).unwrap();
+/// let mut reader = &b"Welcome\r\nPassword: "[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader.read_until_needle(prompts.clone(), &mut before, &mut matched).unwrap();
+/// assert_eq!(prompts.pattern(&matched), Some(1));
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone)]
+pub struct AnyOf {
+    searcher: AhoCorasick,
+    patterns: Vec<Vec<u8>>,
+}
+
+impl AnyOf {
+    /// Creates a needle matching any of `patterns`.
+    pub fn new<I, P>(patterns: I) -> Result<Self, BuildError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        let patterns: Vec<Vec<u8>> = patterns
+            .into_iter()
+            .map(|pattern| pattern.as_ref().to_vec())
+            .collect();
+        let searcher = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostFirst)
+            .build(&patterns)?;
+        Ok(Self { searcher, patterns })
+    }
+
+    /// Returns the index of the pattern that `matched`, a match of this needle, is.
+    pub fn pattern(&self, matched: &[u8]) -> Option<usize> {
+        self.patterns.iter().position(|pattern| pattern == matched)
+    }
+
+    /// Returns the patterns, in the order they were given.
+    pub fn patterns(&self) -> &[Vec<u8>] {
+        &self.patterns
+    }
+}
+
+impl Needle for AnyOf {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        self.searcher.find(haystack).map(|m| m.range())
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.searcher.max_pattern_len())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::io::UntilNeedleRead;
+    use crate::testing::ChunkedReader;
+
+    #[test]
+    fn test_findin() {
+        let needle = AnyOf::new(["login:", "Password:", "#"]).unwrap();
+        assert_eq!(needle.findin(b"host login: "), Some(5..11));
+        assert_eq!(needle.findin(b"# Password:"), Some(0..1));
+        assert_eq!(needle.findin(b"$ "), None);
+        assert_eq!(needle.max_len(), Some(9));
+        assert_eq!(needle.pattern(b"Password:"), Some(1));
+        assert_eq!(needle.pattern(b"#"), Some(2));
+        assert_eq!(needle.pattern(b"$"), None);
+
+        // the first pattern wins at the same position
+        let needle = AnyOf::new(["ab", "a"]).unwrap();
+        assert_eq!(needle.findin(b"xab"), Some(1..3));
+    }
+
+    #[test]
+    fn test_read_until_any_of() {
+        let needle = AnyOf::new(["login: ", "Password: ", "# "]).unwrap();
+        let data = b"Last login: never\r\nPassword: \r\nroot# ";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(data, [size]);
+            let mut found = Vec::new();
+            loop {
+                let (mut before, mut matched) = (Vec::new(), Vec::new());
+                reader
+                    .read_until_needle(needle.clone(), &mut before, &mut matched)
+                    .unwrap();
+                match needle.pattern(&matched) {
+                    Some(index) => found.push((index, before)),
+                    None => break,
+                }
+            }
+            assert_eq!(
+                found,
+                [
+                    (0, b"Last ".to_vec()),
+                    (1, b"never\r\n".to_vec()),
+                    (2, b"\r\nroot".to_vec()),
+                ]
+            );
+        }
+    }
+}
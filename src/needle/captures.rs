@@ -0,0 +1,117 @@
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+use regex::bytes::Regex;
+
+/// A match of a regex needle together with its capture groups.
+///
+/// Group 0 is the whole match; the other groups are numbered and named as in the regex. The
+/// spans of the groups are relative to the matched bytes.
+///
+/// # Example
+/// ```
+/// use regex::bytes::Regex;
+/// use until_needle::io::UntilNeedleRead;
+///
+/// let prompt = Regex::new(r"\[session (?<id>\d+)\]\$ ").unwrap();
+/// let mut reader = &b"login ok\r\n[session 42]$ "[..];
+/// let mut before = Vec::new();
+/// let found = reader.read_until_needle_match(&prompt, &mut before).unwrap().unwrap();
+/// assert_eq!(before, b"login ok\r\n");
+/// assert_eq!(found.name("id"), Some(&b"42"[..]));
+/// assert_eq!(found.range(1), Some(9..11));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NeedleMatch {
+    bytes: Vec<u8>,
+    groups: Vec<Option<Range<usize>>>,
+    names: Vec<Option<String>>,
+}
+
+impl NeedleMatch {
+    /// Finds the capture groups of `regex` in `matched`, a match of it read right after
+    /// `before`.
+    ///
+    /// The end of `before` is searched along with the match, so assertions such as `\b` at its
+    /// start see the same bytes as the search that found it. Returns `None` if `regex` does not
+    /// match `matched`.
+    pub fn captures(regex: &Regex, before: &[u8], matched: &[u8]) -> Option<Self> {
+        // assertions look back at most one character, which is at most 4 bytes
+        let context = &before[before.len().saturating_sub(4)..];
+        let mut haystack = Vec::with_capacity(context.len() + matched.len());
+        haystack.extend_from_slice(context);
+        haystack.extend_from_slice(matched);
+
+        let captures = regex.captures_at(&haystack, context.len())?;
+        let groups = captures
+            .iter()
+            .map(|group| group.map(|m| m.start() - context.len()..m.end() - context.len()))
+            .collect();
+        let names = regex
+            .capture_names()
+            .map(|name| name.map(String::from))
+            .collect();
+        haystack.drain(..context.len());
+        Some(Self {
+            bytes: haystack,
+            groups,
+            names,
+        })
+    }
+
+    /// Returns the matched bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the bytes of group `i`, or `None` if it did not participate in the match.
+    pub fn get(&self, i: usize) -> Option<&[u8]> {
+        self.range(i).map(|range| &self.bytes[range])
+    }
+
+    /// Returns the span of group `i` in the matched bytes.
+    pub fn range(&self, i: usize) -> Option<Range<usize>> {
+        self.groups.get(i).cloned().flatten()
+    }
+
+    /// Returns the bytes of the group named `name`.
+    pub fn name(&self, name: &str) -> Option<&[u8]> {
+        let i = self
+            .names
+            .iter()
+            .position(|group| group.as_deref() == Some(name))?;
+        self.get(i)
+    }
+
+    /// Returns the number of groups, including group 0.
+    pub fn group_count(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Returns the matched bytes, dropping the groups.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captures() {
+        let regex = Regex::new(r"(?<key>\w+)=(\d+)?;").unwrap();
+        let found = NeedleMatch::captures(&regex, b"x ", b"id=;").unwrap();
+        assert_eq!(found.as_bytes(), b"id=;");
+        assert_eq!(found.group_count(), 3);
+        assert_eq!(found.get(0), Some(&b"id=;"[..]));
+        assert_eq!(found.name("key"), Some(&b"id"[..]));
+        assert_eq!(found.get(2), None);
+        assert_eq!(found.get(3), None);
+        assert_eq!(found.name("value"), None);
+
+        // the bytes before the match are seen by assertions
+        let regex = Regex::new(r"\bid").unwrap();
+        assert!(NeedleMatch::captures(&regex, b" ", b"id").is_some());
+        assert!(NeedleMatch::captures(&regex, b"x", b"id").is_none());
+    }
+}
@@ -0,0 +1,93 @@
+use super::Needle;
+use core::ops::Range;
+use regex::bytes::Regex;
+
+/// A needle matching a literal text regardless of case, with Unicode case folding.
+///
+/// Unlike [`CaseInsensitive`](super::CaseInsensitive), which only folds ASCII letters, this
+/// matches `échec` in `ÉCHEC` and `ошибка` in `ОШИБКА`, through a regex built from the escaped
+/// text with the `(?i)` flag. Only simple case folding applies, so `ß` does not match `SS`. The
+/// match is reported in the bytes read, with their case as it was.
+///
+/// Folding may change the length of a character, so the maximum match length is bounded by 4
+/// bytes per character of the text.
+///
+/// # Example
+/// ```
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::UnicodeCaseInsensitive;
+///
+/// let mut reader = "ERREUR : ÉCHEC DE CONNEXION\r\n".as_bytes();
+/// let needle = UnicodeCaseInsensitive::new("échec").unwrap();
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader.read_until_needle(needle, &mut before, &mut matched).unwrap();
+/// assert_eq!(matched, "ÉCHEC".as_bytes());
+/// ```
+#[derive(Debug, Clone)]
+pub struct UnicodeCaseInsensitive {
+    regex: Regex,
+    max_len: usize,
+}
+
+impl UnicodeCaseInsensitive {
+    /// Creates a needle matching `text` in any case.
+    ///
+    /// # Errors
+    /// Fails if the regex built from `text` exceeds the size limits of the regex crate.
+    pub fn new(text: &str) -> Result<Self, regex::Error> {
+        let regex = Regex::new(&format!("(?i){}", regex::escape(text)))?;
+        Ok(Self {
+            regex,
+            max_len: 4 * text.chars().count(),
+        })
+    }
+
+    /// Returns the regex the text is searched with.
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+}
+
+impl Needle for UnicodeCaseInsensitive {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        self.regex.find(haystack).map(|m| m.range())
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.max_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::UntilNeedleRead;
+    use crate::testing::ChunkedReader;
+
+    #[test]
+    fn test_findin() {
+        let needle = UnicodeCaseInsensitive::new("ошибка").unwrap();
+        assert_eq!(needle.findin("x ОШИБКА".as_bytes()), Some(2..14));
+        let needle = UnicodeCaseInsensitive::new("a.b").unwrap();
+        assert_eq!(needle.findin(b"axb A.B"), Some(4..7));
+        // the Kelvin sign folds to k but takes 3 bytes
+        let needle = UnicodeCaseInsensitive::new("ok").unwrap();
+        assert_eq!(needle.findin("O\u{212a}".as_bytes()), Some(0..4));
+        assert_eq!(needle.max_len(), Some(8));
+    }
+
+    #[test]
+    fn test_read_in_chunks() {
+        let data = "Benutzer: Fehler bei ANMELDUNG\r\n".as_bytes();
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(data, [size]);
+            let needle = UnicodeCaseInsensitive::new("Anmeldung").unwrap();
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(needle, &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(before, b"Benutzer: Fehler bei ");
+            assert_eq!(matched, b"ANMELDUNG");
+        }
+    }
+}
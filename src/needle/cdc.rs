@@ -0,0 +1,175 @@
+use super::Needle;
+use core::ops::Range;
+
+/// The number of bytes the hash of [`CdcBoundary`] is computed over.
+const WINDOW: usize = 64;
+
+/// The random values mixed into the hash for each byte, from a fixed seed so that boundaries
+/// never change between builds.
+const GEAR: [u64; 256] = {
+    let mut table = [0; 256];
+    let mut seed: u64 = 0x5eed_5eed_5eed_5eed;
+    let mut i = 0;
+    while i < 256 {
+        // splitmix64
+        seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// A needle matching content-defined chunk boundaries, for deduplicating backup streams.
+///
+/// A rolling Gear hash, as in FastCDC, is computed over the last 64 bytes, and a boundary is
+/// where its top bits are all zero. The match is those 64 bytes, which end the chunk: a chunk
+/// is the data before the needle together with the needle. As a boundary only depends on the
+/// bytes before it, inserting or removing data only changes the chunks around the edit, and
+/// the same data is split the same way however it is read.
+///
+/// A boundary is only looked for once 64 bytes have been read since the start of the search,
+/// which is thus the minimum size of a chunk. To bound the size of a chunk, read with a
+/// [`limit`](crate::UntilNeedle::limit).
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() -> std::io::Result<()> {
+/// use until_needle::needle::CdcBoundary;
+/// use until_needle::UntilNeedle;
+///
+/// let data: Vec<u8> = (0..1u32 << 16)
+///     .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+///     .collect();
+/// let mut reader = &data[..];
+/// let search = UntilNeedle::new(CdcBoundary::new(1024)).limit(8 * 1024);
+/// let mut chunks = Vec::new();
+/// loop {
+///     let (mut chunk, mut boundary) = (Vec::new(), Vec::new());
+///     search.read(&mut reader, &mut chunk, &mut boundary)?;
+///     chunk.append(&mut boundary);
+///     if chunk.is_empty() {
+///         break;
+///     }
+///     chunks.push(chunk);
+/// }
+/// assert!(chunks.len() > 16);
+/// assert_eq!(chunks.concat(), data);
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CdcBoundary {
+    mask: u64,
+}
+
+impl CdcBoundary {
+    /// Creates a needle finding a boundary every `avg_size` bytes on average, rounded up to a
+    /// power of two, past the minimum chunk size.
+    ///
+    /// # Panics
+    /// Panics if `avg_size` is greater than 2^63.
+    pub fn new(avg_size: usize) -> Self {
+        let bits = avg_size.max(1).next_power_of_two().trailing_zeros();
+        assert!(bits < 64, "avg_size must be at most 2^63");
+        Self {
+            mask: !(u64::MAX >> bits),
+        }
+    }
+
+    /// Returns the average distance between boundaries past the minimum chunk size.
+    pub fn avg_size(&self) -> usize {
+        1 << self.mask.count_ones()
+    }
+}
+
+impl Needle for CdcBoundary {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        // each byte is shifted out of the hash after `WINDOW` more
+        let mut hash: u64 = 0;
+        haystack.iter().enumerate().find_map(|(i, &b)| {
+            hash = (hash << 1).wrapping_add(GEAR[b as usize]);
+            let end = i + 1;
+            (end >= WINDOW && hash & self.mask == 0).then(|| end - WINDOW..end)
+        })
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(WINDOW)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns `len` bytes that look random.
+    fn noise(seed: u32, len: usize) -> impl Iterator<Item = u8> {
+        let mut state = seed;
+        (0..len).map(move |_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+    }
+
+    #[test]
+    fn test_findin() {
+        let needle = CdcBoundary::new(1000);
+        assert_eq!(needle.avg_size(), 1024);
+        assert_eq!(CdcBoundary::new(0).avg_size(), 1);
+        assert_eq!(needle.max_len(), Some(WINDOW));
+        assert_eq!(needle.findin(&[0; WINDOW - 1]), None);
+
+        // every window may end a chunk with a mask of no bits
+        assert_eq!(CdcBoundary::new(1).findin(&[7; 100]), Some(0..WINDOW));
+        let data: Vec<u8> = noise(1, 1 << 16).collect();
+        let range = needle.findin(&data).unwrap();
+        assert_eq!(range.len(), WINDOW);
+        // a boundary only depends on the window
+        assert_eq!(needle.findin(&data[range.clone()]), Some(0..WINDOW));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_chunks_survive_insertion() {
+        use crate::io::UntilNeedleRead;
+        use crate::testing::ChunkedReader;
+
+        fn chunks(data: &[u8], read_size: usize) -> Vec<Vec<u8>> {
+            let mut reader = ChunkedReader::new(data, [read_size]);
+            let mut chunks = Vec::new();
+            loop {
+                let (mut chunk, mut boundary) = (Vec::new(), Vec::new());
+                reader
+                    .read_until_needle(CdcBoundary::new(256), &mut chunk, &mut boundary)
+                    .unwrap();
+                chunk.append(&mut boundary);
+                if chunk.is_empty() {
+                    return chunks;
+                }
+                chunks.push(chunk);
+            }
+        }
+
+        let data: Vec<u8> = noise(7, 16 * 1024).collect();
+        let original = chunks(&data, 4096);
+        assert!(original.len() > 16);
+        assert_eq!(original.concat(), data);
+        assert_eq!(chunks(&data, 1), original);
+        assert_eq!(chunks(&data, 100), original);
+
+        let mut edited = b"inserted".to_vec();
+        edited.extend_from_slice(&data);
+        let edited = chunks(&edited, 4096);
+        assert_ne!(edited[0], original[0]);
+        let shared = edited.iter().filter(|c| original.contains(c)).count();
+        assert!(shared + 2 >= original.len());
+    }
+}
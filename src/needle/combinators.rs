@@ -0,0 +1,375 @@
+//! Needles built from other needles.
+//!
+//! The wrappers here combine needles into more complex stop conditions without writing a custom
+//! [`Needle`] implementation: [`AnyOf`] matches the earliest of several needles, [`Sequence`]
+//! matches needles following each other, [`NotPrecededBy`] rejects matches right after some
+//! bytes, and [`Anchored`] and [`Skip`] restrict where a match may start.
+//!
+//! Readers search each chunk together with data kept from earlier ones. Except for `AnyOf`,
+//! these needles depend on what comes before a match or on where the read started, so they have
+//! no maximum length and readers keep and search again everything read since the start of a call.
+
+use super::{Needle, PartialMatch};
+use core::ops::Range;
+
+/// A needle matching the earliest match of any of the needles.
+///
+/// Among needles matching at the same position, the first one given wins. For literal patterns,
+/// `needle::AnyOf` (with the `aho-corasick` feature) finds them all in a single
+/// pass instead of searching for each in turn.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::combinators::AnyOf;
+///
+/// let needle = AnyOf([&b"\r\n"[..], b"\n"]);
+/// let mut reader = &b"first\nsecond\r\n"[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader.read_until_needle(needle, &mut before, &mut matched).unwrap();
+/// assert_eq!((&before[..], &matched[..]), (&b"first"[..], &b"\n"[..]));
+/// assert_eq!(needle.pattern(&matched), Some(1));
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct AnyOf<N, const K: usize>(pub [N; K]);
+
+impl<N: Needle, const K: usize> AnyOf<N, K> {
+    /// Returns the index of the first needle matching all of `matched`, a match of this needle.
+    pub fn pattern(&self, matched: &[u8]) -> Option<usize> {
+        self.0
+            .iter()
+            .position(|needle| needle.findin(matched) == Some(0..matched.len()))
+    }
+}
+
+impl<N: Needle, const K: usize> Needle for AnyOf<N, K> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        find_earliest(&self.0, haystack).map(|(_, range)| range)
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        match find_earliest(&self.0, haystack) {
+            Some((_, range)) => PartialMatch::Full(range),
+            None => partial_earliest(&self.0, haystack),
+        }
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        max_len_of(&self.0)
+    }
+}
+
+/// Finds the earliest match of any of `needles`, with the index of the needle matching it.
+pub(crate) fn find_earliest<N: Needle>(
+    needles: &[N],
+    haystack: &[u8],
+) -> Option<(usize, Range<usize>)> {
+    let mut earliest: Option<(usize, Range<usize>)> = None;
+    for (i, needle) in needles.iter().enumerate() {
+        let Some(range) = needle.findin(haystack) else {
+            continue;
+        };
+        match &earliest {
+            Some((_, found)) if found.start <= range.start => {}
+            _ => earliest = Some((i, range)),
+        }
+    }
+    earliest
+}
+
+/// Returns the longest suffix of `haystack` any of `needles` may begin in, once none matches.
+pub(crate) fn partial_earliest<N: Needle>(needles: &[N], haystack: &[u8]) -> PartialMatch {
+    needles
+        .iter()
+        .filter_map(|needle| match needle.findin_partial(haystack) {
+            PartialMatch::Prefix(start) => Some(start),
+            _ => None,
+        })
+        .min()
+        .map_or(PartialMatch::None, PartialMatch::Prefix)
+}
+
+/// Returns the longest maximum length of `needles`, or `None` if one is unbounded.
+pub(crate) fn max_len_of<N: Needle>(needles: &[N]) -> Option<usize> {
+    needles
+        .iter()
+        .try_fold(0, |len, needle| Some(len.max(needle.max_len()?)))
+}
+
+/// A needle matching each of the needles in turn, with any data between them.
+///
+/// The match spans from the start of the first needle's match to the end of the last one's, and
+/// each needle is searched after the end of the previous one's match.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::combinators::Sequence;
+///
+/// let mut reader = &b"noise BEGIN payload END tail"[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader
+///     .read_until_needle(Sequence(["BEGIN", "END"]), &mut before, &mut matched)
+///     .unwrap();
+/// assert_eq!(matched, b"BEGIN payload END");
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Sequence<N, const K: usize>(pub [N; K]);
+
+impl<N: Needle, const K: usize> Needle for Sequence<N, K> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        let (first, rest) = self.0.split_first()?;
+        let found = first.findin(haystack)?;
+        let mut end = found.end;
+        for needle in rest {
+            end += needle.findin(&haystack[end..])?.end;
+        }
+        Some(found.start..end)
+    }
+}
+
+/// A needle rejecting the matches of another needle that directly follow `prefix`.
+///
+/// Use it to skip escaped delimiters, such as a quote preceded by a backslash. The bytes before
+/// a match are only known from the start of the read, so a match at its very start is accepted.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::combinators::NotPrecededBy;
+///
+/// let mut reader = &br#"say \"hi\"" rest"#[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader
+///     .read_until_needle(NotPrecededBy::new("\"", "\\"), &mut before, &mut matched)
+///     .unwrap();
+/// assert_eq!(before, br#"say \"hi\""#);
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone)]
+pub struct NotPrecededBy<N, P> {
+    needle: N,
+    prefix: P,
+}
+
+impl<N: Needle, P: AsRef<[u8]>> NotPrecededBy<N, P> {
+    /// Wraps `needle`, rejecting its matches that directly follow `prefix`.
+    pub fn new(needle: N, prefix: P) -> Self {
+        Self { needle, prefix }
+    }
+
+    /// Unwraps this `NotPrecededBy`, returning the inner needle.
+    pub fn into_inner(self) -> N {
+        self.needle
+    }
+}
+
+impl<N: Needle, P: AsRef<[u8]>> Needle for NotPrecededBy<N, P> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        let prefix = self.prefix.as_ref();
+        let mut from = 0;
+        while from <= haystack.len() {
+            let found = self.needle.findin(&haystack[from..])?;
+            let (start, end) = (from + found.start, from + found.end);
+            if !haystack[..start].ends_with(prefix) {
+                return Some(start..end);
+            }
+            from = start + 1;
+        }
+        None
+    }
+}
+
+/// A needle only matching at the start of the read.
+///
+/// If the wrapped needle does not match there, the read goes on to EOF.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::combinators::Anchored;
+///
+/// let mut reader = &b"HTTP/1.1 200 OK\r\n"[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader
+///     .read_until_needle(Anchored::new("HTTP/"), &mut before, &mut matched)
+///     .unwrap();
+/// assert_eq!(matched, b"HTTP/");
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone)]
+pub struct Anchored<N> {
+    needle: N,
+}
+
+impl<N: Needle> Anchored<N> {
+    /// Wraps `needle`, only accepting a match starting at offset 0.
+    pub fn new(needle: N) -> Self {
+        Self { needle }
+    }
+
+    /// Unwraps this `Anchored`, returning the inner needle.
+    pub fn into_inner(self) -> N {
+        self.needle
+    }
+}
+
+impl<N: Needle> Needle for Anchored<N> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        self.needle
+            .findin(haystack)
+            .filter(|found| found.start == 0)
+    }
+}
+
+/// A needle ignoring the matches of another needle that start in the first `offset` bytes of
+/// the read.
+///
+/// The skipped bytes still go to the data before the needle. The wrapped needle only sees the
+/// data after them.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::combinators::Skip;
+///
+/// // the frame header may contain the delimiter byte
+/// let mut reader = &b"\x7e\x02payload\x7erest"[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader
+///     .read_until_needle(Skip::new(b"\x7e", 2), &mut before, &mut matched)
+///     .unwrap();
+/// assert_eq!(before, b"\x7e\x02payload");
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone)]
+pub struct Skip<N> {
+    needle: N,
+    offset: usize,
+}
+
+impl<N: Needle> Skip<N> {
+    /// Wraps `needle`, ignoring its matches starting before `offset`.
+    pub fn new(needle: N, offset: usize) -> Self {
+        Self { needle, offset }
+    }
+
+    /// Unwraps this `Skip`, returning the inner needle.
+    pub fn into_inner(self) -> N {
+        self.needle
+    }
+}
+
+impl<N: Needle> Needle for Skip<N> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        let skipped = haystack.get(self.offset..)?;
+        let found = self.needle.findin(skipped)?;
+        Some(self.offset + found.start..self.offset + found.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_any_of() {
+        let needle = AnyOf([&b"cd"[..], b"b", b"bc"]);
+        assert_eq!(needle.findin(b"abcd"), Some(1..2));
+        assert_eq!(needle.findin(b"acd"), Some(1..3));
+        assert_eq!(needle.findin(b"xyz"), None);
+        assert_eq!(needle.max_len(), Some(2));
+        assert_eq!(needle.pattern(b"bc"), Some(2));
+        assert_eq!(needle.pattern(b"x"), None);
+        assert_eq!(needle.findin_partial(b"xc"), PartialMatch::Prefix(1));
+        assert_eq!(needle.findin_partial(b"xy"), PartialMatch::None);
+
+        #[cfg(feature = "regex")]
+        {
+            let needle = AnyOf([regex::bytes::Regex::new("a+").unwrap()]);
+            assert_eq!(needle.max_len(), None);
+        }
+    }
+
+    #[test]
+    fn test_sequence() {
+        let needle = Sequence(["<", ">"]);
+        assert_eq!(needle.findin(b"a<b>c>"), Some(1..4));
+        assert_eq!(needle.findin(b"a>b<c"), None);
+        assert_eq!(needle.findin(b"a<>"), Some(1..3));
+        assert_eq!(Sequence(["ab", "ba"]).findin(b"aba"), None);
+        assert_eq!(Sequence::<&str, 0>([]).findin(b"abc"), None);
+        assert_eq!(needle.max_len(), None);
+    }
+
+    #[test]
+    fn test_not_preceded_by() {
+        let needle = NotPrecededBy::new("\"", "\\");
+        assert_eq!(needle.findin(br#"a\"b"c"#), Some(4..5));
+        assert_eq!(needle.findin(br#""a"#), Some(0..1));
+        assert_eq!(needle.findin(br#"a\"b\""#), None);
+        assert_eq!(needle.max_len(), None);
+
+        let needle = NotPrecededBy::new("aa", "a");
+        assert_eq!(needle.findin(b"aaa"), Some(0..2));
+        assert_eq!(needle.findin(b"baaa"), Some(1..3));
+    }
+
+    #[test]
+    fn test_positional() {
+        let needle = Anchored::new("ab");
+        assert_eq!(needle.findin(b"abab"), Some(0..2));
+        assert_eq!(needle.findin(b"xab"), None);
+
+        let needle = Skip::new("ab", 1);
+        assert_eq!(needle.findin(b"abab"), Some(2..4));
+        assert_eq!(needle.findin(b"a"), None);
+        assert_eq!(needle.findin(b""), None);
+        assert_eq!(Skip::new("ab", 0).findin(b"ab"), Some(0..2));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_in_chunks() {
+        use crate::io::UntilNeedleRead;
+        use crate::testing::ChunkedReader;
+
+        let data = br#"\"x" then <a<b>"#;
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(NotPrecededBy::new("\"", "\\"), &mut before, &mut matched)
+                .unwrap();
+            assert_eq!((&before[..], &matched[..]), (&br#"\"x"#[..], &b"\""[..]));
+
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(Sequence(["<", "b", ">"]), &mut before, &mut matched)
+                .unwrap();
+            assert_eq!((&before[..], &matched[..]), (&b" then "[..], &b"<a<b>"[..]));
+        }
+    }
+}
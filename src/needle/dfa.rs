@@ -0,0 +1,236 @@
+use super::{Needle, PartialMatch};
+use alloc::vec::Vec;
+use core::ops::Range;
+use regex_automata::dfa::dense::{BuildError, DFA};
+use regex_automata::dfa::{Automaton, StartKind};
+use regex_automata::util::primitives::StateID;
+use regex_automata::util::start;
+use regex_automata::Anchored;
+
+/// A regex needle running a DFA, which only keeps the bytes of matches in progress.
+///
+/// A [`Regex`](regex::bytes::Regex) has no maximum match length, so the readers keep all data
+/// read without a match to search it again. This needle instead runs a DFA from each byte that
+/// may begin a match, and tells the readers to keep only the bytes from the first of those that
+/// is still running. Scanning for a regex that matches lines or tokens thus takes memory for one
+/// match at most, whatever the amount of data skipped.
+///
+/// Matches are leftmost-first, as with the regex crate. A match that could still grow with more
+/// data is only reported once a byte ends it. The DFA is built eagerly, which may take time and
+/// memory for large Unicode classes; `(?-u)` keeps it small for ASCII patterns.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::DfaRegex;
+///
+/// let needle = DfaRegex::new(r"[\w-]+[>#] ").unwrap();
+/// let mut reader = &b"Building configuration...\r\nrouter-1# show"[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader.read_until_needle(needle, &mut before, &mut matched).unwrap();
+/// assert_eq!(before, b"Building configuration...\r\n");
+/// assert_eq!(matched, b"router-1# ");
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone)]
+pub struct DfaRegex {
+    dfa: DFA<Vec<u32>>,
+}
+
+/// A run of the DFA from one start.
+struct Thread {
+    start: usize,
+    state: StateID,
+    end: Option<usize>,
+}
+
+impl DfaRegex {
+    /// Creates a needle matching the regex `pattern`.
+    ///
+    /// # Errors
+    /// Fails if `pattern` is not a valid regex, uses a feature a DFA does not support, such as a
+    /// Unicode word boundary, or exceeds the size limits of the DFA.
+    #[allow(clippy::result_large_err)]
+    pub fn new(pattern: &str) -> Result<Self, BuildError> {
+        let dfa = DFA::builder()
+            .configure(DFA::config().start_kind(StartKind::Anchored))
+            .build(pattern)?;
+        Ok(Self { dfa })
+    }
+
+    /// Returns the DFA the regex is searched with.
+    pub fn dfa(&self) -> &DFA<Vec<u32>> {
+        &self.dfa
+    }
+
+    /// Runs the DFA over `haystack`, which is the end of the text if `at_end`.
+    fn search(&self, haystack: &[u8], at_end: bool) -> PartialMatch {
+        // ordered by start; only the last one may have matched, as later starts cannot win
+        let mut threads: Vec<Thread> = Vec::new();
+        let mut states = Vec::new();
+        for (i, &b) in haystack.iter().enumerate() {
+            self.begin(&mut threads, haystack, i);
+            states.clear();
+            threads.retain_mut(|thread| {
+                thread.state = self.dfa.next_state(thread.state, b);
+                // matches are delayed by one byte
+                if self.dfa.is_match_state(thread.state) {
+                    thread.end = Some(i);
+                }
+                if self.dfa.is_dead_state(thread.state) {
+                    return thread.end.is_some();
+                }
+                if thread.end.is_some() {
+                    return true;
+                }
+                // a later start in the same state can only match where this one does
+                let duplicate = states.contains(&thread.state);
+                states.push(thread.state);
+                !duplicate
+            });
+            if let Some(matched) = threads.iter().position(|thread| thread.end.is_some()) {
+                threads.truncate(matched + 1);
+            }
+            if let Some(&Thread {
+                start,
+                state,
+                end: Some(end),
+            }) = threads.first()
+            {
+                if self.dfa.is_dead_state(state) {
+                    return PartialMatch::Full(start..end);
+                }
+            }
+        }
+
+        if !at_end {
+            return match threads.first() {
+                Some(first) => match self.final_end(first, haystack.len()) {
+                    Some(end) => PartialMatch::Full(first.start..end),
+                    None => PartialMatch::Prefix(first.start),
+                },
+                None => PartialMatch::None,
+            };
+        }
+        self.begin(&mut threads, haystack, haystack.len());
+        threads
+            .iter()
+            .find_map(|thread| {
+                let eoi = self.dfa.next_eoi_state(thread.state);
+                if self.dfa.is_match_state(eoi) {
+                    Some(thread.start..haystack.len())
+                } else {
+                    thread.end.map(|end| thread.start..end)
+                }
+            })
+            .map_or(PartialMatch::None, PartialMatch::Full)
+    }
+
+    /// Returns the end of the match of `thread`, if no byte after the end of the haystack, at
+    /// `len`, can change it.
+    fn final_end(&self, thread: &Thread, len: usize) -> Option<usize> {
+        if self.dies(thread.state) {
+            return thread.end;
+        }
+        // matches are delayed, so one ending at `len` is only seen after any next byte
+        let mut next = (0..=u8::MAX).map(|b| self.dfa.next_state(thread.state, b));
+        next.all(|state| self.dfa.is_match_state(state) && self.dies(state))
+            .then_some(len)
+    }
+
+    /// Returns `true` if every byte leads from `state` to the dead state.
+    fn dies(&self, state: StateID) -> bool {
+        (0..=u8::MAX).all(|b| self.dfa.is_dead_state(self.dfa.next_state(state, b)))
+    }
+
+    /// Starts a run at `at`, unless an earlier one has already matched.
+    fn begin(&self, threads: &mut Vec<Thread>, haystack: &[u8], at: usize) {
+        if threads.last().is_some_and(|thread| thread.end.is_some()) {
+            return;
+        }
+        let config = start::Config::new()
+            .anchored(Anchored::Yes)
+            .look_behind(at.checked_sub(1).map(|i| haystack[i]));
+        // no quit bytes are configured, so this never fails
+        if let Ok(state) = self.dfa.start_state(&config) {
+            threads.push(Thread {
+                start: at,
+                state,
+                end: None,
+            });
+        }
+    }
+}
+
+impl Needle for DfaRegex {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        match self.search(haystack, true) {
+            PartialMatch::Full(range) => Some(range),
+            _ => None,
+        }
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        self.search(haystack, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_findin() {
+        let needle = DfaRegex::new(r"\d+").unwrap();
+        assert_eq!(needle.findin(b"ab 123 45"), Some(3..6));
+        assert_eq!(needle.findin(b"ab 123"), Some(3..6));
+        assert_eq!(needle.findin(b"ab"), None);
+        // leftmost-first, like the regex crate
+        let needle = DfaRegex::new(r"ab|abcd|bcdef").unwrap();
+        assert_eq!(needle.findin(b"xabcdef"), Some(1..3));
+        let needle = DfaRegex::new(r"a+?").unwrap();
+        assert_eq!(needle.findin(b"xaaa"), Some(1..2));
+        let needle = DfaRegex::new(r"(?m)^\$ $").unwrap();
+        assert_eq!(needle.findin(b"a$ \n$ "), Some(4..6));
+        let needle = DfaRegex::new(r"(?-u:\b)foo(?-u:\b)").unwrap();
+        assert_eq!(needle.findin(b"food foo"), Some(5..8));
+        assert!(DfaRegex::new(r"(").is_err());
+        assert!(DfaRegex::new(r"\bfoo").is_err());
+    }
+
+    #[test]
+    fn test_findin_partial() {
+        let needle = DfaRegex::new(r"\d+").unwrap();
+        assert_eq!(needle.findin_partial(b"ab 123"), PartialMatch::Prefix(3));
+        assert_eq!(needle.findin_partial(b"ab 123 "), PartialMatch::Full(3..6));
+        assert_eq!(needle.findin_partial(b"ab c"), PartialMatch::None);
+        // only the start of a match in progress is kept
+        let needle = DfaRegex::new(r"<[a-z]*>").unwrap();
+        assert_eq!(needle.findin_partial(b"<a <b>"), PartialMatch::Full(3..6));
+        assert_eq!(needle.findin_partial(b"<a <bc"), PartialMatch::Prefix(3));
+        assert_eq!(needle.findin_partial(b"xyz <"), PartialMatch::Prefix(4));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_in_chunks() {
+        use crate::io::UntilNeedleRead;
+        use crate::testing::ChunkedReader;
+
+        let data = b"login: admin\r\nLast login: today\r\nadmin@host:~$ ls";
+        let needle = DfaRegex::new(r"\w+@[\w-]+:\S*[$#] ").unwrap();
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(needle.clone(), &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(before, b"login: admin\r\nLast login: today\r\n");
+            assert_eq!(matched, b"admin@host:~$ ");
+        }
+    }
+}
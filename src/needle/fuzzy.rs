@@ -0,0 +1,181 @@
+use super::{Needle, PartialMatch};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// A needle matching text within a number of edits of a pattern.
+///
+/// Serial consoles and noisy captures sometimes corrupt a character of an expected prompt. This
+/// needle finds the first substring within `max_edits` insertions, deletions or substitutions of
+/// the pattern (the Levenshtein distance). Among the substrings ending there, the closest one is
+/// the match, and the shortest of those if several are as close. A match that could get closer
+/// with the next byte read is only reported once that byte is known.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::Fuzzy;
+///
+/// let mut reader = &b"Welcome\r\nlogn: "[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader
+///     .read_until_needle(Fuzzy::new("login:", 1), &mut before, &mut matched)
+///     .unwrap();
+/// assert_eq!(before, b"Welcome\r\n");
+/// assert_eq!(matched, b"logn:");
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone)]
+pub struct Fuzzy<P> {
+    pattern: P,
+    max_edits: usize,
+}
+
+impl<P: AsRef<[u8]>> Fuzzy<P> {
+    /// Creates a needle matching text within `max_edits` edits of `pattern`.
+    ///
+    /// # Panics
+    /// Panics if `max_edits` is not less than the length of `pattern`, which would match
+    /// empty text.
+    pub fn new(pattern: P, max_edits: usize) -> Self {
+        assert!(
+            max_edits < pattern.as_ref().len(),
+            "max_edits must be less than the length of the pattern"
+        );
+        Self { pattern, max_edits }
+    }
+
+    /// Returns the pattern.
+    pub fn pattern(&self) -> &[u8] {
+        self.pattern.as_ref()
+    }
+
+    /// Returns the maximum number of edits of a match.
+    pub fn max_edits(&self) -> usize {
+        self.max_edits
+    }
+
+    /// Finds the first match, and whether the next byte may bring a closer one.
+    fn search(&self, haystack: &[u8]) -> Option<(Range<usize>, bool)> {
+        let pattern = self.pattern.as_ref();
+        // the distances of the prefixes of the pattern to the closest text ending at `end`
+        let mut column: Vec<usize> = (0..=pattern.len()).collect();
+        let mut best = None;
+        for (end, &b) in haystack.iter().enumerate() {
+            let mut diagonal = column[0];
+            for i in 1..=pattern.len() {
+                let substituted = diagonal + usize::from(pattern[i - 1] != b);
+                diagonal = column[i];
+                column[i] = substituted.min(column[i] + 1).min(column[i - 1] + 1);
+            }
+            let distance = column[pattern.len()];
+            match best {
+                // the match got closer with this byte
+                Some((_, best_distance)) if distance < best_distance => {
+                    best = Some((end + 1, distance))
+                }
+                Some(_) => break,
+                None if distance <= self.max_edits => best = Some((end + 1, distance)),
+                None => {}
+            }
+        }
+
+        let (end, distance) = best?;
+        let start = self.closest_start(&haystack[..end]);
+        Some((start..end, end == haystack.len() && distance > 0))
+    }
+
+    /// Returns the start of the closest text to the pattern ending at the end of `text`.
+    fn closest_start(&self, text: &[u8]) -> usize {
+        let pattern = self.pattern.as_ref();
+        let longest = text.len().min(pattern.len() + self.max_edits);
+        // the distances of the suffixes of the pattern to the suffixes of the text
+        let mut row: Vec<usize> = (0..=longest).collect();
+        for (i, &p) in pattern.iter().rev().enumerate() {
+            let mut diagonal = row[0];
+            row[0] = i + 1;
+            for len in 1..=longest {
+                let substituted = diagonal + usize::from(text[text.len() - len] != p);
+                diagonal = row[len];
+                row[len] = substituted.min(row[len] + 1).min(row[len - 1] + 1);
+            }
+        }
+        let len = (0..=longest).min_by_key(|&len| row[len]).unwrap_or(0);
+        text.len() - len
+    }
+}
+
+impl<P: AsRef<[u8]>> Needle for Fuzzy<P> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        self.search(haystack).map(|(range, _)| range)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.pattern.as_ref().len() + self.max_edits)
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        match self.search(haystack) {
+            Some((range, false)) => PartialMatch::Full(range),
+            Some((range, true)) => PartialMatch::Prefix(range.start),
+            None => {
+                // the last `max_len - 1` bytes may begin a match
+                let pattern_len = self.pattern.as_ref().len();
+                let start = haystack
+                    .len()
+                    .saturating_sub(pattern_len + self.max_edits - 1);
+                if start < haystack.len() {
+                    PartialMatch::Prefix(start)
+                } else {
+                    PartialMatch::None
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_findin() {
+        let needle = Fuzzy::new("login:", 1);
+        assert_eq!(needle.findin(b"xlogin: "), Some(1..7));
+        assert_eq!(needle.findin(b"xlagin: "), Some(1..7));
+        assert_eq!(needle.findin(b"xlogn: "), Some(1..6));
+        assert_eq!(needle.findin(b"xloggin: "), Some(1..8));
+        assert_eq!(needle.findin(b"xlgn: "), None);
+        assert_eq!(Fuzzy::new("login:", 2).findin(b"xlgn: "), Some(1..5));
+    }
+
+    #[test]
+    fn test_findin_partial() {
+        let needle = Fuzzy::new("login:", 1);
+        assert_eq!(needle.findin_partial(b"xlogin"), PartialMatch::Prefix(1));
+        assert_eq!(needle.findin_partial(b"xlogin:"), PartialMatch::Full(1..7));
+        assert_eq!(needle.findin_partial(b"abcdefgh"), PartialMatch::Prefix(2));
+        assert_eq!(needle.findin_partial(b"ab"), PartialMatch::Prefix(0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_in_chunks() {
+        use crate::io::UntilNeedleRead;
+        use crate::testing::ChunkedReader;
+
+        let data = b"noise\r\nPassw0rd: rest";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(Fuzzy::new("Password:", 1), &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(before, b"noise\r\n");
+            assert_eq!(matched, b"Passw0rd:");
+        }
+    }
+}
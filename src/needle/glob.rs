@@ -0,0 +1,176 @@
+use super::{Needle, PartialMatch};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// A needle matching a glob pattern, as the patterns of TCL expect.
+///
+/// `?` matches any byte and `*` any run of bytes, as short as possible; `\` makes the next byte
+/// literal. Like in expect, a pattern is usually written `*assword:*` to match anywhere in the
+/// output: a `*` at either end of the pattern only says that, and is not part of the match.
+///
+/// A `*` inside the pattern makes the length of a match unbounded, so all data from the first
+/// byte that may begin a match is kept until it is found.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::Glob;
+///
+/// let mut reader = &b"Last login: Mon\r\nuser@host's password: "[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader
+///     .read_until_needle(Glob::new("*@*'s ?assword:*"), &mut before, &mut matched)
+///     .unwrap();
+/// assert_eq!(before, b"Last login: Mon\r\nuser");
+/// assert_eq!(matched, b"@host's password:");
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Byte(u8),
+    Any,
+    Star,
+}
+
+/// The outcome of matching the pattern from one position.
+enum Step {
+    Full(usize),
+    /// The data ran out before the pattern could match or fail.
+    More,
+    Fail,
+}
+
+impl Glob {
+    /// Creates a needle matching the glob `pattern`.
+    ///
+    /// # Panics
+    /// Panics if `pattern` has nothing to match besides `*`.
+    pub fn new(pattern: impl AsRef<[u8]>) -> Self {
+        let mut tokens = Vec::new();
+        let mut bytes = pattern.as_ref().iter();
+        while let Some(&b) = bytes.next() {
+            tokens.push(match b {
+                b'*' => Token::Star,
+                b'?' => Token::Any,
+                b'\\' => Token::Byte(*bytes.next().unwrap_or(&b'\\')),
+                _ => Token::Byte(b),
+            });
+        }
+        let start = tokens.iter().position(|&t| t != Token::Star);
+        let end = tokens.iter().rposition(|&t| t != Token::Star);
+        let (Some(start), Some(end)) = (start, end) else {
+            panic!("the pattern must not be empty");
+        };
+        tokens.truncate(end + 1);
+        tokens.drain(..start);
+        Self { tokens }
+    }
+}
+
+fn match_from(tokens: &[Token], haystack: &[u8], pos: usize) -> Step {
+    let Some((&token, rest)) = tokens.split_first() else {
+        return Step::Full(pos);
+    };
+    match token {
+        Token::Star => {
+            // as short as possible, and always more once the data runs out
+            for from in pos..=haystack.len() {
+                if let Step::Full(end) = match_from(rest, haystack, from) {
+                    return Step::Full(end);
+                }
+            }
+            Step::More
+        }
+        _ if pos == haystack.len() => Step::More,
+        Token::Byte(b) if haystack[pos] != b => Step::Fail,
+        _ => match_from(rest, haystack, pos + 1),
+    }
+}
+
+impl Needle for Glob {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        (0..haystack.len()).find_map(|start| match match_from(&self.tokens, haystack, start) {
+            Step::Full(end) => Some(start..end),
+            _ => None,
+        })
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        if self.tokens.contains(&Token::Star) {
+            return None;
+        }
+        Some(self.tokens.len())
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        for start in 0..haystack.len() {
+            match match_from(&self.tokens, haystack, start) {
+                Step::Full(end) => return PartialMatch::Full(start..end),
+                Step::More => return PartialMatch::Prefix(start),
+                Step::Fail => {}
+            }
+        }
+        PartialMatch::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_findin() {
+        assert_eq!(Glob::new("*assword:*").findin(b"Password: "), Some(1..9));
+        assert_eq!(Glob::new("a?c").findin(b"xabcabc"), Some(1..4));
+        assert_eq!(Glob::new("<*>").findin(b"a<b>c>"), Some(1..4));
+        assert_eq!(Glob::new("\\*\\?").findin(b"a?*?"), Some(2..4));
+        assert_eq!(Glob::new("a*b").findin(b"aaa"), None);
+        assert_eq!(Glob::new("a?c").max_len(), Some(3));
+        assert_eq!(Glob::new("**a*b**").max_len(), None);
+    }
+
+    #[test]
+    fn test_findin_partial() {
+        let glob = Glob::new("[*]$ ");
+        assert_eq!(glob.findin_partial(b"x[a"), PartialMatch::Prefix(1));
+        assert_eq!(glob.findin_partial(b"x[a]$ "), PartialMatch::Full(1..6));
+        assert_eq!(glob.findin_partial(b"xyz"), PartialMatch::None);
+        assert_eq!(
+            Glob::new("ab").findin_partial(b"xa"),
+            PartialMatch::Prefix(1)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_empty() {
+        Glob::new("**");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_in_chunks() {
+        use crate::io::UntilNeedleRead;
+        use crate::testing::ChunkedReader;
+
+        let data = b"boot\r\n[admin@router-1] > rest";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(Glob::new("[*@*] > "), &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(before, b"boot\r\n");
+            assert_eq!(matched, b"[admin@router-1] > ");
+        }
+    }
+}
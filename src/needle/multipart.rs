@@ -0,0 +1,204 @@
+use super::Needle;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// The longest transport padding recognized after a boundary.
+const MAX_PADDING: usize = 32;
+
+/// A needle matching the boundary delimiters of a MIME multipart body (RFC 2046).
+///
+/// A delimiter is CRLF, `--` and the boundary. The CRLF belongs to the delimiter, not to the
+/// part before it, so it is part of the match. An intermediate delimiter is matched up to the
+/// end of its line, including any transport padding and the final CRLF, so the next read starts
+/// at the headers of the next part. The close delimiter, which ends with `--`, is matched up to
+/// those two dashes; [`MultipartBoundary::is_close`] tells the two apart.
+///
+/// Boundary text that does not follow a CRLF is part of the data and does not match, except at
+/// the very start of a search created with [`first`](Self::first).
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::MultipartBoundary;
+///
+/// let mut body = &b"preamble\r\n--XyZ\r\nfirst\r\n--XyZ--\r\n"[..];
+/// let boundary = MultipartBoundary::new("XyZ");
+/// let (mut preamble, mut matched) = (Vec::new(), Vec::new());
+/// body.read_until_needle(boundary.clone(), &mut preamble, &mut matched).unwrap();
+/// assert!(!MultipartBoundary::is_close(&matched));
+///
+/// let (mut part, mut matched) = (Vec::new(), Vec::new());
+/// body.read_until_needle(boundary, &mut part, &mut matched).unwrap();
+/// assert_eq!(part, b"first");
+/// assert!(MultipartBoundary::is_close(&matched));
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipartBoundary {
+    /// `--` followed by the boundary.
+    dash_boundary: Vec<u8>,
+    at_start: bool,
+}
+
+impl MultipartBoundary {
+    /// Creates a needle matching the delimiters of `boundary`, which must not be empty.
+    pub fn new(boundary: impl AsRef<[u8]>) -> Self {
+        let boundary = boundary.as_ref();
+        assert!(!boundary.is_empty(), "the boundary must not be empty");
+        let mut dash_boundary = Vec::with_capacity(boundary.len() + 2);
+        dash_boundary.extend_from_slice(b"--");
+        dash_boundary.extend_from_slice(boundary);
+        Self {
+            dash_boundary,
+            at_start: false,
+        }
+    }
+
+    /// Creates a needle for the first delimiter of a body, which may have no preamble.
+    ///
+    /// A delimiter at offset 0 of the haystack is matched without the CRLF as well, so a search
+    /// starting at the beginning of the body finds a first boundary with no preamble before it.
+    /// Readers search each chunk together with data kept from earlier ones, so to keep offset 0
+    /// at the start of the search, this needle has no maximum length and everything read is
+    /// searched again with each chunk. Use it for the preamble only, and [`new`](Self::new)
+    /// for the parts.
+    pub fn first(boundary: impl AsRef<[u8]>) -> Self {
+        Self {
+            at_start: true,
+            ..Self::new(boundary)
+        }
+    }
+
+    /// Returns `true` if `matched`, a match of this needle, is the close delimiter ending the
+    /// body.
+    pub fn is_close(matched: &[u8]) -> bool {
+        matched.ends_with(b"--")
+    }
+}
+
+impl Needle for MultipartBoundary {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        let dash_boundary = &self.dash_boundary[..];
+        let mut from = 0;
+        while let Some(found) = dash_boundary.findin(&haystack[from..]) {
+            let start = from + found.start;
+            from = start + 1;
+            let begin = if start >= 2 && &haystack[start - 2..start] == b"\r\n" {
+                start - 2
+            } else if start == 0 && self.at_start {
+                start
+            } else {
+                continue;
+            };
+
+            let rest = &haystack[start + dash_boundary.len()..];
+            match rest {
+                [b'-', b'-', ..] => return Some(begin..start + dash_boundary.len() + 2),
+                [] | [b'-'] => return None,
+                [b'-', ..] => continue,
+                _ => {}
+            }
+            let padding = rest
+                .iter()
+                .take(MAX_PADDING + 1)
+                .take_while(|&&b| b == b' ' || b == b'\t')
+                .count();
+            match &rest[padding..] {
+                _ if padding > MAX_PADDING => continue,
+                [b'\r', b'\n', ..] => {
+                    return Some(begin..start + dash_boundary.len() + padding + 2);
+                }
+                // the line is not complete yet
+                [] | [b'\r'] => return None,
+                _ => continue,
+            }
+        }
+        None
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        if self.at_start {
+            return None;
+        }
+        Some(2 + self.dash_boundary.len() + MAX_PADDING + 2)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::io::UntilNeedleRead;
+    use crate::testing::ChunkedReader;
+
+    #[test]
+    fn test_findin() {
+        let boundary = MultipartBoundary::new("b0");
+        assert_eq!(boundary.findin(b"ab\r\n--b0\r\ncd"), Some(2..10));
+        assert_eq!(boundary.findin(b"ab\r\n--b0 \t\r\ncd"), Some(2..12));
+        assert_eq!(boundary.findin(b"ab\r\n--b0--"), Some(2..10));
+        assert!(MultipartBoundary::is_close(b"\r\n--b0--"));
+        assert!(!MultipartBoundary::is_close(b"\r\n--b0\r\n"));
+
+        // boundary text without the CRLF, or followed by more than padding, is data
+        assert_eq!(boundary.findin(b"--b0\r\n"), None);
+        assert_eq!(boundary.findin(b"ab--b0\r\n"), None);
+        assert_eq!(boundary.findin(b"ab\n--b0\r\n"), None);
+        assert_eq!(boundary.findin(b"\r\n--b0x\r\n--b0\r\n"), Some(7..15));
+        assert_eq!(boundary.findin(b"\r\n--b0-x\r\n--b0\r\n"), Some(8..16));
+
+        // an incomplete delimiter line is not a match yet
+        assert_eq!(boundary.findin(b"ab\r\n--b0"), None);
+        assert_eq!(boundary.findin(b"ab\r\n--b0-"), None);
+        assert_eq!(boundary.findin(b"ab\r\n--b0  \r"), None);
+
+        let first = MultipartBoundary::first("b0");
+        assert_eq!(first.findin(b"--b0\r\nab"), Some(0..6));
+        assert_eq!(first.findin(b"ab--b0\r\n\r\n--b0\r\n"), Some(8..16));
+        assert_eq!(first.max_len(), None);
+    }
+
+    /// Splits `body` into its parts, reading it in chunks of `size` bytes.
+    fn parts(body: &[u8], size: usize) -> Vec<Vec<u8>> {
+        let mut reader = ChunkedReader::new(body, [size]);
+        let (mut preamble, mut matched) = (Vec::new(), Vec::new());
+        reader
+            .read_until_needle(MultipartBoundary::first("XyZ"), &mut preamble, &mut matched)
+            .unwrap();
+        assert!(!matched.is_empty());
+
+        let boundary = MultipartBoundary::new("XyZ");
+        let mut parts = Vec::new();
+        loop {
+            let (mut part, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(boundary.clone(), &mut part, &mut matched)
+                .unwrap();
+            assert!(!matched.is_empty(), "no close delimiter");
+            parts.push(part);
+            if MultipartBoundary::is_close(&matched) {
+                return parts;
+            }
+        }
+    }
+
+    #[test]
+    fn test_parts() {
+        let body = b"--XyZ\r\nContent-Type: text/plain\r\n\r\nline--XyZ\r\n\r\n--XyZ  \r\n\
+            \r\n--XyZ--\r\nepilogue";
+        for size in 1..body.len() {
+            assert_eq!(
+                parts(body, size),
+                [&b"Content-Type: text/plain\r\n\r\nline--XyZ\r\n"[..], b""]
+            );
+        }
+
+        let body = b"preamble --XyZ\r\n\r\n--XyZ\r\n\r\n\r\n--XyZ--";
+        for size in 1..body.len() {
+            assert_eq!(parts(body, size), [&b"\r\n"[..]]);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use super::{Needle, PartialMatch};
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+use core::str::FromStr;
+
+/// A literal needle parsed from text with C-style escapes.
+///
+/// Delimiters written in configuration files or command-line arguments cannot hold control
+/// bytes as is. Parsing them as a `Pattern` turns `\xNN`, `\n`, `\r`, `\t`, `\0` and `\\`
+/// into the bytes they stand for; any other character is taken as its UTF-8 encoding.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() -> std::io::Result<()> {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::Pattern;
+///
+/// let delimiter: Pattern = r"\x00\x01END\n".parse().unwrap();
+/// assert_eq!(delimiter.as_bytes(), b"\x00\x01END\n");
+///
+/// let mut reader = &b"frame\x00\x01END\nnext"[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader.read_until_needle(delimiter, &mut before, &mut matched)?;
+/// assert_eq!(before, b"frame");
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pattern {
+    bytes: Vec<u8>,
+}
+
+impl Pattern {
+    /// Returns the bytes the pattern matches.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Unwraps this `Pattern`, returning the bytes it matches.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// The error returned when parsing a [`Pattern`] fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParsePatternError {
+    /// The pattern is empty, which would match everywhere.
+    Empty,
+    /// The escape sequence starting at this byte offset of the text is not valid.
+    InvalidEscape {
+        /// The offset of the backslash.
+        offset: usize,
+    },
+}
+
+impl fmt::Display for ParsePatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParsePatternError::Empty => write!(f, "the pattern is empty"),
+            ParsePatternError::InvalidEscape { offset } => {
+                write!(f, "invalid escape sequence at offset {}", offset)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParsePatternError {}
+
+impl FromStr for Pattern {
+    type Err = ParsePatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = s.as_bytes();
+        let mut bytes = Vec::with_capacity(text.len());
+        let mut i = 0;
+        while i < text.len() {
+            if text[i] != b'\\' {
+                bytes.push(text[i]);
+                i += 1;
+                continue;
+            }
+            let invalid = ParsePatternError::InvalidEscape { offset: i };
+            let (byte, len) = match text.get(i + 1).ok_or(invalid)? {
+                b'n' => (b'\n', 2),
+                b'r' => (b'\r', 2),
+                b't' => (b'\t', 2),
+                b'0' => (b'\0', 2),
+                b'\\' => (b'\\', 2),
+                b'x' => {
+                    let hex = s.get(i + 2..i + 4).ok_or(invalid)?;
+                    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                        return Err(invalid);
+                    }
+                    (u8::from_str_radix(hex, 16).map_err(|_| invalid)?, 4)
+                }
+                _ => return Err(invalid),
+            };
+            bytes.push(byte);
+            i += len;
+        }
+        if bytes.is_empty() {
+            return Err(ParsePatternError::Empty);
+        }
+        Ok(Self { bytes })
+    }
+}
+
+impl Needle for Pattern {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        self.bytes.findin(haystack)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.bytes.len())
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        self.bytes.findin_partial(haystack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let pattern: Pattern = r"a\x7Fb\r\n\t\0\\é".parse().unwrap();
+        assert_eq!(pattern.as_bytes(), "a\x7fb\r\n\t\0\\é".as_bytes());
+        assert_eq!(pattern.max_len(), Some(10));
+
+        assert_eq!("".parse::<Pattern>(), Err(ParsePatternError::Empty));
+        for (text, offset) in [
+            (r"ab\", 2),
+            (r"\q", 0),
+            (r"a\x4", 1),
+            (r"\xg0", 0),
+            (r"\x+1", 0),
+        ] {
+            assert_eq!(
+                text.parse::<Pattern>(),
+                Err(ParsePatternError::InvalidEscape { offset })
+            );
+        }
+    }
+
+    #[test]
+    fn test_findin() {
+        let pattern: Pattern = r"\r\n".parse().unwrap();
+        assert_eq!(pattern.findin(b"ab\r\ncd"), Some(2..4));
+        assert_eq!(pattern.findin_partial(b"ab\r"), PartialMatch::Prefix(2));
+        assert_eq!(pattern.into_bytes(), b"\r\n");
+    }
+}
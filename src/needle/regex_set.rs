@@ -0,0 +1,119 @@
+use super::Needle;
+use alloc::vec::Vec;
+use core::ops::Range;
+use regex::bytes::{Regex, RegexSet};
+
+/// A needle matching any regex of a [`RegexSet`], telling which one matched.
+///
+/// A `RegexSet` only tells which of its regexes match, so each of those is then searched on its
+/// own to find the earliest match. Among regexes matching at the same position, the first one
+/// given wins. [`pattern`](Self::pattern) tells which regex the matched bytes are from, for
+/// example to tell a device prompt from an error banner.
+///
+/// Like a single regex, the set has no maximum match length; wrap it in
+/// [`WithMaxLen`](super::WithMaxLen) to bound how much is searched again with each read.
+///
+/// # Example
+/// ```
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::RegexSetNeedle;
+///
+/// let needle = RegexSetNeedle::new([r"[\w-]+[>#] $", r"% Invalid .*\r\n"]).unwrap();
+/// let mut reader = &b"show vlan\r\n% Invalid input detected\r\nswitch-1# "[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader.read_until_needle(needle.clone(), &mut before, &mut matched).unwrap();
+/// assert_eq!(needle.pattern(&matched), Some(1));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RegexSetNeedle {
+    set: RegexSet,
+    regexes: Vec<Regex>,
+}
+
+impl RegexSetNeedle {
+    /// Creates a needle matching any of the regexes `patterns`.
+    pub fn new<I, S>(patterns: I) -> Result<Self, regex::Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let set = RegexSet::new(patterns)?;
+        let regexes = set
+            .patterns()
+            .iter()
+            .map(|pattern| Regex::new(pattern))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { set, regexes })
+    }
+
+    /// Returns the index of the first regex matching all of `matched`, a match of this needle.
+    pub fn pattern(&self, matched: &[u8]) -> Option<usize> {
+        self.regexes.iter().position(|regex| {
+            regex
+                .find(matched)
+                .is_some_and(|m| m.range() == (0..matched.len()))
+        })
+    }
+
+    /// Returns the set of regexes.
+    pub fn set(&self) -> &RegexSet {
+        &self.set
+    }
+}
+
+impl Needle for RegexSetNeedle {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        self.set
+            .matches(haystack)
+            .iter()
+            .filter_map(|index| self.regexes[index].find(haystack))
+            .min_by_key(|m| m.start())
+            .map(|m| m.range())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::UntilNeedleRead;
+    use crate::testing::ChunkedReader;
+
+    #[test]
+    fn test_findin() {
+        let needle = RegexSetNeedle::new([r"[\w-]+[>#] ", r"% \w+"]).unwrap();
+        assert_eq!(needle.findin(b"ok\r\nsw-1# "), Some(4..10));
+        assert_eq!(needle.findin(b"% Error\r\nsw-1# "), Some(0..7));
+        assert_eq!(needle.findin(b"nothing"), None);
+        assert_eq!(needle.pattern(b"sw-1# "), Some(0));
+        assert_eq!(needle.pattern(b"% Error"), Some(1));
+        assert_eq!(needle.pattern(b"% Error "), None);
+        assert_eq!(needle.max_len(), None);
+
+        // the first regex wins at the same position
+        let needle = RegexSetNeedle::new([r"ab", r"a"]).unwrap();
+        assert_eq!(needle.findin(b"xab"), Some(1..3));
+        assert!(RegexSetNeedle::new([r"("]).is_err());
+    }
+
+    #[test]
+    fn test_read_until_regex_set() {
+        let needle = RegexSetNeedle::new([r"sw-\d# ", r"% [^\r]*\r\n"]).unwrap();
+        let data = b"conf t\r\n% Invalid input\r\nsw-1# ";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(data, [size]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(needle.clone(), &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(needle.pattern(&matched), Some(1));
+            assert_eq!(before, b"conf t\r\n");
+
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(needle.clone(), &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(needle.pattern(&matched), Some(0));
+            assert_eq!(before, b"");
+        }
+    }
+}
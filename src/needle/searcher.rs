@@ -0,0 +1,602 @@
+use super::{Needle, PartialMatch};
+use crate::trace;
+use crate::UntilNeedleStatus;
+use alloc::vec::Vec;
+use core::mem;
+
+/// A search for a needle over data delivered in chunks, independent of any I/O.
+///
+/// The readers of this crate are built on it, and it can drive a search over any other
+/// transport: push each chunk as it arrives and act on the returned [`SearchStep`]. Every byte
+/// pushed is reported once as data before the needle, except for the needle itself.
+///
+/// To find a needle straddling two chunks, the searcher keeps a copy of the trailing bytes that
+/// may begin a match, as told by [`Needle::findin_partial`] (by default the last
+/// [`Needle::max_len`] - 1 bytes, or everything since the start for needles without a maximum
+/// length). These bytes have already been reported as data before the needle when the match is
+/// found, so [`SearchStep::Found`] tells how many of them to take back.
+///
+/// # Example
+/// ```
+/// use until_needle::needle::{SearchStep, Searcher};
+///
+/// let mut searcher = Searcher::new(b"\r\n");
+/// let mut before = Vec::new();
+/// let mut matched = Vec::new();
+/// for chunk in [&b"hello\r"[..], b"\nworld"] {
+///     let step = searcher.push(chunk);
+///     step.apply(chunk, &mut before, &mut matched);
+///     if step.is_done() {
+///         // the rest of `chunk` after `step.consumed()` belongs to the next search
+///         assert_eq!(&chunk[step.consumed()..], b"world");
+///         break;
+///     }
+/// }
+/// assert_eq!((&before[..], &matched[..]), (&b"hello"[..], &b"\r\n"[..]));
+/// assert_eq!(searcher.finish().bytes_read(), 7);
+/// ```
+#[doc(alias = "NeedleScanner")]
+#[derive(Debug, Clone)]
+pub struct Searcher<N> {
+    needle: N,
+    scanner: Scanner,
+}
+
+impl<N: Needle> Searcher<N> {
+    /// Creates a search for `needle` without a limit, consuming the needle.
+    pub fn new(needle: N) -> Self {
+        Self {
+            needle,
+            scanner: Scanner::new(),
+        }
+    }
+
+    /// Sets the maximum number of bytes the search consumes.
+    ///
+    /// See [`UntilNeedle::limit`](crate::UntilNeedle::limit).
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.scanner.limit = limit;
+        self
+    }
+
+    /// Sets whether the search consumes the needle.
+    ///
+    /// See [`UntilNeedle::consume_needle`](crate::UntilNeedle::consume_needle). If the needle
+    /// begins in an earlier chunk, its first [`retract`](SearchStep::Found::retract) bytes were
+    /// consumed with that chunk. They are not counted in the bytes read, and come before the rest
+    /// of the chunk in the data of the next search.
+    pub fn consume_needle(mut self, consume: bool) -> Self {
+        self.scanner.consume_needle = consume;
+        self
+    }
+
+    /// Returns the needle searched for.
+    pub fn needle(&self) -> &N {
+        &self.needle
+    }
+
+    /// Returns the number of bytes consumed so far by the search in progress.
+    pub fn bytes_read(&self) -> usize {
+        self.scanner.bytes_read()
+    }
+
+    /// Returns the trailing bytes reported as data before the needle that may still turn out to
+    /// begin it.
+    pub fn pending(&self) -> &[u8] {
+        self.scanner.pending()
+    }
+
+    /// Searches the next chunk of data.
+    ///
+    /// Only the bytes up to [`SearchStep::consumed`] are part of this search; the rest of the
+    /// chunk is left for the next one. Once a step [ends the search](SearchStep::is_done),
+    /// call [`finish`](Self::finish) before pushing more data.
+    pub fn push<'a>(&'a mut self, chunk: &'a [u8]) -> SearchStep<'a> {
+        self.scanner.push(&self.needle, chunk)
+    }
+
+    /// Ends the search, returning its outcome, and resets the searcher for the next search.
+    ///
+    /// If no step has ended the search, this reports EOF, or that the limit was reached if
+    /// exactly `limit` bytes were consumed.
+    pub fn finish(&mut self) -> UntilNeedleStatus {
+        let status = self.scanner.finish();
+        trace::outcome(&status);
+        status
+    }
+}
+
+/// The outcome of pushing a chunk to a [`Searcher`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchStep<'a> {
+    /// The needle was not found. The whole chunk is consumed as data before the needle.
+    NotFound {
+        /// The number of bytes of the chunk consumed, which is its length.
+        consumed: usize,
+    },
+    /// The needle was found, ending the search.
+    Found {
+        /// The number of bytes at the start of the chunk that come before the needle.
+        before: usize,
+        /// The number of bytes at the end of the data reported before this chunk that are the
+        /// beginning of the needle, not data before it.
+        retract: usize,
+        /// The needle.
+        matched: &'a [u8],
+        /// The number of bytes of the chunk consumed: up to the end of the needle, or up to its
+        /// start if the needle is not consumed.
+        consumed: usize,
+    },
+    /// The limit was reached before the needle was found, ending the search. The consumed
+    /// bytes are data before the needle.
+    LimitReached {
+        /// The number of bytes of the chunk consumed.
+        consumed: usize,
+    },
+}
+
+impl SearchStep<'_> {
+    /// Returns the number of bytes of the chunk consumed by the search.
+    pub fn consumed(&self) -> usize {
+        match *self {
+            SearchStep::NotFound { consumed }
+            | SearchStep::Found { consumed, .. }
+            | SearchStep::LimitReached { consumed } => consumed,
+        }
+    }
+
+    /// Returns `true` if this step ended the search.
+    pub fn is_done(&self) -> bool {
+        !matches!(self, SearchStep::NotFound { .. })
+    }
+
+    /// Returns the data before the needle in `chunk`, the chunk this step was returned for.
+    ///
+    /// This is what [`apply`](Self::apply) appends, for callers that handle the data without
+    /// collecting it, after taking back the last [`SearchStep::Found::retract`] bytes of the
+    /// data of the earlier steps.
+    pub fn data<'c>(&self, chunk: &'c [u8]) -> &'c [u8] {
+        match *self {
+            SearchStep::NotFound { consumed } | SearchStep::LimitReached { consumed } => {
+                &chunk[..consumed]
+            }
+            SearchStep::Found { before, .. } => &chunk[..before],
+        }
+    }
+
+    /// Appends the data before the needle in `chunk` to `before` and the needle to `matched`.
+    ///
+    /// `chunk` must be the chunk this step was returned for, and `before` must hold the data
+    /// before the needle from the earlier steps of the search.
+    pub fn apply(&self, chunk: &[u8], before: &mut Vec<u8>, matched: &mut Vec<u8>) {
+        match *self {
+            SearchStep::NotFound { consumed } | SearchStep::LimitReached { consumed } => {
+                before.extend_from_slice(&chunk[..consumed]);
+            }
+            SearchStep::Found {
+                before: len,
+                retract,
+                matched: needle,
+                ..
+            } => {
+                before.truncate(before.len() - retract);
+                before.extend_from_slice(&chunk[..len]);
+                matched.extend_from_slice(needle);
+            }
+        }
+    }
+}
+
+/// The state of a [`Searcher`], without the needle.
+///
+/// The readers that take a needle on each call keep this instead of a `Searcher`.
+#[derive(Debug, Clone)]
+pub(crate) struct Scanner {
+    /// A copy of the trailing bytes reported so far that may begin a match.
+    tail: Vec<u8>,
+    /// The tail joined with the chunk, searched for a match straddling them.
+    seam: Vec<u8>,
+    bytes_read: usize,
+    found: bool,
+    /// The number of bytes at the start of the last needle found that were consumed, although
+    /// the needle is not.
+    held: usize,
+    pub(crate) limit: usize,
+    pub(crate) consume_needle: bool,
+}
+
+impl Scanner {
+    pub(crate) fn new() -> Self {
+        Self {
+            tail: Vec::new(),
+            seam: Vec::new(),
+            bytes_read: 0,
+            found: false,
+            held: 0,
+            limit: usize::MAX,
+            consume_needle: true,
+        }
+    }
+
+    pub(crate) fn bytes_read(&self) -> usize {
+        self.bytes_read
+    }
+
+    /// Returns the number of bytes at the start of the last needle found that were consumed with
+    /// earlier chunks, although the needle is not consumed.
+    #[cfg(feature = "std")]
+    pub(crate) fn held(&self) -> usize {
+        self.held
+    }
+
+    /// Returns a copy of the trailing bytes reported so far that may begin a match.
+    pub(crate) fn pending(&self) -> &[u8] {
+        &self.tail
+    }
+
+    /// Starts the search with the bytes at the end of `before` that may begin a match, so a
+    /// needle begun by data an earlier search reported, without finding it, is still found.
+    ///
+    /// Those bytes are not counted as read by this search.
+    #[cfg(any(feature = "futures", feature = "tokio"))]
+    pub(crate) fn resume<N: Needle + ?Sized>(&mut self, needle: &N, before: &[u8]) {
+        let keep = needle
+            .max_len()
+            .map_or(before.len(), |len| len.saturating_sub(1));
+        let mut start = before.len().saturating_sub(keep);
+        while start < before.len() {
+            match needle.findin_partial(&before[start..]) {
+                // a needle already in `before` is not the one searched for
+                PartialMatch::Full(range) => start += range.start + 1,
+                PartialMatch::Prefix(prefix) => {
+                    start += prefix;
+                    break;
+                }
+                PartialMatch::None => start = before.len(),
+            }
+        }
+        self.tail.clear();
+        self.tail
+            .extend_from_slice(&before[start.min(before.len())..]);
+    }
+
+    /// Returns `true` if the search has consumed as many bytes as it may.
+    pub(crate) fn at_limit(&self) -> bool {
+        self.bytes_read == self.limit
+    }
+
+    pub(crate) fn push<'a, N: Needle + ?Sized>(
+        &'a mut self,
+        needle: &N,
+        chunk: &'a [u8],
+    ) -> SearchStep<'a> {
+        if self.found {
+            // the previous search was not finished
+            self.finish();
+        }
+        if self.at_limit() {
+            return SearchStep::LimitReached { consumed: 0 };
+        }
+        // never look past the limit
+        let chunk = &chunk[..chunk.len().min(self.limit - self.bytes_read)];
+        let tail_len = self.tail.len();
+        trace::chunk(chunk.len(), self.bytes_read + chunk.len());
+
+        // the range of the match in the searched data, which begins with the tail if `joined`
+        let keep = needle.max_len().map(|len| len.saturating_sub(1));
+        let (found, joined) = if tail_len == 0 {
+            (needle.findin_partial(chunk), false)
+        } else if keep.is_some() {
+            self.seam.clear();
+            self.seam.extend_from_slice(&self.tail);
+            self.seam.extend_from_slice(chunk);
+            (needle.findin_partial(&self.seam), true)
+        } else {
+            // everything may be kept, so search it in place
+            self.tail.extend_from_slice(chunk);
+            (needle.findin_partial(&self.tail), true)
+        };
+
+        let found = match found {
+            PartialMatch::Full(range) => Some(range),
+            partial => {
+                // only the suffix that may begin a match is kept
+                let start = match partial {
+                    PartialMatch::Prefix(start) => start,
+                    _ => usize::MAX,
+                };
+                match (joined, keep) {
+                    (true, None) => {
+                        self.tail.drain(..start.min(tail_len + chunk.len()));
+                    }
+                    (_, _) => {
+                        let searched = if joined { &self.seam[..] } else { chunk };
+                        self.tail.clear();
+                        self.tail
+                            .extend_from_slice(&searched[start.min(searched.len())..]);
+                    }
+                }
+                None
+            }
+        };
+
+        let Some(range) = found else {
+            self.bytes_read += chunk.len();
+            if self.at_limit() {
+                return SearchStep::LimitReached {
+                    consumed: chunk.len(),
+                };
+            }
+            return SearchStep::NotFound {
+                consumed: chunk.len(),
+            };
+        };
+
+        // offsets in the chunk, which the needle may begin before
+        let offset = if joined { tail_len } else { 0 };
+        let before = range.start.saturating_sub(offset);
+        let retract = offset.saturating_sub(range.start);
+        let consumed = if self.consume_needle {
+            range.end.saturating_sub(offset)
+        } else {
+            before
+        };
+        let matched_len = range.end - range.start;
+        trace::found(
+            (self.bytes_read + before).saturating_sub(retract),
+            matched_len,
+        );
+        // the beginning of an unconsumed needle was consumed with earlier chunks, unless it was
+        // reported by an earlier search
+        self.held = if self.consume_needle {
+            0
+        } else {
+            retract.min(self.bytes_read)
+        };
+        self.bytes_read += consumed;
+        self.bytes_read -= self.held;
+        self.found = true;
+
+        let matched = match (joined, keep) {
+            (false, _) => &chunk[range],
+            (true, Some(_)) => &self.seam[range],
+            (true, None) => &self.tail[range],
+        };
+        SearchStep::Found {
+            before,
+            retract,
+            matched,
+            consumed,
+        }
+    }
+
+    /// Ends the search, returning its outcome, and resets the state for the next search.
+    pub(crate) fn finish(&mut self) -> UntilNeedleStatus {
+        let bytes_read = mem::replace(&mut self.bytes_read, 0);
+        self.tail.clear();
+        if mem::replace(&mut self.found, false) {
+            UntilNeedleStatus::Found { bytes_read }
+        } else if bytes_read == self.limit {
+            UntilNeedleStatus::LimitReached { bytes_read }
+        } else {
+            UntilNeedleStatus::Eof { bytes_read }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::testing::ChunkedReader;
+    use crate::UntilNeedle;
+    use std::io::Read;
+
+    type Outcome = (UntilNeedleStatus, Vec<u8>, Vec<u8>, Vec<u8>);
+
+    /// A literal needle that does not report its maximum length.
+    #[derive(Clone)]
+    struct Unbounded(&'static [u8]);
+
+    impl Needle for Unbounded {
+        fn findin(&self, haystack: &[u8]) -> Option<std::ops::Range<usize>> {
+            self.0.findin(haystack)
+        }
+    }
+
+    /// A xorshift generator, so the cases are the same on every run.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    fn search_chunks<N: Needle>(searcher: &mut Searcher<N>, chunks: &[Vec<u8>]) -> Outcome {
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        let mut rest = Vec::new();
+        let mut chunks = chunks.iter();
+        for chunk in chunks.by_ref() {
+            let step = searcher.push(chunk);
+            step.apply(chunk, &mut before, &mut matched);
+            if step.is_done() {
+                rest.extend_from_slice(&chunk[step.consumed()..]);
+                break;
+            }
+        }
+        chunks.for_each(|chunk| rest.extend_from_slice(chunk));
+        (searcher.finish(), before, matched, rest)
+    }
+
+    fn read_sync<N: Needle>(builder: &UntilNeedle<N>, chunks: &[Vec<u8>]) -> Outcome {
+        let mut reader = ChunkedReader::from_chunks(chunks);
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        let status = builder
+            .read(&mut reader, &mut before, &mut matched)
+            .unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        (status, before, matched, rest)
+    }
+
+    #[cfg(feature = "futures")]
+    fn read_async<N: Needle>(builder: &UntilNeedle<N>, chunks: &[Vec<u8>]) -> Outcome {
+        use crate::testing::ChunkedAsyncReader;
+        use futures::AsyncReadExt as _;
+
+        let mut reader = ChunkedAsyncReader::from_chunks(chunks).pending(1);
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        futures::executor::block_on(async {
+            let status = builder
+                .read_async(&mut reader, &mut before, &mut matched)
+                .await
+                .unwrap();
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).await.unwrap();
+            (status, before, matched, rest)
+        })
+    }
+
+    /// Checks that every chunking of random data gives the same result on every path.
+    fn check_chunkings<N: Needle + Clone>(needle: N, rng: &mut Rng) {
+        for _ in 0..200 {
+            let data: Vec<u8> = (0..rng.below(40)).map(|_| b"ab<>"[rng.below(4)]).collect();
+            let limit = [usize::MAX, rng.below(45)][rng.below(2)];
+            let consume = rng.below(2) == 0;
+            let builder = UntilNeedle::new(needle.clone())
+                .limit(limit)
+                .consume_needle(consume);
+
+            // the data in one chunk, and then split at random points
+            let mut expected = None;
+            for round in 0..8 {
+                let mut chunks = Vec::new();
+                let mut rest = &data[..];
+                while !rest.is_empty() {
+                    let len = if round == 0 {
+                        rest.len()
+                    } else {
+                        1 + rng.below(rest.len())
+                    };
+                    chunks.push(rest[..len].to_vec());
+                    rest = &rest[len..];
+                }
+
+                let mut searcher = Searcher::new(needle.clone())
+                    .limit(limit)
+                    .consume_needle(consume);
+                let outcome = search_chunks(&mut searcher, &chunks);
+                let expected = expected.get_or_insert_with(|| outcome.clone());
+                // a needle left in the reader is consumed in part if it straddles two chunks
+                if consume || !outcome.0.is_found() {
+                    assert_eq!(&outcome, expected, "{:?} in {:?}", data, chunks);
+                } else {
+                    assert_eq!(outcome.1, expected.1, "{:?} in {:?}", data, chunks);
+                    assert_eq!(outcome.2, expected.2, "{:?} in {:?}", data, chunks);
+                }
+                assert_eq!(read_sync(&builder, &chunks), outcome);
+                #[cfg(feature = "futures")]
+                assert_eq!(read_async(&builder, &chunks), outcome);
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunkings_agree() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        check_chunkings(&b"<>"[..], &mut rng);
+        check_chunkings(&b"a<<>"[..], &mut rng);
+        check_chunkings(b"b".to_vec(), &mut rng);
+        check_chunkings(Unbounded(b"<a>"), &mut rng);
+        #[cfg(feature = "regex")]
+        check_chunkings(regex::bytes::Regex::new("<a*>").unwrap(), &mut rng);
+    }
+
+    #[test]
+    fn test_pending_bounded() {
+        // a long scan keeps only the bytes that may begin a match
+        let mut searcher = Searcher::new(b"<END>");
+        let mut chunk = vec![b'x'; 1024];
+        chunk.extend_from_slice(b"<EN");
+        for _ in 0..1000 {
+            assert!(!searcher.push(&chunk).is_done());
+        }
+        assert_eq!(searcher.pending(), b"<EN");
+        assert!(searcher.scanner.tail.capacity() < chunk.len() + 3);
+
+        let mut searcher = Searcher::new(Unbounded(b"<END>"));
+        for _ in 0..10 {
+            assert!(!searcher.push(&[b'x'; 1024]).is_done());
+        }
+        assert_eq!(searcher.pending().len(), 10 * 1024);
+    }
+
+    #[test]
+    fn test_push_segments() {
+        // several searches over the same chunks, as driven by an event loop
+        let mut searcher = Searcher::new(b"\r\n");
+        let mut segments = vec![Vec::new()];
+        for mut chunk in [&b"a\r\nbc\r"[..], b"\n\r\nd"] {
+            while !chunk.is_empty() {
+                let step = searcher.push(chunk);
+                let segment = segments.last_mut().unwrap();
+                if let SearchStep::Found { retract, .. } = step {
+                    segment.truncate(segment.len() - retract);
+                }
+                segment.extend_from_slice(step.data(chunk));
+                let (done, used) = (step.is_done(), step.consumed());
+                if done {
+                    searcher.finish();
+                    segments.push(Vec::new());
+                }
+                chunk = &chunk[used..];
+            }
+        }
+        assert_eq!(segments, [&b"a"[..], b"bc", b"", b"d"]);
+    }
+
+    #[test]
+    fn test_push_straddling() {
+        let mut searcher = Searcher::new(b"<END>");
+        assert_eq!(
+            searcher.push(b"abc<E"),
+            SearchStep::NotFound { consumed: 5 }
+        );
+        assert_eq!(searcher.pending(), b"<E");
+        assert_eq!(searcher.push(b"N"), SearchStep::NotFound { consumed: 1 });
+        assert_eq!(
+            searcher.push(b"D>rest"),
+            SearchStep::Found {
+                before: 0,
+                retract: 3,
+                matched: b"<END>",
+                consumed: 2,
+            }
+        );
+        assert_eq!(
+            searcher.finish(),
+            UntilNeedleStatus::Found { bytes_read: 8 }
+        );
+        // the searcher is reset for the next search
+        assert_eq!(searcher.bytes_read(), 0);
+        assert_eq!(searcher.pending(), b"");
+
+        let mut searcher = Searcher::new(b"<END>").limit(4);
+        assert_eq!(
+            searcher.push(b"ab<E"),
+            SearchStep::LimitReached { consumed: 4 }
+        );
+        assert_eq!(
+            searcher.finish(),
+            UntilNeedleStatus::LimitReached { bytes_read: 4 }
+        );
+        assert_eq!(searcher.push(b"ab"), SearchStep::NotFound { consumed: 2 });
+        assert_eq!(searcher.finish(), UntilNeedleStatus::Eof { bytes_read: 2 });
+    }
+}
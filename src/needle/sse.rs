@@ -0,0 +1,125 @@
+use super::{Needle, PartialMatch};
+use core::ops::Range;
+
+/// A needle matching the blank line that ends a Server-Sent Events event.
+///
+/// Lines may end with `\r\n`, `\n` or `\r`, so the match is the line ending of the last line of
+/// the event followed by the line ending of the blank line, such as `\n\n` or `\r\n\r\n`. A
+/// trailing `\r` is only taken as a line ending once the next byte shows it is not followed by
+/// `\n`; at EOF it is, and the `\n` is then left at the start of the next event.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::SseEventEnd;
+///
+/// let mut reader = &b"event: ping\r\ndata: 1\r\n\r\ndata: 2\n\n"[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader
+///     .read_until_needle(SseEventEnd, &mut before, &mut matched)
+///     .unwrap();
+/// assert_eq!(before, b"event: ping\r\ndata: 1");
+/// assert_eq!(matched, b"\r\n\r\n");
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SseEventEnd;
+
+fn is_line_end(b: u8) -> bool {
+    b == b'\r' || b == b'\n'
+}
+
+/// Returns the length of the line ending at `at`.
+fn line_end_len(haystack: &[u8], at: usize) -> usize {
+    if haystack[at] == b'\r' && haystack.get(at + 1) == Some(&b'\n') {
+        2
+    } else {
+        1
+    }
+}
+
+impl Needle for SseEventEnd {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        (0..haystack.len()).find_map(|at| {
+            if !is_line_end(haystack[at]) {
+                return None;
+            }
+            let blank = at + line_end_len(haystack, at);
+            let &b = haystack.get(blank)?;
+            is_line_end(b).then(|| at..blank + line_end_len(haystack, blank))
+        })
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(4)
+    }
+
+    fn findin_partial(&self, haystack: &[u8]) -> PartialMatch {
+        if let Some(range) = self.findin(haystack) {
+            // a `\r` at the end may still be the start of `\r\n`
+            if range.end < haystack.len() || haystack[range.end - 1] == b'\n' {
+                return PartialMatch::Full(range);
+            }
+            return PartialMatch::Prefix(range.start);
+        }
+        match haystack.iter().rposition(|&b| !is_line_end(b)) {
+            Some(last) if last + 1 < haystack.len() => PartialMatch::Prefix(last + 1),
+            Some(_) => PartialMatch::None,
+            None if haystack.is_empty() => PartialMatch::None,
+            None => PartialMatch::Prefix(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_findin() {
+        assert_eq!(SseEventEnd.findin(b"data: 1\n\nrest"), Some(7..9));
+        assert_eq!(SseEventEnd.findin(b"data: 1\r\n\r\n"), Some(7..11));
+        assert_eq!(SseEventEnd.findin(b"data: 1\r\rrest"), Some(7..9));
+        assert_eq!(SseEventEnd.findin(b"a\r\nb\n\r\n"), Some(4..7));
+        assert_eq!(SseEventEnd.findin(b"a\r\nb\r\n"), None);
+    }
+
+    #[test]
+    fn test_findin_partial() {
+        assert_eq!(
+            SseEventEnd.findin_partial(b"a\n\n"),
+            PartialMatch::Full(1..3)
+        );
+        assert_eq!(
+            SseEventEnd.findin_partial(b"a\r\n\r"),
+            PartialMatch::Prefix(1)
+        );
+        assert_eq!(
+            SseEventEnd.findin_partial(b"a\r\n"),
+            PartialMatch::Prefix(1)
+        );
+        assert_eq!(SseEventEnd.findin_partial(b"data"), PartialMatch::None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_in_chunks() {
+        use crate::io::UntilNeedleRead;
+        use crate::testing::ChunkedReader;
+
+        let data = b"data: 1\r\ndata: 2\r\n\r\ndata: 3\n\n";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(SseEventEnd, &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(before, b"data: 1\r\ndata: 2");
+            assert_eq!(matched, b"\r\n\r\n");
+        }
+    }
+}
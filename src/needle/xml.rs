@@ -0,0 +1,172 @@
+use super::Needle;
+use core::ops::Range;
+
+/// A needle matching the end tag that closes the first element with a given name.
+///
+/// A literal such as `</rpc-reply>` also matches inside a comment, a CDATA section or a nested
+/// element of the same name. This needle parses the XML read so far, tracking the depth of the
+/// elements, and only matches the end tag at the depth where the element was opened. Comments,
+/// CDATA sections, processing instructions and declarations are skipped, and `>` is allowed in
+/// quoted attribute values. If the element is empty, as in `<rpc-reply/>`, its tag is the match.
+///
+/// The depth is not kept between searches: each one parses the data from the start of the
+/// read, so all of it is kept until the match.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() {
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::needle::XmlElementEnd;
+///
+/// let mut reader = &b"<rpc-reply><!-- </rpc-reply> --><data/></rpc-reply>]]>]]>"[..];
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader
+///     .read_until_needle(XmlElementEnd::new("rpc-reply"), &mut before, &mut matched)
+///     .unwrap();
+/// assert_eq!(before, b"<rpc-reply><!-- </rpc-reply> --><data/>");
+/// assert_eq!(matched, b"</rpc-reply>");
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone)]
+pub struct XmlElementEnd<T> {
+    name: T,
+}
+
+impl<T: AsRef<[u8]>> XmlElementEnd<T> {
+    /// Creates a needle matching the end of the first element named `name`.
+    pub fn new(name: T) -> Self {
+        Self { name }
+    }
+
+    /// Returns the name of the element.
+    pub fn name(&self) -> &[u8] {
+        self.name.as_ref()
+    }
+}
+
+/// Returns the offset after the first `end` in `bytes` from `from`.
+fn skip_past(bytes: &[u8], from: usize, end: &[u8]) -> Option<usize> {
+    let found = bytes
+        .get(from..)?
+        .windows(end.len())
+        .position(|w| w == end)?;
+    Some(from + found + end.len())
+}
+
+/// Returns the offset after the `>` closing the tag starting at `from`, skipping quoted values.
+fn tag_end(bytes: &[u8], from: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, &b) in bytes.iter().enumerate().skip(from) {
+        match (quote, b) {
+            (Some(q), _) if b == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'"' | b'\'') => quote = Some(b),
+            (None, b'>') => return Some(i + 1),
+            (None, _) => {}
+        }
+    }
+    None
+}
+
+/// Returns the name of the tag whose name starts at the beginning of `bytes`.
+fn tag_name(bytes: &[u8]) -> &[u8] {
+    let len = bytes
+        .iter()
+        .position(|&b| b.is_ascii_whitespace() || b == b'/' || b == b'>')
+        .unwrap_or(bytes.len());
+    &bytes[..len]
+}
+
+/// Constructs skipped as a whole, by their start and end.
+const SKIPPED: [(&[u8], &[u8]); 4] = [
+    (b"<!--", b"-->"),
+    (b"<![CDATA[", b"]]>"),
+    (b"<?", b"?>"),
+    (b"<!", b">"),
+];
+
+impl<T: AsRef<[u8]>> Needle for XmlElementEnd<T> {
+    fn findin(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        let name = self.name.as_ref();
+        let mut depth = 0usize;
+        // the depth of the element once its start tag is read
+        let mut open = None;
+        let mut i = 0;
+        while let Some(found) = haystack[i..].iter().position(|&b| b == b'<') {
+            let start = i + found;
+            let rest = &haystack[start..];
+            if SKIPPED.iter().any(|(prefix, _)| prefix.starts_with(rest)) {
+                // too short to tell which construct this is
+                return None;
+            }
+            if let Some((_, close)) = SKIPPED.iter().find(|(prefix, _)| rest.starts_with(prefix)) {
+                i = skip_past(haystack, start + 2, close)?;
+                continue;
+            }
+            let end = tag_end(haystack, start + 1)?;
+            if let Some(tag) = rest.strip_prefix(b"</") {
+                depth = depth.saturating_sub(1);
+                if open == Some(depth) && tag_name(tag) == name {
+                    return Some(start..end);
+                }
+            } else if haystack[end - 2] != b'/' {
+                if open.is_none() && tag_name(&rest[1..]) == name {
+                    open = Some(depth);
+                }
+                depth += 1;
+            } else if open.is_none() && tag_name(&rest[1..]) == name {
+                return Some(start..end);
+            }
+            i = end;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_findin() {
+        let needle = XmlElementEnd::new("a");
+        assert_eq!(needle.findin(b"<a>x</a>"), Some(4..8));
+        assert_eq!(needle.findin(b"<a><a></a></a>tail"), Some(10..14));
+        assert_eq!(needle.findin(b"<r><a/></r>"), Some(3..7));
+        assert_eq!(needle.findin(b"<a x='>' y=\"</a>\">t</a >"), Some(19..24));
+        assert_eq!(
+            needle.findin(b"<?xml version='1.0'?><a><![CDATA[</a>]]></a>"),
+            Some(40..44)
+        );
+        assert_eq!(
+            needle.findin(b"<!DOCTYPE a><a><!-- </a> --></a>"),
+            Some(28..32)
+        );
+        assert_eq!(needle.findin(b"<ab></ab><a></a>"), Some(12..16));
+        assert_eq!(needle.findin(b"<a><a></a>"), None);
+        assert_eq!(needle.findin(b"<a><!-- </a>"), None);
+        assert_eq!(needle.findin(b"<a></a"), None);
+        assert_eq!(needle.findin(b"<a><!"), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_in_chunks() {
+        use crate::io::UntilNeedleRead;
+        use crate::testing::ChunkedReader;
+
+        let data = b"<rpc-reply><data><![CDATA[</rpc-reply>]]><rpc-reply/></data></rpc-reply>next";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(XmlElementEnd::new("rpc-reply"), &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(matched, b"</rpc-reply>");
+            assert_eq!(before.len() + matched.len(), data.len() - 4);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Reading NETCONF messages framed as in RFC 6242.
+//!
+//! NETCONF 1.0 ends each message with the `]]>]]>` delimiter, which
+//! [`END_OF_MESSAGE`](crate::netconf::END_OF_MESSAGE) matches. NETCONF 1.1 sends each message as
+//! a series of chunks, `\n#<len>\n` followed by `len` bytes, terminated by `\n##\n`.
+//! [`read_message`](crate::netconf::read_message) and, with the `futures` feature,
+//! `read_message_async` read one message in either framing and return its payload.
+
+use crate::io::UntilNeedleRead;
+use std::io::{self, BufRead};
+
+/// The delimiter ending a message in the end-of-message framing of NETCONF 1.0.
+pub const END_OF_MESSAGE: &[u8] = b"]]>]]>";
+
+/// The longest chunk header, `#` and the digits of the largest chunk size allowed.
+const MAX_HEADER_LEN: usize = 11;
+
+/// The framing of NETCONF messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// Messages end with `]]>]]>`, as in NETCONF 1.0.
+    EndOfMessage,
+    /// Messages are split into chunks, as in NETCONF 1.1.
+    Chunked,
+}
+
+/// Reads one NETCONF message from `reader` and returns its payload.
+///
+/// # Returns
+/// - `Some` with the message, without the delimiter or the chunk headers.
+/// - `None` if EOF was reached before any data of a message.
+///
+/// # Errors
+/// If EOF is reached inside a message, an error of kind `UnexpectedEof` is returned. A chunk
+/// header that does not follow RFC 6242 fails with an error of kind `InvalidData`.
+///
+/// # Example
+/// ```
+/// use until_needle::netconf::{read_message, Framing};
+///
+/// let mut reader = &b"\n#4\n<rpc\n#20\n><get-config/></rpc>\n##\n"[..];
+/// let message = read_message(&mut reader, Framing::Chunked).unwrap();
+/// assert_eq!(message.as_deref(), Some(&b"<rpc><get-config/></rpc>"[..]));
+/// ```
+pub fn read_message<R: BufRead>(reader: &mut R, framing: Framing) -> io::Result<Option<Vec<u8>>> {
+    let mut message = Vec::new();
+    if framing == Framing::EndOfMessage {
+        let mut matched = Vec::new();
+        reader.read_until_needle(END_OF_MESSAGE, &mut message, &mut matched)?;
+        return end_of_message(message, !matched.is_empty());
+    }
+
+    loop {
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        reader.read_until_needle("\n#", &mut before, &mut matched)?;
+        if matched.is_empty() {
+            return unexpected_eof(message.is_empty() && before.is_empty());
+        }
+        if !before.is_empty() {
+            return Err(invalid_header());
+        }
+
+        let (mut header, mut newline) = (Vec::new(), Vec::new());
+        reader
+            .read_until_needle_limited("\n", MAX_HEADER_LEN + 1, &mut header, &mut newline)
+            .map_err(|_| invalid_header())?;
+        if newline.is_empty() {
+            return unexpected_eof(false);
+        }
+        let Some(len) = chunk_len(&header)? else {
+            return Ok(Some(message));
+        };
+        let start = message.len();
+        message.resize(start + len, 0);
+        reader.read_exact(&mut message[start..])?;
+    }
+}
+
+/// Asynchronously reads one NETCONF message from `reader` and returns its payload.
+///
+/// This is the async variant of [`read_message`].
+#[cfg(feature = "futures")]
+pub async fn read_message_async<R>(reader: &mut R, framing: Framing) -> io::Result<Option<Vec<u8>>>
+where
+    R: futures_util::io::AsyncBufRead + Unpin,
+{
+    use crate::futures::AsyncUntilNeedleRead;
+    use futures_util::io::AsyncReadExt as _;
+
+    let mut message = Vec::new();
+    if framing == Framing::EndOfMessage {
+        let mut matched = Vec::new();
+        reader
+            .read_until_needle(END_OF_MESSAGE, &mut message, &mut matched)
+            .await?;
+        return end_of_message(message, !matched.is_empty());
+    }
+
+    loop {
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        reader
+            .read_until_needle("\n#", &mut before, &mut matched)
+            .await?;
+        if matched.is_empty() {
+            return unexpected_eof(message.is_empty() && before.is_empty());
+        }
+        if !before.is_empty() {
+            return Err(invalid_header());
+        }
+
+        let (mut header, mut newline) = (Vec::new(), Vec::new());
+        reader
+            .read_until_needle("\n", &mut header, &mut newline)
+            .limit(MAX_HEADER_LEN + 1)
+            .await
+            .map_err(|_| invalid_header())?;
+        if newline.is_empty() {
+            return unexpected_eof(false);
+        }
+        let Some(len) = chunk_len(&header)? else {
+            return Ok(Some(message));
+        };
+        let start = message.len();
+        message.resize(start + len, 0);
+        reader.read_exact(&mut message[start..]).await?;
+    }
+}
+
+/// Ends the read of a message in the end-of-message framing.
+fn end_of_message(message: Vec<u8>, found: bool) -> io::Result<Option<Vec<u8>>> {
+    if found {
+        return Ok(Some(message));
+    }
+    // whitespace after the last message is not a message
+    unexpected_eof(message.iter().all(u8::is_ascii_whitespace))
+}
+
+/// Returns `None` if EOF was reached between messages, or an `UnexpectedEof` error.
+fn unexpected_eof(between_messages: bool) -> io::Result<Option<Vec<u8>>> {
+    if between_messages {
+        return Ok(None);
+    }
+    Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "EOF reached inside a NETCONF message",
+    ))
+}
+
+fn invalid_header() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid NETCONF chunk header")
+}
+
+/// Parses the chunk header following `\n#`, returning `None` for the end of the chunks.
+fn chunk_len(header: &[u8]) -> io::Result<Option<usize>> {
+    if header == b"#" {
+        return Ok(None);
+    }
+    let valid = matches!(header, [b'1'..=b'9', rest @ ..] if rest.iter().all(u8::is_ascii_digit));
+    let len = std::str::from_utf8(header)
+        .ok()
+        .filter(|_| valid)
+        .and_then(|digits| digits.parse::<u32>().ok())
+        .ok_or_else(invalid_header)?;
+    Ok(Some(len as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ChunkedReader;
+
+    #[test]
+    fn test_end_of_message() {
+        let data = b"<hello/>]]>]]><rpc-reply/>]]>]]>\n";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let message = read_message(&mut reader, Framing::EndOfMessage).unwrap();
+            assert_eq!(message.as_deref(), Some(&b"<hello/>"[..]));
+            let message = read_message(&mut reader, Framing::EndOfMessage).unwrap();
+            assert_eq!(message.as_deref(), Some(&b"<rpc-reply/>"[..]));
+            assert_eq!(
+                read_message(&mut reader, Framing::EndOfMessage).unwrap(),
+                None
+            );
+        }
+
+        let err = read_message(&mut &b"<rpc"[..], Framing::EndOfMessage).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_chunked() {
+        let data = b"\n#4\n<rpc\n#20\n><get-config/></rpc>\n##\n\n#2\nab\n##\n";
+        for size in 1..data.len() {
+            let mut reader = ChunkedReader::new(&data[..], [size]);
+            let message = read_message(&mut reader, Framing::Chunked).unwrap();
+            assert_eq!(message.as_deref(), Some(&b"<rpc><get-config/></rpc>"[..]));
+            let message = read_message(&mut reader, Framing::Chunked).unwrap();
+            assert_eq!(message.as_deref(), Some(&b"ab"[..]));
+            assert_eq!(read_message(&mut reader, Framing::Chunked).unwrap(), None);
+        }
+
+        for data in [&b"\n#04\nabcd\n##\n"[..], b"\n#x\n", b"junk\n#1\na\n##\n"] {
+            let err = read_message(&mut &data[..], Framing::Chunked).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        let err = read_message(&mut &b"\n#123456789012\n"[..], Framing::Chunked).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        for data in [&b"\n#4\nab"[..], b"\n#4", b"\n#2\nab"] {
+            let err = read_message(&mut &data[..], Framing::Chunked).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+
+    #[cfg(feature = "futures")]
+    #[tokio::test]
+    async fn test_read_message_async() {
+        use crate::testing::ChunkedAsyncReader;
+
+        let mut reader =
+            ChunkedAsyncReader::from_chunks(["\n#6\n<rp", "c/>\n#", "#\n<hello/>]]>", "]]>"])
+                .pending(1);
+        let message = read_message_async(&mut reader, Framing::Chunked)
+            .await
+            .unwrap();
+        assert_eq!(message.as_deref(), Some(&b"<rpc/>"[..]));
+        let message = read_message_async(&mut reader, Framing::EndOfMessage)
+            .await
+            .unwrap();
+        assert_eq!(message.as_deref(), Some(&b"<hello/>"[..]));
+        let message = read_message_async(&mut reader, Framing::Chunked)
+            .await
+            .unwrap();
+        assert_eq!(message, None);
+    }
+}
@@ -0,0 +1,381 @@
+//! Sinks for the data read until a needle.
+//!
+//! The `read_until_needle_into_out` methods write the data before the needle and the needle
+//! itself to any [`OutputBuf`], such as a `Vec<u8>`, a [`Discard`] sink dropping the data, or
+//! a [`Limited`] wrapper capping how much is stored. With `std`, a [`Writer`] passes the data
+//! on to any `std::io::Write`.
+
+#[cfg(feature = "std")]
+use crate::needle::{Scanner, SearchStep};
+#[cfg(feature = "std")]
+use crate::{Needle, UntilNeedleStatus};
+#[cfg(feature = "alloc")]
+use alloc::{collections::VecDeque, vec::Vec};
+#[cfg(feature = "bytes")]
+use bytes::BytesMut;
+use core::fmt;
+
+/// The error returned by [`OutputBuf::put`] when a sink cannot take all the bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Full;
+
+impl fmt::Display for Full {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("output buffer is full")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Full {}
+
+/// A sink for the data read until a needle.
+pub trait OutputBuf {
+    /// Appends `bytes` to the sink.
+    ///
+    /// A sink that cannot take all of them stores as many as fit and returns [`Full`].
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Full>;
+}
+
+impl<O: OutputBuf + ?Sized> OutputBuf for &mut O {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Full> {
+        (**self).put(bytes)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl OutputBuf for Vec<u8> {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Full> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl OutputBuf for VecDeque<u8> {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Full> {
+        self.extend(bytes);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl OutputBuf for BytesMut {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Full> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// A sink dropping all data, for reads that only need to get past the needle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Discard;
+
+impl OutputBuf for Discard {
+    fn put(&mut self, _bytes: &[u8]) -> Result<(), Full> {
+        Ok(())
+    }
+}
+
+/// A sink storing at most a given number of bytes in another sink.
+///
+/// Once the cap is reached, [`put`](OutputBuf::put) stores what still fits and returns
+/// [`Full`], which ends the read.
+#[derive(Debug, Clone)]
+pub struct Limited<O> {
+    inner: O,
+    remaining: usize,
+}
+
+impl<O> Limited<O> {
+    /// Wraps `inner`, storing at most `limit` more bytes in it.
+    pub fn new(inner: O, limit: usize) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+        }
+    }
+
+    /// Returns the number of bytes that can still be stored.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Returns a reference to the wrapped sink.
+    pub fn get_ref(&self) -> &O {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped sink.
+    pub fn get_mut(&mut self) -> &mut O {
+        &mut self.inner
+    }
+
+    /// Returns the wrapped sink.
+    pub fn into_inner(self) -> O {
+        self.inner
+    }
+}
+
+impl<O: OutputBuf> OutputBuf for Limited<O> {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Full> {
+        let n = bytes.len().min(self.remaining);
+        self.inner.put(&bytes[..n])?;
+        self.remaining -= n;
+        if n < bytes.len() {
+            return Err(Full);
+        }
+        Ok(())
+    }
+}
+
+/// A sink writing the data to an [`std::io::Write`], such as a file, a socket or a hasher.
+///
+/// The data is written as it is read instead of being collected in memory. A write error ends
+/// the read as if the sink were full; [`take_error`](Self::take_error) returns it.
+///
+/// # Example
+/// ```
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::output::Writer;
+/// use until_needle::UntilNeedleStatus;
+///
+/// let mut reader = &b"dump contents\n-- END --\n"[..];
+/// let mut out = Writer::new(Vec::new());
+/// let status = reader
+///     .read_until_needle_into_out(b"-- END --", &mut out, Vec::new())
+///     .unwrap();
+/// assert!(out.take_error().is_none());
+/// assert_eq!(status, UntilNeedleStatus::Found { bytes_read: 23 });
+/// assert_eq!(out.into_inner(), b"dump contents\n");
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct Writer<W> {
+    inner: W,
+    error: Option<std::io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<W> Writer<W> {
+    /// Wraps `inner`, writing the data to it.
+    pub fn new(inner: W) -> Self {
+        Self { inner, error: None }
+    }
+
+    /// Returns the error of the write that ended the read, if any, and clears it.
+    pub fn take_error(&mut self) -> Option<std::io::Error> {
+        self.error.take()
+    }
+
+    /// Returns a reference to the wrapped writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> OutputBuf for Writer<W> {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Full> {
+        if self.error.is_some() {
+            return Err(Full);
+        }
+        self.inner.write_all(bytes).map_err(|e| {
+            self.error = Some(e);
+            Full
+        })
+    }
+}
+
+/// A search writing the data before the needle to an [`OutputBuf`].
+///
+/// Sinks cannot take back data, so the bytes the [`Scanner`] keeps because they may begin a
+/// match are held back until the next chunk shows whether they do. Only those bytes are
+/// stored: the last [`Needle::max_len`] - 1 bytes, or the suffix told by
+/// [`Needle::findin_partial`] for needles without a maximum length.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub(crate) struct HeldSearch {
+    scanner: Scanner,
+    /// The bytes held back before the last chunk, taken from the scanner before it moves on.
+    held: Vec<u8>,
+}
+
+#[cfg(feature = "std")]
+impl Default for HeldSearch {
+    fn default() -> Self {
+        Self {
+            scanner: Scanner::new(),
+            held: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl HeldSearch {
+    /// Searches `chunk`, returning the number of its bytes consumed and the outcome if this
+    /// ended the search.
+    pub(crate) fn push<N: Needle + ?Sized>(
+        &mut self,
+        needle: &N,
+        chunk: &[u8],
+        mut before: impl OutputBuf,
+        mut matched: impl OutputBuf,
+    ) -> (usize, Option<UntilNeedleStatus>) {
+        self.held.clear();
+        self.held.extend_from_slice(self.scanner.pending());
+
+        let put = match self.scanner.push(needle, chunk) {
+            SearchStep::Found {
+                before: len,
+                retract,
+                matched: needle,
+                consumed,
+            } => {
+                let put = before
+                    .put(&self.held[..self.held.len() - retract])
+                    .and_then(|()| before.put(&chunk[..len]))
+                    .and_then(|()| matched.put(needle));
+                return (consumed, Some(self.finish_with(put)));
+            }
+            step => {
+                // everything but the bytes that may still begin a match is decided
+                let decided = self.held.len() + step.consumed() - self.scanner.pending().len();
+                let from_held = decided.min(self.held.len());
+                before
+                    .put(&self.held[..from_held])
+                    .and_then(|()| before.put(&chunk[..decided - from_held]))
+            }
+        };
+        match put {
+            Ok(()) => (chunk.len(), None),
+            Err(full) => (chunk.len(), Some(self.finish_with(Err(full)))),
+        }
+    }
+
+    /// Ends the search at EOF, writing the bytes held back.
+    pub(crate) fn finish(&mut self, mut before: impl OutputBuf) -> UntilNeedleStatus {
+        let put = before.put(self.scanner.pending());
+        self.finish_with(put)
+    }
+
+    fn finish_with(&mut self, put: Result<(), Full>) -> UntilNeedleStatus {
+        self.held.clear();
+        match (self.scanner.finish(), put) {
+            (status, Err(Full)) => UntilNeedleStatus::OutputFull {
+                bytes_read: status.bytes_read(),
+            },
+            (status, Ok(())) => status,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limited() {
+        let mut out = Limited::new(Vec::new(), 5);
+        assert_eq!(out.put(b"abc"), Ok(()));
+        assert_eq!(out.remaining(), 2);
+        assert_eq!(out.put(b"def"), Err(Full));
+        assert_eq!(out.remaining(), 0);
+        assert_eq!(out.put(b""), Ok(()));
+        assert_eq!(out.into_inner(), b"abcde");
+
+        let mut inner = Limited::new(Vec::new(), 2);
+        let mut out = Limited::new(&mut inner, 5);
+        assert_eq!(out.put(b"abc"), Err(Full));
+        assert_eq!(out.remaining(), 5);
+        assert_eq!(inner.get_ref(), b"ab");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_writer() {
+        let mut buf = [0; 4];
+        let mut out = Writer::new(&mut buf[..]);
+        assert_eq!(out.put(b"abc"), Ok(()));
+        assert_eq!(out.put(b"de"), Err(Full));
+        assert_eq!(
+            out.take_error().map(|e| e.kind()),
+            Some(std::io::ErrorKind::WriteZero)
+        );
+        assert!(out.take_error().is_none());
+        assert_eq!(out.get_ref(), b"");
+        assert_eq!(buf, *b"abcd");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_held_search() {
+        let mut search = HeldSearch::default();
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        for chunk in [&b"ab<"[..], b"x<E"] {
+            let step = search.push(&b"<END>"[..], chunk, &mut before, &mut matched);
+            assert_eq!(step, (3, None));
+        }
+        // only the bytes that may begin the needle are held back
+        assert_eq!(before, b"ab<x");
+        let step = search.push(&b"<END>"[..], b"ND>rest", &mut before, &mut matched);
+        assert_eq!(step, (3, Some(UntilNeedleStatus::Found { bytes_read: 9 })));
+        assert_eq!((&before[..], &matched[..]), (&b"ab<x"[..], &b"<END>"[..]));
+
+        let mut before = Limited::new(Vec::new(), 4);
+        let step = search.push(&b"<END>"[..], b"abcdefghi", &mut before, Discard);
+        assert_eq!(
+            step,
+            (9, Some(UntilNeedleStatus::OutputFull { bytes_read: 9 }))
+        );
+        assert_eq!(before.get_ref(), b"abcd");
+
+        let mut before = Vec::new();
+        assert_eq!(
+            search.push(&b"<END>"[..], b"ab<E", &mut before, Discard),
+            (4, None)
+        );
+        assert_eq!(
+            search.finish(&mut before),
+            UntilNeedleStatus::Eof { bytes_read: 4 }
+        );
+        assert_eq!(before, b"ab<E");
+    }
+
+    /// A literal needle without a maximum length that still tells where a match may begin.
+    #[cfg(feature = "std")]
+    struct Partial(&'static [u8]);
+
+    #[cfg(feature = "std")]
+    impl Needle for Partial {
+        fn findin(&self, haystack: &[u8]) -> Option<core::ops::Range<usize>> {
+            self.0.findin(haystack)
+        }
+
+        fn findin_partial(&self, haystack: &[u8]) -> crate::needle::PartialMatch {
+            self.0.findin_partial(haystack)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_held_search_partial() {
+        let mut search = HeldSearch::default();
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let step = search.push(&Partial(b"<END>"), b"abcdef<E", &mut before, &mut matched);
+        assert_eq!(step, (8, None));
+        assert_eq!(before, b"abcdef");
+        let step = search.push(&Partial(b"<END>"), b"ND>", &mut before, &mut matched);
+        assert_eq!(step, (3, Some(UntilNeedleStatus::Found { bytes_read: 11 })));
+        assert_eq!(matched, b"<END>");
+    }
+}
@@ -0,0 +1,406 @@
+//! A transcript of the bytes passing through a reader or transport.
+
+use std::io::{self, BufRead, Read, Write};
+#[cfg(any(feature = "futures", feature = "tokio"))]
+use std::pin::Pin;
+#[cfg(any(feature = "futures", feature = "tokio"))]
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+/// Where a recorded chunk of bytes was going.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Read,
+    Written,
+}
+
+/// The sink of a transcript and how its entries are formatted.
+#[derive(Debug)]
+struct Transcript<W> {
+    sink: W,
+    start: Option<Instant>,
+    directions: bool,
+}
+
+impl<W: Write> Transcript<W> {
+    fn record(&mut self, direction: Direction, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        // a broken transcript must not break the session, so errors are ignored
+        let _ = self.write_entry(direction, bytes);
+    }
+
+    fn write_entry(&mut self, direction: Direction, bytes: &[u8]) -> io::Result<()> {
+        if self.start.is_none() && !self.directions {
+            return self.sink.write_all(bytes);
+        }
+        if let Some(start) = self.start {
+            let elapsed = start.elapsed();
+            write!(
+                self.sink,
+                "[{}.{:03}] ",
+                elapsed.as_secs(),
+                elapsed.subsec_millis()
+            )?;
+        }
+        if self.directions {
+            self.sink.write_all(match direction {
+                Direction::Read => b"< ",
+                Direction::Written => b"> ",
+            })?;
+        }
+        self.sink.write_all(bytes)?;
+        if !bytes.ends_with(b"\n") {
+            self.sink.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// A wrapper copying every byte read from or written to a reader or transport to a sink.
+///
+/// Wrap the reader of an automation run in a `Recorder` to keep a transcript of what the peer
+/// sent, and of what was sent to it, so that a failed run can be diagnosed from a file. Bytes
+/// are recorded as they are read from the inner reader, including those read ahead of a needle.
+///
+/// By default the sink gets the bytes as they are. With [`timestamps`](Self::timestamps) or
+/// [`directions`](Self::directions), each chunk becomes a line prefixed with the time elapsed
+/// since the recorder was created and with `<` for bytes read and `>` for bytes written.
+/// Errors writing to the sink are ignored.
+///
+/// The recorder implements the reader and writer traits of std, and those of `futures` and
+/// `tokio` when their features are enabled, for an inner value implementing them.
+///
+/// # Example
+/// ```
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::recorder::Recorder;
+///
+/// let mut reader = Recorder::new(&b"login: admin\n"[..], Vec::new()).directions();
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader.read_until_needle("login: ", &mut before, &mut matched).unwrap();
+/// assert_eq!(reader.sink(), b"< login: admin\n");
+/// ```
+#[derive(Debug)]
+pub struct Recorder<T, W> {
+    inner: T,
+    transcript: Transcript<W>,
+    /// The number of bytes at the front of the inner buffer that were already recorded.
+    recorded: usize,
+}
+
+impl<T, W> Recorder<T, W> {
+    /// Wraps `inner`, copying the bytes passing through it to `sink`.
+    pub fn new(inner: T, sink: W) -> Self {
+        Self {
+            inner,
+            transcript: Transcript {
+                sink,
+                start: None,
+                directions: false,
+            },
+            recorded: 0,
+        }
+    }
+
+    /// Prefixes each recorded chunk with the time elapsed since now, in seconds.
+    pub fn timestamps(mut self) -> Self {
+        self.transcript.start = Some(Instant::now());
+        self
+    }
+
+    /// Prefixes each recorded chunk with `<` if it was read and `>` if it was written.
+    pub fn directions(mut self) -> Self {
+        self.transcript.directions = true;
+        self
+    }
+
+    /// Gets a reference to the underlying reader or transport.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader or transport.
+    ///
+    /// Bytes read or written through it directly are not recorded.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Returns a reference to the sink of the transcript.
+    pub fn sink(&self) -> &W {
+        &self.transcript.sink
+    }
+
+    /// Unwraps this `Recorder`, returning the underlying reader or transport and the sink.
+    pub fn into_inner(self) -> (T, W) {
+        (self.inner, self.transcript.sink)
+    }
+
+    /// Returns the bytes of `read`, the data returned by a read, that are not yet recorded.
+    fn unrecorded<'b>(&mut self, read: &'b [u8]) -> &'b [u8] {
+        let seen = self.recorded.min(read.len());
+        self.recorded -= seen;
+        &read[seen..]
+    }
+
+    fn consumed(&mut self, amt: usize) {
+        self.recorded = self.recorded.saturating_sub(amt);
+    }
+}
+
+impl<T: Read, W: Write> Read for Recorder<T, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let new = self.unrecorded(&buf[..n]);
+        self.transcript.record(Direction::Read, new);
+        Ok(n)
+    }
+}
+
+impl<T: BufRead, W: Write> BufRead for Recorder<T, W> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let available = self.inner.fill_buf()?;
+        let seen = self.recorded.min(available.len());
+        self.recorded = available.len();
+        self.transcript.record(Direction::Read, &available[seen..]);
+        Ok(available)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.consumed(amt);
+        self.inner.consume(amt);
+    }
+}
+
+impl<T: Write, W: Write> Write for Recorder<T, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.transcript.record(Direction::Written, &buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let _ = self.transcript.sink.flush();
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<T, W> futures_util::io::AsyncRead for Recorder<T, W>
+where
+    T: futures_util::io::AsyncRead + Unpin,
+    W: Write + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let n = futures_core::ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let new = this.unrecorded(&buf[..n]);
+        this.transcript.record(Direction::Read, new);
+        Poll::Ready(Ok(n))
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<T, W> futures_util::io::AsyncBufRead for Recorder<T, W>
+where
+    T: futures_util::io::AsyncBufRead + Unpin,
+    W: Write + Unpin,
+{
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let Recorder {
+            inner,
+            transcript,
+            recorded,
+        } = self.get_mut();
+        let available = futures_core::ready!(Pin::new(inner).poll_fill_buf(cx))?;
+        let seen = (*recorded).min(available.len());
+        *recorded = available.len();
+        transcript.record(Direction::Read, &available[seen..]);
+        Poll::Ready(Ok(available))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        this.consumed(amt);
+        Pin::new(&mut this.inner).consume(amt);
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<T, W> futures_util::io::AsyncWrite for Recorder<T, W>
+where
+    T: futures_util::io::AsyncWrite + Unpin,
+    W: Write + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let n = futures_core::ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+        this.transcript.record(Direction::Written, &buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let _ = this.transcript.sink.flush();
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T, W> ::tokio::io::AsyncRead for Recorder<T, W>
+where
+    T: ::tokio::io::AsyncRead + Unpin,
+    W: Write + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ::tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        std::task::ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let new = this.unrecorded(&buf.filled()[filled..]);
+        this.transcript.record(Direction::Read, new);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T, W> ::tokio::io::AsyncBufRead for Recorder<T, W>
+where
+    T: ::tokio::io::AsyncBufRead + Unpin,
+    W: Write + Unpin,
+{
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let Recorder {
+            inner,
+            transcript,
+            recorded,
+        } = self.get_mut();
+        let available = std::task::ready!(Pin::new(inner).poll_fill_buf(cx))?;
+        let seen = (*recorded).min(available.len());
+        *recorded = available.len();
+        transcript.record(Direction::Read, &available[seen..]);
+        Poll::Ready(Ok(available))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        this.consumed(amt);
+        Pin::new(&mut this.inner).consume(amt);
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T, W> ::tokio::io::AsyncWrite for Recorder<T, W>
+where
+    T: ::tokio::io::AsyncWrite + Unpin,
+    W: Write + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let n = std::task::ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+        this.transcript.record(Direction::Written, &buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let _ = this.transcript.sink.flush();
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::UntilNeedleRead;
+    use crate::testing::ChunkedReader;
+
+    #[test]
+    fn test_recorder() {
+        let data = b"login: admin\r\n$ ls\r\nfile\r\n$ ";
+        for size in 1..data.len() {
+            let inner = ChunkedReader::new(&data[..], [size]);
+            let mut reader = Recorder::new(inner, Vec::new());
+            for needle in ["login: ", "$ ", "$ "] {
+                let (mut before, mut matched) = (Vec::new(), Vec::new());
+                reader
+                    .read_until_needle(needle, &mut before, &mut matched)
+                    .unwrap();
+            }
+            // every byte is recorded once, including those read ahead of a needle
+            assert_eq!(reader.sink(), data);
+        }
+
+        let mut reader = std::io::BufReader::with_capacity(4, &b"abcdefgh"[..]);
+        reader.fill_buf().unwrap();
+        let mut reader = Recorder::new(reader, Vec::new());
+        let mut buf = [0; 2];
+        reader.fill_buf().unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(reader.sink(), b"abcdefgh");
+    }
+
+    #[cfg(feature = "futures")]
+    #[tokio::test]
+    async fn test_recorder_futures() {
+        use crate::futures::AsyncUntilNeedleRead;
+        use crate::testing::ChunkedAsyncReader;
+
+        let inner = ChunkedAsyncReader::from_chunks(["ab<E", "ND>cd<", "END>"]).pending(1);
+        let mut reader = Recorder::new(inner, Vec::new());
+        for _ in 0..2 {
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(b"<END>", &mut before, &mut matched)
+                .await
+                .unwrap();
+        }
+        assert_eq!(reader.sink(), b"ab<END>cd<END>");
+    }
+
+    #[test]
+    fn test_recorder_entries() {
+        let inner = std::io::Cursor::new(Vec::new());
+        let mut transport = Recorder::new(inner, Vec::new()).directions();
+        transport.write_all(b"ls\n$ ").unwrap();
+        transport.get_mut().set_position(0);
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        transport
+            .read_until_needle("$ ", &mut before, &mut matched)
+            .unwrap();
+        assert_eq!(transport.sink(), b"> ls\n$ \n< ls\n$ \n");
+
+        let mut reader = Recorder::new(&b"ok"[..], Vec::new()).timestamps();
+        reader.read_to_end(&mut Vec::new()).unwrap();
+        let transcript = String::from_utf8(reader.into_inner().1).unwrap();
+        assert!(transcript.starts_with("[0."), "{transcript}");
+        assert!(transcript.ends_with("] ok\n"), "{transcript}");
+    }
+}
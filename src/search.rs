@@ -0,0 +1,156 @@
+use crate::needle::Scanner;
+use crate::trace::{self, Span};
+use crate::{LimitExceeded, Needle, UntilNeedleStatus};
+use std::io;
+
+/// The default number of chunks an async search processes per poll before yielding.
+///
+/// This mirrors the budget tokio gives each task, so that a reader that is always ready does
+/// not keep the executor thread busy for an entire long scan.
+pub(crate) const DEFAULT_BUDGET: usize = 128;
+
+/// The state of a search over the chunks delivered by an async reader.
+///
+/// The async implementations consume every chunk they are given. The consumed data is appended
+/// to the caller's `before` buffer right away, so dropping a search in progress never loses it;
+/// once the needle is found, the part of it already appended is taken back from the end of
+/// `before`. The state must therefore be used with the same `before` buffer until the search
+/// completes. It is then reset and can be used for the next search.
+///
+/// The search itself is done by a [`Searcher`](crate::needle::Searcher), which the sync readers
+/// use as well, so both find the same matches.
+#[derive(Debug, Clone)]
+pub struct SearchState {
+    scanner: Scanner,
+    budget: usize,
+    /// Whether a search continues a needle begun at the end of `before`, by the data of a read
+    /// that was dropped or cancelled.
+    resume: bool,
+    /// The span of the search in progress.
+    span: Option<Span>,
+}
+
+impl Default for SearchState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SearchState {
+    /// Creates the state for a new search.
+    pub fn new() -> Self {
+        Self {
+            scanner: Scanner::new(),
+            budget: DEFAULT_BUDGET,
+            resume: false,
+            span: None,
+        }
+    }
+
+    /// Makes each search start with the bytes at the end of `before` that may begin a needle,
+    /// for the futures that hold the state only while they are polled.
+    pub(crate) fn resuming(mut self) -> Self {
+        self.resume = true;
+        self
+    }
+
+    /// Sets how many chunks are processed in a single poll before yielding.
+    ///
+    /// When the reader always has data ready, a search would otherwise run to completion in one
+    /// poll and starve other tasks on the same executor thread. After processing `chunks`
+    /// chunks, the task is woken and `Poll::Pending` is returned to let other futures run. This
+    /// does not change the result. Defaults to 128.
+    pub fn with_budget(mut self, chunks: usize) -> Self {
+        self.budget = chunks.max(1);
+        self
+    }
+
+    /// Sets the maximum number of bytes a search consumes from the reader.
+    ///
+    /// See [`UntilNeedle::limit`](crate::UntilNeedle::limit).
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.scanner.limit = limit;
+        self
+    }
+
+    /// Sets whether a search consumes the needle from the reader.
+    ///
+    /// See [`UntilNeedle::consume_needle`](crate::UntilNeedle::consume_needle).
+    pub fn with_consume_needle(mut self, consume: bool) -> Self {
+        self.scanner.consume_needle = consume;
+        self
+    }
+
+    /// Returns the number of bytes read so far by the search in progress.
+    pub fn bytes_read(&self) -> usize {
+        self.scanner.bytes_read()
+    }
+
+    /// Returns the number of chunks processed in a single poll before yielding.
+    pub(crate) fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Searches `available`, the data currently buffered by the reader, together with the data
+    /// read before.
+    ///
+    /// Returns whether the search has finished and how many bytes of `available` to consume.
+    pub(crate) fn step<N: Needle + ?Sized>(
+        &mut self,
+        needle: &N,
+        available: &[u8],
+        before: &mut Vec<u8>,
+        matched: &mut Vec<u8>,
+    ) -> (bool, usize) {
+        let span = self.span.get_or_insert_with(|| trace::read_span(needle));
+        if available.is_empty() {
+            // EOF reached
+            return (true, 0);
+        }
+        span.in_scope(|| {
+            if self.resume && self.scanner.bytes_read() == 0 {
+                // nothing read yet, so the end of `before` is what the last search left
+                self.scanner.resume(needle, before);
+            }
+            let step = self.scanner.push(needle, available);
+            step.apply(available, before, matched);
+            (step.is_done(), step.consumed())
+        })
+    }
+
+    /// Returns the outcome of the search and resets the state for the next search.
+    pub(crate) fn finish(&mut self) -> UntilNeedleStatus {
+        let status = self.scanner.finish();
+        self.end(status)
+    }
+
+    /// Ends the search with the status made by `status` from the number of bytes read, and
+    /// resets the state for the next search.
+    pub(crate) fn finish_with(
+        &mut self,
+        status: impl FnOnce(usize) -> UntilNeedleStatus,
+    ) -> UntilNeedleStatus {
+        let status = status(self.scanner.finish().bytes_read());
+        self.end(status)
+    }
+
+    fn end(&mut self, status: UntilNeedleStatus) -> UntilNeedleStatus {
+        match self.span.take() {
+            Some(span) => span.in_scope(|| trace::outcome(&status)),
+            None => trace::outcome(&status),
+        }
+        status
+    }
+}
+
+/// Returns the number of bytes read by a search, failing with [`LimitExceeded`] if it reached
+/// its limit, for the futures that report only the number of bytes read.
+pub(crate) fn limit_exceeded(status: UntilNeedleStatus) -> io::Result<usize> {
+    match status {
+        UntilNeedleStatus::LimitReached { bytes_read } => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            LimitExceeded { limit: bytes_read },
+        )),
+        status => Ok(status.bytes_read()),
+    }
+}
@@ -0,0 +1,348 @@
+use crate::Needle;
+use bytes::{Buf, Bytes, BytesMut};
+use futures_core::ready;
+use futures_core::stream::{FusedStream, Stream};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Splits a stream of byte chunks into the segments between occurrences of `needle`.
+///
+/// The returned stream yields the data between needles, without the needles themselves, and the
+/// data after the last needle once the input stream ends, unless it is empty. Errors of the
+/// input stream are passed through unchanged, and splitting continues with the next chunk.
+///
+/// Needles split across chunks are found. A segment that lies entirely within one chunk is a
+/// slice of that chunk and is not copied; only segments spanning several chunks are assembled
+/// in a new buffer. For needles with a [maximum length](Needle::max_len), only the last
+/// `max_len - 1` bytes of the previous chunks are searched again when a chunk arrives.
+///
+/// # Example
+/// ```
+/// use bytes::Bytes;
+/// use futures::{executor::block_on, stream, TryStreamExt as _};
+///
+/// let chunks = stream::iter(vec![
+///     Ok::<_, std::io::Error>(Bytes::from_static(b"one\r")),
+///     Ok(Bytes::from_static(b"\ntwo\r\nthr")),
+///     Ok(Bytes::from_static(b"ee")),
+/// ]);
+/// let segments: Vec<Bytes> = block_on(until_needle::stream::split(chunks, b"\r\n").try_collect())
+///     .unwrap();
+/// assert_eq!(segments, vec!["one", "two", "three"]);
+/// ```
+pub fn split<S, N>(stream: S, needle: N) -> Split<S, N> {
+    Split {
+        stream,
+        needle,
+        carry: BytesMut::new(),
+        scan_from: 0,
+        chunk: Bytes::new(),
+        done: false,
+    }
+}
+
+/// An extension trait to split streams of byte chunks at a needle, such as HTTP or gRPC bodies.
+///
+/// The chunks are scanned directly, without going through `AsyncRead`, and the error type of
+/// the stream is kept.
+///
+/// # Example
+/// ```
+/// use bytes::Bytes;
+/// use futures::{executor::block_on, stream, TryStreamExt as _};
+/// use until_needle::stream::TryStreamUntilNeedleExt;
+///
+/// #[derive(Debug)]
+/// struct BodyError;
+///
+/// let body = stream::iter([
+///     Ok::<_, BodyError>(Bytes::from_static(b"{\"a\":1}\n{\"a\"")),
+///     Ok(Bytes::from_static(b":2}\n")),
+/// ]);
+/// let records: Vec<Bytes> = block_on(body.split_by_needle(b"\n").try_collect()).unwrap();
+/// assert_eq!(records, vec![r#"{"a":1}"#, r#"{"a":2}"#]);
+/// ```
+pub trait TryStreamUntilNeedleExt: Sized {
+    /// Splits the stream into the segments between occurrences of `needle`.
+    ///
+    /// This is [`split`] as a method.
+    fn split_by_needle<N: Needle>(self, needle: N) -> Split<Self, N> {
+        split(self, needle)
+    }
+}
+
+impl<S, B, E> TryStreamUntilNeedleExt for S
+where
+    S: Stream<Item = Result<B, E>>,
+    B: Into<Bytes>,
+{
+}
+
+/// A stream of the segments of a chunk stream between occurrences of a needle.
+///
+/// This struct is created by [`split`] and [`TryStreamUntilNeedleExt::split_by_needle`].
+#[derive(Debug)]
+pub struct Split<S, N> {
+    stream: S,
+    needle: N,
+    /// Data of previous chunks that belongs to the current segment.
+    carry: BytesMut,
+    /// The offset in `carry` from which a needle may still start.
+    scan_from: usize,
+    /// The part of the last chunk that has not been split yet.
+    chunk: Bytes,
+    done: bool,
+}
+
+impl<S, N> Split<S, N> {
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Consumes the `Split`, returning the underlying stream.
+    ///
+    /// Data received but not yet yielded as a segment is lost.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S, N: Needle> Split<S, N> {
+    /// Splits the next segment off the pending data, if its needle has been received.
+    fn next_segment(&mut self) -> Option<Bytes> {
+        if self.chunk.is_empty() {
+            return None;
+        }
+        if self.carry.is_empty() {
+            // the segment may lie within the chunk
+            return match self.needle.findin(&self.chunk) {
+                Some(range) => {
+                    let segment = self.chunk.split_to(range.start);
+                    self.chunk.advance(range.end - range.start);
+                    Some(segment)
+                }
+                None => {
+                    self.carry_chunk();
+                    None
+                }
+            };
+        }
+
+        let max_len = match self.needle.max_len() {
+            Some(len) => len,
+            None => {
+                // an unbounded needle may start anywhere in the segment
+                self.carry.extend_from_slice(&self.chunk);
+                self.chunk.clear();
+                let range = self.needle.findin(&self.carry)?;
+                let segment = self.carry.split_to(range.start).freeze();
+                self.carry.advance(range.end - range.start);
+                self.chunk = self.carry.split().freeze();
+                self.scan_from = 0;
+                return Some(segment);
+            }
+        };
+
+        // a needle starting in the carried data ends within the first `max_len - 1` bytes
+        let tail = &self.carry[self.scan_from..];
+        let head = &self.chunk[..self.chunk.len().min(max_len.saturating_sub(1))];
+        let mut window = Vec::with_capacity(tail.len() + head.len());
+        window.extend_from_slice(tail);
+        window.extend_from_slice(head);
+        if let Some(range) = self.needle.findin(&window) {
+            if range.start < tail.len() {
+                let tail_len = tail.len();
+                self.carry.truncate(self.scan_from + range.start);
+                self.chunk.advance(range.end - tail_len);
+                self.scan_from = 0;
+                return Some(self.carry.split().freeze());
+            }
+        }
+
+        match self.needle.findin(&self.chunk) {
+            Some(range) => {
+                self.carry.extend_from_slice(&self.chunk[..range.start]);
+                self.chunk.advance(range.end);
+                self.scan_from = 0;
+                Some(self.carry.split().freeze())
+            }
+            None => {
+                self.carry_chunk();
+                None
+            }
+        }
+    }
+
+    /// Moves the pending chunk to the carried data of the current segment.
+    fn carry_chunk(&mut self) {
+        self.carry.extend_from_slice(&self.chunk);
+        self.chunk.clear();
+        if let Some(len) = self.needle.max_len() {
+            let scan_from = self.carry.len().saturating_sub(len.saturating_sub(1));
+            self.scan_from = self.scan_from.max(scan_from);
+        }
+    }
+}
+
+impl<S, B, E, N> Stream for Split<S, N>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: Into<Bytes>,
+    N: Needle + Unpin,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if this.done {
+            return Poll::Ready(None);
+        }
+        loop {
+            if let Some(segment) = this.next_segment() {
+                return Poll::Ready(Some(Ok(segment)));
+            }
+            match ready!(Pin::new(&mut this.stream).poll_next(cx)) {
+                Some(Ok(chunk)) => this.chunk = chunk.into(),
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    this.done = true;
+                    this.scan_from = 0;
+                    if this.carry.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                    return Poll::Ready(Some(Ok(this.carry.split().freeze())));
+                }
+            }
+        }
+    }
+}
+
+impl<S, B, E, N> FusedStream for Split<S, N>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: Into<Bytes>,
+    N: Needle + Unpin,
+{
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, stream::iter, StreamExt as _};
+
+    /// A literal needle that does not report its maximum length.
+    struct Unbounded(&'static [u8]);
+
+    impl Needle for Unbounded {
+        fn findin(&self, haystack: &[u8]) -> Option<std::ops::Range<usize>> {
+            self.0.findin(haystack)
+        }
+    }
+
+    /// Splits `data` at each occurrence of `needle`, as a reference for `split`.
+    fn reference_split(data: &[u8], needle: &[u8]) -> Vec<Vec<u8>> {
+        let mut segments = Vec::new();
+        let mut start = 0;
+        let mut i = 0;
+        while i + needle.len() <= data.len() {
+            if &data[i..i + needle.len()] == needle {
+                segments.push(data[start..i].to_vec());
+                i += needle.len();
+                start = i;
+            } else {
+                i += 1;
+            }
+        }
+        if start < data.len() {
+            segments.push(data[start..].to_vec());
+        }
+        segments
+    }
+
+    /// Cuts `data` into chunks of 1 to 3 bytes.
+    fn chunks(data: &[u8], mut seed: u32) -> Vec<Result<Bytes, ()>> {
+        let mut chunks = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let len = ((seed >> 16) as usize % 3 + 1).min(rest.len());
+            chunks.push(Ok(Bytes::copy_from_slice(&rest[..len])));
+            rest = &rest[len..];
+        }
+        chunks
+    }
+
+    fn collect<N: Needle + Unpin>(chunks: Vec<Result<Bytes, ()>>, needle: N) -> Vec<Vec<u8>> {
+        block_on(
+            split(iter(chunks), needle)
+                .map(|s| s.unwrap().to_vec())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_split_small_chunks() {
+        let data = b"ab<>|cd<><>|<>|<>|e<|f<>|g<>";
+        for needle in [&b"<>|"[..], b"<>", b"|"] {
+            let expected = reference_split(data, needle);
+            for seed in 0..50 {
+                assert_eq!(collect(chunks(data, seed), needle), expected);
+                assert_eq!(collect(chunks(data, seed), Unbounded(needle)), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_zero_copy() {
+        let chunk = Bytes::from_static(b"one\r\ntwo\r\nthr");
+        let chunks: Vec<Result<_, ()>> = vec![Ok(chunk.clone()), Ok(Bytes::from_static(b"ee"))];
+        let segments: Vec<Bytes> =
+            block_on(split(iter(chunks), b"\r\n").map(Result::unwrap).collect());
+        assert_eq!(segments, vec!["one", "two", "three"]);
+        // the segments within the first chunk share its memory
+        assert_eq!(segments[0].as_ptr(), chunk.as_ptr());
+        assert_eq!(segments[1].as_ptr(), chunk[5..].as_ptr());
+    }
+
+    #[test]
+    fn test_split_by_needle() {
+        let chunks: Vec<Result<_, ()>> = vec![Ok(&b"a<"[..]), Ok(b">b<>"), Ok(b"c")];
+        let segments: Vec<Bytes> = block_on(
+            iter(chunks)
+                .split_by_needle(b"<>")
+                .map(Result::unwrap)
+                .collect(),
+        );
+        assert_eq!(segments, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_split_error() {
+        #[derive(Debug, PartialEq)]
+        struct MyError;
+
+        let chunks = vec![
+            Ok(Bytes::from_static(b"a\nb")),
+            Err(MyError),
+            Ok(Bytes::from_static(b"c\n")),
+        ];
+        let items: Vec<_> = block_on(split(iter(chunks), b"\n").collect());
+        assert_eq!(
+            items,
+            vec![
+                Ok(Bytes::from_static(b"a")),
+                Err(MyError),
+                Ok(Bytes::from_static(b"bc"))
+            ]
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! Telnet option negotiation filtered out of a stream.
+
+use std::io::{self, BufRead, Read, Write};
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+/// A telnet transport exposing only the application data to readers.
+///
+/// Telnet interleaves commands starting with the IAC byte with the output of the remote
+/// device, which defeats matching prompts in it. This wrapper removes the commands and the
+/// subnegotiations from what is read, and refuses every option the peer offers or asks for by
+/// answering `WILL` with `DONT` and `DO` with `WONT` on the transport. An escaped `IAC IAC` is
+/// read as a single 255 byte, and 255 bytes written are escaped the same way.
+///
+/// # Example
+/// ```no_run
+/// use std::net::TcpStream;
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::telnet::TelnetFiltered;
+///
+/// let stream = TcpStream::connect("192.0.2.1:23")?;
+/// let mut telnet = TelnetFiltered::new(stream);
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// telnet.read_until_needle("login: ", &mut before, &mut matched)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct TelnetFiltered<T> {
+    inner: T,
+    /// Application data not yet consumed.
+    buf: Vec<u8>,
+    pos: usize,
+    /// Raw bytes of a command cut off at the end of the last read.
+    partial: Vec<u8>,
+}
+
+impl<T> TelnetFiltered<T> {
+    /// Wraps the telnet transport `inner`.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            pos: 0,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Gets a reference to the underlying transport.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying transport.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwraps this `TelnetFiltered`, returning the underlying transport.
+    ///
+    /// Application data read from it but not consumed yet is lost.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// Splits `raw` into application data and the replies to its commands.
+///
+/// Returns the number of bytes parsed; the rest is a command cut off at the end of `raw`.
+fn parse(raw: &[u8], data: &mut Vec<u8>, replies: &mut Vec<u8>) -> usize {
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] != IAC {
+            let end = raw[i..]
+                .iter()
+                .position(|&b| b == IAC)
+                .map_or(raw.len(), |n| i + n);
+            data.extend_from_slice(&raw[i..end]);
+            i = end;
+            continue;
+        }
+        let Some(&command) = raw.get(i + 1) else {
+            return i;
+        };
+        match command {
+            IAC => {
+                data.push(IAC);
+                i += 2;
+            }
+            WILL | WONT | DO | DONT => {
+                let Some(&option) = raw.get(i + 2) else {
+                    return i;
+                };
+                match command {
+                    WILL => replies.extend_from_slice(&[IAC, DONT, option]),
+                    DO => replies.extend_from_slice(&[IAC, WONT, option]),
+                    _ => {}
+                }
+                i += 3;
+            }
+            SB => {
+                let end = raw[i + 2..].windows(2).position(|w| w == [IAC, SE]);
+                match end {
+                    Some(n) => i += 2 + n + 2,
+                    None => return i,
+                }
+            }
+            _ => i += 2,
+        }
+    }
+    i
+}
+
+impl<T: Read + Write> Read for TelnetFiltered<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<T: Read + Write> BufRead for TelnetFiltered<T> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let mut chunk = [0; 4096];
+        while self.pos == self.buf.len() {
+            let n = match self.inner.read(&mut chunk) {
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if n == 0 {
+                // EOF, dropping a command cut off by it
+                self.partial.clear();
+                break;
+            }
+            self.partial.extend_from_slice(&chunk[..n]);
+            self.buf.clear();
+            self.pos = 0;
+            let mut replies = Vec::new();
+            let parsed = parse(&self.partial, &mut self.buf, &mut replies);
+            self.partial.drain(..parsed);
+            if !replies.is_empty() {
+                self.inner.write_all(&replies)?;
+                self.inner.flush()?;
+            }
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buf.len());
+    }
+}
+
+impl<T: Write> Write for TelnetFiltered<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut escaped = Vec::with_capacity(buf.len());
+        for &b in buf {
+            escaped.push(b);
+            if b == IAC {
+                escaped.push(IAC);
+            }
+        }
+        self.inner.write_all(&escaped)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::UntilNeedleRead;
+    use crate::testing::ChunkedReader;
+
+    /// A transport reading from chunks and recording what is written to it.
+    struct Transport {
+        input: ChunkedReader,
+        output: Vec<u8>,
+    }
+
+    impl Read for Transport {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Transport {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let (mut data, mut replies) = (Vec::new(), Vec::new());
+        let raw = [
+            b'a', IAC, WILL, 1, b'b', IAC, IAC, IAC, DO, 24, IAC, 241, b'c',
+        ];
+        assert_eq!(parse(&raw, &mut data, &mut replies), raw.len());
+        assert_eq!(data, [b'a', b'b', IAC, b'c']);
+        assert_eq!(replies, [IAC, DONT, 1, IAC, WONT, 24]);
+
+        let (mut data, mut replies) = (Vec::new(), Vec::new());
+        let raw = [b'x', IAC, SB, 24, 1, IAC, SE, b'y', IAC, SB, 31];
+        assert_eq!(parse(&raw, &mut data, &mut replies), 8);
+        assert_eq!(data, b"xy");
+        assert!(replies.is_empty());
+        assert_eq!(parse(&[IAC, DO], &mut data, &mut replies), 0);
+    }
+
+    #[test]
+    fn test_read_until_needle() {
+        let mut raw = vec![IAC, DO, 1, IAC, WILL, 3];
+        raw.extend_from_slice(b"Welcome\r\n");
+        raw.extend_from_slice(&[IAC, SB, 24, 1, IAC, SE]);
+        raw.extend_from_slice(b"log");
+        raw.extend_from_slice(&[IAC, 241]);
+        raw.extend_from_slice(b"in: ");
+        for size in 1..raw.len() {
+            let input = ChunkedReader::new(raw.clone(), [size]);
+            let mut telnet = TelnetFiltered::new(Transport {
+                input,
+                output: Vec::new(),
+            });
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            telnet
+                .read_until_needle("login: ", &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(before, b"Welcome\r\n");
+            assert_eq!(matched, b"login: ");
+
+            telnet.write_all(&[b'p', IAC]).unwrap();
+            let output = telnet.into_inner().output;
+            assert_eq!(output, [IAC, WONT, 1, IAC, DONT, 3, b'p', IAC, IAC]);
+        }
+    }
+}
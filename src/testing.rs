@@ -0,0 +1,483 @@
+//! Readers for testing code that reads until a needle.
+//!
+//! Reads over a network or a pipe deliver data in pieces of unpredictable size, are sometimes
+//! not ready, and fail now and then. These readers reproduce that from in-memory data, so a test
+//! can check that a needle straddling two reads is found and that errors are handled.
+//!
+//! This module is available with the `testing` feature.
+
+use std::io;
+#[cfg(any(feature = "futures", feature = "tokio"))]
+use std::pin::Pin;
+#[cfg(any(feature = "futures", feature = "tokio"))]
+use std::task::{Context, Poll};
+
+/// In-memory data delivered in chunks of given sizes.
+#[derive(Debug, Clone)]
+struct Chunks {
+    data: Vec<u8>,
+    pos: usize,
+    sizes: Vec<usize>,
+    /// The index in `sizes` of the size of the next chunk.
+    next: usize,
+    /// The end of the current chunk, or `pos` if a new chunk is to start.
+    end: usize,
+}
+
+impl Chunks {
+    fn new(data: impl Into<Vec<u8>>, sizes: impl IntoIterator<Item = usize>) -> Self {
+        let sizes: Vec<usize> = sizes.into_iter().collect();
+        assert!(
+            !sizes.is_empty() && sizes.iter().all(|&size| size > 0),
+            "chunk sizes must not be empty or zero"
+        );
+        Self {
+            data: data.into(),
+            pos: 0,
+            sizes,
+            next: 0,
+            end: 0,
+        }
+    }
+
+    fn from_chunks<C: AsRef<[u8]>>(chunks: impl IntoIterator<Item = C>) -> Self {
+        let mut data = Vec::new();
+        let mut sizes = Vec::new();
+        for chunk in chunks {
+            let chunk = chunk.as_ref();
+            if !chunk.is_empty() {
+                data.extend_from_slice(chunk);
+                sizes.push(chunk.len());
+            }
+        }
+        if sizes.is_empty() {
+            sizes.push(1);
+        }
+        Self::new(data, sizes)
+    }
+
+    /// Returns `true` if the next call to `available` starts a new chunk.
+    #[cfg(any(feature = "futures", feature = "tokio"))]
+    fn at_boundary(&self) -> bool {
+        self.pos == self.end && self.pos < self.data.len()
+    }
+
+    fn available(&mut self) -> &[u8] {
+        if self.pos == self.end {
+            let size = self.sizes[self.next % self.sizes.len()];
+            if self.pos < self.data.len() {
+                self.next += 1;
+            }
+            self.end = (self.pos + size).min(self.data.len());
+        }
+        &self.data[self.pos..self.end]
+    }
+
+    fn consume(&mut self, amt: usize) {
+        assert!(self.pos + amt <= self.end, "consumed more than available");
+        self.pos += amt;
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let available = self.available();
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        n
+    }
+}
+
+/// A reader delivering in-memory data in chunks of given sizes.
+///
+/// Each chunk is returned by a separate `fill_buf` call, so a needle straddling two of them is
+/// seen in two pieces. The sizes are used in turn, starting over after the last one.
+///
+/// # Example
+/// ```
+/// use std::io::BufRead;
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::testing::ChunkedReader;
+///
+/// let mut reader = ChunkedReader::new(b"hello\r\nworld", [3, 1]);
+/// assert_eq!(reader.fill_buf().unwrap(), b"hel");
+///
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader.read_until_needle(b"\r\n", &mut before, &mut matched).unwrap();
+/// assert_eq!(before, b"hello");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChunkedReader {
+    chunks: Chunks,
+}
+
+impl ChunkedReader {
+    /// Creates a reader delivering `data` in chunks of `chunk_sizes` bytes.
+    ///
+    /// # Panics
+    /// Panics if `chunk_sizes` is empty or contains zero.
+    pub fn new(data: impl Into<Vec<u8>>, chunk_sizes: impl IntoIterator<Item = usize>) -> Self {
+        Self {
+            chunks: Chunks::new(data, chunk_sizes),
+        }
+    }
+
+    /// Creates a reader delivering each of `chunks` in turn. Empty chunks are skipped.
+    pub fn from_chunks<C: AsRef<[u8]>>(chunks: impl IntoIterator<Item = C>) -> Self {
+        Self {
+            chunks: Chunks::from_chunks(chunks),
+        }
+    }
+
+    /// Returns the number of bytes consumed so far.
+    pub fn position(&self) -> usize {
+        self.chunks.pos
+    }
+}
+
+impl io::Read for ChunkedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.chunks.read(buf))
+    }
+}
+
+impl io::BufRead for ChunkedReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.chunks.available())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.chunks.consume(amt)
+    }
+}
+
+/// An async reader delivering in-memory data in chunks of given sizes, optionally returning
+/// `Poll::Pending` before each chunk.
+///
+/// This is [`ChunkedReader`] for async code. With [`pending`](Self::pending), it reports that it
+/// is not ready before delivering each chunk, waking the task right away, which exercises the
+/// code that resumes a read in a later poll.
+///
+/// It implements the `AsyncBufRead` traits of `futures` and `tokio`, as enabled by their
+/// features.
+///
+/// # Example
+/// ```
+/// use until_needle::futures::AsyncUntilNeedleRead;
+/// use until_needle::testing::ChunkedAsyncReader;
+///
+/// # futures::executor::block_on(async {
+/// let mut reader = ChunkedAsyncReader::new(b"hello\r\nworld", [1]).pending(2);
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader
+///     .read_until_needle(b"\r\n", &mut before, &mut matched)
+///     .await
+///     .unwrap();
+/// assert_eq!(before, b"hello");
+/// assert_eq!(reader.pending_count(), 14);
+/// # });
+/// ```
+#[cfg(any(feature = "futures", feature = "tokio"))]
+#[derive(Debug, Clone)]
+pub struct ChunkedAsyncReader {
+    chunks: Chunks,
+    /// How many times to return `Poll::Pending` before each chunk.
+    pending: usize,
+    /// How many times `Poll::Pending` is still to be returned before the next chunk.
+    pending_left: usize,
+    pending_count: usize,
+}
+
+#[cfg(any(feature = "futures", feature = "tokio"))]
+impl ChunkedAsyncReader {
+    /// Creates a reader delivering `data` in chunks of `chunk_sizes` bytes.
+    ///
+    /// # Panics
+    /// Panics if `chunk_sizes` is empty or contains zero.
+    pub fn new(data: impl Into<Vec<u8>>, chunk_sizes: impl IntoIterator<Item = usize>) -> Self {
+        Self {
+            chunks: Chunks::new(data, chunk_sizes),
+            pending: 0,
+            pending_left: 0,
+            pending_count: 0,
+        }
+    }
+
+    /// Creates a reader delivering each of `chunks` in turn. Empty chunks are skipped.
+    pub fn from_chunks<C: AsRef<[u8]>>(chunks: impl IntoIterator<Item = C>) -> Self {
+        Self {
+            chunks: Chunks::from_chunks(chunks),
+            pending: 0,
+            pending_left: 0,
+            pending_count: 0,
+        }
+    }
+
+    /// Sets how many times `Poll::Pending` is returned before each chunk. Defaults to 0.
+    pub fn pending(mut self, times: usize) -> Self {
+        self.pending = times;
+        self.pending_left = times;
+        self
+    }
+
+    /// Returns the number of bytes consumed so far.
+    pub fn position(&self) -> usize {
+        self.chunks.pos
+    }
+
+    /// Returns how many times `Poll::Pending` has been returned.
+    pub fn pending_count(&self) -> usize {
+        self.pending_count
+    }
+
+    fn poll_available(&mut self, cx: &mut Context<'_>) -> Poll<&[u8]> {
+        if self.chunks.at_boundary() {
+            if self.pending_left > 0 {
+                self.pending_left -= 1;
+                self.pending_count += 1;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.pending_left = self.pending;
+        }
+        Poll::Ready(self.chunks.available())
+    }
+
+    fn poll_read_into(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<usize> {
+        let n = match self.poll_available(cx) {
+            Poll::Ready(available) => available.len().min(buf.len()),
+            Poll::Pending => return Poll::Pending,
+        };
+        Poll::Ready(self.chunks.read(&mut buf[..n]))
+    }
+}
+
+#[cfg(feature = "futures")]
+impl futures_util::io::AsyncRead for ChunkedAsyncReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_read_into(cx, buf).map(Ok)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl futures_util::io::AsyncBufRead for ChunkedAsyncReader {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.get_mut().poll_available(cx).map(Ok)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().chunks.consume(amt)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl ::tokio::io::AsyncRead for ChunkedAsyncReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ::tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let n = match this.poll_read_into(cx, buf.initialize_unfilled()) {
+            Poll::Ready(n) => n,
+            Poll::Pending => return Poll::Pending,
+        };
+        buf.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl ::tokio::io::AsyncBufRead for ChunkedAsyncReader {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.get_mut().poll_available(cx).map(Ok)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().chunks.consume(amt)
+    }
+}
+
+/// A reader wrapper failing once with an error of a given kind after a given number of bytes.
+///
+/// The bytes up to the offset are delivered as by the inner reader, the next read fails, and
+/// the reads after it continue with the rest of the data. Errors of kind `Interrupted` are
+/// retried by the readers of this crate, while other kinds are returned to the caller.
+///
+/// It implements `BufRead`, and `futures`' `AsyncBufRead` when the `futures` feature is enabled.
+///
+/// # Example
+/// ```
+/// use std::io::ErrorKind;
+/// use until_needle::io::UntilNeedleRead;
+/// use until_needle::testing::{ChunkedReader, ErrorAfter};
+///
+/// let inner = ChunkedReader::new(b"hello\r\nworld", [4]);
+/// let mut reader = ErrorAfter::new(inner, 6, ErrorKind::ConnectionReset);
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// let err = reader.read_until_needle(b"\r\n", &mut before, &mut matched).unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::ConnectionReset);
+/// // the data read before the error is kept
+/// assert_eq!(before, b"hello\r");
+/// ```
+#[derive(Debug)]
+pub struct ErrorAfter<R> {
+    inner: R,
+    /// The number of bytes still to be delivered before the error.
+    left: usize,
+    kind: io::ErrorKind,
+    failed: bool,
+}
+
+impl<R> ErrorAfter<R> {
+    /// Wraps `inner`, failing with an error of `kind` once `n` bytes have been read.
+    pub fn new(inner: R, n: usize, kind: io::ErrorKind) -> Self {
+        Self {
+            inner,
+            left: n,
+            kind,
+            failed: false,
+        }
+    }
+
+    /// Returns `true` if the error has been returned.
+    pub fn has_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps this `ErrorAfter`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Returns the error if it is due, or how many bytes may be delivered before it.
+    fn check(&mut self) -> io::Result<usize> {
+        if self.failed {
+            return Ok(usize::MAX);
+        }
+        if self.left == 0 {
+            self.failed = true;
+            return Err(io::Error::new(self.kind, "injected error"));
+        }
+        Ok(self.left)
+    }
+
+    fn advance(&mut self, amt: usize) {
+        if !self.failed {
+            self.left -= amt;
+        }
+    }
+}
+
+impl<R: io::Read> io::Read for ErrorAfter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let max = self.check()?.min(buf.len());
+        let n = self.inner.read(&mut buf[..max])?;
+        self.advance(n);
+        Ok(n)
+    }
+}
+
+impl<R: io::BufRead> io::BufRead for ErrorAfter<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let max = self.check()?;
+        let available = self.inner.fill_buf()?;
+        Ok(&available[..available.len().min(max)])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.advance(amt);
+        self.inner.consume(amt)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<R: futures_util::io::AsyncRead + Unpin> futures_util::io::AsyncRead for ErrorAfter<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let max = this.check()?.min(buf.len());
+        let n = futures_core::ready!(Pin::new(&mut this.inner).poll_read(cx, &mut buf[..max]))?;
+        this.advance(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<R: futures_util::io::AsyncBufRead + Unpin> futures_util::io::AsyncBufRead for ErrorAfter<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        let max = this.check()?;
+        let available = futures_core::ready!(Pin::new(&mut this.inner).poll_fill_buf(cx))?;
+        Poll::Ready(Ok(&available[..available.len().min(max)]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        this.advance(amt);
+        Pin::new(&mut this.inner).consume(amt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, Read};
+
+    #[test]
+    fn test_chunked_reader() {
+        let mut reader = ChunkedReader::new(b"abcdefg", [2, 3]);
+        assert_eq!(reader.fill_buf().unwrap(), b"ab");
+        reader.consume(1);
+        // the rest of a chunk is returned until it is consumed
+        assert_eq!(reader.fill_buf().unwrap(), b"b");
+        reader.consume(1);
+        assert_eq!(reader.fill_buf().unwrap(), b"cde");
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"cdefg");
+        assert_eq!(reader.position(), 7);
+    }
+
+    #[test]
+    fn test_error_after() {
+        let inner = ChunkedReader::new(b"abcdefg", [5]);
+        let mut reader = ErrorAfter::new(inner, 3, io::ErrorKind::WouldBlock);
+        assert_eq!(reader.fill_buf().unwrap(), b"abc");
+        reader.consume(3);
+        assert_eq!(
+            reader.fill_buf().unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+        assert!(reader.has_failed());
+        assert_eq!(reader.fill_buf().unwrap(), b"de");
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn test_chunked_async_reader() {
+        use futures::AsyncReadExt as _;
+
+        let mut reader = ChunkedAsyncReader::new(b"abcdefg", [3]).pending(1);
+        let mut data = Vec::new();
+        futures::executor::block_on(reader.read_to_end(&mut data)).unwrap();
+        assert_eq!(data, b"abcdefg");
+        assert_eq!(reader.pending_count(), 3);
+    }
+}
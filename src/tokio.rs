@@ -0,0 +1,1812 @@
+use crate::needle::PartialMatch;
+use crate::output::{Discard, HeldSearch, OutputBuf};
+use crate::search::limit_exceeded;
+pub use crate::search::SearchState;
+use crate::{Needle, SkipReport, UntilNeedleOutput, UntilNeedleStatus};
+use ::tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+use ::tokio::time::{sleep, Instant, Sleep};
+#[cfg(feature = "bytes")]
+use bytes::{Bytes, BytesMut};
+use pin_project_lite::pin_project;
+use std::future::Future;
+use std::io::{self};
+use std::mem;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+/// The trait to extend tokio's `AsyncBufRead` for `read_until_needle` functionality.
+///
+/// It works directly on tokio readers, without compat adapters. Readers that only implement
+/// `AsyncRead`, such as `TcpStream` halves or `ChildStdout`, need to be wrapped in a
+/// [`tokio::io::BufReader`] first.
+///
+/// # Example
+/// ```
+/// use tokio::io::{AsyncWriteExt as _, BufReader};
+/// use until_needle::tokio::AsyncUntilNeedleRead;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let (client, mut server) = tokio::io::duplex(64);
+/// let (read_half, _write_half) = tokio::io::split(client);
+/// server.write_all(b"220 ready\r\n").await?;
+///
+/// let mut reader = BufReader::new(read_half);
+/// let (mut before, mut matched) = (Vec::new(), Vec::new());
+/// reader.read_until_needle(b"\r\n", &mut before, &mut matched).await?;
+/// assert_eq!(before, b"220 ready");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Readers that are not `Unpin`
+///
+/// The methods need `Self: Unpin`, which readers built from `async` code, such as a
+/// `tokio_util::io::StreamReader` over a generated stream, are not. A pinned reference
+/// `Pin<&mut R>` is `Unpin` and implements `AsyncBufRead` for any such `R`, so it works as the
+/// reader, without boxing: pin the reader once, with [`tokio::pin!`] or `Box::pin`, and call the
+/// methods on `reader.as_mut()`. [`poll_read_until_needle`] also takes a `Pin<&mut R>`.
+///
+/// ```
+/// use std::pin::Pin;
+/// use tokio::io::AsyncBufRead;
+/// use until_needle::tokio::AsyncUntilNeedleRead;
+///
+/// async fn read_line<R: AsyncBufRead + ?Sized>(
+///     mut reader: Pin<&mut R>,
+/// ) -> std::io::Result<Vec<u8>> {
+///     let (mut line, mut newline) = (Vec::new(), Vec::new());
+///     reader.read_until_needle(b"\n", &mut line, &mut newline).await?;
+///     Ok(line)
+/// }
+/// ```
+pub trait AsyncUntilNeedleRead: AsyncBufRead {
+    /// Asynchronously reads data from the underlying reader until the specified `needle` is found or EOF is reached.
+    ///
+    /// # Arguments
+    /// - `needle`: An object implementing the `Needle` trait, which defines the search pattern.
+    /// - `before`: A mutable buffer to store the data read before the `needle` is found.
+    /// - `matched`: A mutable buffer to store the `needle` itself, if found. If EOF is reached without finding
+    ///   the needle, this buffer will not be modified.
+    ///
+    /// # Returns
+    /// - On success, it returns the total number of bytes read, including the needle.
+    /// - If EOF is reached before the needle is found, `matched` will remain untouched.
+    ///
+    /// Each new chunk is searched together with the last [`Needle::max_len`] - 1 bytes read
+    /// before it, so memory and search time stay proportional to the data read. For needles
+    /// without a maximum length, such as regular expressions not wrapped in
+    /// [`WithMaxLen`](crate::needle::WithMaxLen), everything read since the call started is
+    /// searched again with each chunk.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe: every byte consumed from the reader has already been appended
+    /// to `before` whenever the future returns `Poll::Pending`. If the future is dropped, calling
+    /// this method again with the same buffers continues the read. The new call first searches
+    /// the end of `before` again, the last [`Needle::max_len`] - 1 bytes or the suffix told by
+    /// [`Needle::findin_partial`], so a needle straddling the point where the first future was
+    /// dropped is still found, and its beginning is moved from `before` to `matched`. For the
+    /// same reason, clear `before` between messages instead of appending a new message to data
+    /// that may begin the needle.
+    fn read_until_needle<'a, N>(
+        &'a mut self,
+        needle: N,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+    ) -> ReadUntilNeedle<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a;
+
+    /// Asynchronously reads data until the specified `needle` is found or EOF is reached, giving
+    /// up after `limit` bytes.
+    ///
+    /// This is [`read_until_needle`](Self::read_until_needle) with
+    /// [`ReadUntilNeedle::limit`]: if `limit` bytes were read without finding the needle, the
+    /// future fails with an error of kind `InvalidData` wrapping
+    /// [`LimitExceeded`](crate::LimitExceeded), and the bytes read are left in `before`.
+    fn read_until_needle_limited<'a, N>(
+        &'a mut self,
+        needle: N,
+        limit: usize,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+    ) -> ReadUntilNeedle<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a;
+
+    /// Asynchronously reads data until the specified `needle` is found, EOF is reached, or
+    /// `cancel` completes.
+    ///
+    /// This works like [`read_until_needle`](Self::read_until_needle), but stops at the next
+    /// opportunity once `cancel` completes, such as `CancellationToken::cancelled` from `tokio_util`.
+    ///
+    /// # Returns
+    /// - [`UntilNeedleStatus::Found`] or [`UntilNeedleStatus::Eof`] like `read_until_needle`.
+    /// - [`UntilNeedleStatus::Cancelled`] if `cancel` completed first. Everything read so far has
+    ///   been appended to `before` and the reader is left where the read stopped, so calling
+    ///   `read_until_needle` again with the same buffers continues from there, and still finds
+    ///   a needle whose beginning was read before the cancel.
+    fn read_until_needle_until<'a, N, C>(
+        &'a mut self,
+        needle: N,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+        cancel: C,
+    ) -> ReadUntilNeedleUntil<'a, Self, N, C>
+    where
+        Self: Unpin,
+        N: Needle + 'a,
+        C: Future<Output = ()>;
+
+    /// Asynchronously reads data until the specified `needle` is found, EOF is reached, or the
+    /// `timeout` passes.
+    ///
+    /// This works like [`read_until_needle`](Self::read_until_needle), but gives up once `timeout`
+    /// has passed since this method was called. An idle timeout, which fires when no data
+    /// arrives for a while, can be added with [`ReadUntilNeedleTimeout::idle_timeout`].
+    ///
+    /// # Returns
+    /// - [`UntilNeedleStatus::Found`] or [`UntilNeedleStatus::Eof`] like `read_until_needle`.
+    /// - [`UntilNeedleStatus::TimedOut`] if a time limit passed first. Everything read so far has
+    ///   been appended to `before` and the reader is left where the read stopped, so calling this
+    ///   method again continues from there.
+    fn read_until_needle_timeout<'a, N>(
+        &'a mut self,
+        needle: N,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+        timeout: Duration,
+    ) -> ReadUntilNeedleTimeout<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a;
+
+    /// Asynchronously reads data until the specified `needle` is found or EOF is reached,
+    /// returning owned buffers.
+    ///
+    /// This is [`read_until_needle`](Self::read_until_needle) for callers that just want the
+    /// data back, without checking reused buffers to tell whether the needle was found.
+    /// [`UntilNeedleOutput::matched`] is `None` if EOF was reached before the needle was found.
+    ///
+    /// # Example
+    /// ```
+    /// use until_needle::tokio::AsyncUntilNeedleRead;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> std::io::Result<()> {
+    /// let mut reader = &b"user=alice\nhome"[..];
+    /// let output = reader.read_until_needle_owned(b"\n").await?;
+    /// assert_eq!(output.matched.as_deref(), Some(&b"\n"[..]));
+    /// let output = reader.read_until_needle_owned(b"\n").await?;
+    /// assert_eq!((&output.before[..], output.matched), (&b"home"[..], None));
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn read_until_needle_owned<N>(&mut self, needle: N) -> ReadUntilNeedleOwned<'_, Self, N>
+    where
+        Self: Unpin,
+        N: Needle;
+
+    /// Asynchronously reads data until the specified `needle` is found or EOF is reached,
+    /// keeping the progress of the search in `state`.
+    ///
+    /// This is [`read_until_needle`](Self::read_until_needle) for reads raced in
+    /// `tokio::select!`. When the future is dropped, the search in progress is left in `state`,
+    /// so a new future with the same `state` and `before` continues it, also finding a needle
+    /// that straddles the point where the first future was dropped. The state is reset once the
+    /// future completes and can be used for the next search. Options such as a
+    /// [limit](SearchState::with_limit) are set on the state.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use tokio::io::AsyncWriteExt as _;
+    /// use until_needle::tokio::{AsyncUntilNeedleRead, SearchState};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> std::io::Result<()> {
+    /// let (client, mut server) = tokio::io::duplex(64);
+    /// let mut reader = tokio::io::BufReader::new(client);
+    /// let mut state = SearchState::new();
+    /// let (mut before, mut matched) = (Vec::new(), Vec::new());
+    ///
+    /// server.write_all(b"progress 50%\r\nlog").await?;
+    /// tokio::select! {
+    ///     _ = reader.read_until_needle_with_state("login: ", &mut state, &mut before, &mut matched) => {}
+    ///     _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+    /// }
+    /// server.write_all(b"in: ").await?;
+    /// reader
+    ///     .read_until_needle_with_state("login: ", &mut state, &mut before, &mut matched)
+    ///     .await?;
+    /// assert_eq!(before, b"progress 50%\r\n");
+    /// assert_eq!(matched, b"login: ");
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn read_until_needle_with_state<'a, N>(
+        &'a mut self,
+        needle: N,
+        state: &'a mut SearchState,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+    ) -> ReadUntilNeedleWithState<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a;
+
+    /// Asynchronously reads data until the specified `needle` is found or EOF is reached,
+    /// writing it to any [`OutputBuf`].
+    ///
+    /// This is the async counterpart of
+    /// [`UntilNeedleRead::read_until_needle_into_out`](crate::io::UntilNeedleRead::read_until_needle_into_out).
+    /// Only the bytes that may begin the needle are held by the future until the next chunk
+    /// shows they do not, as told by [`Needle::max_len`] or [`Needle::findin_partial`], so they
+    /// are lost if it is dropped.
+    fn read_until_needle_into_out<N, O, M>(
+        &mut self,
+        needle: N,
+        before: O,
+        matched: M,
+    ) -> ReadUntilNeedleIntoOut<'_, Self, N, O, M>
+    where
+        Self: Unpin,
+        N: Needle,
+        O: OutputBuf,
+        M: OutputBuf;
+
+    /// Asynchronously skips data until the specified `needle` is found or EOF is reached.
+    ///
+    /// This is the async counterpart of
+    /// [`UntilNeedleRead::skip_until_needle`](crate::io::UntilNeedleRead::skip_until_needle),
+    /// for reads that only need to get past a marker: the data before the needle is dropped as
+    /// it is read and only counted. The needle is consumed, so a following read starts right
+    /// after it.
+    fn skip_until_needle<N>(&mut self, needle: N) -> SkipUntilNeedle<'_, Self, N>
+    where
+        Self: Unpin,
+        N: Needle;
+
+    /// Creates an adapter reading from the underlying reader up to the next occurrence of
+    /// `needle`.
+    ///
+    /// The adapter implements [`AsyncRead`] and [`AsyncBufRead`] and reports EOF once the needle
+    /// is reached, so a bounded region can be handed to an async decoder or to
+    /// [`tokio::io::copy`]. Nothing past the needle is consumed from the underlying reader,
+    /// which is returned by [`TakeUntil::into_inner`] to continue reading after the needle.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::io::{AsyncReadExt as _, BufReader};
+    /// use until_needle::tokio::AsyncUntilNeedleRead;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> std::io::Result<()> {
+    /// let reader = BufReader::new(&b"{\"id\":1}\n\0{\"id\":2}"[..]);
+    /// let mut take = reader.take_until(b"\n\0");
+    /// let mut document = String::new();
+    /// take.read_to_string(&mut document).await?;
+    /// assert_eq!(document, r#"{"id":1}"#);
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn take_until<N>(self, needle: N) -> TakeUntil<Self, N>
+    where
+        Self: Unpin + Sized,
+        N: Needle;
+
+    /// Asynchronously reads data until the specified `needle` is found or EOF is reached,
+    /// accumulating it in a [`BytesMut`].
+    ///
+    /// This is the async counterpart of
+    /// [`UntilNeedleRead::read_until_needle_bytes`](crate::io::UntilNeedleRead::read_until_needle_bytes).
+    /// The data is appended to `buf` as it is read, so if the future is dropped, nothing read so
+    /// far is lost.
+    #[cfg(feature = "bytes")]
+    fn read_until_needle_bytes<'a, N>(
+        &'a mut self,
+        needle: N,
+        buf: &'a mut BytesMut,
+    ) -> ReadUntilNeedleBytes<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a;
+}
+
+impl<R> AsyncUntilNeedleRead for R
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+{
+    fn read_until_needle<'a, N>(
+        &'a mut self,
+        needle: N,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+    ) -> ReadUntilNeedle<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a,
+    {
+        ReadUntilNeedle {
+            reader: self,
+            needle,
+            state: SearchState::new().resuming(),
+            before,
+            matched,
+        }
+    }
+
+    fn read_until_needle_limited<'a, N>(
+        &'a mut self,
+        needle: N,
+        limit: usize,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+    ) -> ReadUntilNeedle<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a,
+    {
+        self.read_until_needle(needle, before, matched).limit(limit)
+    }
+
+    fn read_until_needle_until<'a, N, C>(
+        &'a mut self,
+        needle: N,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+        cancel: C,
+    ) -> ReadUntilNeedleUntil<'a, Self, N, C>
+    where
+        Self: Unpin,
+        N: Needle + 'a,
+        C: Future<Output = ()>,
+    {
+        ReadUntilNeedleUntil {
+            inner: self.read_until_needle(needle, before, matched),
+            cancel,
+        }
+    }
+
+    fn read_until_needle_timeout<'a, N>(
+        &'a mut self,
+        needle: N,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+        timeout: Duration,
+    ) -> ReadUntilNeedleTimeout<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a,
+    {
+        self.read_until_needle(needle, before, matched)
+            .timeout(timeout)
+    }
+
+    fn read_until_needle_owned<N>(&mut self, needle: N) -> ReadUntilNeedleOwned<'_, Self, N>
+    where
+        Self: Unpin,
+        N: Needle,
+    {
+        ReadUntilNeedleOwned {
+            reader: self,
+            needle,
+            state: SearchState::new(),
+            before: Vec::new(),
+            matched: Vec::new(),
+        }
+    }
+
+    fn read_until_needle_with_state<'a, N>(
+        &'a mut self,
+        needle: N,
+        state: &'a mut SearchState,
+        before: &'a mut Vec<u8>,
+        matched: &'a mut Vec<u8>,
+    ) -> ReadUntilNeedleWithState<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a,
+    {
+        ReadUntilNeedleWithState {
+            reader: self,
+            needle,
+            state,
+            before,
+            matched,
+        }
+    }
+
+    fn read_until_needle_into_out<N, O, M>(
+        &mut self,
+        needle: N,
+        before: O,
+        matched: M,
+    ) -> ReadUntilNeedleIntoOut<'_, Self, N, O, M>
+    where
+        Self: Unpin,
+        N: Needle,
+        O: OutputBuf,
+        M: OutputBuf,
+    {
+        ReadUntilNeedleIntoOut {
+            reader: self,
+            needle,
+            search: HeldSearch::default(),
+            before,
+            matched,
+        }
+    }
+
+    fn skip_until_needle<N>(&mut self, needle: N) -> SkipUntilNeedle<'_, Self, N>
+    where
+        Self: Unpin,
+        N: Needle,
+    {
+        SkipUntilNeedle {
+            inner: self.read_until_needle_into_out(needle, Discard, Vec::new()),
+        }
+    }
+
+    fn take_until<N>(self, needle: N) -> TakeUntil<Self, N>
+    where
+        Self: Unpin + Sized,
+        N: Needle,
+    {
+        TakeUntil {
+            inner: self,
+            needle,
+            consume_needle: true,
+            held: Vec::new(),
+            pos: 0,
+            readable: 0,
+            matched: Vec::new(),
+            found: false,
+            eof: false,
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    fn read_until_needle_bytes<'a, N>(
+        &'a mut self,
+        needle: N,
+        buf: &'a mut BytesMut,
+    ) -> ReadUntilNeedleBytes<'a, Self, N>
+    where
+        Self: Unpin,
+        N: Needle + 'a,
+    {
+        ReadUntilNeedleBytes {
+            reader: self,
+            needle,
+            scan_from: buf.len(),
+            buf,
+        }
+    }
+}
+
+/// A future that reads data until the specified needle is found.
+pub struct ReadUntilNeedle<'a, R, N>
+where
+    R: Unpin + ?Sized,
+{
+    reader: &'a mut R,
+    needle: N,
+    state: SearchState,
+    before: &'a mut Vec<u8>,
+    matched: &'a mut Vec<u8>,
+}
+
+impl<R: ?Sized + Unpin, N> ReadUntilNeedle<'_, R, N> {
+    /// Sets how many chunks the future processes in a single poll before yielding.
+    ///
+    /// When the reader always has data ready, the future would otherwise run the whole search in
+    /// one poll and starve other tasks on the same executor thread. After processing `chunks`
+    /// chunks, it wakes its task and returns `Poll::Pending` to let other futures run. This does
+    /// not change the result. Defaults to 128.
+    pub fn budget(mut self, chunks: usize) -> Self {
+        self.state = self.state.with_budget(chunks);
+        self
+    }
+
+    /// Sets the maximum number of bytes read before giving up on the needle.
+    ///
+    /// If the needle has not been found within `max_bytes` bytes, the future fails with an
+    /// error of kind `InvalidData` wrapping [`LimitExceeded`](crate::LimitExceeded), after
+    /// appending those bytes to `before`. This bounds the memory used when scanning untrusted
+    /// streams. Defaults to no limit.
+    pub fn limit(mut self, max_bytes: usize) -> Self {
+        self.state = self.state.with_limit(max_bytes);
+        self
+    }
+
+    /// Sets whether the needle is consumed from the reader.
+    ///
+    /// Defaults to `true`. When `false`, the needle is still copied to `matched`, but left in the
+    /// reader for the next read, as when it is the magic starting the next message. See
+    /// [`UntilNeedle::consume_needle`](crate::UntilNeedle::consume_needle) for a needle
+    /// straddling two buffers of the reader.
+    ///
+    /// # Example
+    /// ```
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> std::io::Result<()> {
+    /// use tokio::io::AsyncReadExt;
+    /// use until_needle::tokio::AsyncUntilNeedleRead;
+    ///
+    /// let mut reader = &b"first\x7fELFsecond"[..];
+    /// let (mut before, mut matched) = (Vec::new(), Vec::new());
+    /// let n = reader
+    ///     .read_until_needle(b"\x7fELF", &mut before, &mut matched)
+    ///     .consume_needle(false)
+    ///     .await?;
+    /// assert_eq!((n, &matched[..]), (5, &b"\x7fELF"[..]));
+    /// let mut rest = Vec::new();
+    /// reader.read_to_end(&mut rest).await?;
+    /// assert_eq!(rest, b"\x7fELFsecond");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn consume_needle(mut self, consume: bool) -> Self {
+        self.state = self.state.with_consume_needle(consume);
+        self
+    }
+}
+
+impl<'a, R: ?Sized + Unpin, N> ReadUntilNeedle<'a, R, N> {
+    /// Gives up on the needle once `timeout` has passed since this method was called.
+    ///
+    /// The returned future resolves to [`UntilNeedleStatus::TimedOut`] with the number of bytes
+    /// read if the time limit passes first. Those bytes have been appended to `before`, so they
+    /// are not lost as with a `tokio::time::timeout` wrapper.
+    ///
+    /// # Example
+    /// ```
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> std::io::Result<()> {
+    /// use std::time::Duration;
+    /// use tokio::io::AsyncWriteExt;
+    /// use until_needle::tokio::AsyncUntilNeedleRead;
+    /// use until_needle::UntilNeedleStatus;
+    ///
+    /// let (client, mut server) = tokio::io::duplex(64);
+    /// server.write_all(b"booting").await?;
+    /// let mut reader = tokio::io::BufReader::new(client);
+    /// let (mut before, mut matched) = (Vec::new(), Vec::new());
+    /// let status = reader
+    ///     .read_until_needle("$ ", &mut before, &mut matched)
+    ///     .timeout(Duration::from_millis(10))
+    ///     .await?;
+    /// assert_eq!(status, UntilNeedleStatus::TimedOut { bytes_read: 7 });
+    /// assert_eq!(before, b"booting");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn timeout(self, timeout: Duration) -> ReadUntilNeedleTimeout<'a, R, N> {
+        ReadUntilNeedleTimeout {
+            inner: self,
+            deadline: Some(Box::pin(sleep(timeout))),
+            idle: None,
+        }
+    }
+
+    /// Gives up on the needle once no data arrives for `timeout`, with no overall deadline.
+    ///
+    /// The timer restarts whenever the reader yields data, so a slow stream that keeps sending
+    /// output is read for as long as it takes. The returned future resolves to
+    /// [`UntilNeedleStatus::TimedOut`] like [`timeout`](Self::timeout). Use
+    /// [`ReadUntilNeedleTimeout::idle_timeout`] after `timeout` to have both limits.
+    pub fn idle_timeout(self, timeout: Duration) -> ReadUntilNeedleTimeout<'a, R, N> {
+        ReadUntilNeedleTimeout {
+            inner: self,
+            deadline: None,
+            idle: None,
+        }
+        .idle_timeout(timeout)
+    }
+}
+
+impl<R: ?Sized + Unpin, N> Unpin for ReadUntilNeedle<'_, R, N> {}
+
+impl<'a, R, N> Future for ReadUntilNeedle<'a, R, N>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+{
+    type Output = io::Result<usize>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ReadUntilNeedle {
+            reader,
+            needle,
+            state,
+            before,
+            matched,
+        } = &mut *self;
+        let reader = Pin::new(reader);
+        read_until_needle_internal(reader, cx, needle, state, before, matched)
+            .map(|status| status.and_then(limit_exceeded))
+    }
+}
+
+pin_project! {
+    /// A future that reads data until the specified needle is found or a cancel signal
+    /// completes.
+    ///
+    /// This struct is created by [`AsyncUntilNeedleRead::read_until_needle_until`].
+    pub struct ReadUntilNeedleUntil<'a, R, N, C>
+    where
+        R: Unpin,
+        R: ?Sized,
+    {
+        inner: ReadUntilNeedle<'a, R, N>,
+        #[pin]
+        cancel: C,
+    }
+}
+
+impl<R, N, C> Future for ReadUntilNeedleUntil<'_, R, N, C>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+    C: Future<Output = ()>,
+{
+    type Output = io::Result<UntilNeedleStatus>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let ReadUntilNeedle {
+            reader,
+            needle,
+            state,
+            before,
+            matched,
+        } = this.inner;
+
+        let reader = Pin::new(&mut **reader);
+        if let Poll::Ready(status) =
+            read_until_needle_internal(reader, cx, needle, state, before, matched)
+        {
+            return Poll::Ready(status);
+        }
+
+        ready!(this.cancel.poll(cx));
+        Poll::Ready(Ok(state.finish_with(|bytes_read| {
+            UntilNeedleStatus::Cancelled { bytes_read }
+        })))
+    }
+}
+
+/// A future that reads data until the specified needle is found or a time limit passes.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::read_until_needle_timeout`] and
+/// [`ReadUntilNeedle::timeout`].
+pub struct ReadUntilNeedleTimeout<'a, R, N>
+where
+    R: Unpin + ?Sized,
+{
+    inner: ReadUntilNeedle<'a, R, N>,
+    deadline: Option<Pin<Box<Sleep>>>,
+    idle: Option<(Duration, Pin<Box<Sleep>>)>,
+}
+
+impl<R: ?Sized + Unpin, N> ReadUntilNeedleTimeout<'_, R, N> {
+    /// Additionally gives up when no data arrives for `timeout`.
+    ///
+    /// The timer restarts whenever the reader yields data.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle = Some((timeout, Box::pin(sleep(timeout))));
+        self
+    }
+
+    /// Sets how many chunks the future processes in a single poll before yielding.
+    ///
+    /// See [`ReadUntilNeedle::budget`].
+    pub fn budget(mut self, chunks: usize) -> Self {
+        self.inner = self.inner.budget(chunks);
+        self
+    }
+}
+
+impl<'a, R, N> Future for ReadUntilNeedleTimeout<'a, R, N>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+{
+    type Output = io::Result<UntilNeedleStatus>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ReadUntilNeedleTimeout {
+            inner,
+            deadline,
+            idle,
+        } = &mut *self;
+        let ReadUntilNeedle {
+            reader,
+            needle,
+            state,
+            before,
+            matched,
+        } = inner;
+
+        let bytes_read = state.bytes_read();
+        let reader = Pin::new(&mut **reader);
+        if let Poll::Ready(status) =
+            read_until_needle_internal(reader, cx, needle, state, before, matched)
+        {
+            return Poll::Ready(status);
+        }
+
+        if let Some((timeout, sleep)) = idle {
+            if state.bytes_read() != bytes_read {
+                sleep.as_mut().reset(Instant::now() + *timeout);
+            }
+            if sleep.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Ok(timed_out(state)));
+            }
+        }
+        if let Some(deadline) = deadline {
+            if deadline.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Ok(timed_out(state)));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+/// A future that reads data until the specified needle is found, returning owned buffers.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::read_until_needle_owned`].
+pub struct ReadUntilNeedleOwned<'a, R, N>
+where
+    R: Unpin + ?Sized,
+{
+    reader: &'a mut R,
+    needle: N,
+    state: SearchState,
+    before: Vec<u8>,
+    matched: Vec<u8>,
+}
+
+impl<R: ?Sized + Unpin, N> Unpin for ReadUntilNeedleOwned<'_, R, N> {}
+
+impl<R, N> Future for ReadUntilNeedleOwned<'_, R, N>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+{
+    type Output = io::Result<UntilNeedleOutput>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ReadUntilNeedleOwned {
+            reader,
+            needle,
+            state,
+            before,
+            matched,
+        } = &mut *self;
+        let reader = Pin::new(&mut **reader);
+        let status = ready!(read_until_needle_internal(
+            reader, cx, needle, state, before, matched
+        ))?;
+        Poll::Ready(Ok(UntilNeedleOutput {
+            before: mem::take(before),
+            matched: status.is_found().then(|| mem::take(matched)),
+            bytes_read: status.bytes_read(),
+        }))
+    }
+}
+
+/// A future that reads data until the specified needle is found, with the search kept in a
+/// [`SearchState`] owned by the caller.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::read_until_needle_with_state`].
+pub struct ReadUntilNeedleWithState<'a, R, N>
+where
+    R: Unpin + ?Sized,
+{
+    reader: &'a mut R,
+    needle: N,
+    state: &'a mut SearchState,
+    before: &'a mut Vec<u8>,
+    matched: &'a mut Vec<u8>,
+}
+
+impl<R: ?Sized + Unpin, N> Unpin for ReadUntilNeedleWithState<'_, R, N> {}
+
+impl<R, N> Future for ReadUntilNeedleWithState<'_, R, N>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+{
+    type Output = io::Result<usize>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ReadUntilNeedleWithState {
+            reader,
+            needle,
+            state,
+            before,
+            matched,
+        } = &mut *self;
+        let reader = Pin::new(&mut **reader);
+        read_until_needle_internal(reader, cx, needle, state, before, matched)
+            .map(|status| status.and_then(limit_exceeded))
+    }
+}
+
+/// A future that reads data into an [`OutputBuf`] until the specified needle is found.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::read_until_needle_into_out`].
+pub struct ReadUntilNeedleIntoOut<'a, R, N, O, M>
+where
+    R: Unpin + ?Sized,
+{
+    reader: &'a mut R,
+    needle: N,
+    search: HeldSearch,
+    before: O,
+    matched: M,
+}
+
+impl<R: ?Sized + Unpin, N, O, M> Unpin for ReadUntilNeedleIntoOut<'_, R, N, O, M> {}
+
+impl<R, N, O, M> Future for ReadUntilNeedleIntoOut<'_, R, N, O, M>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+    O: OutputBuf,
+    M: OutputBuf,
+{
+    type Output = io::Result<UntilNeedleStatus>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            let (used, status) = {
+                let available = ready!(Pin::new(&mut *this.reader).poll_fill_buf(cx))?;
+                if available.is_empty() {
+                    // EOF
+                    return Poll::Ready(Ok(this.search.finish(&mut this.before)));
+                }
+                this.search
+                    .push(&this.needle, available, &mut this.before, &mut this.matched)
+            };
+
+            Pin::new(&mut *this.reader).consume(used);
+            if let Some(status) = status {
+                return Poll::Ready(Ok(status));
+            }
+        }
+    }
+}
+
+/// A future that skips data until the specified needle is found.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::skip_until_needle`].
+pub struct SkipUntilNeedle<'a, R, N>
+where
+    R: Unpin + ?Sized,
+{
+    inner: ReadUntilNeedleIntoOut<'a, R, N, Discard, Vec<u8>>,
+}
+
+impl<R, N> Future for SkipUntilNeedle<'_, R, N>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+{
+    type Output = io::Result<SkipReport>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let status = ready!(Pin::new(&mut self.inner).poll(cx))?;
+        let matched = mem::take(&mut self.inner.matched);
+        if !status.is_found() {
+            return Poll::Ready(Ok(SkipReport {
+                skipped: status.bytes_read(),
+                matched: None,
+            }));
+        }
+        Poll::Ready(Ok(SkipReport {
+            skipped: status.bytes_read() - matched.len(),
+            matched: Some(matched),
+        }))
+    }
+}
+
+/// A reader that reads from the underlying reader up to the next occurrence of a needle.
+///
+/// As long as the data buffered by the underlying reader is enough to rule out a needle, it is
+/// passed through without copying. Only the last bytes of a buffer that may begin a needle
+/// completed by the next buffer are taken from the underlying reader and held by the adapter
+/// until the next buffer decides it. Needles without a [maximum length](Needle::max_len) may
+/// span any number of buffers, so all data is held until the needle is found.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::take_until`].
+#[derive(Debug)]
+pub struct TakeUntil<R, N> {
+    inner: R,
+    needle: N,
+    consume_needle: bool,
+    /// Data taken from the inner reader; `held[pos..readable]` can be read through the adapter.
+    held: Vec<u8>,
+    pos: usize,
+    readable: usize,
+    matched: Vec<u8>,
+    found: bool,
+    eof: bool,
+}
+
+impl<R, N> TakeUntil<R, N> {
+    /// Sets whether the needle is consumed from the underlying reader once it is reached.
+    ///
+    /// Defaults to `true`, leaving the underlying reader positioned right after the needle. When
+    /// `false`, it is left positioned at the needle, so the needle is read next. If the needle
+    /// was split across buffers of the underlying reader, its beginning has already been taken
+    /// and is returned by [`into_inner`](Self::into_inner) instead.
+    pub fn consume_needle(mut self, consume: bool) -> Self {
+        self.consume_needle = consume;
+        self
+    }
+
+    /// Returns `true` if the needle has been reached, and `false` if it has not been reached yet
+    /// or the underlying reader reached EOF first.
+    pub fn is_found(&self) -> bool {
+        self.found
+    }
+
+    /// Returns the needle once it has been reached.
+    pub fn matched(&self) -> Option<&[u8]> {
+        self.found.then_some(&self.matched[..])
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps this `TakeUntil`, returning the underlying reader and the data taken from it that
+    /// has not been read through the adapter.
+    ///
+    /// The held data comes before anything still in the underlying reader.
+    pub fn into_inner(mut self) -> (R, Vec<u8>) {
+        self.held.drain(..self.pos);
+        (self.inner, self.held)
+    }
+}
+
+impl<R, N> AsyncRead for TakeUntil<R, N>
+where
+    R: AsyncBufRead + Unpin,
+    N: Needle + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let available = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let n = available.len().min(buf.remaining());
+        buf.put_slice(&available[..n]);
+        self.consume(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R, N> AsyncBufRead for TakeUntil<R, N>
+where
+    R: AsyncBufRead + Unpin,
+    N: Needle + Unpin,
+{
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        loop {
+            if this.pos < this.readable {
+                return Poll::Ready(Ok(&this.held[this.pos..this.readable]));
+            }
+            if this.found || this.eof {
+                return Poll::Ready(Ok(&[]));
+            }
+            if this.pos == this.held.len() {
+                this.held.clear();
+                this.pos = 0;
+                this.readable = 0;
+                // search the buffer of the inner reader; only the suffix that may begin a
+                // needle completed later is taken from it
+                let (pass, used) = {
+                    let available = ready!(Pin::new(&mut this.inner).poll_fill_buf(cx))?;
+                    if available.is_empty() {
+                        this.eof = true;
+                        continue;
+                    }
+                    match this.needle.findin_partial(available) {
+                        PartialMatch::Full(range) if range.start > 0 => (range.start, 0),
+                        PartialMatch::Full(range) => {
+                            this.found = true;
+                            this.matched.extend_from_slice(&available[range.clone()]);
+                            (0, if this.consume_needle { range.end } else { 0 })
+                        }
+                        PartialMatch::Prefix(start) if start > 0 => (start, 0),
+                        PartialMatch::Prefix(_) => {
+                            this.held.extend_from_slice(available);
+                            (0, available.len())
+                        }
+                        PartialMatch::None => (available.len(), 0),
+                    }
+                };
+                Pin::new(&mut this.inner).consume(used);
+                if pass > 0 {
+                    let available = ready!(Pin::new(&mut this.inner).poll_fill_buf(cx))?;
+                    return Poll::Ready(Ok(&available[..pass]));
+                }
+                continue;
+            }
+
+            // the held data may begin a needle; search it together with the next buffer
+            let used = {
+                let available = ready!(Pin::new(&mut this.inner).poll_fill_buf(cx))?;
+                let held_len = this.held.len() - this.pos;
+                if available.is_empty() {
+                    this.eof = true;
+                    this.readable = this.held.len();
+                    0
+                } else {
+                    // a needle starting in the held bytes ends within the next `max_len - 1`
+                    let head = match this.needle.max_len() {
+                        Some(len) => &available[..available.len().min(len.saturating_sub(1))],
+                        None => available,
+                    };
+                    let mut window = Vec::with_capacity(held_len + head.len());
+                    window.extend_from_slice(&this.held[this.pos..]);
+                    window.extend_from_slice(head);
+                    match this.needle.findin_partial(&window) {
+                        PartialMatch::Full(range) if range.start < held_len => {
+                            this.found = true;
+                            this.matched.extend_from_slice(&window[range.clone()]);
+                            this.readable = this.pos + range.start;
+                            if this.consume_needle {
+                                this.held.truncate(this.readable);
+                                range.end.saturating_sub(held_len)
+                            } else {
+                                0
+                            }
+                        }
+                        // the held bytes that cannot begin a needle are passed on
+                        PartialMatch::Prefix(start) if start > 0 => {
+                            this.readable = this.pos + start.min(held_len);
+                            0
+                        }
+                        PartialMatch::Prefix(_) => {
+                            this.held.extend_from_slice(available);
+                            available.len()
+                        }
+                        PartialMatch::Full(_) | PartialMatch::None => {
+                            this.readable = this.held.len();
+                            0
+                        }
+                    }
+                }
+            };
+            Pin::new(&mut this.inner).consume(used);
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        if this.pos < this.readable {
+            this.pos = (this.pos + amt).min(this.readable);
+        } else {
+            Pin::new(&mut this.inner).consume(amt);
+        }
+    }
+}
+
+/// A future that reads data into a [`BytesMut`] until the specified needle is found.
+///
+/// This struct is created by [`AsyncUntilNeedleRead::read_until_needle_bytes`].
+#[cfg(feature = "bytes")]
+pub struct ReadUntilNeedleBytes<'a, R, N>
+where
+    R: Unpin + ?Sized,
+{
+    reader: &'a mut R,
+    needle: N,
+    buf: &'a mut BytesMut,
+    scan_from: usize,
+}
+
+#[cfg(feature = "bytes")]
+impl<R: ?Sized + Unpin, N> Unpin for ReadUntilNeedleBytes<'_, R, N> {}
+
+#[cfg(feature = "bytes")]
+impl<R, N> Future for ReadUntilNeedleBytes<'_, R, N>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    N: Needle,
+{
+    type Output = io::Result<Option<(Bytes, Bytes)>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            let (found, used) = {
+                let available = ready!(Pin::new(&mut *this.reader).poll_fill_buf(cx))?;
+                if available.is_empty() {
+                    // EOF
+                    return Poll::Ready(Ok(None));
+                }
+                crate::io::search_bytes_mut(&this.needle, available, this.buf, &mut this.scan_from)
+            };
+
+            Pin::new(&mut *this.reader).consume(used);
+            if let Some(range) = found {
+                return Poll::Ready(Ok(Some(crate::io::split_bytes_mut(this.buf, range))));
+            }
+        }
+    }
+}
+
+/// Ends a search that ran out of time.
+fn timed_out(state: &mut SearchState) -> UntilNeedleStatus {
+    state.finish_with(|bytes_read| UntilNeedleStatus::TimedOut { bytes_read })
+}
+
+/// Attempts to read data from `reader` until `needle` is found or EOF is reached.
+///
+/// This is the poll-based counterpart of [`AsyncUntilNeedleRead::read_until_needle`], for use
+/// inside a hand-written [`Future`], a protocol state machine or a codec that cannot borrow the
+/// reader for the lifetime of a [`ReadUntilNeedle`] future. The progress of the search is kept
+/// in `state`, which must be passed together with the same `before` buffer on every call until
+/// `Poll::Ready` is returned. The state is then reset and can be used for the next search.
+///
+/// Returns `Poll::Ready(Ok(n))` with the total number of bytes read, including the needle, once
+/// the needle is found or EOF is reached. `Poll::Pending` is returned when the reader is not
+/// ready, or after the [budget](SearchState::with_budget) of chunks is used up. If the
+/// [limit](SearchState::with_limit) of the state is reached, an error of kind `InvalidData`
+/// wrapping [`LimitExceeded`](crate::LimitExceeded) is returned.
+///
+/// # Example
+/// ```
+/// use std::future::poll_fn;
+/// use std::pin::Pin;
+/// use until_needle::tokio::{poll_read_until_needle, SearchState};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let mut reader = &b"HELO relay\r\nMAIL FROM:<a@example.com>\r\n"[..];
+/// let mut state = SearchState::new();
+/// let (mut line, mut crlf) = (Vec::new(), Vec::new());
+/// let n = poll_fn(|cx| {
+///     poll_read_until_needle(
+///         Pin::new(&mut reader),
+///         cx,
+///         b"\r\n",
+///         &mut state,
+///         &mut line,
+///         &mut crlf,
+///     )
+/// })
+/// .await?;
+/// assert_eq!((n, &line[..]), (12, &b"HELO relay"[..]));
+/// # Ok(())
+/// # }
+/// ```
+pub fn poll_read_until_needle<R, N>(
+    reader: Pin<&mut R>,
+    cx: &mut Context<'_>,
+    needle: &N,
+    state: &mut SearchState,
+    before: &mut Vec<u8>,
+    matched: &mut Vec<u8>,
+) -> Poll<io::Result<usize>>
+where
+    R: AsyncBufRead + ?Sized,
+    N: Needle + ?Sized,
+{
+    read_until_needle_internal(reader, cx, needle, state, before, matched)
+        .map(|status| status.and_then(limit_exceeded))
+}
+
+/// Internal function to read until the needle is found.
+fn read_until_needle_internal<R, N>(
+    mut reader: Pin<&mut R>,
+    cx: &mut Context<'_>,
+    needle: &N,
+    state: &mut SearchState,
+    before: &mut Vec<u8>,
+    matched: &mut Vec<u8>,
+) -> Poll<io::Result<UntilNeedleStatus>>
+where
+    R: AsyncBufRead + ?Sized,
+    N: Needle + ?Sized,
+{
+    let mut budget = state.budget();
+    loop {
+        if budget == 0 {
+            // yield to other tasks; the search continues on the next poll
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        budget -= 1;
+
+        let (done, used) = {
+            let available = ready!(reader.as_mut().poll_fill_buf(cx))?;
+            state.step(needle, available, before, matched)
+        };
+
+        reader.as_mut().consume(used);
+
+        if done {
+            return Poll::Ready(Ok(state.finish()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::needle::FnNeedle;
+    use ::tokio::io::{AsyncReadExt as _, AsyncWriteExt as _, BufReader};
+
+    #[tokio::test]
+    async fn test_read_until_needle() {
+        let (client, mut server) = ::tokio::io::duplex(4);
+        let writer = ::tokio::spawn(async move {
+            for chunk in [&b"hel"[..], b"lo wo", b"rld", b"!!"] {
+                server.write_all(chunk).await.unwrap();
+                ::tokio::task::yield_now().await;
+            }
+        });
+
+        let mut reader = BufReader::with_capacity(3, client);
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        let mut buf = Vec::new();
+
+        assert_eq!(
+            reader
+                .read_until_needle(b"world", &mut before, &mut matched)
+                .await
+                .unwrap(),
+            11
+        );
+        assert_eq!(before, b"hello ");
+        assert_eq!(matched, b"world");
+
+        writer.await.unwrap();
+        assert_eq!(reader.read_to_end(&mut buf).await.unwrap(), 2);
+        assert_eq!(buf, b"!!");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_eof() {
+        let (client, mut server) = ::tokio::io::duplex(64);
+        server.write_all(b"no needle").await.unwrap();
+        drop(server);
+
+        let mut reader = BufReader::new(client);
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        assert_eq!(
+            reader
+                .read_until_needle(b"world", &mut before, &mut matched)
+                .await
+                .unwrap(),
+            9
+        );
+        assert_eq!(before, b"no needle");
+        assert_eq!(matched, b"");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_dropped() {
+        let (client, mut server) = ::tokio::io::duplex(64);
+        let mut reader = BufReader::new(client);
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+
+        server.write_all(b"hello w").await.unwrap();
+        let read = reader.read_until_needle(b"world", &mut before, &mut matched);
+        assert!(::tokio::time::timeout(Duration::from_millis(10), read)
+            .await
+            .is_err());
+        assert_eq!(before, b"hello w");
+
+        server.write_all(b"orld: world!").await.unwrap();
+        let n = reader
+            .read_until_needle(b"world", &mut before, &mut matched)
+            .await
+            .unwrap();
+        assert_eq!(n, 4);
+        assert_eq!((&before[..], &matched[..]), (&b"hello "[..], &b"world"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_consume_needle() {
+        let mut reader = BufReader::with_capacity(8, &b"head<>tail<>"[..]);
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let n = reader
+            .read_until_needle(b"<>", &mut before, &mut matched)
+            .consume_needle(false)
+            .await
+            .unwrap();
+        assert_eq!(n, 4);
+        assert_eq!((&before[..], &matched[..]), (&b"head"[..], &b"<>"[..]));
+
+        // the needle left in the reader is found again at once
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let n = reader
+            .read_until_needle(b"<>", &mut before, &mut matched)
+            .await
+            .unwrap();
+        assert_eq!((n, &before[..]), (2, &b""[..]));
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"tail<>");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_timeout() {
+        let (client, mut server) = ::tokio::io::duplex(64);
+        server.write_all(b"booting").await.unwrap();
+
+        let mut reader = BufReader::new(client);
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        // the stream stalls forever
+        assert_eq!(
+            reader
+                .read_until_needle_timeout(
+                    b"$ ",
+                    &mut before,
+                    &mut matched,
+                    Duration::from_millis(50)
+                )
+                .await
+                .unwrap(),
+            UntilNeedleStatus::TimedOut { bytes_read: 7 }
+        );
+        assert_eq!(before, b"booting");
+        assert_eq!(matched, b"");
+
+        // retrying continues where the timed out read stopped
+        let writer = ::tokio::spawn(async move {
+            ::tokio::time::sleep(Duration::from_millis(20)).await;
+            server.write_all(b"...\n$ ").await.unwrap();
+            server
+        });
+        assert_eq!(
+            reader
+                .read_until_needle_timeout(
+                    b"$ ",
+                    &mut before,
+                    &mut matched,
+                    Duration::from_secs(10)
+                )
+                .await
+                .unwrap(),
+            UntilNeedleStatus::Found { bytes_read: 6 }
+        );
+        assert_eq!(before, b"booting...\n");
+        assert_eq!(matched, b"$ ");
+        writer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_idle_only() {
+        let (client, mut server) = ::tokio::io::duplex(64);
+        let writer = ::tokio::spawn(async move {
+            for _ in 0..4 {
+                server.write_all(b"..").await.unwrap();
+                ::tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            // keep the stream open without sending anything
+            ::tokio::time::sleep(Duration::from_secs(10)).await;
+        });
+
+        let mut reader = BufReader::new(client);
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let status = reader
+            .read_until_needle(b"$ ", &mut before, &mut matched)
+            .idle_timeout(Duration::from_millis(200))
+            .await
+            .unwrap();
+        assert_eq!(status, UntilNeedleStatus::TimedOut { bytes_read: 8 });
+        assert_eq!(before, b"........");
+        writer.abort();
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_idle_timeout() {
+        let (client, mut server) = ::tokio::io::duplex(64);
+        let writer = ::tokio::spawn(async move {
+            for _ in 0..5 {
+                server.write_all(b"..").await.unwrap();
+                ::tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            server.write_all(b"done").await.unwrap();
+            // keep the stream open without sending anything
+            ::tokio::time::sleep(Duration::from_secs(10)).await;
+        });
+
+        let mut reader = BufReader::new(client);
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        // data keeps arriving within the idle timeout until the needle shows up
+        assert_eq!(
+            reader
+                .read_until_needle_timeout(
+                    b"done",
+                    &mut before,
+                    &mut matched,
+                    Duration::from_secs(10)
+                )
+                .idle_timeout(Duration::from_millis(500))
+                .await
+                .unwrap(),
+            UntilNeedleStatus::Found { bytes_read: 14 }
+        );
+
+        before.clear();
+        matched.clear();
+        assert_eq!(
+            reader
+                .read_until_needle_timeout(
+                    b"$ ",
+                    &mut before,
+                    &mut matched,
+                    Duration::from_secs(10)
+                )
+                .idle_timeout(Duration::from_millis(50))
+                .await
+                .unwrap(),
+            UntilNeedleStatus::TimedOut { bytes_read: 0 }
+        );
+        writer.abort();
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_until() {
+        let (client, mut server) = ::tokio::io::duplex(64);
+        server.write_all(b"shutting").await.unwrap();
+
+        let token = tokio_util::sync::CancellationToken::new();
+        let canceller = token.clone();
+        ::tokio::spawn(async move {
+            ::tokio::time::sleep(Duration::from_millis(20)).await;
+            canceller.cancel();
+        });
+
+        let mut reader = BufReader::new(client);
+        let mut before = Vec::new();
+        let mut matched = Vec::new();
+        assert_eq!(
+            reader
+                .read_until_needle_until(b"\n", &mut before, &mut matched, token.cancelled())
+                .await
+                .unwrap(),
+            UntilNeedleStatus::Cancelled { bytes_read: 8 }
+        );
+        assert_eq!(before, b"shutting");
+
+        server.write_all(b" down\n").await.unwrap();
+        assert_eq!(
+            reader
+                .read_until_needle(b"\n", &mut before, &mut matched)
+                .await
+                .unwrap(),
+            6
+        );
+        assert_eq!(before, b"shutting down");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_until_straddled() {
+        let (client, mut server) = ::tokio::io::duplex(64);
+        let mut reader = BufReader::new(client);
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        server.write_all(b"ab<").await.unwrap();
+
+        let token = tokio_util::sync::CancellationToken::new();
+        let canceller = token.clone();
+        ::tokio::spawn(async move {
+            ::tokio::time::sleep(Duration::from_millis(20)).await;
+            canceller.cancel();
+        });
+        assert_eq!(
+            reader
+                .read_until_needle_until(b"<>", &mut before, &mut matched, token.cancelled())
+                .await
+                .unwrap(),
+            UntilNeedleStatus::Cancelled { bytes_read: 3 }
+        );
+
+        server.write_all(b">f").await.unwrap();
+        drop(server);
+        let status = reader
+            .read_until_needle_until(b"<>", &mut before, &mut matched, std::future::pending())
+            .await
+            .unwrap();
+        assert_eq!(status, UntilNeedleStatus::Found { bytes_read: 1 });
+        assert_eq!((&before[..], &matched[..]), (&b"ab"[..], &b"<>"[..]));
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"f");
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_limit() {
+        let mut reader = BufReader::with_capacity(3, &b"abcdefgh<END>"[..]);
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let err = reader
+            .read_until_needle(b"<END>", &mut before, &mut matched)
+            .limit(4)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "needle not found within 4 bytes");
+        assert_eq!(before, b"abcd");
+
+        let n = reader
+            .read_until_needle(b"<END>", &mut before, &mut matched)
+            .limit(9)
+            .await
+            .unwrap();
+        assert_eq!(n, 9);
+        assert_eq!(before, b"abcdefgh");
+
+        let mut reader = &b"ab<END>"[..];
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let n = reader
+            .read_until_needle_limited(b"<END>", 7, &mut before, &mut matched)
+            .await
+            .unwrap();
+        assert_eq!((n, &matched[..]), (7, &b"<END>"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_into_out() {
+        use crate::output::{Discard, Limited};
+
+        let mut reader = BufReader::with_capacity(3, &b"ab<END>cd<END>efghij"[..]);
+        let mut before = Vec::new();
+        let status = reader
+            .read_until_needle_into_out(b"<END>", &mut before, Discard)
+            .await
+            .unwrap();
+        assert_eq!(status, UntilNeedleStatus::Found { bytes_read: 7 });
+        assert_eq!(before, b"ab");
+
+        let mut before = Limited::new(Vec::new(), 2);
+        let status = reader
+            .read_until_needle_into_out(b"<END>", &mut before, Discard)
+            .await
+            .unwrap();
+        assert_eq!(status, UntilNeedleStatus::Found { bytes_read: 7 });
+        assert_eq!(before.get_ref(), b"cd");
+
+        let status = reader
+            .read_until_needle_into_out(b"<END>", &mut before, Discard)
+            .await
+            .unwrap();
+        assert_eq!(status, UntilNeedleStatus::OutputFull { bytes_read: 1 });
+    }
+
+    #[tokio::test]
+    async fn test_skip_until_needle() {
+        let mut reader = BufReader::with_capacity(4, &b"noise noise<MARK>payload"[..]);
+        let report = reader.skip_until_needle(b"<MARK>").await.unwrap();
+        assert_eq!(
+            report,
+            SkipReport {
+                skipped: 11,
+                matched: Some(b"<MARK>".to_vec()),
+            }
+        );
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"payload");
+
+        let mut reader = BufReader::new(&b"no marker"[..]);
+        let report = reader.skip_until_needle(b"<MARK>").await.unwrap();
+        assert_eq!((report.skipped, report.matched), (9, None));
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_owned() {
+        let mut reader = BufReader::with_capacity(2, &b"key=value"[..]);
+        assert_eq!(
+            reader.read_until_needle_owned(b"=").await.unwrap(),
+            UntilNeedleOutput {
+                before: b"key".to_vec(),
+                matched: Some(b"=".to_vec()),
+                bytes_read: 4,
+            }
+        );
+
+        let output = reader.read_until_needle_owned(b"=").await.unwrap();
+        assert_eq!(output.before, b"value");
+        assert_eq!(output.matched, None);
+        assert_eq!(output.bytes_read, 5);
+    }
+
+    #[tokio::test]
+    async fn test_read_until_needle_with_state() {
+        let (client, mut server) = ::tokio::io::duplex(64);
+        let mut reader = BufReader::new(client);
+        let mut state = SearchState::new().with_limit(12);
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+
+        // each future is dropped once the data written so far is consumed
+        for chunk in [&b"abc<E"[..], b"N", b"D>de"] {
+            server.write_all(chunk).await.unwrap();
+            let read = reader.read_until_needle_with_state(
+                b"<END>",
+                &mut state,
+                &mut before,
+                &mut matched,
+            );
+            let result = ::tokio::time::timeout(Duration::from_millis(10), read).await;
+            if chunk != b"D>de" {
+                assert!(result.is_err());
+                assert_eq!(state.bytes_read(), before.len());
+            } else {
+                assert_eq!(result.unwrap().unwrap(), 8);
+            }
+        }
+        assert_eq!((&before[..], &matched[..]), (&b"abc"[..], &b"<END>"[..]));
+        assert_eq!(state.bytes_read(), 0);
+
+        // the state keeps its limit for the next search
+        server.write_all(b"fghijklmnopq").await.unwrap();
+        let err = reader
+            .read_until_needle_with_state(b"<END>", &mut state, &mut Vec::new(), &mut matched)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_poll_read_until_needle() {
+        use std::future::poll_fn;
+
+        let mut reader = BufReader::with_capacity(2, &b"a<END>b<END>"[..]);
+        let needle: &dyn Needle = &b"<END>";
+        let mut state = SearchState::new().with_budget(1);
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let mut polls = 0;
+        let n = poll_fn(|cx| {
+            polls += 1;
+            poll_read_until_needle(
+                Pin::new(&mut reader),
+                cx,
+                needle,
+                &mut state,
+                &mut before,
+                &mut matched,
+            )
+        })
+        .await
+        .unwrap();
+        assert_eq!(n, 6);
+        assert_eq!((&before[..], &matched[..]), (&b"a"[..], &b"<END>"[..]));
+        // one chunk per poll
+        assert!(polls > 1);
+
+        let mut state = SearchState::new().with_limit(3);
+        let err = poll_fn(|cx| {
+            poll_read_until_needle(
+                Pin::new(&mut reader),
+                cx,
+                needle,
+                &mut state,
+                &mut Vec::new(),
+                &mut Vec::new(),
+            )
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_not_unpin_reader() {
+        use futures::stream;
+        use tokio_util::io::StreamReader;
+
+        // the stream holds an async block, so the reader is not Unpin
+        let chunks = stream::unfold(0, |i| async move {
+            let chunk: &[u8] = [&b"abc<E"[..], b"ND>d"].get(i)?;
+            ::tokio::task::yield_now().await;
+            Some((Ok::<_, io::Error>(chunk), i + 1))
+        });
+        let reader = StreamReader::new(chunks);
+        ::tokio::pin!(reader);
+
+        let (mut before, mut matched) = (Vec::new(), Vec::new());
+        let n = reader
+            .as_mut()
+            .read_until_needle(b"<END>", &mut before, &mut matched)
+            .await
+            .unwrap();
+        assert_eq!((n, &before[..]), (8, &b"abc"[..]));
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"d");
+    }
+
+    #[tokio::test]
+    async fn test_take_until() {
+        let data = b"header<>|body<>|tail";
+        for capacity in 1..data.len() {
+            for consume_needle in [true, false] {
+                let reader = BufReader::with_capacity(capacity, &data[..]);
+                let mut take = reader.take_until(b"<>|").consume_needle(consume_needle);
+                let mut out = Vec::new();
+                ::tokio::io::copy(&mut take, &mut out).await.unwrap();
+                assert_eq!(out, b"header");
+                assert_eq!(take.matched(), Some(&b"<>|"[..]));
+
+                let (mut reader, mut rest) = take.into_inner();
+                reader.read_to_end(&mut rest).await.unwrap();
+                if consume_needle {
+                    assert_eq!(rest, b"body<>|tail");
+                } else {
+                    assert_eq!(rest, b"<>|body<>|tail");
+                }
+            }
+        }
+
+        let mut take = (&b"no needle"[..]).take_until(b"<>|");
+        let mut out = Vec::new();
+        take.read_to_end(&mut out).await.unwrap();
+        assert_eq!(out, b"no needle");
+        assert!(!take.is_found());
+
+        // a needle without a maximum length holds the data until it is found
+        let unbounded = FnNeedle(|haystack: &[u8]| b"<>".findin(haystack));
+        let mut take = BufReader::with_capacity(3, &b"ab<>c"[..]).take_until(unbounded);
+        let mut out = Vec::new();
+        take.read_to_end(&mut out).await.unwrap();
+        assert_eq!(out, b"ab");
+        let (mut reader, mut rest) = take.into_inner();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"c");
+    }
+
+    #[cfg(feature = "bytes")]
+    #[tokio::test]
+    async fn test_read_until_needle_bytes() {
+        let mut reader = BufReader::with_capacity(3, &b"key=value;x"[..]);
+        let mut buf = BytesMut::new();
+        let (before, matched) = reader
+            .read_until_needle_bytes(b"=", &mut buf)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((&before[..], &matched[..]), (&b"key"[..], &b"="[..]));
+        assert_eq!(matched.as_ptr(), before[before.len()..].as_ptr());
+
+        let (before, _) = reader
+            .read_until_needle_bytes(b";", &mut buf)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(before, &b"value"[..]);
+
+        assert!(reader
+            .read_until_needle_bytes(b";", &mut buf)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(buf, &b"x"[..]);
+    }
+}
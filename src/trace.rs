@@ -0,0 +1,227 @@
+//! Instrumentation of searches, emitted with `tracing` when the `tracing` feature is enabled.
+//!
+//! Without the feature, every function here is empty and the span is a unit struct, so the
+//! instrumentation compiles away.
+
+use crate::{Needle, UntilNeedleStatus};
+
+#[cfg(feature = "tracing")]
+pub(crate) type Span = tracing::Span;
+
+/// A stand-in for `tracing::Span` when the `tracing` feature is disabled.
+#[cfg(not(feature = "tracing"))]
+#[derive(Debug, Clone)]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        f()
+    }
+}
+
+/// Creates the span of a read until `needle`.
+#[allow(unused_variables)]
+pub(crate) fn read_span<N: Needle + ?Sized>(needle: &N) -> Span {
+    #[cfg(feature = "tracing")]
+    return tracing::debug_span!(
+        "read_until_needle",
+        needle = core::any::type_name::<N>(),
+        max_len = needle.max_len(),
+    );
+    #[cfg(not(feature = "tracing"))]
+    Span
+}
+
+/// Records a chunk received by a search.
+#[allow(unused_variables)]
+pub(crate) fn chunk(bytes: usize, total: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(bytes, total, "chunk received");
+}
+
+/// Records the needle found `offset` bytes after the start of a search.
+#[allow(unused_variables)]
+pub(crate) fn found(offset: usize, len: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(offset, len, "needle found");
+}
+
+/// Records which of several needles was found, by its index.
+#[cfg(feature = "std")]
+#[allow(unused_variables)]
+pub(crate) fn pattern(index: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(index, "pattern matched");
+}
+
+/// Records the outcome of a search that ended without finding the needle.
+#[allow(unused_variables)]
+pub(crate) fn outcome(status: &UntilNeedleStatus) {
+    #[cfg(feature = "tracing")]
+    match *status {
+        UntilNeedleStatus::Found { .. } => {}
+        UntilNeedleStatus::Eof { bytes_read } => {
+            tracing::debug!(bytes_read, "EOF reached without a match")
+        }
+        UntilNeedleStatus::TimedOut { bytes_read } => {
+            tracing::debug!(bytes_read, "timed out without a match")
+        }
+        UntilNeedleStatus::Cancelled { bytes_read } => {
+            tracing::debug!(bytes_read, "cancelled without a match")
+        }
+        UntilNeedleStatus::LimitReached { bytes_read } => {
+            tracing::debug!(bytes_read, "limit reached without a match")
+        }
+        UntilNeedleStatus::OutputFull { bytes_read } => {
+            tracing::debug!(bytes_read, "output full without a match")
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::io::UntilNeedleRead;
+    use crate::UntilNeedle;
+    use std::fmt::Write as _;
+    use std::io::BufReader;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing_subscriber::layer::{Context, SubscriberExt as _};
+    use tracing_subscriber::Layer;
+
+    /// A layer recording each event as its message, fields and the name of its span.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl<S> Layer<S> for Recorder
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+            struct Fields(String);
+
+            impl Visit for Fields {
+                fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                    if field.name() == "message" {
+                        write!(self.0, "{:?}", value).unwrap();
+                    } else {
+                        write!(self.0, " {}={:?}", field.name(), value).unwrap();
+                    }
+                }
+            }
+
+            let mut fields = Fields(String::new());
+            event.record(&mut fields);
+            let span = ctx.event_span(event).map_or("", |span| span.name());
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{}: {}", span, fields.0));
+        }
+    }
+
+    fn record(f: impl FnOnce()) -> Vec<String> {
+        let recorder = Recorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        tracing::subscriber::with_default(subscriber, f);
+        let events = recorder.0.lock().unwrap().clone();
+        events
+    }
+
+    #[test]
+    fn test_sync_events() {
+        let events = record(|| {
+            let mut reader = BufReader::with_capacity(4, &b"abc<END>rest"[..]);
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_needle(b"<END>", &mut before, &mut matched)
+                .unwrap();
+            assert_eq!(matched, b"<END>");
+        });
+        assert_eq!(
+            events,
+            [
+                "read_until_needle: chunk received bytes=4 total=4",
+                "read_until_needle: chunk received bytes=4 total=8",
+                "read_until_needle: needle found offset=3 len=5",
+            ]
+        );
+
+        let events = record(|| {
+            let mut reader = &b"abcdef"[..];
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            let builder = UntilNeedle::new(b"<END>").limit(4);
+            builder
+                .read(&mut reader, &mut before, &mut matched)
+                .unwrap();
+            builder
+                .read(&mut reader, &mut before, &mut matched)
+                .unwrap();
+        });
+        assert_eq!(
+            events,
+            [
+                "read_until_needle: chunk received bytes=4 total=4",
+                "read_until_needle: limit reached without a match bytes_read=4",
+                "read_until_needle: chunk received bytes=2 total=2",
+                "read_until_needle: EOF reached without a match bytes_read=2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pattern_events() {
+        let events = record(|| {
+            let mut reader = &b"ab$ "[..];
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            reader
+                .read_until_any(&["# ", "$ "], &mut before, &mut matched)
+                .unwrap();
+
+            let mut session = crate::io::Session::new(&b"login: "[..], std::io::sink());
+            session.expect(&["$ ", "login: "]).unwrap();
+        });
+        assert_eq!(
+            events,
+            [
+                "read_until_needle: chunk received bytes=4 total=4",
+                "read_until_needle: needle found offset=2 len=2",
+                ": pattern matched index=1",
+                ": pattern matched index=1",
+            ]
+        );
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn test_async_events() {
+        use crate::futures::AsyncUntilNeedleRead;
+        use futures::stream::{iter, TryStreamExt as _};
+
+        let events = record(|| {
+            let chunks: Vec<std::io::Result<&[u8]>> = vec![Ok(b"ab<E"), Ok(b"ND>")];
+            let mut reader = iter(chunks).into_async_read();
+            let (mut before, mut matched) = (Vec::new(), Vec::new());
+            futures::executor::block_on(async {
+                reader
+                    .read_until_needle(b"<END>", &mut before, &mut matched)
+                    .await
+                    .unwrap();
+                reader
+                    .read_until_needle(b"<END>", &mut before, &mut matched)
+                    .await
+                    .unwrap();
+            });
+        });
+        assert_eq!(
+            events,
+            [
+                "read_until_needle: chunk received bytes=4 total=4",
+                "read_until_needle: chunk received bytes=3 total=7",
+                "read_until_needle: needle found offset=2 len=5",
+                "read_until_needle: EOF reached without a match bytes_read=0",
+            ]
+        );
+    }
+}